@@ -0,0 +1,298 @@
+//! Price alerts evaluated against streaming or polled quotes.
+//!
+//! Register conditions on symbols with an [`AlertEngine`](struct.AlertEngine.html),
+//! then feed it quotes: streamed ones with `on_quote()`, or polled ones with
+//! `poll()`, which requests quotes for every symbol with an alert. Callbacks
+//! run when a condition becomes true, and again only after it was false in
+//! between, so an alert doesn't fire on every tick while the price stays
+//! past its threshold.
+//!
+//! ```no_run
+//! use std::{thread, time::Duration};
+//! use tda_sdk::{
+//!     alerts::{AlertEngine, Condition},
+//!     Client,
+//! };
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let mut alerts = AlertEngine::new();
+//!
+//! alerts.add("AAPL", Condition::CrossesAbove(150.0), |event| println!("AAPL crossed 150 at {}", event.price));
+//! alerts.add_once("TSLA", Condition::PercentChange(-5.0), |event| println!("TSLA down {:.1}%", event.percent_change.unwrap()));
+//!
+//! loop {
+//!     alerts.poll(&client).unwrap();
+//!     thread::sleep(Duration::from_secs(15));
+//! }
+//! ```
+
+use crate::{responses::GetQuotesResponse, streamer::LevelOneQuote, Client, ClientError};
+
+use std::{collections::HashMap, fmt};
+
+/// Condition on the price of a symbol.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    /// The price is at or above a level.
+    Above(f64),
+    /// The price is at or below a level.
+    Below(f64),
+    /// The price moved from below a level to at or above it.
+    CrossesAbove(f64),
+    /// The price moved from above a level to at or below it.
+    CrossesBelow(f64),
+    /// The price changed by at least this percentage from the previous close,
+    /// upwards for positive percentages and downwards for negative ones.
+    PercentChange(f64),
+}
+
+impl Condition {
+    fn is_met(self, price: f64, previous: Option<f64>, percent_change: Option<f64>) -> bool {
+        match self {
+            Condition::Above(level) => price >= level,
+            Condition::Below(level) => price <= level,
+            Condition::CrossesAbove(level) => previous.is_some_and(|previous| previous < level) && price >= level,
+            Condition::CrossesBelow(level) => previous.is_some_and(|previous| previous > level) && price <= level,
+            Condition::PercentChange(threshold) => percent_change.is_some_and(|change| match threshold >= 0.0 {
+                true => change >= threshold,
+                false => change <= threshold,
+            }),
+        }
+    }
+}
+
+/// Identifies an alert registered with an [`AlertEngine`](struct.AlertEngine.html).
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct AlertId(u64);
+
+/// Passed to the callback of an alert when it fires.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertEvent {
+    pub alert_id: AlertId,
+    pub symbol: String,
+    pub condition: Condition,
+    pub price: f64,
+    /// Price before this update, if the engine saw one.
+    pub previous_price: Option<f64>,
+    /// Change from the previous close in percent, if the close is known.
+    pub percent_change: Option<f64>,
+}
+
+type Callback = Box<dyn FnMut(&AlertEvent) + Send>;
+
+struct Alert {
+    id: AlertId,
+    symbol: String,
+    condition: Condition,
+    once: bool,
+    /// Whether the condition was met on the last update, to only fire when
+    /// it becomes true.
+    met: bool,
+    callback: Callback,
+}
+
+/// Evaluates registered alerts against price updates.
+#[derive(Default)]
+pub struct AlertEngine {
+    alerts: Vec<Alert>,
+    next_id: u64,
+    last_prices: HashMap<String, f64>,
+    close_prices: HashMap<String, f64>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an alert which fires every time its condition becomes true.
+    pub fn add(&mut self, symbol: &str, condition: Condition, callback: impl FnMut(&AlertEvent) + Send + 'static) -> AlertId {
+        self.register(symbol, condition, false, Box::new(callback))
+    }
+
+    /// Register an alert which is removed after it fires once.
+    pub fn add_once(&mut self, symbol: &str, condition: Condition, callback: impl FnMut(&AlertEvent) + Send + 'static) -> AlertId {
+        self.register(symbol, condition, true, Box::new(callback))
+    }
+
+    fn register(&mut self, symbol: &str, condition: Condition, once: bool, callback: Callback) -> AlertId {
+        self.next_id += 1;
+
+        let id = AlertId(self.next_id);
+
+        self.alerts.push(Alert {
+            id,
+            symbol: symbol.to_string(),
+            condition,
+            once,
+            met: false,
+            callback,
+        });
+
+        id
+    }
+
+    /// Remove an alert, returning whether it was registered.
+    pub fn remove(&mut self, id: AlertId) -> bool {
+        let len = self.alerts.len();
+
+        self.alerts.retain(|alert| alert.id != id);
+
+        self.alerts.len() != len
+    }
+
+    /// Symbols with at least one alert, sorted and without duplicates.
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.alerts.iter().map(|alert| alert.symbol.clone()).collect();
+
+        symbols.sort();
+        symbols.dedup();
+
+        symbols
+    }
+
+    /// Evaluate the alerts of a symbol against a new price, running the
+    /// callbacks of the ones which fire. Returns the events of the fired
+    /// alerts.
+    pub fn on_price(&mut self, symbol: &str, price: f64) -> Vec<AlertEvent> {
+        let previous_price = self.last_prices.insert(symbol.to_string(), price);
+        let percent_change = self
+            .close_prices
+            .get(symbol)
+            .filter(|close| **close != 0.0)
+            .map(|close| (price - close) / close * 100.0);
+        let mut events = Vec::new();
+
+        for alert in self.alerts.iter_mut().filter(|alert| alert.symbol == symbol) {
+            let met = alert.condition.is_met(price, previous_price, percent_change);
+            let fire = met && !alert.met;
+
+            alert.met = met;
+
+            if fire {
+                let event = AlertEvent {
+                    alert_id: alert.id,
+                    symbol: symbol.to_string(),
+                    condition: alert.condition,
+                    price,
+                    previous_price,
+                    percent_change,
+                };
+
+                (alert.callback)(&event);
+                events.push(event);
+            }
+        }
+
+        let fired_once: Vec<AlertId> = events.iter().map(|event| event.alert_id).collect();
+
+        self.alerts.retain(|alert| !(alert.once && fired_once.contains(&alert.id)));
+
+        events
+    }
+
+    /// Set the previous close of a symbol, which percent change conditions
+    /// compare against.
+    pub fn set_close_price(&mut self, symbol: &str, close_price: f64) {
+        self.close_prices.insert(symbol.to_string(), close_price);
+    }
+
+    /// Evaluate a streamed quote. The quote should be merged with the
+    /// previous updates of the symbol, so it carries the previous close.
+    pub fn on_quote(&mut self, quote: &LevelOneQuote) -> Vec<AlertEvent> {
+        if let Some(close_price) = quote.close_price {
+            self.set_close_price(&quote.symbol, close_price);
+        }
+
+        match quote.last_price {
+            Some(price) => self.on_price(&quote.symbol, price),
+            None => Vec::new(),
+        }
+    }
+
+    /// Evaluate polled quotes.
+    pub fn on_quotes(&mut self, quotes: &GetQuotesResponse) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+
+        for (symbol, quote) in quotes {
+            if let Some(close_price) = quote.close_price() {
+                self.set_close_price(symbol, close_price);
+            }
+
+            if let Some(price) = quote.last_price() {
+                events.extend(self.on_price(symbol, price));
+            }
+        }
+
+        events
+    }
+
+    /// Request quotes for every symbol with an alert and evaluate them.
+    pub fn poll(&mut self, client: &Client) -> Result<Vec<AlertEvent>, ClientError> {
+        let symbols = self.symbols();
+
+        if symbols.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let symbols: Vec<&str> = symbols.iter().map(String::as_str).collect();
+        let quotes = client.get_quotes(&symbols)?;
+
+        Ok(self.on_quotes(&quotes.found))
+    }
+}
+
+impl fmt::Debug for AlertEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlertEngine")
+            .field("alerts", &self.alerts.len())
+            .field("last_prices", &self.last_prices)
+            .field("close_prices", &self.close_prices)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn fires_on_transitions() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = AlertEngine::new();
+
+        let record = |fired: &Arc<Mutex<Vec<String>>>, name: &'static str| {
+            let fired = fired.clone();
+
+            move |_: &AlertEvent| fired.lock().unwrap().push(name.to_string())
+        };
+
+        engine.add("AAPL", Condition::Above(150.0), record(&fired, "above"));
+        engine.add("AAPL", Condition::CrossesBelow(140.0), record(&fired, "crosses below"));
+        engine.add_once("AAPL", Condition::PercentChange(-10.0), record(&fired, "down 10%"));
+        engine.set_close_price("AAPL", 150.0);
+
+        for price in [149.0, 151.0, 152.0, 149.0, 150.5, 139.0, 135.0, 141.0, 134.0] {
+            engine.on_price("AAPL", price);
+        }
+
+        assert_eq!(*fired.lock().unwrap(), ["above", "above", "crosses below", "down 10%", "crosses below"]);
+        assert_eq!(engine.alerts.len(), 2);
+    }
+
+    #[test]
+    fn evaluates_polled_quotes() {
+        let quotes: GetQuotesResponse = serde_json::from_str(include_str!("../tests/fixtures/quotes.json")).unwrap();
+        let mut engine = AlertEngine::new();
+
+        engine.add("AAPL", Condition::PercentChange(0.5), |_| {});
+        engine.add("MSFT", Condition::Above(0.0), |_| {});
+
+        let events = engine.on_quotes(&quotes);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].symbol, "AAPL");
+        assert!(events[0].percent_change.unwrap() > 0.5);
+    }
+}
@@ -0,0 +1,245 @@
+//! Assignment and exercise risk of options near expiration.
+//!
+//! Short options which finish in the money get assigned, and long ones get
+//! exercised automatically, leaving positions in the underlying which the
+//! account may not be able to carry. An
+//! [`AssignmentChecker`](struct.AssignmentChecker.html) finds the options
+//! expiring soon whose underlying is in the money or close to the strike,
+//! and can suggest orders closing them:
+//!
+//! ```no_run
+//! use tda_sdk::{assignment::AssignmentChecker, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let risks = client.assignment_risk("123456789", &AssignmentChecker::default()).unwrap();
+//!
+//! for risk in &risks {
+//!     println!("{} {:?}, underlying at {}", risk.symbol, risk.moneyness, risk.underlying_price);
+//!
+//!     client.place_order("123456789", &risk.closing_order(None)).unwrap();
+//! }
+//! ```
+
+use crate::{
+    instruments::Instrument,
+    orders::{Instruction, Order},
+    responses::{GetQuotesResponse, Position},
+    symbols::{OptionSymbol, PutCall},
+    Symbol,
+};
+use chrono::NaiveDate;
+
+/// Days before expiration options are checked, by default.
+pub const DEFAULT_DAYS: i64 = 2;
+
+/// Distance from the strike within which options are at the money, as a
+/// fraction of the strike, by default.
+pub const DEFAULT_AT_THE_MONEY: f64 = 0.01;
+
+/// Where the underlying is compared to the strike.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Moneyness {
+    InTheMoney,
+    /// Within the at the money distance of the strike, where the outcome
+    /// depends on the last minutes of trading.
+    AtTheMoney,
+    OutOfTheMoney,
+}
+
+/// Option position at risk of assignment or exercise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssignmentRisk {
+    pub symbol: String,
+    pub underlying: String,
+    pub put_call: PutCall,
+    pub strike: f64,
+    pub expiration: NaiveDate,
+    pub days_to_expiration: i64,
+    /// Contracts held, negative for short positions.
+    pub quantity: f64,
+    pub underlying_price: f64,
+    pub moneyness: Moneyness,
+    /// Amount the option is in the money by per share, zero if it isn't.
+    pub intrinsic_value: f64,
+}
+
+impl AssignmentRisk {
+    /// Whether the position is short, so it would be assigned rather than
+    /// exercised.
+    pub fn is_short(&self) -> bool {
+        self.quantity < 0.0
+    }
+
+    /// Order closing the position, at the market or with a limit price.
+    pub fn closing_order(&self, price: Option<f64>) -> Order {
+        let instruction = if self.is_short() { Instruction::BuyToClose } else { Instruction::SellToClose };
+        let quantity = self.quantity.abs();
+
+        match price {
+            Some(price) => Order::option_limit(instruction, &self.symbol, quantity, price),
+            None => Order::option_market(instruction, &self.symbol, quantity),
+        }
+    }
+}
+
+/// Finds options expiring soon which are in or at the money.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AssignmentChecker {
+    /// Options expiring within this many days are checked.
+    pub days: i64,
+    /// Distance from the strike within which options are at the money, as a
+    /// fraction of the strike.
+    pub at_the_money: f64,
+    /// Whether to report long options, which get exercised automatically,
+    /// and not just short ones.
+    pub include_long: bool,
+}
+
+impl Default for AssignmentChecker {
+    fn default() -> Self {
+        Self {
+            days: DEFAULT_DAYS,
+            at_the_money: DEFAULT_AT_THE_MONEY,
+            include_long: true,
+        }
+    }
+}
+
+impl AssignmentChecker {
+    /// Options among `positions` at risk on `today`, priced with the quotes
+    /// of their underlyings. Options without a quote of their underlying are
+    /// left out. Sorted by expiration, then by symbol.
+    pub fn check<'a>(&self, positions: impl IntoIterator<Item = &'a Position>, quotes: &GetQuotesResponse, today: NaiveDate) -> Vec<AssignmentRisk> {
+        let mut risks: Vec<AssignmentRisk> = positions
+            .into_iter()
+            .filter_map(|position| {
+                let symbol = option_symbol(&position.instrument)?;
+                let quantity = position.quantity();
+                let days_to_expiration = (symbol.expiration() - today).num_days();
+
+                if quantity == 0.0 || !(0..=self.days).contains(&days_to_expiration) || (quantity > 0.0 && !self.include_long) {
+                    return None;
+                }
+
+                let underlying_price = quotes.get(symbol.underlying()).and_then(|quote| quote.last_price())?;
+                let strike = symbol.strike();
+                let intrinsic_value = match symbol.put_call() {
+                    PutCall::Call => underlying_price - strike,
+                    PutCall::Put => strike - underlying_price,
+                };
+                let moneyness = if (underlying_price - strike).abs() <= strike * self.at_the_money {
+                    Moneyness::AtTheMoney
+                } else if intrinsic_value > 0.0 {
+                    Moneyness::InTheMoney
+                } else {
+                    return None;
+                };
+
+                Some(AssignmentRisk {
+                    symbol: position.instrument.symbol().to_string(),
+                    underlying: symbol.underlying().to_string(),
+                    put_call: symbol.put_call(),
+                    strike,
+                    expiration: symbol.expiration(),
+                    days_to_expiration,
+                    quantity,
+                    underlying_price,
+                    moneyness,
+                    intrinsic_value: intrinsic_value.max(0.0),
+                })
+            })
+            .collect();
+
+        risks.sort_by(|a, b| (a.expiration, &a.symbol).cmp(&(b.expiration, &b.symbol)));
+        risks
+    }
+
+    /// Underlyings of the options among `positions` which expire within the
+    /// checked days, to fetch quotes for.
+    pub fn underlyings<'a>(&self, positions: impl IntoIterator<Item = &'a Position>, today: NaiveDate) -> Vec<String> {
+        let mut underlyings: Vec<String> = positions
+            .into_iter()
+            .filter_map(|position| option_symbol(&position.instrument))
+            .filter(|symbol| (0..=self.days).contains(&(symbol.expiration() - today).num_days()))
+            .map(|symbol| symbol.underlying().to_string())
+            .collect();
+
+        underlyings.sort();
+        underlyings.dedup();
+        underlyings
+    }
+}
+
+fn option_symbol(instrument: &Instrument) -> Option<OptionSymbol> {
+    match instrument {
+        Instrument::Option { symbol, .. } => match Symbol::parse(symbol) {
+            Ok(Symbol::Option(option)) => Some(option),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::Quote;
+    use serde_json::json;
+
+    fn position(symbol: &str, quantity: f64) -> Position {
+        Position {
+            average_price: 0.0,
+            current_day_profit_loss: 0.0,
+            current_day_profit_loss_percentage: 0.0,
+            instrument: Instrument::option(symbol),
+            long_quantity: quantity.max(0.0),
+            market_value: 0.0,
+            settled_long_quantity: None,
+            settled_short_quantity: None,
+            short_quantity: (-quantity).max(0.0),
+        }
+    }
+
+    #[test]
+    fn finds_options_at_risk() {
+        let today = NaiveDate::from_ymd_opt(2021, 6, 17).unwrap();
+        let positions = vec![
+            position("AAPL_061821C120", -2.0),
+            position("AAPL_061821P130", 1.0),
+            position("AAPL_061821C125", -1.0),
+            position("AAPL_061821C130", -1.0),
+            position("AAPL_071621C100", -1.0),
+            position("MSFT_061821C250", -1.0),
+        ];
+        let quotes: GetQuotesResponse = vec![("AAPL".to_string(), Quote::from_value(json!({"assetType": "EQUITY", "symbol": "AAPL", "lastPrice": 125.5})))]
+            .into_iter()
+            .collect();
+
+        let checker = AssignmentChecker::default();
+        let risks = checker.check(&positions, &quotes, today);
+        let found: Vec<(&str, Moneyness)> = risks.iter().map(|risk| (risk.symbol.as_str(), risk.moneyness)).collect();
+
+        assert_eq!(checker.underlyings(&positions, today), vec!["AAPL".to_string(), "MSFT".to_string()]);
+        assert_eq!(found, vec![
+            ("AAPL_061821C120", Moneyness::InTheMoney),
+            ("AAPL_061821C125", Moneyness::AtTheMoney),
+            ("AAPL_061821P130", Moneyness::InTheMoney),
+        ]);
+        assert_eq!(risks[0].intrinsic_value, 5.5);
+        assert_eq!(risks[0].days_to_expiration, 1);
+
+        let order = risks[0].closing_order(Some(5.6));
+
+        assert_eq!(order.order_leg_collection[0].instruction, Instruction::BuyToClose);
+        assert_eq!(order.order_leg_collection[0].quantity, 2.0);
+        assert_eq!(order.price, Some(5.6));
+        assert_eq!(risks[2].closing_order(None).order_leg_collection[0].instruction, Instruction::SellToClose);
+
+        let short_only = AssignmentChecker {
+            include_long: false,
+            ..AssignmentChecker::default()
+        };
+
+        assert_eq!(short_only.check(&positions, &quotes, today).len(), 2);
+    }
+}
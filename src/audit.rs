@@ -0,0 +1,235 @@
+//! Audit log of requests which change something.
+//!
+//! Every order placed and every watchlist written by a client with an
+//! [`AuditSink`](trait.AuditSink.html) is recorded with its time, endpoint,
+//! account, payload, and result, whether it succeeded or not. Account IDs are
+//! masked and secret fields redacted before a record reaches the sink.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use tda_sdk::{audit::JsonLinesSink, Client};
+//!
+//! let sink = JsonLinesSink::open("audit.jsonl").unwrap();
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! client.set_audit_sink(Some(Arc::new(sink)));
+//! ```
+
+use crate::{
+    ids::{AccountId, OrderId},
+    ClientError,
+    SECRET_FIELDS,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+/// Receives a record of every request which changes something.
+pub trait AuditSink: fmt::Debug + Send + Sync {
+    /// Save a record. Errors are logged, and don't fail the request.
+    fn record(&self, record: &AuditRecord) -> io::Result<()>;
+
+    /// Write out buffered records, such as when the client shuts down.
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Request which changed something, and its result.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    /// HTTP method, such as `POST`.
+    pub method: String,
+    /// Path of the endpoint with placeholders for its parameters, such as
+    /// `/accounts/{accountId}/orders`.
+    pub endpoint: String,
+    /// Masked ID of the account.
+    pub account_id: String,
+    /// Body of the request, with account IDs masked and secrets redacted.
+    pub payload: Value,
+    /// Correlation ID of the requests of the call, see the
+    /// [`correlation`](../correlation/index.html) module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    pub result: AuditResult,
+}
+
+/// Result of an audited request.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AuditResult {
+    Success {
+        /// ID of the order placed, for order requests.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        order_id: Option<OrderId>,
+    },
+    Failure {
+        error: String,
+    },
+}
+
+/// Sink appending records to a file as JSON lines.
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    file: Mutex<File>,
+}
+
+impl JsonLinesSink {
+    /// Open a file to append records to, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl AuditSink for JsonLinesSink {
+    fn record(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+
+        file.write_all(line.as_bytes())?;
+        file.flush()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.file.lock().unwrap().sync_data()
+    }
+}
+
+/// Build the record of a request and send it to a sink.
+pub(crate) fn record(
+    sink: &dyn AuditSink,
+    method: &str,
+    endpoint: &str,
+    account_id: &AccountId,
+    payload: &impl Serialize,
+    correlation_id: &str,
+    result: Result<Option<OrderId>, &ClientError>,
+) {
+    let mut payload = serde_json::to_value(payload).unwrap_or_default();
+
+    redact(&mut payload);
+
+    let record = AuditRecord {
+        timestamp: Utc::now(),
+        method: method.to_string(),
+        endpoint: endpoint.to_string(),
+        account_id: account_id.masked(),
+        payload,
+        correlation_id: Some(correlation_id.to_string()),
+        result: match result {
+            Ok(order_id) => AuditResult::Success { order_id },
+            Err(error) => AuditResult::Failure { error: error.to_string() },
+        },
+    };
+
+    if let Err(error) = sink.record(&record) {
+        log::warn!("Failed to record {} {} in the audit log: {}", method, endpoint, error);
+    }
+}
+
+/// Mask account IDs and redact secret fields anywhere in a payload.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (name, value) in object.iter_mut() {
+                if SECRET_FIELDS.contains(&name.as_str()) {
+                    *value = Value::from("***");
+                } else if name == "accountId" {
+                    if let Some(account_id) = value.as_str().map(AccountId::from).or_else(|| value.as_i64().map(|id| AccountId::from(id.to_string()))) {
+                        *value = Value::from(account_id.masked());
+                    }
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{test_client, MockTransport},
+        orders::{Instruction, Order},
+    };
+    use std::sync::Arc;
+
+    #[derive(Debug, Default)]
+    struct MemorySink(Mutex<Vec<AuditRecord>>);
+
+    impl AuditSink for MemorySink {
+        fn record(&self, record: &AuditRecord) -> io::Result<()> {
+            self.0.lock().unwrap().push(record.clone());
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn records_orders() {
+        let transport = Arc::new(MockTransport::new());
+        let sink = Arc::new(MemorySink::default());
+        let mut client = test_client(transport.clone());
+        let mut order = Order::equity_market(Instruction::Buy, "AAPL", 10.0);
+
+        order.account_id = Some(AccountId::from("123456789"));
+        client.set_audit_sink(Some(sink.clone()));
+        transport.respond_with_headers("POST", "/v1/accounts/123456789/orders", 201, "", &[("Location", "/v1/accounts/123456789/orders/42")]);
+
+        client.place_order("123456789", &order).unwrap();
+        client.set_trading_enabled(false);
+        client.place_order("123456789", &order).unwrap_err();
+
+        let records = sink.0.lock().unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].endpoint, "/accounts/{accountId}/orders");
+        assert_eq!(records[0].account_id, "*****6789");
+        assert_eq!(records[0].payload["accountId"], "*****6789");
+        assert_eq!(records[0].payload["orderLegCollection"][0]["instrument"]["symbol"], "AAPL");
+        assert_eq!(records[0].result, AuditResult::Success { order_id: Some(OrderId::new(42)) });
+        assert!(records[0].correlation_id.is_some());
+        assert_eq!(records[1].result, AuditResult::Failure { error: "Trading is disabled".to_string() });
+    }
+
+    #[test]
+    fn appends_json_lines() {
+        let path = std::env::temp_dir().join(format!("tda-sdk-audit-{}.jsonl", std::process::id()));
+        let sink = JsonLinesSink::open(&path).unwrap();
+        let record = AuditRecord {
+            timestamp: Utc::now(),
+            method: "PUT".to_string(),
+            endpoint: "/accounts/{accountId}/watchlists/{watchlistId}".to_string(),
+            account_id: "*****6789".to_string(),
+            payload: serde_json::json!({"refresh_token": "***"}),
+            correlation_id: None,
+            result: AuditResult::Success { order_id: None },
+        };
+
+        sink.record(&record).unwrap();
+        sink.record(&record).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents.lines().count(), 2);
+        assert_eq!(serde_json::from_str::<AuditRecord>(contents.lines().next().unwrap()).unwrap(), record);
+        assert!(contents.contains(r#""result":{"status":"success"}"#));
+    }
+}
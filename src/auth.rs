@@ -0,0 +1,324 @@
+//! Interactive OAuth login.
+//!
+//! Getting the first refresh token requires logging in through the browser and
+//! capturing the authorization code the login page redirects with.
+//! [`interactive_login()`](fn.interactive_login.html) does the whole dance: it
+//! serves the redirect URI of the app on localhost, opens the login page,
+//! waits for the redirect, checks that its `state` matches the one sent to
+//! the login page, and exchanges the code for tokens.
+//!
+//! The app must be registered with `https://127.0.0.1:PORT` as its callback
+//! URL. The local server uses a self-signed certificate, so the browser warns
+//! about it once before following the redirect.
+//!
+//! ```no_run
+//! use tda_sdk::{
+//!     auth::{interactive_login, DEFAULT_LOGIN_TIMEOUT},
+//!     token_store::FileTokenStore,
+//!     Client,
+//! };
+//!
+//! let tokens = interactive_login("CLIENT_ID", 8182, DEFAULT_LOGIN_TIMEOUT, |url| println!("Log in at: {}", url)).unwrap();
+//! let client = Client::from_tokens(tokens);
+//!
+//! client.save_to(&FileTokenStore::new("tokens.json")).unwrap();
+//! ```
+
+use crate::{
+    token_store::StoredTokens,
+    transport::{percent_decode, percent_encode},
+    Client,
+    ClientError,
+};
+use rustls::{
+    pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+    ServerConfig,
+    ServerConnection,
+    StreamOwned,
+};
+use thiserror::Error;
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener},
+    process::Command,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Login page of the API.
+const AUTH_URL: &str = "https://auth.tdameritrade.com/auth";
+
+/// Suffix the login page expects after the client ID.
+const CLIENT_ID_SUFFIX: &str = "@AMER.OAUTHAP";
+
+/// How long a browser connection may stay idle before it is dropped.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the listener is checked for browser connections.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Suggested time to wait for the user to log in.
+pub const DEFAULT_LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Page shown in the browser once the code was received.
+const DONE_PAGE: &str = "<!DOCTYPE html><title>tda-sdk</title><p>Login complete, you can close this window.</p>";
+
+/// Log in through the browser and return the tokens of the user.
+///
+/// Gives the login URL to `show_url`, in case the browser can't be opened,
+/// and blocks until the login page redirects to `https://127.0.0.1:{port}`,
+/// failing with `AuthError::TimedOut` if it hasn't after `timeout`.
+pub fn interactive_login(client_id: &str, port: u16, timeout: Duration, show_url: impl FnOnce(&str)) -> Result<StoredTokens, AuthError> {
+    let redirect_uri = redirect_uri(port);
+    let state = random_state()?;
+    let url = authorization_url(client_id, &redirect_uri, &state);
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let config = Arc::new(server_config()?);
+
+    show_url(&url);
+
+    if let Err(error) = open_browser(&url) {
+        log::warn!("Failed to open the browser: {}", error);
+    }
+
+    let code = wait_for_code(&listener, config, &state, Instant::now() + timeout)?;
+
+    let mut client = Client::new(client_id, "", None);
+    let response = client.exchange_authorization_code(&code, &redirect_uri)?;
+
+    client.apply_token_response(response);
+
+    Ok(client.stored_tokens())
+}
+
+/// Serve browser connections until one is the login redirect, returning its
+/// authorization code, or fail with `AuthError::TimedOut` after `deadline`.
+fn wait_for_code(listener: &TcpListener, config: Arc<ServerConfig>, state: &str, deadline: Instant) -> Result<String, AuthError> {
+    listener.set_nonblocking(true)?;
+
+    loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(AuthError::TimedOut);
+                }
+
+                thread::sleep(ACCEPT_INTERVAL);
+
+                continue;
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+
+        let mut stream = StreamOwned::new(ServerConnection::new(config.clone())?, stream);
+
+        // Browsers drop the first connections until the certificate is
+        // accepted, and may request other paths such as the favicon.
+        match read_redirect(&mut stream, state) {
+            Ok(Some(redirect)) => {
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    DONE_PAGE.len(),
+                    DONE_PAGE,
+                );
+
+                stream.write_all(response.as_bytes()).ok();
+                stream.conn.send_close_notify();
+                stream.flush().ok();
+
+                return redirect;
+            }
+            Ok(None) => {
+                stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").ok();
+            }
+            Err(error) => log::debug!("Dropped login connection: {}", error),
+        }
+    }
+}
+
+/// Redirect URI served by [`interactive_login()`](fn.interactive_login.html).
+pub fn redirect_uri(port: u16) -> String {
+    format!("https://127.0.0.1:{}", port)
+}
+
+/// URL of the login page, which redirects to `redirect_uri` with the
+/// authorization code and `state`.
+pub fn authorization_url(client_id: &str, redirect_uri: &str, state: &str) -> String {
+    let client_id = if client_id.ends_with(CLIENT_ID_SUFFIX) {
+        client_id.to_string()
+    } else {
+        format!("{}{}", client_id, CLIENT_ID_SUFFIX)
+    };
+
+    format!(
+        "{}?response_type=code&redirect_uri={}&client_id={}&state={}",
+        AUTH_URL,
+        percent_encode(redirect_uri),
+        percent_encode(&client_id),
+        percent_encode(state),
+    )
+}
+
+/// Unguessable `state` tying the redirect to this login, so a redirect forged
+/// by another page can't log the app in to another account.
+fn random_state() -> Result<String, AuthError> {
+    let mut bytes = [0; 16];
+
+    rustls::crypto::ring::default_provider().secure_random.fill(&mut bytes).map_err(rustls::Error::from)?;
+
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Self-signed certificate for the local server.
+fn server_config() -> Result<ServerConfig, AuthError> {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(vec![certified.cert.der().clone()], key)?;
+
+    Ok(config)
+}
+
+/// Read the request line of a browser request, returning the result of the
+/// redirect, or `None` if it is a request for something else.
+fn read_redirect(stream: impl io::Read, state: &str) -> io::Result<Option<Result<String, AuthError>>> {
+    let mut request_line = String::new();
+
+    BufReader::new(stream).read_line(&mut request_line)?;
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or_default();
+
+    Ok(parse_redirect(target, state))
+}
+
+/// Get the authorization code or the error from the target of a redirect.
+/// Redirects without the `state` of this login may have been forged by
+/// another page, and are ignored like any other request.
+fn parse_redirect(target: &str, state: &str) -> Option<Result<String, AuthError>> {
+    let (path, query) = target.split_once('?')?;
+
+    if path != "/" {
+        return None;
+    }
+
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| percent_decode(value))
+    };
+
+    if param("state").as_deref() != Some(state) {
+        if param("code").is_some() || param("error").is_some() {
+            log::warn!("Ignored login redirect with the wrong state");
+        }
+
+        return None;
+    }
+
+    match (param("code"), param("error")) {
+        (Some(code), _) => Some(Ok(code)),
+        (None, Some(error)) => Some(Err(AuthError::Denied(error))),
+        (None, None) => None,
+    }
+}
+
+/// Open a URL in the default browser.
+fn open_browser(url: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    command.arg(url).spawn()?;
+
+    Ok(())
+}
+
+/// Represents all possible errors of the interactive login.
+#[derive(Debug, Error)]
+pub enum AuthError {
+    /// The local server failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The self-signed certificate could not be generated.
+    #[error("Failed to generate a certificate: {0}")]
+    Certificate(#[from] rcgen::Error),
+
+    /// The TLS configuration of the local server is invalid.
+    #[error("TLS error: {0}")]
+    Tls(#[from] rustls::Error),
+
+    /// The login page redirected with an error.
+    #[error("Login failed: {0}")]
+    Denied(String),
+
+    /// The login page didn't redirect in time.
+    #[error("Timed out waiting for the login redirect")]
+    TimedOut,
+
+    /// The authorization code could not be exchanged for tokens.
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorization_url_encodes_params() {
+        assert_eq!(
+            authorization_url("KEY", &redirect_uri(8182), "STATE"),
+            "https://auth.tdameritrade.com/auth?response_type=code&redirect_uri=https%3A%2F%2F127.0.0.1%3A8182&client_id=KEY%40AMER.OAUTHAP&state=STATE",
+        );
+        assert!(authorization_url("KEY@AMER.OAUTHAP", "https://127.0.0.1", "STATE").contains("client_id=KEY%40AMER.OAUTHAP&"));
+    }
+
+    #[test]
+    fn parse_redirect_target() {
+        assert!(matches!(parse_redirect("/?code=abc%2Bdef%3D&state=STATE", "STATE"), Some(Ok(code)) if code == "abc+def="));
+        assert!(parse_redirect("/?code=abc&state=OTHER", "STATE").is_none());
+        assert!(parse_redirect("/?code=abc", "STATE").is_none());
+        assert!(matches!(parse_redirect("/?error=access_denied&state=STATE", "STATE"), Some(Err(AuthError::Denied(_)))));
+        assert!(parse_redirect("/?error=access_denied&state=OTHER", "STATE").is_none());
+        assert!(parse_redirect("/?error=access_denied", "STATE").is_none());
+        assert!(parse_redirect("/favicon.ico", "STATE").is_none());
+        assert!(parse_redirect("/", "STATE").is_none());
+    }
+
+    #[test]
+    fn times_out_without_redirect() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let config = Arc::new(server_config().unwrap());
+        let started = Instant::now();
+        let result = wait_for_code(&listener, config, "STATE", started + Duration::from_millis(200));
+
+        assert!(matches!(result, Err(AuthError::TimedOut)));
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn random_states() {
+        let state = random_state().unwrap();
+
+        assert_eq!(state.len(), 32);
+        assert_ne!(state, random_state().unwrap());
+    }
+}
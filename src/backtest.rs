@@ -0,0 +1,211 @@
+//! Run a [`Strategy`](../strategy/trait.Strategy.html) against historical
+//! candles.
+//!
+//! Candles from `get_price_history()` are replayed in time order across all
+//! symbols. Orders placed by the strategy go to a
+//! [`PaperBroker`](../paper/struct.PaperBroker.html) and are filled against
+//! the following candles, and the value of the account is recorded after every
+//! timestamp.
+//!
+//! ```
+//! use tda_sdk::{
+//!     backtest::{backtest, BacktestParams},
+//!     orders::{Instruction, Order},
+//!     responses::{Candle, GetPriceHistoryResponse},
+//!     strategy::{OrderSink, Strategy},
+//! };
+//!
+//! struct BuyAndHold;
+//!
+//! impl Strategy for BuyAndHold {
+//!     fn on_bar(&mut self, symbol: &str, _bar: &Candle, orders: &mut dyn OrderSink) {
+//!         orders.place_order(&Order::equity_market(Instruction::Buy, symbol, 1.0)).unwrap();
+//!     }
+//! }
+//!
+//! let history = GetPriceHistoryResponse {
+//!     candles: vec![
+//!         Candle { open: 10.0, high: 10.0, low: 10.0, close: 10.0, volume: 0, datetime: 1 },
+//!         Candle { open: 11.0, high: 11.0, low: 11.0, close: 12.0, volume: 0, datetime: 2 },
+//!     ],
+//!     empty: false,
+//!     symbol: "AAPL".to_string(),
+//! };
+//! let params = BacktestParams { initial_cash: 100.0, ..BacktestParams::default() };
+//!
+//! let report = backtest(&mut BuyAndHold, &[history], &params);
+//!
+//! assert_eq!(report.trades.len(), 1);
+//! assert_eq!(report.final_equity(), Some(101.0));
+//! ```
+
+use crate::{
+    paper::PaperBroker,
+    responses::{Candle, GetPriceHistoryResponse},
+    stats::PerformanceReport,
+    strategy::{Fill, Strategy},
+};
+
+use std::collections::HashMap;
+
+/// Parameters for the [`backtest()`](fn.backtest.html) function.
+#[derive(Clone, Debug, Default)]
+pub struct BacktestParams {
+    /// Cash the paper account starts with.
+    pub initial_cash: f64,
+
+    /// Flat commission charged on every fill.
+    pub commission: f64,
+}
+
+/// Value of the account at a point in time.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EquityPoint {
+    /// Timestamp of the candles, in milliseconds since the epoch.
+    pub datetime: i64,
+    pub equity: f64,
+}
+
+/// Result of the [`backtest()`](fn.backtest.html) function.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BacktestReport {
+    /// Value of the account after each timestamp, marked at the close.
+    pub equity_curve: Vec<EquityPoint>,
+
+    /// Every fill, in the order it happened.
+    pub trades: Vec<Fill>,
+}
+
+impl BacktestReport {
+    /// Value of the account at the end of the backtest.
+    pub fn final_equity(&self) -> Option<f64> {
+        self.equity_curve.last().map(|point| point.equity)
+    }
+
+    /// Performance metrics of the equity curve and trades. `periods_per_year`
+    /// depends on the frequency of the candles, see
+    /// [`PerformanceReport::new()`](../stats/struct.PerformanceReport.html#method.new).
+    pub fn performance(&self, periods_per_year: f64) -> PerformanceReport {
+        let equity: Vec<f64> = self.equity_curve.iter().map(|point| point.equity).collect();
+
+        PerformanceReport::new(&equity, &self.trades, periods_per_year)
+    }
+}
+
+/// Replay `history` through `strategy` with a paper account.
+///
+/// For every candle, pending orders are filled first (calling `on_fill()`),
+/// then `on_bar()` is called with the candle. Orders which are still pending
+/// at the end are left unfilled.
+pub fn backtest<S: Strategy>(strategy: &mut S, history: &[GetPriceHistoryResponse], params: &BacktestParams) -> BacktestReport {
+    let mut broker = PaperBroker::new(params.initial_cash);
+    let mut report = BacktestReport::default();
+    let mut prices = HashMap::new();
+    let mut candles: Vec<(&str, &Candle)> = history
+        .iter()
+        .flat_map(|response| response.candles.iter().map(move |candle| (response.symbol.as_str(), candle)))
+        .collect();
+
+    broker.commission = params.commission;
+    candles.sort_by_key(|(_, candle)| candle.datetime);
+
+    for (index, (symbol, candle)) in candles.iter().enumerate() {
+        for fill in broker.process_bar(symbol, candle) {
+            strategy.on_fill(&fill, &mut broker);
+            report.trades.push(fill);
+        }
+
+        prices.insert(symbol.to_string(), candle.close);
+        strategy.on_bar(symbol, candle, &mut broker);
+
+        let end_of_timestamp = candles.get(index + 1).is_none_or(|(_, next)| next.datetime != candle.datetime);
+
+        if end_of_timestamp {
+            report.equity_curve.push(EquityPoint {
+                datetime: candle.datetime,
+                equity: broker.equity(&prices),
+            });
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        orders::{Instruction, Order},
+        strategy::OrderSink,
+    };
+
+    /// Buys on the first bar of each symbol and sells once it closes above
+    /// the entry price.
+    #[derive(Default)]
+    struct TakeProfit {
+        entries: HashMap<String, f64>,
+        fills: usize,
+    }
+
+    impl Strategy for TakeProfit {
+        fn on_bar(&mut self, symbol: &str, bar: &Candle, orders: &mut dyn OrderSink) {
+            match self.entries.get(symbol) {
+                None => {
+                    orders.place_order(&Order::equity_market(Instruction::Buy, symbol, 10.0)).unwrap();
+                    self.entries.insert(symbol.to_string(), f64::MAX);
+                }
+                Some(entry) if bar.close > *entry => {
+                    orders.place_order(&Order::equity_market(Instruction::Sell, symbol, 10.0)).unwrap();
+                    self.entries.insert(symbol.to_string(), f64::MAX);
+                }
+                _ => {}
+            }
+        }
+
+        fn on_fill(&mut self, fill: &Fill, _orders: &mut dyn OrderSink) {
+            self.fills += 1;
+
+            if fill.instruction == "Buy" {
+                self.entries.insert(fill.symbol.clone(), fill.price);
+            }
+        }
+    }
+
+    fn history(symbol: &str, closes: &[f64]) -> GetPriceHistoryResponse {
+        GetPriceHistoryResponse {
+            candles: closes
+                .iter()
+                .enumerate()
+                .map(|(index, close)| Candle {
+                    open: *close,
+                    high: *close,
+                    low: *close,
+                    close: *close,
+                    volume: 0,
+                    datetime: index as i64,
+                })
+                .collect(),
+            empty: false,
+            symbol: symbol.to_string(),
+        }
+    }
+
+    #[test]
+    fn replays_candles() {
+        let mut strategy = TakeProfit::default();
+        let params = BacktestParams {
+            initial_cash: 1_000.0,
+            commission: 1.0,
+        };
+
+        let report = backtest(&mut strategy, &[history("AAPL", &[10.0, 10.0, 12.0, 15.0]), history("MSFT", &[20.0, 20.0])], &params);
+
+        // AAPL is bought at 10 and sold at the open after closing at 12.
+        // MSFT is bought at 20 and marked at 20.
+        assert_eq!(strategy.fills, 3);
+        assert_eq!(report.trades.iter().map(|fill| fill.price).collect::<Vec<_>>(), vec![10.0, 20.0, 15.0]);
+        assert_eq!(report.equity_curve.len(), 4);
+        assert_eq!(report.final_equity(), Some(1_000.0 + 50.0 - 3.0));
+        assert_eq!(report.performance(252.0).closed_trades, vec![50.0]);
+    }
+}
@@ -0,0 +1,202 @@
+//! Performance of an equity curve relative to a benchmark.
+//!
+//! [`Client::compare_to_benchmark()`](../struct.Client.html#method.compare_to_benchmark)
+//! fetches the daily candles of a benchmark over the dates of an equity
+//! curve, such as daily snapshots of the liquidation value of an account,
+//! and compares the two on the dates they have in common:
+//!
+//! ```no_run
+//! use chrono::NaiveDate;
+//! use tda_sdk::Client;
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let equity = vec![
+//!     (NaiveDate::from_ymd_opt(2020, 5, 4).unwrap(), 10_000.0),
+//!     (NaiveDate::from_ymd_opt(2020, 5, 5).unwrap(), 10_150.0),
+//!     (NaiveDate::from_ymd_opt(2020, 5, 6).unwrap(), 10_080.0),
+//! ];
+//!
+//! let comparison = client.compare_to_benchmark(&equity, "SPY").unwrap();
+//!
+//! println!("Excess return: {:.2}%", comparison.excess_return * 100.0);
+//! println!("Beta: {:?}", comparison.beta);
+//! ```
+//!
+//! Candles can also be compared directly with
+//! [`compare()`](fn.compare.html), such as after adjusting them for
+//! dividends with the [`candles`](../candles/index.html) module.
+
+use crate::{
+    responses::Candle,
+    stats::{max_drawdown, returns, total_return, TRADING_DAYS_PER_YEAR},
+    time::TdaDateTime,
+};
+use chrono::NaiveDate;
+
+use std::collections::BTreeMap;
+
+/// Equity and benchmark on a date they have in common.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RelativePoint {
+    pub date: NaiveDate,
+    pub equity: f64,
+    /// Close of the benchmark.
+    pub benchmark: f64,
+    /// Return of the equity since the first point.
+    pub equity_return: f64,
+    /// Return of the benchmark since the first point.
+    pub benchmark_return: f64,
+    /// Growth of the equity relative to the benchmark since the first point,
+    /// positive when the equity is ahead.
+    pub relative_return: f64,
+}
+
+/// Relative performance series and summary statistics.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BenchmarkComparison {
+    pub symbol: String,
+    /// Points on the dates the equity curve and the benchmark have in common.
+    pub points: Vec<RelativePoint>,
+    pub equity_return: f64,
+    pub benchmark_return: f64,
+    /// Equity return minus benchmark return.
+    pub excess_return: f64,
+    pub equity_max_drawdown: f64,
+    pub benchmark_max_drawdown: f64,
+    /// Sensitivity of daily equity returns to benchmark returns, or `None`
+    /// if the benchmark didn't move.
+    pub beta: Option<f64>,
+    /// Correlation of daily returns, or `None` if either didn't move.
+    pub correlation: Option<f64>,
+    /// Annualized standard deviation of the daily excess returns.
+    pub tracking_error: f64,
+    /// Annualized mean daily excess return divided by the tracking error,
+    /// or `None` without tracking error.
+    pub information_ratio: Option<f64>,
+}
+
+/// Compare an equity curve with the daily candles of a benchmark, keeping
+/// the dates both have. Candles are dated in the market's timezone.
+pub fn compare(symbol: &str, equity: &[(NaiveDate, f64)], benchmark: &[Candle]) -> BenchmarkComparison {
+    let closes: BTreeMap<NaiveDate, f64> = benchmark
+        .iter()
+        .filter_map(|candle| Some((TdaDateTime::from_millis(candle.datetime)?.date(), candle.close)))
+        .collect();
+
+    let aligned: Vec<(NaiveDate, f64, f64)> = equity
+        .iter()
+        .filter_map(|(date, equity)| closes.get(date).map(|close| (*date, *equity, *close)))
+        .collect();
+
+    let equity: Vec<f64> = aligned.iter().map(|(_, equity, _)| *equity).collect();
+    let benchmark: Vec<f64> = aligned.iter().map(|(_, _, close)| *close).collect();
+
+    let points = aligned
+        .iter()
+        .map(|(date, equity, close)| {
+            let equity_return = equity / aligned[0].1 - 1.0;
+            let benchmark_return = close / aligned[0].2 - 1.0;
+
+            RelativePoint {
+                date: *date,
+                equity: *equity,
+                benchmark: *close,
+                equity_return,
+                benchmark_return,
+                relative_return: (1.0 + equity_return) / (1.0 + benchmark_return) - 1.0,
+            }
+        })
+        .collect();
+
+    let equity_returns = returns(&equity);
+    let benchmark_returns = returns(&benchmark);
+    let excess: Vec<f64> = equity_returns.iter().zip(&benchmark_returns).map(|(equity, benchmark)| equity - benchmark).collect();
+
+    let equity_variance = variance(&equity_returns);
+    let benchmark_variance = variance(&benchmark_returns);
+    let covariance = covariance(&equity_returns, &benchmark_returns);
+    let tracking_error = variance(&excess).sqrt() * TRADING_DAYS_PER_YEAR.sqrt();
+
+    BenchmarkComparison {
+        symbol: symbol.to_string(),
+        points,
+        equity_return: total_return(&equity),
+        benchmark_return: total_return(&benchmark),
+        excess_return: total_return(&equity) - total_return(&benchmark),
+        equity_max_drawdown: max_drawdown(&equity),
+        benchmark_max_drawdown: max_drawdown(&benchmark),
+        beta: (benchmark_variance > 0.0).then(|| covariance / benchmark_variance),
+        correlation: (equity_variance > 0.0 && benchmark_variance > 0.0).then(|| covariance / (equity_variance * benchmark_variance).sqrt()),
+        tracking_error,
+        information_ratio: (tracking_error > 0.0).then(|| mean(&excess) * TRADING_DAYS_PER_YEAR / tracking_error),
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    match values.len() {
+        0 => 0.0,
+        count => values.iter().sum::<f64>() / count as f64,
+    }
+}
+
+/// Sample covariance, or `0.0` with fewer than two values.
+fn covariance(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() < 2 {
+        return 0.0;
+    }
+
+    let (mean_a, mean_b) = (mean(a), mean(b));
+
+    a.iter().zip(b).map(|(a, b)| (a - mean_a) * (b - mean_b)).sum::<f64>() / (a.len() - 1) as f64
+}
+
+fn variance(values: &[f64]) -> f64 {
+    covariance(values, values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2020, 5, day).unwrap()
+    }
+
+    fn candle(day: u32, close: f64) -> Candle {
+        Candle {
+            close,
+            datetime: TdaDateTime::from_date(date(day)).unwrap().timestamp_millis(),
+            ..Candle::default()
+        }
+    }
+
+    #[test]
+    fn compares_aligned_dates() {
+        let equity = [(date(4), 100.0), (date(5), 104.0), (date(6), 102.0), (date(7), 99.0), (date(9), 110.0)];
+        let benchmark = [candle(1, 280.0), candle(4, 200.0), candle(5, 202.0), candle(6, 201.0), candle(7, 198.0)];
+
+        let comparison = compare("SPY", &equity, &benchmark);
+
+        assert_eq!(comparison.points.len(), 4);
+        assert_eq!(comparison.points[0].relative_return, 0.0);
+        assert!((comparison.points[1].relative_return - (1.04 / 1.01 - 1.0)).abs() < 1e-12);
+        assert!((comparison.equity_return + 0.01).abs() < 1e-12);
+        assert!((comparison.benchmark_return + 0.01).abs() < 1e-12);
+        assert!(comparison.excess_return.abs() < 1e-12);
+        assert!(comparison.beta.unwrap() > 1.0);
+        assert!(comparison.correlation.unwrap() > 0.9);
+        assert!(comparison.tracking_error > 0.0);
+    }
+
+    #[test]
+    fn flat_benchmark() {
+        let equity = [(date(4), 100.0), (date(5), 101.0), (date(6), 102.0)];
+        let benchmark = [candle(4, 50.0), candle(5, 50.0), candle(6, 50.0)];
+
+        let comparison = compare("SPY", &equity, &benchmark);
+
+        assert_eq!(comparison.beta, None);
+        assert_eq!(comparison.correlation, None);
+        assert!((comparison.excess_return - 0.02).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,112 @@
+//! Small command-line tool built on the SDK.
+//!
+//! Reads the client ID and refresh token from the `TDA_CLIENT_ID` and
+//! `TDA_REFRESH_TOKEN` environment variables, and prints responses as JSON.
+//!
+//! ```text
+//! tda accounts
+//! tda quotes AAPL MSFT
+//! tda history AAPL --period 5d
+//! ```
+
+use serde::Serialize;
+use tda_sdk::{
+    params::{GetAccountsParams, GetPriceHistoryParams},
+    Client,
+};
+
+use std::{env, process};
+
+const USAGE: &str = "Usage:
+    tda accounts
+    tda quotes <SYMBOL>...
+    tda history <SYMBOL> [--period <PERIOD>]
+
+PERIOD is a number followed by d (days), m (months), or y (years), or `ytd`.
+
+The TDA_CLIENT_ID and TDA_REFRESH_TOKEN environment variables must be set.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(message) = run(&args) {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (command, args) = args.split_first().ok_or(USAGE)?;
+
+    match command.as_str() {
+        "accounts" => print(&client()?.get_accounts(GetAccountsParams::default()).map_err(|e| e.to_string())?),
+        "quotes" if !args.is_empty() => {
+            let symbols: Vec<&str> = args.iter().map(String::as_str).collect();
+
+            print(&client()?.get_quotes(&symbols).map_err(|e| e.to_string())?)
+        }
+        "history" if !args.is_empty() => {
+            let params = match args.get(1..) {
+                Some([flag, period]) if flag == "--period" => period_params(period)?,
+                Some([]) => GetPriceHistoryParams::default(),
+                _ => return Err(USAGE.to_string()),
+            };
+
+            print(&client()?.get_price_history(&args[0], params).map_err(|e| e.to_string())?)
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Create a client with a fresh access token.
+fn client() -> Result<Client, String> {
+    let client_id = env::var("TDA_CLIENT_ID").map_err(|_| "TDA_CLIENT_ID is not set")?;
+    let refresh_token = env::var("TDA_REFRESH_TOKEN").map_err(|_| "TDA_REFRESH_TOKEN is not set")?;
+    let mut client = Client::new(&client_id, &refresh_token, None);
+
+    let access_token = client.get_access_token().map_err(|e| e.to_string())?;
+    client.set_access_token(&Some(access_token.into()));
+
+    Ok(client)
+}
+
+/// Convert a period such as `5d` into price history parameters.
+fn period_params(period: &str) -> Result<GetPriceHistoryParams, String> {
+    let invalid = || format!("Invalid period `{}`\n\n{}", period, USAGE);
+
+    if period == "ytd" {
+        return Ok(GetPriceHistoryParams {
+            period_type: Some("ytd".to_string()),
+            ..GetPriceHistoryParams::default()
+        });
+    }
+
+    let (count, unit) = period.split_at(period.len().saturating_sub(1));
+    let count: u32 = count.parse().map_err(|_| invalid())?;
+    let (period_type, frequency_type) = match unit {
+        "d" => ("day", "minute"),
+        "m" => ("month", "daily"),
+        "y" => ("year", "daily"),
+        _ => return Err(invalid()),
+    };
+
+    Ok(GetPriceHistoryParams {
+        frequency_type: Some(frequency_type.to_string()),
+        period_type: Some(period_type.to_string()),
+        period: Some(count.to_string()),
+        ..GetPriceHistoryParams::default()
+    })
+}
+
+fn print<T: Serialize>(value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+
+    println!("{}", json);
+
+    Ok(())
+}
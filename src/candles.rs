@@ -0,0 +1,449 @@
+//! Candles tagged with the trading session they belong to.
+//!
+//! Requesting price history with `need_extended_hours_data` mixes pre-market,
+//! regular and post-market candles without saying which is which. A
+//! [`CandleSeries`](struct.CandleSeries.html) tags each candle using the hours
+//! returned by `get_market_hours()`, so the sessions can be split afterwards.
+//!
+//! ```no_run
+//! use chrono::NaiveDate;
+//! use tda_sdk::{
+//!     candles::CandleSeries,
+//!     params::{GetMarketHoursParams, GetPriceHistoryParams},
+//!     Client,
+//! };
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let history = client.get_price_history("AAPL", GetPriceHistoryParams {
+//!     need_extended_hours_data: Some(true),
+//!     ..GetPriceHistoryParams::default()
+//! }).unwrap();
+//! let hours = client.get_market_hours("EQUITY", GetMarketHoursParams {
+//!     date: NaiveDate::from_ymd_opt(2020, 5, 5),
+//! }).unwrap();
+//!
+//! let regular = CandleSeries::new(history, &[hours["equity"]["EQ"].clone()]).regular_hours_only();
+//! ```
+//!
+//! Price history is only partially adjusted for splits, so this module can also
+//! detect likely splits from the gaps they leave between candles, along with
+//! the change in volume, and adjust earlier candles for them.
+//!
+//! ```
+//! use tda_sdk::{candles::{adjust_for_splits, detect_splits, DEFAULT_SPLIT_TOLERANCE}, responses::Candle};
+//!
+//! let candle = |datetime, price, volume| Candle { open: price, high: price, low: price, close: price, volume, datetime };
+//! let mut candles = vec![candle(1, 400.0, 100), candle(2, 101.0, 380)];
+//!
+//! let splits = detect_splits(&candles, DEFAULT_SPLIT_TOLERANCE);
+//! adjust_for_splits(&mut candles, &splits);
+//!
+//! assert_eq!(splits[0].ratio, 4.0);
+//! assert_eq!((candles[0].close, candles[0].volume), (100.0, 400));
+//! ```
+//!
+//! For long backtests, candles can be adjusted for dividends too, so the
+//! series reflects total return as if dividends were reinvested. Dividends
+//! come from fundamentals, which only have the latest one, or from the
+//! dividends received in an account:
+//!
+//! ```no_run
+//! use tda_sdk::{
+//!     candles::{adjust_for_dividends, dividends_from_transactions},
+//!     params::{GetPriceHistoryParams, GetTransactionsParams},
+//!     Client,
+//! };
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let mut history = client.get_price_history("AAPL", GetPriceHistoryParams::default()).unwrap();
+//! let transactions = client.get_transactions("123456789", GetTransactionsParams::default()).unwrap();
+//!
+//! adjust_for_dividends(&mut history.candles, &dividends_from_transactions(&transactions, "AAPL"));
+//! ```
+
+use crate::{
+    responses::{Candle, DividendInfo, GetPriceHistoryResponse, MarketHours, Session, Transaction},
+    time::TdaDateTime,
+    transactions::TransactionKind,
+};
+use chrono::NaiveDate;
+
+/// Split ratios looked for by [`detect_splits()`](fn.detect_splits.html), as
+/// new shares per old share. Reverse splits are detected from the reciprocals.
+pub const SPLIT_RATIOS: [f64; 10] = [1.5, 2.0, 3.0, 4.0, 5.0, 7.0, 8.0, 10.0, 15.0, 20.0];
+
+/// Default relative difference allowed between a gap and a split ratio.
+pub const DEFAULT_SPLIT_TOLERANCE: f64 = 0.02;
+
+/// Stock split, effective from the open of a candle.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Split {
+    /// Timestamp of the first candle after the split, in milliseconds since
+    /// the epoch.
+    pub datetime: i64,
+
+    /// New shares per old share, such as `2.0` for a 2-for-1 split or `0.1`
+    /// for a 1-for-10 reverse split.
+    pub ratio: f64,
+}
+
+/// Find gaps between consecutive candles which look like splits.
+///
+/// A gap is a split if the previous close divided by the next open is within
+/// `tolerance` of one of the [`SPLIT_RATIOS`](constant.SPLIT_RATIOS.html) or
+/// their reciprocals, and the volume corroborates it: a split multiplies the
+/// share count by its ratio, so the volume must move the same way by at least
+/// the square root of the ratio. Candles without volume never corroborate a
+/// split. Large genuine moves on heavy volume can still look like splits, so
+/// check the results against a corporate actions source when it matters.
+pub fn detect_splits(candles: &[Candle], tolerance: f64) -> Vec<Split> {
+    candles
+        .windows(2)
+        .filter(|pair| pair[0].close > 0.0 && pair[1].open > 0.0 && pair[0].volume > 0 && pair[1].volume > 0)
+        .filter_map(|pair| {
+            let gap = pair[0].close / pair[1].open;
+            let ratio = SPLIT_RATIOS
+                .iter()
+                .flat_map(|ratio| [*ratio, 1.0 / ratio])
+                .find(|ratio| (gap / ratio - 1.0).abs() <= tolerance)?;
+            let volume_ratio = pair[1].volume as f64 / pair[0].volume as f64;
+
+            if (volume_ratio.ln() / ratio.ln()) < 0.5 {
+                return None;
+            }
+
+            Some(Split {
+                datetime: pair[1].datetime,
+                ratio,
+            })
+        })
+        .collect()
+}
+
+/// Adjust the candles before each split, so prices and volumes are comparable
+/// with the candles after it.
+pub fn adjust_for_splits(candles: &mut [Candle], splits: &[Split]) {
+    for candle in candles {
+        let ratio: f64 = splits.iter().filter(|split| candle.datetime < split.datetime).map(|split| split.ratio).product();
+
+        if ratio != 1.0 {
+            candle.open /= ratio;
+            candle.high /= ratio;
+            candle.low /= ratio;
+            candle.close /= ratio;
+            candle.volume = (candle.volume as f64 * ratio).round() as i64;
+        }
+    }
+}
+
+/// Cash dividend, paid to holders from before the open of a candle.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Dividend {
+    /// Timestamp of the ex-dividend date, in milliseconds since the epoch.
+    /// Candles before it are adjusted.
+    pub datetime: i64,
+
+    /// Amount paid per share.
+    pub amount: f64,
+}
+
+impl Dividend {
+    /// Dividend going ex at midnight of a date in the market's timezone.
+    pub fn on(ex_date: NaiveDate, amount: f64) -> Option<Self> {
+        Some(Self {
+            datetime: TdaDateTime::from_date(ex_date)?.timestamp_millis(),
+            amount,
+        })
+    }
+
+    /// Latest dividend of fundamental data, if the symbol pays one.
+    pub fn from_info(info: &DividendInfo) -> Option<Self> {
+        Self::on(info.ex_date?, info.pay_amount).filter(|dividend| dividend.amount > 0.0)
+    }
+}
+
+/// Dividends per share of a symbol received in an account, from the shares
+/// held according to the trades among the transactions.
+///
+/// This is an approximation: transactions don't have the ex-dividend date,
+/// only the payment date, which is their settlement date, so the payment
+/// date stands in for the ex-date, and the shares held just before the
+/// payment for those held on the ex-date. The payment is usually a few weeks
+/// after the ex-date, which barely matters for long horizons, but trades in
+/// between skew the amount per share. Dividends paid on shares bought before
+/// the first transaction can't be split per share and are left out.
+pub fn dividends_from_transactions<'a>(transactions: impl IntoIterator<Item = &'a Transaction>, symbol: &str) -> Vec<Dividend> {
+    let mut events: Vec<(TdaDateTime, &Transaction)> = transactions
+        .into_iter()
+        .filter(|transaction| transaction.symbol() == Some(symbol))
+        .filter_map(|transaction| Some((*transaction.transaction_date.as_ref()?, transaction)))
+        .collect();
+
+    // Dividends come before trades made at the same time, which they weren't
+    // paid on.
+    events.sort_by_key(|(time, transaction)| (*time, transaction.kind() != TransactionKind::Dividend));
+
+    let mut shares = 0.0;
+    let mut dividends = Vec::new();
+
+    for (paid_at, transaction) in events {
+        match transaction.kind() {
+            TransactionKind::Dividend if shares > 0.0 => {
+                dividends.extend(Dividend::on(transaction.settlement_date.unwrap_or_else(|| paid_at.date()), transaction.net_amount / shares));
+            }
+            TransactionKind::Trade => {
+                let Some(item) = &transaction.transaction_item else { continue };
+                let quantity = item.amount.unwrap_or_default().abs();
+
+                match item.instruction.as_deref() {
+                    Some("BUY") => shares += quantity,
+                    Some("SELL") => shares = (shares - quantity).max(0.0),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    dividends.sort_by_key(|dividend| dividend.datetime);
+    dividends
+}
+
+/// Adjust the candles before each dividend for it, so the series reflects
+/// total return.
+///
+/// Prices before a dividend are multiplied by one minus the dividend divided
+/// by the last close before it, the way data vendors adjust. Volumes are left
+/// alone. Dividends without a candle before them are ignored.
+pub fn adjust_for_dividends(candles: &mut [Candle], dividends: &[Dividend]) {
+    let factors: Vec<(i64, f64)> = dividends
+        .iter()
+        .filter_map(|dividend| {
+            let previous_close = candles.iter().rev().find(|candle| candle.datetime < dividend.datetime)?.close;
+
+            (previous_close > dividend.amount).then(|| (dividend.datetime, 1.0 - dividend.amount / previous_close))
+        })
+        .collect();
+
+    for candle in candles {
+        let factor: f64 = factors.iter().filter(|(datetime, _)| candle.datetime < *datetime).map(|(_, factor)| factor).product();
+
+        if factor != 1.0 {
+            candle.open *= factor;
+            candle.high *= factor;
+            candle.low *= factor;
+            candle.close *= factor;
+        }
+    }
+}
+
+/// Candle in a [`CandleSeries`](struct.CandleSeries.html)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SessionCandle {
+    pub candle: Candle,
+    /// Session the candle starts in, or `None` if it is outside of the market
+    /// hours the series was tagged with.
+    pub session: Option<Session>,
+}
+
+/// Candles of a symbol tagged with their trading session.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CandleSeries {
+    pub candles: Vec<SessionCandle>,
+    pub symbol: String,
+}
+
+impl CandleSeries {
+    /// Tag every candle of `history` with its session in `hours`, which
+    /// should cover each day of the history.
+    pub fn new(history: GetPriceHistoryResponse, hours: &[MarketHours]) -> Self {
+        let candles = history
+            .candles
+            .into_iter()
+            .map(|candle| SessionCandle {
+                candle,
+                session: hours.iter().find_map(|day| day.session_at(candle.datetime)),
+            })
+            .collect();
+
+        Self {
+            candles,
+            symbol: history.symbol,
+        }
+    }
+
+    /// Keep only the candles of a session.
+    pub fn session(mut self, session: Session) -> Self {
+        self.candles.retain(|candle| candle.session == Some(session));
+
+        self
+    }
+
+    /// Keep only the candles of the regular session.
+    pub fn regular_hours_only(self) -> Self {
+        self.session(Session::Regular)
+    }
+
+    /// Adjust the candles for splits, see
+    /// [`adjust_for_splits()`](fn.adjust_for_splits.html).
+    pub fn adjust_for_splits(&mut self, splits: &[Split]) -> &mut Self {
+        let mut candles: Vec<Candle> = self.candles.iter().map(|candle| candle.candle).collect();
+
+        adjust_for_splits(&mut candles, splits);
+
+        for (tagged, candle) in self.candles.iter_mut().zip(candles) {
+            tagged.candle = candle;
+        }
+
+        self
+    }
+
+    /// Adjust the candles for dividends, see
+    /// [`adjust_for_dividends()`](fn.adjust_for_dividends.html).
+    pub fn adjust_for_dividends(&mut self, dividends: &[Dividend]) -> &mut Self {
+        let mut candles: Vec<Candle> = self.candles.iter().map(|candle| candle.candle).collect();
+
+        adjust_for_dividends(&mut candles, dividends);
+
+        for (tagged, candle) in self.candles.iter_mut().zip(candles) {
+            tagged.candle = candle;
+        }
+
+        self
+    }
+
+    /// Drop the session tags, such as to pass the candles to a backtest.
+    pub fn into_history(self) -> GetPriceHistoryResponse {
+        GetPriceHistoryResponse {
+            empty: self.candles.is_empty(),
+            candles: self.candles.into_iter().map(|candle| candle.candle).collect(),
+            symbol: self.symbol,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::GetMarketHoursResponse;
+
+    #[test]
+    fn regular_hours_only() {
+        let response: GetMarketHoursResponse = serde_json::from_str(include_str!("../tests/fixtures/market_hours.json")).unwrap();
+        let hours = response["equity"]["EQ"].clone();
+        let candle = |datetime| Candle { datetime, ..Candle::default() };
+
+        // 08:00, 09:30, 15:59 and 16:00 EDT on 2020-05-05, and a day later.
+        let history = GetPriceHistoryResponse {
+            candles: vec![
+                candle(1_588_680_000_000),
+                candle(1_588_685_400_000),
+                candle(1_588_708_740_000),
+                candle(1_588_708_800_000),
+                candle(1_588_771_800_000),
+            ],
+            empty: false,
+            symbol: "AAPL".to_string(),
+        };
+
+        let series = CandleSeries::new(history, &[hours]);
+        let sessions: Vec<_> = series.candles.iter().map(|candle| candle.session).collect();
+
+        assert_eq!(sessions, vec![Some(Session::Pre), Some(Session::Regular), Some(Session::Regular), Some(Session::Post), None]);
+
+        let regular = series.regular_hours_only().into_history();
+
+        assert_eq!(regular.candles.iter().map(|candle| candle.datetime).collect::<Vec<_>>(), vec![1_588_685_400_000, 1_588_708_740_000]);
+    }
+
+    #[test]
+    fn split_adjustment() {
+        let candle = |datetime, open, close, volume| Candle {
+            open,
+            high: open.max(close),
+            low: open.min(close),
+            close,
+            volume,
+            datetime,
+        };
+        let mut candles = vec![
+            candle(1, 100.0, 120.0, 1_000),
+            candle(2, 60.5, 62.0, 2_100),
+            candle(3, 62.0, 70.0, 2_000),
+            candle(4, 700.0, 690.0, 190),
+            candle(5, 460.0, 450.0, 210),
+        ];
+
+        // A 2-for-1 split, a normal 13% move, a 1-for-10 reverse split, and
+        // a 33% drop which matches a 3-for-2 split but keeps its volume.
+        let splits = detect_splits(&candles, DEFAULT_SPLIT_TOLERANCE);
+
+        assert_eq!(splits, vec![Split { datetime: 2, ratio: 2.0 }, Split { datetime: 4, ratio: 0.1 }]);
+
+        adjust_for_splits(&mut candles, &splits);
+
+        assert!((candles[0].close - 600.0).abs() < 1e-9);
+        assert_eq!(candles[0].volume, 200);
+        assert!((candles[2].close - 700.0).abs() < 1e-9);
+        assert_eq!(candles[2].volume, 200);
+        assert_eq!(candles[3].close, 690.0);
+    }
+
+    #[test]
+    fn dividend_adjustment() {
+        let ex_date = NaiveDate::from_ymd_opt(2020, 5, 8).unwrap();
+        let dividend = Dividend::on(ex_date, 2.0).unwrap();
+        let candle = |datetime: i64, close| Candle {
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1_000,
+            datetime,
+        };
+        let mut candles = vec![
+            candle(dividend.datetime - 2 * 86_400_000, 90.0),
+            candle(dividend.datetime - 86_400_000 + 9 * 3_600_000, 100.0),
+            candle(dividend.datetime + 9 * 3_600_000, 98.0),
+        ];
+
+        adjust_for_dividends(&mut candles, &[dividend]);
+
+        assert!((candles[0].close - 88.2).abs() < 1e-9);
+        assert!((candles[1].close - 98.0).abs() < 1e-9);
+        assert_eq!(candles[1].volume, 1_000);
+        assert_eq!(candles[2].close, 98.0);
+    }
+
+    #[test]
+    fn dividends_per_share_from_transactions() {
+        let transactions: Vec<Transaction> = serde_json::from_str(include_str!("../tests/fixtures/transactions.json")).unwrap();
+        let dividends = dividends_from_transactions(&transactions, "AAPL");
+
+        // 20.50 paid on the 10 shares bought on 2020-05-04.
+        assert_eq!(dividends, vec![Dividend::on(NaiveDate::from_ymd_opt(2020, 5, 14).unwrap(), 2.05).unwrap()]);
+        assert!(dividends_from_transactions(&transactions, "MSFT").is_empty());
+    }
+
+    #[test]
+    fn dividends_after_a_sale() {
+        let mut transactions: Vec<Transaction> = serde_json::from_str(include_str!("../tests/fixtures/transactions.json")).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2020, 8, day).unwrap();
+        let mut sale = transactions.iter().find(|transaction| transaction.kind() == TransactionKind::Trade).unwrap().clone();
+        let mut dividend = transactions.iter().find(|transaction| transaction.kind() == TransactionKind::Dividend).unwrap().clone();
+
+        sale.transaction_date = TdaDateTime::from_date(date(3));
+        sale.transaction_item.as_mut().unwrap().instruction = Some("SELL".to_string());
+        sale.transaction_item.as_mut().unwrap().amount = Some(4.0);
+        dividend.transaction_date = TdaDateTime::from_date(date(13));
+        dividend.settlement_date = Some(date(13));
+        dividend.net_amount = 4.92;
+        transactions.extend([sale, dividend]);
+
+        let dividends = dividends_from_transactions(&transactions, "AAPL");
+
+        // The second dividend is paid on the 6 shares left.
+        assert_eq!(dividends.len(), 2);
+        assert_eq!(dividends[1], Dividend::on(date(13), 0.82).unwrap());
+    }
+}
@@ -0,0 +1,516 @@
+//! Record and replay API responses.
+//!
+//! A [`CassetteTransport`](struct.CassetteTransport.html) either sends requests
+//! to the API and records every interaction to a cassette file, or replays a
+//! cassette without touching the network. Record once with live credentials,
+//! commit the cassette, and tests run in CI without them.
+//!
+//! Secrets are scrubbed before anything is written: the `Authorization`
+//! header, token, code, and client ID form fields, and tokens in response
+//! bodies.
+//! Account IDs are replaced everywhere they appear by placeholders such as
+//! `900000001`, the same one for an account throughout a cassette, so tests
+//! replaying it call the API with the placeholders the replayed responses
+//! hand them.
+//!
+//! Requests match a recording by method, path, query parameters in any
+//! order, and body.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use tda_sdk::{cassette::CassetteTransport, AccessToken, Client};
+//!
+//! let token = AccessToken { expires_at: 0, scope: Vec::new(), token: "TOKEN".to_string() };
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", Some(token));
+//!
+//! // Records through the proxy and TLS settings of the client when
+//! // `TDA_RECORD` is set, and replays otherwise.
+//! let transport = CassetteTransport::from_env("tests/cassettes/quotes.json", Arc::new(client.http_transport())).unwrap();
+//!
+//! client.set_transport(Some(Arc::new(transport)));
+//!
+//! let quotes = client.get_quotes(&["AAPL"]).unwrap();
+//! ```
+
+use crate::{
+    transport::{percent_decode, HttpRequest, HttpResponse, Transport},
+    SECRET_FIELDS,
+};
+use serde_json::Value;
+
+use std::{
+    env,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Environment variable which switches
+/// [`CassetteTransport::from_env()`](struct.CassetteTransport.html#method.from_env)
+/// to recording.
+pub const RECORD_ENV_VAR: &str = "TDA_RECORD";
+
+/// Replacement of scrubbed secrets.
+const SCRUBBED: &str = "***";
+
+/// Fields of request and response bodies holding account IDs.
+const ACCOUNT_ID_FIELDS: &[&str] = &["accountId", "accountNumber", "primaryAccountId"];
+
+/// Recorded interactions, in the order they happened.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Load a cassette file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+
+        serde_json::from_str(&json).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Save the cassette as pretty JSON, so diffs of re-recorded cassettes
+    /// stay readable.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, json + "\n")
+    }
+}
+
+/// Single request and its response in a [`Cassette`](struct.Cassette.html).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Interaction {
+    pub request: HttpRequest,
+    pub response: RecordedResponse,
+}
+
+/// Response in an [`Interaction`](struct.Interaction.html), with its body
+/// decompressed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Whether a [`CassetteTransport`](struct.CassetteTransport.html) records or
+/// replays.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Record,
+    Replay,
+}
+
+/// Transport recording interactions to, or replaying them from, a cassette.
+#[derive(Debug)]
+pub struct CassetteTransport {
+    path: PathBuf,
+    /// Transport the requests are sent through when recording.
+    inner: Option<Arc<dyn Transport>>,
+    state: Mutex<CassetteState>,
+}
+
+#[derive(Debug, Default)]
+struct CassetteState {
+    cassette: Cassette,
+    replayed: Vec<bool>,
+    /// Account IDs seen while recording, replaced by the placeholder of
+    /// their position.
+    account_ids: Vec<String>,
+}
+
+impl CassetteTransport {
+    /// Send requests through `inner`, such as the transport returned by
+    /// `Client::http_transport()`, and record them to a new cassette at
+    /// `path`, replacing any existing one. The file is written after every
+    /// interaction.
+    pub fn record(path: impl AsRef<Path>, inner: Arc<dyn Transport>) -> Self {
+        Self::new(path.as_ref(), Some(inner), Cassette::default())
+    }
+
+    /// Replay the cassette at `path`.
+    pub fn replay(path: impl AsRef<Path>) -> io::Result<Self> {
+        let cassette = Cassette::load(&path)?;
+
+        Ok(Self::new(path.as_ref(), None, cassette))
+    }
+
+    /// Record through `inner` if the [`RECORD_ENV_VAR`](constant.RECORD_ENV_VAR.html)
+    /// environment variable is set, and replay otherwise.
+    pub fn from_env(path: impl AsRef<Path>, inner: Arc<dyn Transport>) -> io::Result<Self> {
+        match env::var_os(RECORD_ENV_VAR) {
+            Some(_) => Ok(Self::record(path, inner)),
+            None => Self::replay(path),
+        }
+    }
+
+    fn new(path: &Path, inner: Option<Arc<dyn Transport>>, cassette: Cassette) -> Self {
+        let replayed = vec![false; cassette.interactions.len()];
+
+        Self {
+            path: path.to_path_buf(),
+            inner,
+            state: Mutex::new(CassetteState {
+                cassette,
+                replayed,
+                account_ids: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        match self.inner {
+            Some(_) => Mode::Record,
+            None => Mode::Replay,
+        }
+    }
+
+    /// Copy of the interactions recorded or loaded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.state.lock().unwrap().cassette.clone()
+    }
+
+    fn record_interaction(&self, inner: &dyn Transport, request: &HttpRequest) -> io::Result<HttpResponse> {
+        let response = inner.send(request)?;
+        let encoding = response.header("Content-Encoding").map(str::to_string);
+        let headers: Vec<(String, String)> = response
+            .headers
+            .into_iter()
+            .filter(|(name, _)| !name.eq_ignore_ascii_case("Content-Encoding") && !name.eq_ignore_ascii_case("Content-Length"))
+            .collect();
+        let mut body = String::new();
+
+        crate::decoding_reader(response.body, encoding.as_deref()).read_to_string(&mut body)?;
+
+        let recorded = RecordedResponse {
+            status: response.status,
+            headers,
+            body,
+        };
+        let mut state = self.state.lock().unwrap();
+        let interaction = state.scrub_account_ids(Interaction {
+            request: scrub_request(request),
+            response: RecordedResponse {
+                body: crate::redact_body(&recorded.body),
+                ..recorded.clone()
+            },
+        });
+
+        state.cassette.interactions.push(interaction);
+        state.cassette.save(&self.path)?;
+
+        Ok(to_response(&recorded))
+    }
+
+    fn replay_interaction(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+        let request = scrub_request(request);
+        let mut state = self.state.lock().unwrap();
+        let matching: Vec<usize> = state
+            .cassette
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| matches(&interaction.request, &request))
+            .map(|(index, _)| index)
+            .collect();
+
+        // Repeated requests replay their recordings in order, and keep
+        // replaying the last one once they run out.
+        let index = matching
+            .iter()
+            .find(|index| !state.replayed[**index])
+            .or_else(|| matching.last())
+            .copied()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No recorded interaction for {} {} in {}", request.method, request.url, self.path.display()),
+                )
+            })?;
+
+        state.replayed[index] = true;
+
+        Ok(to_response(&state.cassette.interactions[index].response))
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+        match &self.inner {
+            Some(inner) => self.record_interaction(inner.as_ref(), request),
+            None => self.replay_interaction(request),
+        }
+    }
+}
+
+impl CassetteState {
+    /// Replace the account IDs of an interaction by their placeholders,
+    /// learning the IDs in its URL and bodies first.
+    fn scrub_account_ids(&mut self, mut interaction: Interaction) -> Interaction {
+        let mut found = Vec::new();
+        let mut segments = interaction.request.path().split('/');
+
+        while let Some(segment) = segments.next() {
+            if segment == "accounts" {
+                found.extend(segments.next().filter(|id| !id.is_empty()).map(str::to_string));
+            }
+        }
+
+        for body in interaction.request.body.iter().chain(Some(&interaction.response.body)) {
+            if let Ok(json) = serde_json::from_str::<Value>(body) {
+                collect_account_ids(&json, &mut found);
+            }
+        }
+
+        for id in found {
+            if !self.account_ids.contains(&id) {
+                self.account_ids.push(id);
+            }
+        }
+
+        for (index, id) in self.account_ids.iter().enumerate() {
+            let placeholder = (900_000_001 + index).to_string();
+            let texts = interaction
+                .request
+                .body
+                .iter_mut()
+                .chain(Some(&mut interaction.request.url))
+                .chain(Some(&mut interaction.response.body))
+                .chain(interaction.response.headers.iter_mut().map(|(_, value)| value));
+
+            for text in texts {
+                *text = replace_id(text, id, &placeholder);
+            }
+        }
+
+        interaction
+    }
+}
+
+/// Add the values of the account ID fields anywhere in `json` to `ids`.
+fn collect_account_ids(json: &Value, ids: &mut Vec<String>) {
+    match json {
+        Value::Object(object) => {
+            for (name, value) in object {
+                match value {
+                    Value::String(id) if ACCOUNT_ID_FIELDS.contains(&name.as_str()) => ids.push(id.clone()),
+                    Value::Number(id) if ACCOUNT_ID_FIELDS.contains(&name.as_str()) => ids.push(id.to_string()),
+                    _ => collect_account_ids(value, ids),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect_account_ids(value, ids)),
+        _ => {}
+    }
+}
+
+/// Replace the occurrences of `id` in `text` which aren't part of a longer
+/// word or number.
+fn replace_id(text: &str, id: &str, placeholder: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut replaced = String::with_capacity(text.len());
+    let mut copied = 0;
+
+    for (start, _) in text.match_indices(id) {
+        let end = start + id.len();
+        let bounded = (start == 0 || !bytes[start - 1].is_ascii_alphanumeric()) && bytes.get(end).is_none_or(|byte| !byte.is_ascii_alphanumeric());
+
+        if bounded {
+            replaced.push_str(&text[copied..start]);
+            replaced.push_str(placeholder);
+            copied = end;
+        }
+    }
+
+    replaced.push_str(&text[copied..]);
+    replaced
+}
+
+/// Whether a recorded request matches a request, by method, path, query
+/// parameters in any order, and body.
+fn matches(recorded: &HttpRequest, request: &HttpRequest) -> bool {
+    recorded.method.eq_ignore_ascii_case(&request.method)
+        && recorded.path() == request.path()
+        && sorted_query(recorded) == sorted_query(request)
+        && recorded.body == request.body
+}
+
+/// Decoded query parameters of a request, sorted.
+fn sorted_query(request: &HttpRequest) -> Vec<(String, String)> {
+    let mut query: Vec<(String, String)> = match request.url.split_once('?') {
+        Some((_, query)) => query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+                (percent_decode(name), percent_decode(value))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    query.sort();
+    query
+}
+
+/// Copy of a request with its secrets replaced.
+fn scrub_request(request: &HttpRequest) -> HttpRequest {
+    let headers = request
+        .headers
+        .iter()
+        .filter(|(name, _)| !name.eq_ignore_ascii_case("Accept-Encoding"))
+        .map(|(name, value)| match name.eq_ignore_ascii_case("Authorization") {
+            true => (name.clone(), format!("Bearer {}", SCRUBBED)),
+            false => (name.clone(), value.clone()),
+        })
+        .collect();
+
+    let body = request.body.as_ref().map(|body| match request.header("Content-Type") {
+        Some("application/x-www-form-urlencoded") => body
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if SECRET_FIELDS.contains(&name) || name == "client_id" => format!("{}={}", name, SCRUBBED),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&"),
+        _ => body.clone(),
+    });
+
+    HttpRequest {
+        method: request.method.clone(),
+        url: request.url.clone(),
+        headers,
+        body,
+    }
+}
+
+fn to_response(recorded: &RecordedResponse) -> HttpResponse {
+    HttpResponse {
+        headers: recorded.headers.clone(),
+        ..HttpResponse::new(recorded.status, recorded.body.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    fn request(url: &str) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: vec![("Authorization".to_string(), "Bearer SECRET".to_string())],
+            body: None,
+        }
+    }
+
+    #[test]
+    fn scrubs_secrets() {
+        let form = HttpRequest {
+            method: "POST".to_string(),
+            headers: vec![("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string())],
+            body: Some("grant_type=refresh_token&refresh_token=SECRET&client_id=ID".to_string()),
+            ..request("https://api.tdameritrade.com/v1/oauth2/token")
+        };
+
+        let scrubbed = scrub_request(&form);
+
+        assert_eq!(scrubbed.body.as_deref(), Some("grant_type=refresh_token&refresh_token=***&client_id=***"));
+        assert_eq!(scrub_request(&request("/")).header("Authorization"), Some("Bearer ***"));
+    }
+
+    #[test]
+    fn replays_in_order() {
+        let path = std::env::temp_dir().join(format!("tda-sdk-cassette-{}.json", std::process::id()));
+        let response = |body: &str| RecordedResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: body.to_string(),
+        };
+        let cassette = Cassette {
+            interactions: vec![
+                Interaction {
+                    request: scrub_request(&request("https://example.com/a")),
+                    response: response("1"),
+                },
+                Interaction {
+                    request: scrub_request(&request("https://example.com/a")),
+                    response: response("2"),
+                },
+            ],
+        };
+
+        cassette.save(&path).unwrap();
+
+        let transport = CassetteTransport::replay(&path).unwrap();
+        let body = |url| {
+            let mut body = String::new();
+            transport.send(&request(url)).unwrap().body.read_to_string(&mut body).unwrap();
+            body
+        };
+
+        assert_eq!(body("https://example.com/a"), "1");
+        assert_eq!(body("https://example.com/a"), "2");
+        assert_eq!(body("https://example.com/a"), "2");
+        assert_eq!(transport.send(&request("https://example.com/b")).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn matches_query_in_any_order() {
+        let recorded = request("https://api.tdameritrade.com/v1/marketdata/quotes?symbol=AAPL%2CMSFT&fields=quote");
+
+        assert!(matches(&recorded, &request("https://api.tdameritrade.com/v1/marketdata/quotes?fields=quote&symbol=AAPL,MSFT")));
+        assert!(matches(&recorded, &request("http://localhost:8080/v1/marketdata/quotes?fields=quote&symbol=AAPL,MSFT")));
+        assert!(!matches(&recorded, &request("https://api.tdameritrade.com/v1/marketdata/quotes?symbol=AAPL")));
+        assert!(!matches(&recorded, &request("https://api.tdameritrade.com/v1/marketdata/AAPL/quotes?fields=quote&symbol=AAPL,MSFT")));
+    }
+
+    #[test]
+    fn records_through_inner_transport_without_account_ids() {
+        let path = std::env::temp_dir().join(format!("tda-sdk-cassette-accounts-{}.json", std::process::id()));
+        let mock = Arc::new(MockTransport::new());
+
+        mock.respond("GET", "/v1/accounts", 200, include_str!("../tests/fixtures/accounts.json"))
+            .respond_with_headers("POST", "/v1/accounts/123456789/orders", 201, "", &[("Location", "/v1/accounts/123456789/orders/42")]);
+
+        let transport = CassetteTransport::record(&path, mock.clone());
+
+        transport.send(&request("https://api.tdameritrade.com/v1/accounts")).unwrap();
+        transport
+            .send(&HttpRequest {
+                method: "POST".to_string(),
+                body: Some(r#"{"accountId":"123456789","orderType":"MARKET"}"#.to_string()),
+                ..request("https://api.tdameritrade.com/v1/accounts/123456789/orders")
+            })
+            .unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+        let interactions = Cassette::load(&path).unwrap().interactions;
+
+        assert_eq!(mock.requests().len(), 2);
+        assert!(!saved.contains("123456789"));
+        assert_eq!(interactions[1].request.url, "https://api.tdameritrade.com/v1/accounts/900000001/orders");
+        assert_eq!(interactions[1].request.body.as_deref(), Some(r#"{"accountId":"900000001","orderType":"MARKET"}"#));
+        assert!(interactions[1].response.headers.contains(&("Location".to_string(), "/v1/accounts/900000001/orders/42".to_string())));
+
+        let replay = CassetteTransport::replay(&path).unwrap();
+
+        assert_eq!(replay.mode(), Mode::Replay);
+        assert_eq!(replay.send(&request("https://api.tdameritrade.com/v1/accounts/900000001/orders")).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(replay.send(&interactions[1].request).unwrap().status, 201);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,322 @@
+//! Archive of option chain snapshots.
+//!
+//! The API has no historical options data, so an archive has to be built by
+//! snapshotting chains over time. A [`ChainRecorder`](struct.ChainRecorder.html)
+//! fetches the chains of some symbols on a schedule and appends a compact
+//! snapshot of each to a gzipped JSON lines file per symbol and trading day,
+//! which it can read back later:
+//!
+//! ```no_run
+//! use chrono::{Duration as ChronoDuration, NaiveDate, Utc};
+//! use std::time::Duration;
+//! use tda_sdk::{chain_recorder::ChainRecorder, params::GetOptionChainParams, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let mut recorder = ChainRecorder::new("chains");
+//! recorder.set_params(GetOptionChainParams {
+//!     strike_count: Some(20),
+//!     ..GetOptionChainParams::default()
+//! });
+//!
+//! recorder.run_until(&client, &["AAPL", "SPY"], Duration::from_secs(15 * 60), Utc::now() + ChronoDuration::hours(7));
+//!
+//! let snapshots = recorder.read("AAPL", NaiveDate::from_ymd_opt(2020, 5, 4).unwrap()).unwrap();
+//! ```
+//!
+//! Files are named `<dir>/<SYMBOL>/<YYYY-MM-DD>.jsonl.gz`, dated in the
+//! market's time zone. Every snapshot is its own gzip member, so a file can be
+//! appended to without rewriting it, and read with any gzip tool.
+
+use crate::{
+    params::GetOptionChainParams,
+    responses::{ExpirationMap, OptionChain},
+    time::MARKET_TIMEZONE,
+    Client,
+    ClientError,
+    Decimal,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use thiserror::Error;
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Extension of the files of an archive.
+const EXTENSION: &str = "jsonl.gz";
+
+/// Option chain at a point in time, keeping only what is needed to study it
+/// later.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ChainSnapshot {
+    #[serde(rename = "at")]
+    pub taken_at: DateTime<Utc>,
+    pub symbol: String,
+    #[serde(rename = "u", default, skip_serializing_if = "Option::is_none")]
+    pub underlying_price: Option<f64>,
+    #[serde(rename = "c")]
+    pub contracts: Vec<SnapshotContract>,
+}
+
+/// Contract of a [`ChainSnapshot`](struct.ChainSnapshot.html), with short
+/// field names to keep archives small.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SnapshotContract {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// `CALL` or `PUT`.
+    #[serde(rename = "pc")]
+    pub put_call: String,
+    #[serde(rename = "e")]
+    pub expiration: NaiveDate,
+    #[serde(rename = "k")]
+    pub strike: Decimal,
+    #[serde(rename = "b", default, skip_serializing_if = "Option::is_none")]
+    pub bid: Option<f64>,
+    #[serde(rename = "a", default, skip_serializing_if = "Option::is_none")]
+    pub ask: Option<f64>,
+    #[serde(rename = "l", default, skip_serializing_if = "Option::is_none")]
+    pub last: Option<f64>,
+    #[serde(rename = "m", default, skip_serializing_if = "Option::is_none")]
+    pub mark: Option<f64>,
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<i64>,
+    #[serde(rename = "oi", default, skip_serializing_if = "Option::is_none")]
+    pub open_interest: Option<i64>,
+    #[serde(rename = "iv", default, skip_serializing_if = "Option::is_none")]
+    pub volatility: Option<f64>,
+    #[serde(rename = "de", default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<f64>,
+    #[serde(rename = "ga", default, skip_serializing_if = "Option::is_none")]
+    pub gamma: Option<f64>,
+    #[serde(rename = "th", default, skip_serializing_if = "Option::is_none")]
+    pub theta: Option<f64>,
+    #[serde(rename = "ve", default, skip_serializing_if = "Option::is_none")]
+    pub vega: Option<f64>,
+}
+
+impl ChainSnapshot {
+    /// Snapshot of a chain fetched at `taken_at`.
+    pub fn from_chain(chain: &OptionChain, taken_at: DateTime<Utc>) -> Self {
+        let mut contracts = Vec::new();
+
+        for map in [&chain.call_exp_date_map, &chain.put_exp_date_map] {
+            contracts.extend(snapshot_contracts(map));
+        }
+
+        Self {
+            taken_at,
+            symbol: chain.symbol.clone(),
+            underlying_price: chain.underlying_price,
+            contracts,
+        }
+    }
+
+    /// Trading day of the snapshot, in the market's time zone.
+    pub fn date(&self) -> NaiveDate {
+        self.taken_at.with_timezone(&MARKET_TIMEZONE).date_naive()
+    }
+}
+
+fn snapshot_contracts(map: &ExpirationMap) -> impl Iterator<Item = SnapshotContract> + '_ {
+    map.iter().flat_map(|(expiration, strikes)| {
+        strikes.iter().flat_map(move |(strike, contracts)| {
+            contracts.iter().map(move |contract| SnapshotContract {
+                symbol: contract.symbol.clone(),
+                put_call: contract.put_call.clone(),
+                expiration: expiration.date,
+                strike: *strike,
+                bid: contract.bid,
+                ask: contract.ask,
+                last: contract.last,
+                mark: contract.mark,
+                volume: contract.total_volume,
+                open_interest: contract.open_interest,
+                volatility: contract.volatility,
+                delta: contract.delta,
+                gamma: contract.gamma,
+                theta: contract.theta,
+                vega: contract.vega,
+            })
+        })
+    })
+}
+
+/// Snapshots option chains into an archive directory, and reads them back.
+#[derive(Debug)]
+pub struct ChainRecorder {
+    dir: PathBuf,
+    params: GetOptionChainParams,
+}
+
+impl ChainRecorder {
+    /// Recorder archiving into `dir`, which is created when needed.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            params: GetOptionChainParams::default(),
+        }
+    }
+
+    /// Set the parameters chains are requested with, such as to limit the
+    /// strikes recorded. Defaults to the whole chain.
+    pub fn set_params(&mut self, params: GetOptionChainParams) -> &mut Self {
+        self.params = params;
+
+        self
+    }
+
+    /// File holding the snapshots of a symbol on a trading day.
+    pub fn path(&self, symbol: &str, date: NaiveDate) -> PathBuf {
+        self.symbol_dir(symbol).join(format!("{}.{}", date.format("%Y-%m-%d"), EXTENSION))
+    }
+
+    /// Fetch the chain of a symbol and append its snapshot to the archive.
+    pub fn record(&self, client: &Client, symbol: &str) -> Result<ChainSnapshot, ChainRecorderError> {
+        let chain = client.get_option_chain(symbol, self.params.clone())?;
+        let snapshot = ChainSnapshot::from_chain(&chain, client.clock().now());
+
+        self.write(&snapshot)?;
+
+        Ok(snapshot)
+    }
+
+    /// Record the chains of `symbols` every `interval` until `until`,
+    /// according to the clock of the client. Failures are logged and don't
+    /// stop the recording.
+    pub fn run_until(&self, client: &Client, symbols: &[&str], interval: Duration, until: DateTime<Utc>) {
+        while client.clock().now() < until {
+            let started = client.clock().instant();
+
+            for symbol in symbols {
+                if let Err(error) = self.record(client, symbol) {
+                    log::warn!("Failed to record the option chain of {}: {}", symbol, error);
+                }
+            }
+
+            if let Some(remaining) = interval.checked_sub(client.clock().instant().duration_since(started)) {
+                client.clock().sleep(remaining);
+            }
+        }
+    }
+
+    /// Append a snapshot to the archive.
+    pub fn write(&self, snapshot: &ChainSnapshot) -> io::Result<()> {
+        let path = self.path(&snapshot.symbol, snapshot.date());
+
+        fs::create_dir_all(self.symbol_dir(&snapshot.symbol))?;
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut encoder = GzEncoder::new(file, Compression::default());
+
+        serde_json::to_writer(&mut encoder, snapshot)?;
+        encoder.write_all(b"\n")?;
+        encoder.finish()?.flush()
+    }
+
+    /// Snapshots of a symbol on a trading day, in the order they were taken.
+    /// Returns nothing if none were recorded.
+    pub fn read(&self, symbol: &str, date: NaiveDate) -> io::Result<Vec<ChainSnapshot>> {
+        let file = match File::open(self.path(symbol, date)) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        BufReader::new(MultiGzDecoder::new(file))
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+
+    /// Trading days with snapshots of a symbol, sorted.
+    pub fn dates(&self, symbol: &str) -> io::Result<Vec<NaiveDate>> {
+        let entries = match fs::read_dir(self.symbol_dir(symbol)) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut dates = Vec::new();
+
+        for entry in entries {
+            let name = entry?.file_name();
+            let date = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(EXTENSION))
+                .and_then(|name| name.strip_suffix('.'))
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+
+            dates.extend(date);
+        }
+
+        dates.sort();
+
+        Ok(dates)
+    }
+
+    /// Directory of a symbol, with characters which can't appear in file
+    /// names replaced.
+    fn symbol_dir(&self, symbol: &str) -> PathBuf {
+        self.dir.join(symbol.replace(['/', '\\', ':'], "_"))
+    }
+}
+
+/// Errors encountered while recording a chain.
+#[derive(Debug, Error)]
+pub enum ChainRecorderError {
+    /// The chain couldn't be fetched.
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    /// The snapshot couldn't be written to the archive.
+    #[error("Failed to write chain snapshot: {0}")]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        mock::{test_client, MockTransport},
+    };
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    #[test]
+    fn records_and_reads_snapshots() {
+        let dir = std::env::temp_dir().join(format!("tda-sdk-chains-{}", std::process::id()));
+        let transport = Arc::new(MockTransport::new());
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2021, 6, 14, 15, 0, 0).unwrap()));
+        let mut client = test_client(transport.clone());
+        let recorder = ChainRecorder::new(&dir);
+
+        client.set_clock(clock.clone());
+        transport.respond("GET", "/v1/marketdata/chains", 200, include_str!("../tests/fixtures/option_chain.json"));
+
+        recorder.run_until(&client, &["AAPL"], Duration::from_secs(15 * 60), clock.now() + chrono::Duration::minutes(30));
+
+        let date = NaiveDate::from_ymd_opt(2021, 6, 14).unwrap();
+        let snapshots = recorder.read("AAPL", date).unwrap();
+        let dates = recorder.dates("AAPL").unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(transport.requests().len(), 2);
+        assert_eq!(dates, vec![date]);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].taken_at, Utc.with_ymd_and_hms(2021, 6, 14, 15, 15, 0).unwrap());
+
+        let contract = snapshots[0].contracts.iter().find(|contract| contract.symbol == "AAPL_071621C122.5").unwrap();
+
+        assert_eq!(contract.expiration, NaiveDate::from_ymd_opt(2021, 7, 16).unwrap());
+        assert_eq!(contract.strike, Decimal::new(1225, 1));
+        assert_eq!(contract.open_interest, Some(1500));
+        assert_eq!(recorder.read("AAPL", date.succ_opt().unwrap()).unwrap(), Vec::new());
+    }
+}
@@ -0,0 +1,132 @@
+//! Source of the current time.
+//!
+//! Token expiry, rate limiting, and the order guards depend on the current
+//! time. They read it from a [`Clock`](trait.Clock.html) instead of the system
+//! clock, so tests can control it with a [`MockClock`](struct.MockClock.html)
+//! rather than sleeping:
+//!
+//! ```
+//! use std::{sync::Arc, time::Duration};
+//! use chrono::{TimeZone, Utc};
+//! use tda_sdk::{clock::MockClock, rate_limit::RateLimiter};
+//!
+//! let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2020, 5, 4, 14, 30, 0).unwrap()));
+//! let mut limiter = RateLimiter::new(1, Duration::from_secs(10));
+//! limiter.set_clock(clock.clone());
+//!
+//! limiter.acquire();
+//!
+//! // Returns immediately, advancing the clock instead of sleeping.
+//! assert_eq!(limiter.acquire(), Duration::from_secs(10));
+//! assert_eq!(clock.now(), Utc.with_ymd_and_hms(2020, 5, 4, 14, 30, 10).unwrap());
+//! ```
+
+use chrono::{DateTime, Utc};
+
+use std::{
+    fmt,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Current wall clock and monotonic time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Current wall clock time, such as for comparing with expirations.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Current monotonic time, for measuring elapsed time.
+    fn instant(&self) -> Instant;
+
+    /// Block for `duration`.
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Clock of the system. Used unless another is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock which only moves when advanced, for tests. Sleeping advances it
+/// instead of blocking.
+#[derive(Debug)]
+pub struct MockClock {
+    start: DateTime<Utc>,
+    started: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl MockClock {
+    /// Clock stopped at `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            start: now,
+            started: Instant::now(),
+            elapsed: Mutex::default(),
+        }
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// Current time of the clock, without going through the trait.
+    pub fn now(&self) -> DateTime<Utc> {
+        Clock::now(self)
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = *self.elapsed.lock().unwrap();
+
+        self.start + chrono::Duration::from_std(elapsed).unwrap()
+    }
+
+    fn instant(&self) -> Instant {
+        self.started + *self.elapsed.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn mock_clock_advances() {
+        let start = Utc.with_ymd_and_hms(2020, 5, 4, 14, 30, 0).unwrap();
+        let clock = MockClock::new(start);
+        let instant = clock.instant();
+
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(90));
+        clock.sleep(Duration::from_millis(500));
+
+        assert_eq!(clock.now(), start + chrono::Duration::milliseconds(90_500));
+        assert_eq!(clock.instant() - instant, Duration::from_millis(90_500));
+    }
+}
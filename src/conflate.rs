@@ -0,0 +1,287 @@
+//! Conflation of quote updates for consumers which can't keep up with every
+//! tick.
+//!
+//! A [`Conflator`](struct.Conflator.html) merges the updates of each symbol
+//! into its latest snapshot, and hands out the snapshots of the symbols which
+//! changed once per interval. It works with streamed quotes, whose updates
+//! only carry the fields which changed, as well as with polled quotes:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tda_sdk::{conflate::Conflator, streamer::{parse_message, LevelOneQuote}};
+//!
+//! let mut conflator: Conflator<LevelOneQuote> = Conflator::new(Duration::from_millis(250));
+//!
+//! # let messages: Vec<String> = Vec::new();
+//! for message in messages {
+//!     for event in conflator.push_events(parse_message(&message).unwrap()) {
+//!         println!("{:?}", event);
+//!     }
+//!
+//!     if let Some(snapshots) = conflator.tick() {
+//!         for (symbol, quote) in snapshots {
+//!             println!("{} {:?}", symbol, quote.last_price);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Any iterator of updates can be conflated with
+//! [`conflate()`](fn.conflate.html), such as the events of an
+//! [`EventQueue`](../streamer/struct.EventQueue.html).
+
+use crate::{
+    clock::{Clock, SystemClock},
+    responses::{GetQuotesResponse, Quote},
+    streamer::{LevelOneQuote, StreamerEvent},
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Update which can be merged into the previous value of its symbol.
+pub trait Conflate: Clone {
+    /// Merge a newer update into this one.
+    fn conflate(&mut self, update: Self);
+}
+
+impl Conflate for LevelOneQuote {
+    fn conflate(&mut self, update: Self) {
+        self.merge(&update);
+    }
+}
+
+/// Polled quotes are complete, so the newest one replaces the previous.
+impl Conflate for Quote {
+    fn conflate(&mut self, update: Self) {
+        *self = update;
+    }
+}
+
+/// Merges updates per symbol, and releases the symbols which changed at most
+/// once per interval.
+#[derive(Debug)]
+pub struct Conflator<T> {
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+    latest: HashMap<String, T>,
+    changed: BTreeSet<String>,
+    released_at: Option<Instant>,
+}
+
+impl<T: Conflate> Conflator<T> {
+    /// Release changed symbols at most once every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            clock: Arc::new(SystemClock),
+            latest: HashMap::new(),
+            changed: BTreeSet::new(),
+            released_at: None,
+        }
+    }
+
+    /// Set the clock intervals are measured with. Defaults to the system
+    /// clock.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+        self.released_at = None;
+
+        self
+    }
+
+    /// Merge an update into the snapshot of its symbol.
+    pub fn push(&mut self, symbol: &str, update: T) {
+        match self.latest.get_mut(symbol) {
+            Some(latest) => latest.conflate(update),
+            None => {
+                self.latest.insert(symbol.to_string(), update);
+            }
+        }
+
+        self.changed.insert(symbol.to_string());
+    }
+
+    /// Snapshots of the symbols which changed since the last release, if an
+    /// interval passed since then. The first call releases right away.
+    pub fn tick(&mut self) -> Option<BTreeMap<String, T>> {
+        let now = self.clock.instant();
+
+        if self.released_at.is_some_and(|released_at| now.duration_since(released_at) < self.interval) {
+            return None;
+        }
+
+        self.released_at = Some(now);
+
+        Some(self.flush())
+    }
+
+    /// Snapshots of the symbols which changed since the last release,
+    /// regardless of the interval.
+    pub fn flush(&mut self) -> BTreeMap<String, T> {
+        std::mem::take(&mut self.changed)
+            .into_iter()
+            .filter_map(|symbol| self.latest.get(&symbol).cloned().map(|latest| (symbol, latest)))
+            .collect()
+    }
+
+    /// Latest snapshot of a symbol, whether released or not.
+    pub fn latest(&self, symbol: &str) -> Option<&T> {
+        self.latest.get(symbol)
+    }
+}
+
+impl Conflator<LevelOneQuote> {
+    /// Merge the quotes among streamer events, returning the other events so
+    /// they can be handled right away.
+    pub fn push_events(&mut self, events: Vec<StreamerEvent>) -> Vec<StreamerEvent> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                StreamerEvent::Quote(quote) => {
+                    let symbol = quote.symbol.clone();
+
+                    self.push(&symbol, quote);
+
+                    None
+                }
+                event => Some(event),
+            })
+            .collect()
+    }
+}
+
+impl Conflator<Quote> {
+    /// Merge quotes polled with `get_quotes()`.
+    pub fn push_quotes(&mut self, quotes: GetQuotesResponse) {
+        for (symbol, quote) in quotes {
+            self.push(&symbol, quote);
+        }
+    }
+}
+
+/// Conflate an iterator of updates keyed by symbol, yielding the snapshots of
+/// the symbols which changed at most once every `interval`.
+///
+/// Snapshots are only yielded when an update arrives after the interval, and
+/// once more when the updates run out.
+pub fn conflate<I, T>(updates: I, interval: Duration) -> Conflated<I::IntoIter, T>
+where
+    I: IntoIterator<Item = (String, T)>,
+    T: Conflate,
+{
+    Conflated {
+        updates: updates.into_iter(),
+        conflator: Conflator::new(interval),
+        done: false,
+    }
+}
+
+/// Iterator returned by [`conflate()`](fn.conflate.html).
+#[derive(Debug)]
+pub struct Conflated<I, T> {
+    updates: I,
+    conflator: Conflator<T>,
+    done: bool,
+}
+
+impl<I, T> Conflated<I, T> {
+    /// Set the clock intervals are measured with.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self
+    where
+        T: Conflate,
+    {
+        self.conflator.set_clock(clock);
+
+        self
+    }
+}
+
+impl<I, T> Iterator for Conflated<I, T>
+where
+    I: Iterator<Item = (String, T)>,
+    T: Conflate,
+{
+    type Item = BTreeMap<String, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        for (symbol, update) in self.updates.by_ref() {
+            self.conflator.push(&symbol, update);
+
+            if let Some(snapshots) = self.conflator.tick() {
+                return Some(snapshots);
+            }
+        }
+
+        self.done = true;
+
+        Some(self.conflator.flush()).filter(|snapshots| !snapshots.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn quote(symbol: &str, bid_price: Option<f64>, last_price: Option<f64>) -> LevelOneQuote {
+        LevelOneQuote {
+            symbol: symbol.to_string(),
+            bid_price,
+            last_price,
+            ..LevelOneQuote::default()
+        }
+    }
+
+    #[test]
+    fn conflates_streamed_quotes() {
+        let clock = Arc::new(MockClock::default());
+        let mut conflator = Conflator::new(Duration::from_secs(1));
+
+        conflator.set_clock(clock.clone());
+
+        let others = conflator.push_events(vec![
+            StreamerEvent::Quote(quote("AAPL", Some(120.0), Some(120.1))),
+            StreamerEvent::Heartbeat,
+            StreamerEvent::Quote(quote("MSFT", None, Some(180.0))),
+        ]);
+
+        assert_eq!(others, vec![StreamerEvent::Heartbeat]);
+        assert_eq!(conflator.tick().unwrap().len(), 2);
+
+        conflator.push("AAPL", quote("AAPL", None, Some(120.2)));
+        conflator.push("AAPL", quote("AAPL", Some(120.15), None));
+
+        assert_eq!(conflator.tick(), None);
+
+        clock.advance(Duration::from_secs(1));
+
+        let snapshots = conflator.tick().unwrap();
+
+        assert_eq!(snapshots.keys().collect::<Vec<_>>(), ["AAPL"]);
+        assert_eq!(snapshots["AAPL"], quote("AAPL", Some(120.15), Some(120.2)));
+    }
+
+    #[test]
+    fn conflates_iterators() {
+        let updates = vec![
+            ("AAPL".to_string(), quote("AAPL", None, Some(1.0))),
+            ("AAPL".to_string(), quote("AAPL", None, Some(2.0))),
+            ("MSFT".to_string(), quote("MSFT", None, Some(3.0))),
+        ];
+
+        let batches: Vec<BTreeMap<String, LevelOneQuote>> = conflate(updates, Duration::from_secs(60)).collect();
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0]["AAPL"].last_price, Some(1.0));
+        assert_eq!(batches[1]["AAPL"].last_price, Some(2.0));
+        assert_eq!(batches[1]["MSFT"].last_price, Some(3.0));
+    }
+}
@@ -0,0 +1,131 @@
+//! Correlation IDs tying requests to the calls which sent them.
+//!
+//! Every request carries a correlation ID in a header, `X-Correlation-ID` by
+//! default, which also prefixes the debug logs of the request and is kept in
+//! audit records. Calls which change something, such as placing an order,
+//! use one ID for all the requests they send. To trace a flow across
+//! services, run it in a [`scope()`](fn.scope.html) with an ID of your own:
+//!
+//! ```no_run
+//! use tda_sdk::{correlation, orders::{Instruction, Order}, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let order = Order::equity_market(Instruction::Buy, "AAPL", 1.0);
+//!
+//! let result = correlation::scope("rebalance-42", || client.place_order("123456789", &order));
+//!
+//! if let Err(error) = result {
+//!     eprintln!("[{}] {}", correlation::last().unwrap_or_default(), error);
+//! }
+//! ```
+//!
+//! IDs are kept per thread, so they follow a call on a client shared between
+//! threads. Work handed to other threads takes the ID along with
+//! [`within()`](fn.within.html). Errors from a response carry the ID of its
+//! request, see `ClientError::correlation_id()`.
+
+use std::{
+    cell::RefCell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Header requests carry their correlation ID in, by default.
+pub const DEFAULT_HEADER: &str = "X-Correlation-ID";
+
+thread_local! {
+    static SCOPED: RefCell<Option<String>> = const { RefCell::new(None) };
+    static LAST: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// New random correlation ID, such as `5f3c1a9e02b4d7c8-0000002a`.
+pub fn generate() -> String {
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_nanos() as u64).unwrap_or_default();
+    let mut hasher = RandomState::new().build_hasher();
+
+    hasher.write_u64(nanos);
+    hasher.write_u64(count);
+
+    format!("{:016x}-{:08x}", hasher.finish(), count as u32)
+}
+
+/// Run `f` with every request it sends on this thread carrying `id`. Scopes
+/// can be nested, and the outer ID is restored when `f` returns or panics.
+pub fn scope<T>(id: &str, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<String>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED.with(|scoped| *scoped.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(SCOPED.with(|scoped| scoped.replace(Some(id.to_string()))));
+
+    f()
+}
+
+/// Run `f` in the scope of `id`, if any, such as an ID taken with
+/// [`current()`](fn.current.html) before handing work to another thread.
+pub fn within<T>(id: Option<&str>, f: impl FnOnce() -> T) -> T {
+    match id {
+        Some(id) => scope(id, f),
+        None => f(),
+    }
+}
+
+/// ID of the enclosing [`scope()`](fn.scope.html) on this thread, if any.
+pub fn current() -> Option<String> {
+    SCOPED.with(|scoped| scoped.borrow().clone())
+}
+
+/// ID of the last request sent on this thread, to report along with its
+/// error.
+pub fn last() -> Option<String> {
+    LAST.with(|last| last.borrow().clone())
+}
+
+/// Run `f` in the enclosing scope, or in a new one with a generated ID, so
+/// all the requests of a call share an ID.
+pub(crate) fn in_call<T>(f: impl FnOnce(&str) -> T) -> T {
+    let id = current().unwrap_or_else(generate);
+
+    scope(&id, || f(&id))
+}
+
+/// ID of a request about to be sent: the scoped one, or a new one.
+pub(crate) fn next() -> String {
+    let id = current().unwrap_or_else(generate);
+
+    LAST.with(|last| *last.borrow_mut() = Some(id.clone()));
+
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_ids() {
+        assert_ne!(generate(), generate());
+        assert_eq!(current(), None);
+
+        scope("outer", || {
+            assert_eq!(next(), "outer");
+
+            scope("inner", || assert_eq!(current().as_deref(), Some("inner")));
+
+            assert_eq!(current().as_deref(), Some("outer"));
+        });
+
+        assert_eq!(current(), None);
+        assert_eq!(last().as_deref(), Some("outer"));
+        assert_ne!(next(), "outer");
+    }
+}
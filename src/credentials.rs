@@ -0,0 +1,250 @@
+//! Credentials of many users of the same app.
+//!
+//! Apps acting for many users, such as advisor tools, hold a client ID and
+//! refresh token per user. The [`CredentialManager`](struct.CredentialManager.html)
+//! keeps them keyed by user, hands out clients with a valid access token, and
+//! shares one [`RateLimiter`](../rate_limit/struct.RateLimiter.html) between
+//! all users of the same app key.
+//!
+//! ```no_run
+//! use tda_sdk::credentials::CredentialManager;
+//!
+//! let manager = CredentialManager::default();
+//!
+//! manager.add_user("alice", "CLIENT_ID", "ALICE_REFRESH_TOKEN");
+//! manager.add_user("bob", "CLIENT_ID", "BOB_REFRESH_TOKEN");
+//!
+//! let accounts = manager.client("alice").unwrap().get_accounts(Default::default()).unwrap();
+//!
+//! // Refresh tokens rotate when renewed, so store them again afterwards.
+//! for user in manager.renew_refresh_tokens(7) {
+//!     let refresh_token = manager.refresh_token(&user).unwrap();
+//! }
+//! ```
+
+use crate::{
+    rate_limit::{RateLimiter, DEFAULT_REQUESTS_PER_MINUTE},
+    AccessToken,
+    Client,
+    ClientError,
+};
+use thiserror::Error;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Client ID and tokens of a single user.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Credentials {
+    pub client_id: String,
+    pub refresh_token: String,
+    /// Timestamp in milliseconds when the refresh token expires, if known.
+    pub refresh_token_expires_at: Option<i64>,
+    pub access_token: Option<AccessToken>,
+}
+
+/// Holds the credentials of many users, keyed by user.
+#[derive(Debug)]
+pub struct CredentialManager {
+    users: Mutex<HashMap<String, Arc<Mutex<Credentials>>>>,
+    rate_limiters: Mutex<HashMap<String, Arc<RateLimiter>>>,
+    requests_per_minute: usize,
+}
+
+impl CredentialManager {
+    /// Create a manager limiting each app key to `requests_per_minute`.
+    pub fn new(requests_per_minute: usize) -> Self {
+        Self {
+            users: Mutex::default(),
+            rate_limiters: Mutex::default(),
+            requests_per_minute,
+        }
+    }
+
+    /// Add a user, replacing their credentials if they were already added.
+    pub fn add_user(&self, user: &str, client_id: &str, refresh_token: &str) {
+        self.insert(user, Credentials {
+            client_id: client_id.to_string(),
+            refresh_token: refresh_token.to_string(),
+            refresh_token_expires_at: None,
+            access_token: None,
+        });
+    }
+
+    /// Add a user with credentials restored from storage.
+    pub fn insert(&self, user: &str, credentials: Credentials) {
+        self.users.lock().unwrap().insert(user.to_string(), Arc::new(Mutex::new(credentials)));
+    }
+
+    /// Remove a user, returning their credentials.
+    pub fn remove_user(&self, user: &str) -> Option<Credentials> {
+        let credentials = self.users.lock().unwrap().remove(user)?;
+        let credentials = credentials.lock().unwrap().clone();
+
+        Some(credentials)
+    }
+
+    /// Every user, in no particular order.
+    pub fn users(&self) -> Vec<String> {
+        self.users.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Current credentials of a user.
+    pub fn credentials(&self, user: &str) -> Option<Credentials> {
+        Some(self.user(user).ok()?.lock().unwrap().clone())
+    }
+
+    /// Current refresh token of a user, which changes when it is renewed.
+    pub fn refresh_token(&self, user: &str) -> Option<String> {
+        self.credentials(user).map(|credentials| credentials.refresh_token)
+    }
+
+    /// Rate limiter shared by every client of an app key.
+    pub fn rate_limiter(&self, client_id: &str) -> Arc<RateLimiter> {
+        self.rate_limiters
+            .lock()
+            .unwrap()
+            .entry(client_id.to_string())
+            .or_insert_with(|| Arc::new(RateLimiter::per_minute(self.requests_per_minute)))
+            .clone()
+    }
+
+    /// Create a client for a user, with a valid access token.
+    ///
+    /// The access token is reused across clients of the same user until it
+    /// expires, and refreshing it is serialized per user, so concurrent calls
+    /// for one user only refresh it once.
+    pub fn client(&self, user: &str) -> Result<Client, CredentialError> {
+        let credentials = self.user(user)?;
+        let mut credentials = credentials.lock().unwrap();
+        let mut client = self.build(&credentials);
+
+        if client.access_token_needs_refresh() {
+            client.refresh_access_token()?;
+            credentials.access_token = client.access_token.clone();
+        }
+
+        Ok(client)
+    }
+
+    /// Get a new access token for a user, even if the current one is valid.
+    pub fn refresh(&self, user: &str) -> Result<(), CredentialError> {
+        let credentials = self.user(user)?;
+        let mut credentials = credentials.lock().unwrap();
+        let mut client = self.build(&credentials);
+
+        client.refresh_access_token()?;
+        credentials.access_token = client.access_token;
+
+        Ok(())
+    }
+
+    /// Renew the refresh token of every user whose token expires within
+    /// `days`, returning the users whose token was renewed.
+    ///
+    /// Failures are logged and skipped, so one revoked user doesn't prevent
+    /// renewing the others.
+    pub fn renew_refresh_tokens(&self, days: i64) -> Vec<String> {
+        let users: Vec<(String, Arc<Mutex<Credentials>>)> = self
+            .users
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(user, credentials)| (user.clone(), credentials.clone()))
+            .collect();
+        let mut renewed = Vec::new();
+
+        for (user, credentials) in users {
+            let mut credentials = credentials.lock().unwrap();
+            let mut client = self.build(&credentials);
+
+            if !client.refresh_token_expires_within(days) {
+                continue;
+            }
+
+            match client.renew_refresh_token() {
+                Ok(_) => {
+                    credentials.refresh_token = client.refresh_token().to_string();
+                    credentials.refresh_token_expires_at = client.refresh_token_expires_at();
+                    credentials.access_token = client.access_token;
+                    renewed.push(user);
+                }
+                Err(error) => log::warn!("Failed to renew the refresh token of {}: {}", user, error),
+            }
+        }
+
+        renewed
+    }
+
+    fn user(&self, user: &str) -> Result<Arc<Mutex<Credentials>>, CredentialError> {
+        self.users
+            .lock()
+            .unwrap()
+            .get(user)
+            .cloned()
+            .ok_or_else(|| CredentialError::UnknownUser(user.to_string()))
+    }
+
+    fn build(&self, credentials: &Credentials) -> Client {
+        let mut client = Client::new(&credentials.client_id, &credentials.refresh_token, credentials.access_token.clone());
+
+        client
+            .set_refresh_token_expires_at(credentials.refresh_token_expires_at)
+            .set_rate_limiter(Some(self.rate_limiter(&credentials.client_id)));
+
+        client
+    }
+}
+
+impl Default for CredentialManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_REQUESTS_PER_MINUTE)
+    }
+}
+
+/// Represents all possible errors of the
+/// [`CredentialManager`](struct.CredentialManager.html).
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    /// No credentials were added for the user.
+    #[error("Unknown user: {0}")]
+    UnknownUser(String),
+
+    /// Refreshing a token failed.
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manage_users() {
+        let manager = CredentialManager::default();
+        let token = AccessToken {
+            expires_at: i64::MAX,
+            scope: Vec::new(),
+            token: "ACCESS_TOKEN".to_string(),
+        };
+
+        manager.add_user("alice", "APP", "ALICE_REFRESH_TOKEN");
+        manager.insert("bob", Credentials {
+            client_id: "APP".to_string(),
+            refresh_token: "BOB_REFRESH_TOKEN".to_string(),
+            refresh_token_expires_at: None,
+            access_token: Some(token.clone()),
+        });
+
+        // A valid access token is reused without refreshing.
+        assert_eq!(manager.client("bob").unwrap().access_token, Some(token));
+        assert!(matches!(manager.client("carol"), Err(CredentialError::UnknownUser(_))));
+        assert!(Arc::ptr_eq(&manager.rate_limiter("APP"), &manager.rate_limiter("APP")));
+        assert!(!Arc::ptr_eq(&manager.rate_limiter("APP"), &manager.rate_limiter("OTHER_APP")));
+
+        assert_eq!(manager.remove_user("alice").unwrap().refresh_token, "ALICE_REFRESH_TOKEN");
+        assert_eq!(manager.users(), vec!["bob".to_string()]);
+    }
+}
@@ -0,0 +1,158 @@
+//! Quotes with relative volume and distance from the 52-week range.
+//!
+//! A [`QuoteEnriched`](struct.QuoteEnriched.html) joins a quote with the
+//! daily candles of its symbol, comparing today's volume with the average of
+//! the previous days and the price with its 52-week high and low.
+//! [`Scanner::enriched_quotes()`](../scanner/struct.Scanner.html#method.enriched_quotes)
+//! fetches both for many symbols at once:
+//!
+//! ```no_run
+//! use tda_sdk::{scanner::Scanner, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let report = Scanner::new().enriched_quotes(&client, &["AAPL", "MSFT"]);
+//!
+//! for hit in &report.hits {
+//!     let quote = &hit.value;
+//!
+//!     println!("{}: {:?}x volume, {:?} from the high", hit.symbol, quote.relative_volume, quote.from_high);
+//! }
+//! ```
+
+use crate::{
+    params::GetPriceHistoryParams,
+    responses::{Candle, Quote},
+    time::TdaDateTime,
+};
+use chrono::NaiveDate;
+
+/// Days of volume averaged for the relative volume, by default.
+pub const DEFAULT_VOLUME_DAYS: usize = 20;
+
+/// Quote with statistics computed from daily candles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuoteEnriched {
+    pub quote: Quote,
+    /// Average daily volume of the days before today.
+    pub average_volume: Option<f64>,
+    /// Volume so far today divided by the average daily volume.
+    pub relative_volume: Option<f64>,
+    /// From the quote, or from the candles of the last 52 weeks if the quote
+    /// doesn't have it.
+    pub fifty_two_week_high: Option<f64>,
+    /// From the quote, or from the candles of the last 52 weeks if the quote
+    /// doesn't have it.
+    pub fifty_two_week_low: Option<f64>,
+    /// Distance of the last price below the 52-week high, as a fraction of
+    /// the high. Zero or negative.
+    pub from_high: Option<f64>,
+    /// Distance of the last price above the 52-week low, as a fraction of the
+    /// low. Zero or positive.
+    pub from_low: Option<f64>,
+}
+
+impl QuoteEnriched {
+    /// Enrich a quote with the daily candles of its symbol, averaging the
+    /// volume of the [`DEFAULT_VOLUME_DAYS`](constant.DEFAULT_VOLUME_DAYS.html)
+    /// days before `today`.
+    pub fn new(quote: Quote, candles: &[Candle], today: NaiveDate) -> Self {
+        Self::with_volume_days(quote, candles, today, DEFAULT_VOLUME_DAYS)
+    }
+
+    /// Like [`new()`](#method.new), averaging the volume of `volume_days`
+    /// days.
+    pub fn with_volume_days(quote: Quote, candles: &[Candle], today: NaiveDate, volume_days: usize) -> Self {
+        let dated: Vec<(NaiveDate, &Candle)> = candles
+            .iter()
+            .filter_map(|candle| Some((TdaDateTime::from_millis(candle.datetime)?.date(), candle)))
+            .collect();
+
+        let previous: Vec<f64> = dated
+            .iter()
+            .filter(|(date, _)| *date < today)
+            .rev()
+            .take(volume_days)
+            .map(|(_, candle)| candle.volume as f64)
+            .collect();
+        let average_volume = (!previous.is_empty()).then(|| previous.iter().sum::<f64>() / previous.len() as f64);
+
+        let year_ago = today - chrono::Duration::weeks(52);
+        let year = dated.iter().filter(|(date, _)| *date >= year_ago).map(|(_, candle)| candle);
+        let fifty_two_week_high = quote.fifty_two_week_high().or_else(|| year.clone().map(|candle| candle.high).reduce(f64::max));
+        let fifty_two_week_low = quote.fifty_two_week_low().or_else(|| year.map(|candle| candle.low).reduce(f64::min));
+
+        let price = quote.last_price();
+        let relative_volume = match (quote.total_volume(), average_volume) {
+            (Some(volume), Some(average)) if average > 0.0 => Some(volume as f64 / average),
+            _ => None,
+        };
+
+        Self {
+            average_volume,
+            relative_volume,
+            fifty_two_week_high,
+            fifty_two_week_low,
+            from_high: distance(price, fifty_two_week_high),
+            from_low: distance(price, fifty_two_week_low),
+            quote,
+        }
+    }
+}
+
+/// Price history needed to enrich quotes: a year of daily candles.
+pub fn price_history_params() -> GetPriceHistoryParams {
+    GetPriceHistoryParams::daily_for_years(1)
+}
+
+fn distance(price: Option<f64>, reference: Option<f64>) -> Option<f64> {
+    match (price, reference) {
+        (Some(price), Some(reference)) if reference > 0.0 => Some(price / reference - 1.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candle(date: NaiveDate, high: f64, low: f64, volume: i64) -> Candle {
+        Candle {
+            close: high,
+            datetime: TdaDateTime::from_date(date).unwrap().timestamp_millis(),
+            high,
+            low,
+            open: low,
+            volume,
+        }
+    }
+
+    #[test]
+    fn enriches_quotes() {
+        let today = NaiveDate::from_ymd_opt(2020, 5, 6).unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2020, 5, day).unwrap();
+        let candles = vec![
+            candle(NaiveDate::from_ymd_opt(2019, 1, 2).unwrap(), 500.0, 1.0, 0),
+            candle(day(1), 110.0, 90.0, 1_000),
+            candle(day(4), 120.0, 95.0, 2_000),
+            candle(day(5), 115.0, 80.0, 3_000),
+            candle(day(6), 130.0, 100.0, 9_000),
+        ];
+        let quote = Quote::from_value(json!({"assetType": "EQUITY", "symbol": "AAPL", "lastPrice": 104.0, "totalVolume": 5_000}));
+
+        let enriched = QuoteEnriched::with_volume_days(quote, &candles, today, 2);
+
+        // Today's candle is left out of the average, and the 2019 candle
+        // out of the 52-week range.
+        assert_eq!(enriched.average_volume, Some(2_500.0));
+        assert_eq!(enriched.relative_volume, Some(2.0));
+        assert_eq!(enriched.fifty_two_week_high, Some(130.0));
+        assert_eq!(enriched.fifty_two_week_low, Some(80.0));
+        assert!((enriched.from_high.unwrap() + 0.2).abs() < 1e-12);
+        assert!((enriched.from_low.unwrap() - 0.3).abs() < 1e-12);
+
+        let quote = Quote::from_value(json!({"assetType": "EQUITY", "symbol": "AAPL", "lastPrice": 104.0, "52WkHigh": 208.0}));
+
+        assert_eq!(QuoteEnriched::new(quote, &candles, today).fifty_two_week_high, Some(208.0));
+    }
+}
@@ -0,0 +1,347 @@
+//! Pre-trade check against earnings and ex-dividend dates.
+//!
+//! An [`EventGuard`](struct.EventGuard.html) set on the client with
+//! `Client::set_event_guard()` checks every order before it is placed, and
+//! rejects it, or logs a warning, when one of its symbols has an event within
+//! a blackout window around today. Options are checked against their
+//! underlying.
+//!
+//! Ex-dividend dates come from the fundamental data of each symbol, fetched
+//! again once a day by default. TDA doesn't report upcoming earnings dates,
+//! so those have to be added from another source. Windows are in calendar
+//! days, so one spanning a weekend or holiday covers fewer trading days, and
+//! today is the date in `America/New_York`.
+//!
+//! ```no_run
+//! use chrono::NaiveDate;
+//! use std::sync::Arc;
+//! use tda_sdk::{
+//!     event_guard::EventGuard,
+//!     orders::{Instruction, Order},
+//!     Client,
+//! };
+//!
+//! let mut guard = EventGuard::new(2, 1);
+//! guard.add_earnings_date("AAPL", NaiveDate::from_ymd_opt(2020, 7, 30).unwrap());
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! client.set_event_guard(Some(Arc::new(guard)));
+//!
+//! // Fails with `ClientError::OrderRejected` from two days before the
+//! // earnings until the day after.
+//! client.place_order("123456789", &Order::equity_market(Instruction::Buy, "AAPL", 10.0)).unwrap();
+//! ```
+
+use crate::{
+    instruments::Instrument,
+    orders::Order,
+    time::MARKET_TIMEZONE,
+    Client,
+    ClientError,
+    Symbol,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+};
+
+/// Kind of a [`CorporateEvent`](struct.CorporateEvent.html).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EventKind {
+    Earnings,
+    ExDividend,
+}
+
+/// Dated event of a symbol which orders are kept away from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CorporateEvent {
+    pub symbol: String,
+    pub kind: EventKind,
+    pub date: NaiveDate,
+}
+
+impl fmt::Display for CorporateEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            EventKind::Earnings => "earnings",
+            EventKind::ExDividend => "ex-dividend date",
+        };
+
+        write!(f, "{} {} on {}", self.symbol, kind, self.date)
+    }
+}
+
+/// What an [`EventGuard`](struct.EventGuard.html) does with orders inside a
+/// blackout window.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GuardAction {
+    /// Fail the order with `ClientError::OrderRejected`.
+    #[default]
+    Reject,
+
+    /// Log a warning and place the order anyway.
+    Warn,
+}
+
+/// How long a looked up ex-dividend date is used before it's looked up again.
+pub const DEFAULT_EX_DIVIDEND_TTL: Duration = Duration::days(1);
+
+/// Rejects or warns on orders placed around earnings and ex-dividend dates.
+#[derive(Debug)]
+pub struct EventGuard {
+    action: GuardAction,
+    days_after: i64,
+    days_before: i64,
+    earnings_dates: HashMap<String, Vec<NaiveDate>>,
+    /// Ex-dividend date of each symbol already looked up.
+    ex_dividend_dates: Mutex<HashMap<String, CachedDate>>,
+    ex_dividend_ttl: Duration,
+}
+
+/// Ex-dividend date of a symbol, `None` if it has none, along with when it
+/// expires. Dates set by hand never do.
+#[derive(Clone, Copy, Debug)]
+struct CachedDate {
+    date: Option<NaiveDate>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl EventGuard {
+    /// Guard orders from `days_before` calendar days before an event until
+    /// `days_after` calendar days after it, both inclusive.
+    pub fn new(days_before: i64, days_after: i64) -> Self {
+        Self {
+            action: GuardAction::default(),
+            days_after,
+            days_before,
+            earnings_dates: HashMap::new(),
+            ex_dividend_dates: Mutex::default(),
+            ex_dividend_ttl: DEFAULT_EX_DIVIDEND_TTL,
+        }
+    }
+
+    /// Set whether orders in a blackout window are rejected or only warned
+    /// about. Defaults to rejecting them.
+    pub fn set_action(&mut self, action: GuardAction) -> &mut Self {
+        self.action = action;
+
+        self
+    }
+
+    /// Add an upcoming earnings date of a symbol.
+    pub fn add_earnings_date(&mut self, symbol: &str, date: NaiveDate) -> &mut Self {
+        self.earnings_dates.entry(symbol.to_string()).or_default().push(date);
+
+        self
+    }
+
+    /// Set how long a looked up ex-dividend date is used before it's looked
+    /// up again. Defaults to a day.
+    pub fn set_ex_dividend_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ex_dividend_ttl = ttl;
+
+        self
+    }
+
+    /// Set the ex-dividend date of a symbol instead of looking it up in its
+    /// fundamental data.
+    pub fn set_ex_dividend_date(&self, symbol: &str, date: Option<NaiveDate>) -> &Self {
+        self.ex_dividend_dates.lock().unwrap().insert(symbol.to_string(), CachedDate { date, expires_at: None });
+
+        self
+    }
+
+    /// Known events of a symbol, looking up its ex-dividend date the first
+    /// time and once the last lookup expires.
+    pub fn events(&self, client: &Client, symbol: &str) -> Result<Vec<CorporateEvent>, ClientError> {
+        let now = client.clock().now();
+        let cached = self.ex_dividend_dates.lock().unwrap().get(symbol).copied();
+        let ex_dividend_date = match cached {
+            Some(cached) if cached.expires_at.is_none_or(|expires_at| now < expires_at) => cached.date,
+            _ => {
+                let date = client.get_dividend_info(symbol)?.and_then(|dividend| dividend.ex_date);
+
+                let expires_at = Some(now + self.ex_dividend_ttl);
+
+                self.ex_dividend_dates.lock().unwrap().insert(symbol.to_string(), CachedDate { date, expires_at });
+
+                date
+            }
+        };
+
+        let earnings = self
+            .earnings_dates
+            .get(symbol)
+            .into_iter()
+            .flatten()
+            .map(|date| (EventKind::Earnings, *date));
+
+        Ok(earnings
+            .chain(ex_dividend_date.map(|date| (EventKind::ExDividend, date)))
+            .map(|(kind, date)| CorporateEvent {
+                symbol: symbol.to_string(),
+                kind,
+                date,
+            })
+            .collect())
+    }
+
+    /// Events of the symbols of an order whose blackout window includes
+    /// `today`.
+    pub fn violations(&self, client: &Client, order: &Order, today: NaiveDate) -> Result<Vec<CorporateEvent>, ClientError> {
+        let mut violations = Vec::new();
+
+        for symbol in order_symbols(order) {
+            violations.extend(self.events(client, &symbol)?.into_iter().filter(|event| {
+                today >= event.date - Duration::days(self.days_before) && today <= event.date + Duration::days(self.days_after)
+            }));
+        }
+
+        Ok(violations)
+    }
+
+    /// Check an order about to be placed, failing with
+    /// `ClientError::OrderRejected` if it violates a blackout window and the
+    /// guard rejects violations. Today is the date in the market timezone, so
+    /// evening orders aren't checked against the next UTC day.
+    pub fn check(&self, client: &Client, order: &Order) -> Result<(), ClientError> {
+        let today = client.clock().now().with_timezone(&MARKET_TIMEZONE).date_naive();
+        let violations = self.violations(client, order, today)?;
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let events: Vec<String> = violations.iter().map(CorporateEvent::to_string).collect();
+
+        match self.action {
+            GuardAction::Reject => Err(ClientError::OrderRejected(format!("too close to {}", events.join(", ")))),
+            GuardAction::Warn => {
+                log::warn!("Placing order close to {}", events.join(", "));
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Symbols an order trades, with options replaced by their underlying.
+fn order_symbols(order: &Order) -> Vec<String> {
+    let mut symbols: Vec<String> = order
+        .order_leg_collection
+        .iter()
+        .map(|leg| match &leg.instrument {
+            Instrument::Option {
+                underlying_symbol: Some(underlying),
+                ..
+            } => underlying.clone(),
+            instrument => match Symbol::parse(instrument.symbol()) {
+                Ok(Symbol::Option(option)) => option.underlying().to_string(),
+                _ => instrument.symbol().to_string(),
+            },
+        })
+        .collect();
+
+    symbols.sort();
+    symbols.dedup();
+
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        mock::{test_client, MockTransport},
+        orders::{ComplexOrderStrategyType, Instruction, OrderLeg, OrderType},
+    };
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2020, month, day).unwrap()
+    }
+
+    #[test]
+    fn blackout_windows() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let mut guard = EventGuard::new(2, 1);
+
+        transport.respond("GET", "/v1/instruments", 200, include_str!("../tests/fixtures/instruments_fundamental.json"));
+        guard.add_earnings_date("AAPL", date(7, 30));
+
+        let order = Order::option_spread(ComplexOrderStrategyType::Vertical, OrderType::NetDebit, Some(1.0), vec![
+            OrderLeg::option(Instruction::BuyToOpen, "AAPL_051520C300", 1.0),
+            OrderLeg::option(Instruction::SellToOpen, "AAPL_051520C310", 1.0),
+        ]);
+
+        // The ex-dividend date of AAPL is 2020-05-08 in the fixture.
+        assert_eq!(guard.violations(&client, &order, date(5, 5)).unwrap(), []);
+        assert_eq!(guard.violations(&client, &order, date(5, 6)).unwrap()[0].kind, EventKind::ExDividend);
+        assert_eq!(guard.violations(&client, &order, date(5, 9)).unwrap().len(), 1);
+        assert_eq!(guard.violations(&client, &order, date(5, 10)).unwrap(), []);
+        assert_eq!(guard.violations(&client, &order, date(7, 31)).unwrap()[0].to_string(), "AAPL earnings on 2020-07-30");
+
+        // Fundamentals are only fetched once.
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn looks_up_expired_dates_again() {
+        let transport = Arc::new(MockTransport::new());
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2020, 5, 4, 14, 30, 0).unwrap()));
+        let mut client = test_client(transport.clone());
+        let guard = EventGuard::new(0, 0);
+
+        transport.respond("GET", "/v1/instruments", 200, include_str!("../tests/fixtures/instruments_fundamental.json"));
+        client.set_clock(clock.clone());
+
+        guard.events(&client, "AAPL").unwrap();
+        clock.advance(std::time::Duration::from_secs(23 * 60 * 60));
+        guard.events(&client, "AAPL").unwrap();
+
+        assert_eq!(transport.requests().len(), 1);
+
+        clock.advance(std::time::Duration::from_secs(60 * 60));
+        guard.events(&client, "AAPL").unwrap();
+
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[test]
+    fn checks_the_market_date() {
+        let transport = Arc::new(MockTransport::new());
+        // 9pm in New York, already the next day in UTC.
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2020, 5, 8, 1, 0, 0).unwrap()));
+        let mut client = test_client(transport.clone());
+        let guard = EventGuard::new(0, 0);
+
+        guard.set_ex_dividend_date("AAPL", Some(date(5, 7)));
+        client.set_clock(clock);
+
+        let order = Order::equity_market(Instruction::Buy, "AAPL", 1.0);
+
+        assert!(matches!(guard.check(&client, &order), Err(ClientError::OrderRejected(_))));
+    }
+
+    #[test]
+    fn rejects_orders() {
+        let transport = Arc::new(MockTransport::new());
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2020, 5, 7, 15, 0, 0).unwrap()));
+        let mut client = test_client(transport.clone());
+        let guard = EventGuard::new(0, 0);
+
+        guard.set_ex_dividend_date("AAPL", Some(date(5, 7)));
+        client.set_clock(clock).set_event_guard(Some(Arc::new(guard)));
+
+        let result = client.place_order("123", &Order::equity_market(Instruction::Buy, "AAPL", 1.0));
+
+        assert!(matches!(result, Err(ClientError::OrderRejected(ref message)) if message.contains("AAPL ex-dividend date")));
+        assert_eq!(transport.requests(), []);
+    }
+}
@@ -0,0 +1,202 @@
+//! Deserializers for numbers the API sometimes sends as strings.
+//!
+//! Some endpoints intermittently quote numeric fields, sending `"12.5"`
+//! where they usually send `12.5`, or send `"NaN"` for values they couldn't
+//! compute. The money and quantity fields of the response structs use these
+//! helpers so both forms parse, and structs of apps can use them too:
+//!
+//! ```
+//! use tda_sdk::flexible::{flexible_f64, flexible_option_f64};
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Fill {
+//!     #[serde(deserialize_with = "flexible_f64")]
+//!     price: f64,
+//!     #[serde(default, deserialize_with = "flexible_option_f64")]
+//!     commission: Option<f64>,
+//! }
+//!
+//! let fill: Fill = serde_json::from_str(r#"{"price": "1,234.5", "commission": "NaN"}"#).unwrap();
+//!
+//! assert_eq!(fill.price, 1234.5);
+//! assert_eq!(fill.commission, None);
+//! ```
+//!
+//! Fields using `flexible_option_f64()` need `#[serde(default)]` to stay
+//! optional.
+//!
+//! Timestamps come in seconds from some endpoints and in milliseconds from
+//! others. [`epoch_millis()`](fn.epoch_millis.html) tells them apart by size
+//! and always gives milliseconds:
+//!
+//! ```
+//! use tda_sdk::flexible::normalize_epoch_millis;
+//!
+//! assert_eq!(normalize_epoch_millis(1588622400.0), 1588622400000);
+//! assert_eq!(normalize_epoch_millis(1588622400000.0), 1588622400000);
+//! ```
+
+use crate::money::{self, Locale};
+use rust_decimal::prelude::ToPrimitive;
+use serde::{de, Deserializer};
+
+use std::fmt;
+
+/// Deserialize a number sent as a JSON number or as a string. Strings may
+/// use thousands separators and a dollar sign, and `"NaN"` becomes `f64::NAN`.
+pub fn flexible_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    deserializer
+        .deserialize_any(FlexibleVisitor)?
+        .ok_or_else(|| de::Error::invalid_type(de::Unexpected::Unit, &FlexibleVisitor))
+        .map(|value| value.unwrap_or(f64::NAN))
+}
+
+/// Deserialize an optional number sent as a JSON number or as a string, with
+/// `null` and `"NaN"` as `None`.
+pub fn flexible_option_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Ok(deserializer.deserialize_option(FlexibleVisitor)?.flatten())
+}
+
+/// Timestamps below this many milliseconds, early 1973, are taken as seconds.
+/// Timestamps in seconds stay below it until the year 5138.
+const SECONDS_THRESHOLD: f64 = 1e11;
+
+/// Timestamps from this many milliseconds, the year 5138, are taken as
+/// microseconds, or nanoseconds if they are larger still.
+const MICROSECONDS_THRESHOLD: f64 = 1e14;
+
+/// Milliseconds since epoch of a timestamp in seconds, milliseconds,
+/// microseconds, or nanoseconds, told apart by its size. Timestamps before
+/// the epoch are negative and told apart by the size of their absolute
+/// value. Non-finite timestamps become `0`.
+pub fn normalize_epoch_millis(timestamp: f64) -> i64 {
+    if !timestamp.is_finite() {
+        return 0;
+    }
+
+    let mut millis = timestamp.abs();
+
+    if millis < SECONDS_THRESHOLD {
+        millis *= 1000.0;
+    }
+
+    while millis >= MICROSECONDS_THRESHOLD {
+        millis /= 1000.0;
+    }
+
+    (millis.round() as i64) * timestamp.signum() as i64
+}
+
+/// Deserialize a timestamp sent in seconds or milliseconds since epoch, as a
+/// number or a string, into milliseconds. See
+/// [`normalize_epoch_millis()`](fn.normalize_epoch_millis.html). Timestamps
+/// before the epoch stay negative.
+pub fn epoch_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    flexible_f64(deserializer).map(normalize_epoch_millis)
+}
+
+/// Visits a number, a numeric string, or nothing. `None` is for a missing
+/// value, and `Some(None)` for `"NaN"`.
+struct FlexibleVisitor;
+
+impl<'de> de::Visitor<'de> for FlexibleVisitor {
+    type Value = Option<Option<f64>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a number or a numeric string")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(Some(Some(value as f64)))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(Some(Some(value as f64)))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(Some(Some(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        let trimmed = value.trim();
+
+        if trimmed.eq_ignore_ascii_case("NaN") {
+            return Ok(Some(None));
+        }
+
+        trimmed
+            .parse::<f64>()
+            .ok()
+            .or_else(|| money::parse_amount(trimmed, Locale::EnUs).ok()?.to_f64())
+            .map(|number| Some(Some(number)))
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Fields {
+        #[serde(deserialize_with = "flexible_f64")]
+        required: f64,
+        #[serde(default, deserialize_with = "flexible_option_f64")]
+        optional: Option<f64>,
+    }
+
+    fn parse(json: &str) -> Result<Fields, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn numbers_and_strings() {
+        assert_eq!(parse(r#"{"required": 1, "optional": 2.5}"#).unwrap(), Fields { required: 1.0, optional: Some(2.5) });
+        assert_eq!(parse(r#"{"required": " -1.5 ", "optional": "$1,000"}"#).unwrap(), Fields { required: -1.5, optional: Some(1000.0) });
+        assert_eq!(parse(r#"{"required": "1e3", "optional": null}"#).unwrap().required, 1000.0);
+        assert_eq!(parse(r#"{"required": 0, "optional": "NaN"}"#).unwrap().optional, None);
+        assert_eq!(parse(r#"{"required": 0}"#).unwrap().optional, None);
+        assert!(parse(r#"{"required": "NaN"}"#).unwrap().required.is_nan());
+        assert!(parse(r#"{"required": null}"#).is_err());
+        assert!(parse(r#"{"required": "abc"}"#).is_err());
+        assert!(parse(r#"{"required": true}"#).is_err());
+    }
+
+    #[test]
+    fn epoch_resolutions() {
+        #[derive(Deserialize)]
+        struct Stamp {
+            #[serde(deserialize_with = "epoch_millis")]
+            datetime: i64,
+        }
+
+        let millis = |json: &str| serde_json::from_str::<Stamp>(json).unwrap().datetime;
+
+        assert_eq!(millis(r#"{"datetime": 1588622400}"#), 1588622400000);
+        assert_eq!(millis(r#"{"datetime": 1588622400.5}"#), 1588622400500);
+        assert_eq!(millis(r#"{"datetime": "1588622400"}"#), 1588622400000);
+        assert_eq!(millis(r#"{"datetime": 1588622400123}"#), 1588622400123);
+        assert_eq!(millis(r#"{"datetime": 1588622400123456}"#), 1588622400123);
+        assert_eq!(normalize_epoch_millis(1588622400123456789.0), 1588622400123);
+        assert_eq!(normalize_epoch_millis(-1.0), -1000);
+        assert_eq!(normalize_epoch_millis(-86400.0), -86_400_000);
+        assert_eq!(normalize_epoch_millis(-631152000000.0), -631_152_000_000);
+        assert_eq!(normalize_epoch_millis(-631152000000000.0), -631_152_000_000);
+        assert_eq!(normalize_epoch_millis(0.0), 0);
+        assert_eq!(normalize_epoch_millis(f64::NAN), 0);
+        assert_eq!(millis(r#"{"datetime": -86400}"#), -86_400_000);
+    }
+}
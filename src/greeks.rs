@@ -0,0 +1,249 @@
+//! Net greeks of option positions, per underlying and across the portfolio.
+//!
+//! Positions don't carry greeks, so they come from fetched option chains or
+//! from a pricing model of your own. Shares of an underlying count towards
+//! its delta, one per share:
+//!
+//! ```no_run
+//! use tda_sdk::{greeks::RiskReport, params::{GetAccountParams, GetOptionChainParams}, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let account = client.get_account("123456789", GetAccountParams { fields: Some("positions".to_string()) }).unwrap();
+//! let chain = client.get_option_chain("AAPL", GetOptionChainParams::default()).unwrap();
+//!
+//! let report = RiskReport::from_chains(account.positions(), &[chain]);
+//!
+//! for (underlying, risk) in &report.underlyings {
+//!     println!("{}: delta {:.0}, theta {:.2}/day", underlying, risk.greeks.delta, risk.greeks.theta);
+//! }
+//!
+//! println!("Portfolio: {:?}", report.total);
+//! ```
+//!
+//! Greeks are for whole positions: the greeks of a contract times its
+//! multiplier and the number of contracts, negative for short positions.
+//! Delta is in shares of the underlying, theta in dollars per day, and vega
+//! in dollars per point of volatility.
+
+use crate::{
+    instruments::Instrument,
+    responses::{OptionChain, OptionContract, Position},
+    Symbol,
+};
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Shares per contract of standard options.
+pub const DEFAULT_MULTIPLIER: f64 = 100.0;
+
+/// Sensitivities of an option or a position.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Greeks {
+    pub delta: f64,
+    pub gamma: f64,
+    pub theta: f64,
+    pub vega: f64,
+}
+
+impl Greeks {
+    /// Greeks of a contract, if the chain has them all.
+    pub fn from_contract(contract: &OptionContract) -> Option<Self> {
+        let greeks = Self {
+            delta: contract.delta?,
+            gamma: contract.gamma?,
+            theta: contract.theta?,
+            vega: contract.vega?,
+        };
+
+        // Chains send NaN for contracts without a price.
+        [greeks.delta, greeks.gamma, greeks.theta, greeks.vega].iter().all(|greek| greek.is_finite()).then_some(greeks)
+    }
+
+    /// Greeks multiplied by a number of shares.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            delta: self.delta * factor,
+            gamma: self.gamma * factor,
+            theta: self.theta * factor,
+            vega: self.vega * factor,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.delta += other.delta;
+        self.gamma += other.gamma;
+        self.theta += other.theta;
+        self.vega += other.vega;
+    }
+}
+
+/// Net greeks of the positions of an underlying.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UnderlyingRisk {
+    pub greeks: Greeks,
+    /// Price of the underlying, if a chain of it was given.
+    pub underlying_price: Option<f64>,
+}
+
+impl UnderlyingRisk {
+    /// Dollars gained or lost per dollar the underlying moves up, times the
+    /// price of the underlying. The exposure to compare across underlyings.
+    pub fn dollar_delta(&self) -> Option<f64> {
+        self.underlying_price.map(|price| self.greeks.delta * price)
+    }
+}
+
+/// Net greeks per underlying and across the portfolio.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RiskReport {
+    /// Risk by underlying symbol.
+    pub underlyings: BTreeMap<String, UnderlyingRisk>,
+    /// Greeks of every underlying added up.
+    pub total: Greeks,
+    /// Option positions left out because their greeks weren't known.
+    pub missing: Vec<String>,
+}
+
+impl RiskReport {
+    /// Aggregate the greeks of positions, looking up the greeks of each
+    /// option contract by symbol with `greeks`, such as from a pricing model.
+    pub fn new<'a, F>(positions: impl IntoIterator<Item = &'a Position>, mut greeks: F) -> Self
+    where
+        F: FnMut(&str) -> Option<Greeks>,
+    {
+        let mut report = Self::default();
+
+        for position in positions {
+            let quantity = position.quantity();
+
+            match &position.instrument {
+                Instrument::Equity { symbol, .. } => {
+                    let risk = report.underlyings.entry(symbol.clone()).or_default();
+
+                    risk.greeks.add(&Greeks {
+                        delta: quantity,
+                        ..Greeks::default()
+                    });
+                }
+                Instrument::Option {
+                    symbol,
+                    option_multiplier,
+                    underlying_symbol,
+                    ..
+                } => {
+                    let underlying = underlying_symbol.clone().or_else(|| match Symbol::parse(symbol) {
+                        Ok(Symbol::Option(option)) => Some(option.underlying().to_string()),
+                        _ => None,
+                    });
+
+                    match (underlying, greeks(symbol)) {
+                        (Some(underlying), Some(greeks)) => {
+                            let shares = quantity * option_multiplier.unwrap_or(DEFAULT_MULTIPLIER);
+
+                            report.underlyings.entry(underlying).or_default().greeks.add(&greeks.scaled(shares));
+                        }
+                        _ => report.missing.push(symbol.clone()),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for risk in report.underlyings.values() {
+            report.total.add(&risk.greeks);
+        }
+
+        report
+    }
+
+    /// Aggregate the greeks of positions using the greeks of fetched option
+    /// chains, which also give the prices of their underlyings.
+    pub fn from_chains<'a>(positions: impl IntoIterator<Item = &'a Position>, chains: &[OptionChain]) -> Self {
+        let contracts: HashMap<String, Greeks> = chains
+            .iter()
+            .flat_map(OptionChain::contracts)
+            .filter_map(|contract| Some((normalize(&contract.symbol), Greeks::from_contract(contract)?)))
+            .collect();
+
+        let mut report = Self::new(positions, |symbol| contracts.get(&normalize(symbol)).copied());
+
+        for chain in chains {
+            if let Some(risk) = report.underlyings.get_mut(&chain.symbol) {
+                risk.underlying_price = chain.underlying_price;
+            }
+        }
+
+        report
+    }
+}
+
+/// Option symbols in the format of TDA, so positions in OCC format match
+/// contracts of chains.
+fn normalize(symbol: &str) -> String {
+    match Symbol::parse(symbol) {
+        Ok(Symbol::Option(option)) => option.to_tda(),
+        _ => symbol.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(instrument: Instrument, quantity: f64) -> Position {
+        Position {
+            average_price: 0.0,
+            current_day_profit_loss: 0.0,
+            current_day_profit_loss_percentage: 0.0,
+            instrument,
+            long_quantity: quantity.max(0.0),
+            market_value: 0.0,
+            settled_long_quantity: None,
+            settled_short_quantity: None,
+            short_quantity: (-quantity).max(0.0),
+        }
+    }
+
+    #[test]
+    fn aggregates_chain_greeks() {
+        let chain: OptionChain = serde_json::from_str(include_str!("../tests/fixtures/option_chain.json")).unwrap();
+        let positions = vec![
+            position(Instrument::equity("AAPL"), 100.0),
+            position(Instrument::option("AAPL_071621C122.5"), -2.0),
+            position(Instrument::option("AAPL  210618C00095000"), 1.0),
+            position(Instrument::option("MSFT_061821C250"), 1.0),
+        ];
+
+        let report = RiskReport::from_chains(&positions, &[chain]);
+        let aapl = &report.underlyings["AAPL"];
+
+        // 100 shares, minus 200 and plus 100 shares of 0.45 delta calls.
+        assert!((aapl.greeks.delta - 55.0).abs() < 1e-9);
+        assert!((aapl.greeks.theta - 8.0).abs() < 1e-9);
+        assert_eq!(aapl.underlying_price, Some(125.5));
+        assert!((aapl.dollar_delta().unwrap() - 55.0 * 125.5).abs() < 1e-9);
+        assert_eq!(report.total, aapl.greeks);
+        assert_eq!(report.missing, vec!["MSFT_061821C250".to_string()]);
+    }
+
+    #[test]
+    fn aggregates_model_greeks() {
+        let positions = vec![position(Instrument::option("SPY_061821P400"), -1.0)];
+
+        let report = RiskReport::new(&positions, |_| {
+            Some(Greeks {
+                delta: -0.3,
+                gamma: 0.02,
+                theta: -0.1,
+                vega: 0.2,
+            })
+        });
+
+        assert_eq!(report.total, Greeks {
+            delta: 30.0,
+            gamma: -2.0,
+            theta: 10.0,
+            vega: -20.0,
+        });
+    }
+}
@@ -0,0 +1,250 @@
+//! Combined view of several accounts.
+//!
+//! [`Client::household_summary()`](../struct.Client.html#method.household_summary)
+//! adds up the balances and day profit and loss of every account the user
+//! can access, and merges their positions by symbol, for people managing
+//! accounts for their whole family.
+//!
+//! ```no_run
+//! use tda_sdk::Client;
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let household = client.household_summary().unwrap();
+//!
+//! println!("{} accounts worth {}", household.accounts.len(), household.totals.liquidation_value);
+//!
+//! for position in &household.positions {
+//!     println!("{}: {} shares in {} accounts", position.symbol, position.quantity(), position.accounts.len());
+//! }
+//! ```
+
+use crate::{
+    ids::AccountId,
+    money::Money,
+    orders::AssetType,
+    responses::{Account, CurrentBalances},
+};
+
+use std::{collections::BTreeMap, fmt};
+
+/// Balances, positions, and day profit and loss across accounts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HouseholdSummary {
+    /// Summary of each account, in the order they were given.
+    pub accounts: Vec<AccountSummary>,
+    /// Balances of all accounts added up.
+    pub totals: Balances,
+    /// Positions merged by symbol, sorted by symbol.
+    pub positions: Vec<HouseholdPosition>,
+    /// Profit and loss of the current day across all positions.
+    pub day_profit_loss: f64,
+}
+
+impl HouseholdSummary {
+    /// Summarize accounts fetched with the `positions` field. Accounts
+    /// fetched without it only count towards the balances.
+    pub fn from_accounts(accounts: &[Account]) -> Self {
+        let mut summary = Self::default();
+        let mut positions: BTreeMap<String, HouseholdPosition> = BTreeMap::new();
+
+        for account in accounts {
+            let account_id = account.account_id();
+            let balances = Balances::from(account.securities_account.current_balances());
+            let mut day_profit_loss = 0.0;
+
+            for position in account.positions() {
+                let symbol = position.instrument.symbol();
+                let merged = positions.entry(symbol.to_string()).or_insert_with(|| HouseholdPosition {
+                    symbol: symbol.to_string(),
+                    asset_type: position.instrument.asset_type(),
+                    long_quantity: 0.0,
+                    short_quantity: 0.0,
+                    market_value: 0.0,
+                    cost_basis: 0.0,
+                    day_profit_loss: 0.0,
+                    accounts: Vec::new(),
+                });
+
+                merged.long_quantity += position.long_quantity;
+                merged.short_quantity += position.short_quantity;
+                merged.market_value += position.market_value;
+                merged.cost_basis += position.average_price * position.quantity();
+                merged.day_profit_loss += position.current_day_profit_loss;
+
+                if !merged.accounts.contains(account_id) {
+                    merged.accounts.push(account_id.clone());
+                }
+
+                day_profit_loss += position.current_day_profit_loss;
+            }
+
+            summary.totals += &balances;
+            summary.day_profit_loss += day_profit_loss;
+            summary.accounts.push(AccountSummary {
+                account_id: account_id.clone(),
+                account_type: account.securities_account.account_type().to_string(),
+                balances,
+                day_profit_loss,
+            });
+        }
+
+        summary.positions = positions.into_values().collect();
+
+        summary
+    }
+
+    /// Merged position of a symbol.
+    pub fn position(&self, symbol: &str) -> Option<&HouseholdPosition> {
+        self.positions.iter().find(|position| position.symbol == symbol)
+    }
+}
+
+/// Shows the totals, then a line for each account, with masked IDs, and for
+/// each position.
+impl fmt::Display for HouseholdSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Total {} (day {})", Money::usd(self.totals.liquidation_value), Money::usd(self.day_profit_loss))?;
+
+        for account in &self.accounts {
+            writeln!(
+                f,
+                "  {} {} (day {})",
+                account.account_id.masked(),
+                Money::usd(account.balances.liquidation_value),
+                Money::usd(account.day_profit_loss),
+            )?;
+        }
+
+        for position in &self.positions {
+            writeln!(f, "  {} x{} {}", position.symbol, position.quantity(), Money::usd(position.market_value))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Summary of one account in a [`HouseholdSummary`](struct.HouseholdSummary.html).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountSummary {
+    pub account_id: AccountId,
+    /// `CASH` or `MARGIN`
+    pub account_type: String,
+    pub balances: Balances,
+    pub day_profit_loss: f64,
+}
+
+/// Current balances which can be added up across accounts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Balances {
+    pub liquidation_value: f64,
+    pub cash_balance: f64,
+    pub long_market_value: f64,
+    pub short_market_value: f64,
+    pub buying_power: f64,
+}
+
+impl From<&CurrentBalances> for Balances {
+    fn from(balances: &CurrentBalances) -> Self {
+        Self {
+            liquidation_value: balances.liquidation_value,
+            cash_balance: balances.cash_balance,
+            long_market_value: balances.long_market_value,
+            short_market_value: balances.short_market_value,
+            buying_power: balances.buying_power.or(balances.cash_available_for_trading).unwrap_or_default(),
+        }
+    }
+}
+
+impl std::ops::AddAssign<&Balances> for Balances {
+    fn add_assign(&mut self, other: &Balances) {
+        self.liquidation_value += other.liquidation_value;
+        self.cash_balance += other.cash_balance;
+        self.long_market_value += other.long_market_value;
+        self.short_market_value += other.short_market_value;
+        self.buying_power += other.buying_power;
+    }
+}
+
+/// Position of a symbol merged across the accounts holding it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HouseholdPosition {
+    pub symbol: String,
+    pub asset_type: AssetType,
+    pub long_quantity: f64,
+    pub short_quantity: f64,
+    pub market_value: f64,
+    /// Average price times quantity, negative for short positions.
+    pub cost_basis: f64,
+    pub day_profit_loss: f64,
+    /// Accounts holding the symbol.
+    pub accounts: Vec<AccountId>,
+}
+
+impl HouseholdPosition {
+    /// Net quantity held, negative for short positions.
+    pub fn quantity(&self) -> f64 {
+        self.long_quantity - self.short_quantity
+    }
+
+    /// Average price across accounts, weighted by quantity.
+    pub fn average_price(&self) -> Option<f64> {
+        let quantity = self.quantity();
+
+        match quantity != 0.0 {
+            true => Some(self.cost_basis / quantity),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn account(account_id: &str, liquidation_value: f64, positions: Value) -> Account {
+        let mut account: Value = serde_json::from_str(include_str!("../tests/fixtures/accounts.json")).unwrap();
+        let securities_account = &mut account[0]["securitiesAccount"];
+
+        securities_account["accountId"] = json!(account_id);
+        securities_account["currentBalances"]["liquidationValue"] = json!(liquidation_value);
+        securities_account["positions"] = positions;
+
+        serde_json::from_value(account[0].take()).unwrap()
+    }
+
+    fn position(symbol: &str, quantity: f64, average_price: f64, day_profit_loss: f64) -> Value {
+        json!({
+            "averagePrice": average_price,
+            "currentDayProfitLoss": day_profit_loss,
+            "currentDayProfitLossPercentage": 0.0,
+            "instrument": { "assetType": "EQUITY", "symbol": symbol },
+            "longQuantity": quantity,
+            "marketValue": quantity * 100.0,
+            "shortQuantity": 0.0,
+        })
+    }
+
+    #[test]
+    fn merge_accounts() {
+        let accounts = [
+            account("123456789", 10_000.0, json!([position("AAPL", 10.0, 90.0, 5.0), position("MSFT", 5.0, 80.0, -2.0)])),
+            account("987654321", 5_000.0, json!([position("AAPL", 30.0, 110.0, 15.0)])),
+        ];
+
+        let household = HouseholdSummary::from_accounts(&accounts);
+        let aapl = household.position("AAPL").unwrap();
+
+        assert_eq!(household.totals.liquidation_value, 15_000.0);
+        assert_eq!(household.day_profit_loss, 18.0);
+        assert_eq!(household.accounts[0].day_profit_loss, 3.0);
+        assert_eq!(household.positions.len(), 2);
+        assert_eq!(aapl.quantity(), 40.0);
+        assert_eq!(aapl.average_price(), Some(105.0));
+        assert_eq!(aapl.accounts, [AccountId::from("123456789"), AccountId::from("987654321")]);
+        assert_eq!(
+            household.to_string(),
+            "Total $15,000.00 (day $18.00)\n  *****6789 $10,000.00 (day $3.00)\n  *****4321 $5,000.00 (day $15.00)\n  AAPL x40 $4,000.00\n  MSFT x5 $500.00\n",
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Client-side idempotency for order submission.
+//!
+//! When placing an order times out, the order may or may not have reached
+//! the API, and blindly retrying it can fill it twice. An
+//! [`IdempotencyGuard`](struct.IdempotencyGuard.html) remembers a key the
+//! caller gives every logical order, and refuses to submit a key again within
+//! a window. If the outcome of the previous attempt is unknown, the guard can
+//! look for the order among the recent orders of the account before letting
+//! a retry through.
+//!
+//! ```no_run
+//! use std::{sync::Arc, time::Duration};
+//! use tda_sdk::{
+//!     idempotency::IdempotencyGuard,
+//!     orders::{Instruction, Order},
+//!     Client,
+//!     ClientError,
+//! };
+//!
+//! let mut guard = IdempotencyGuard::new(Duration::from_secs(15 * 60));
+//! guard.set_cross_check(true);
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! client.set_idempotency_guard(Some(Arc::new(guard)));
+//!
+//! let order = Order::equity_market(Instruction::Buy, "AAPL", 10.0);
+//!
+//! match client.place_order_with_key("123456789", &order, "rebalance-2020-05-04-AAPL") {
+//!     Ok(order_id) => println!("placed {}", order_id),
+//!     Err(ClientError::DuplicateOrder { order_id, .. }) => println!("already placed as {:?}", order_id),
+//!     Err(error) => println!("failed: {}", error),
+//! }
+//! ```
+
+use crate::{
+    ids::{AccountId, OrderId},
+    orders::{Order, OrderStatus},
+    params::GetOrdersParams,
+    Client,
+    ClientError,
+};
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Refuses to submit an order twice under the same key within a window.
+#[derive(Debug)]
+pub struct IdempotencyGuard {
+    window: Duration,
+    cross_check: bool,
+    submissions: Mutex<HashMap<String, Submission>>,
+}
+
+/// Attempt to submit an order under a key.
+#[derive(Clone, Copy, Debug)]
+struct Submission {
+    at: Instant,
+    /// ID of the placed order, or `None` while the outcome is unknown.
+    order_id: Option<OrderId>,
+    /// Whether the order is being submitted, or checked for, right now.
+    in_flight: bool,
+}
+
+impl IdempotencyGuard {
+    /// Refuse to submit a key again for `window` after it was first
+    /// submitted.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            cross_check: false,
+            submissions: Mutex::default(),
+        }
+    }
+
+    /// Set whether a retry of a key whose previous attempt has an unknown
+    /// outcome, such as a timeout, is checked against the recent orders of
+    /// the account. If no matching order is found, the retry is let through;
+    /// otherwise it fails with the ID of the order found. Without
+    /// cross-checking, such retries are refused. Keys still being submitted
+    /// are always refused. Disabled by default.
+    pub fn set_cross_check(&mut self, cross_check: bool) -> &mut Self {
+        self.cross_check = cross_check;
+
+        self
+    }
+
+    /// Forget a key, so an order can be submitted under it again, such as
+    /// after confirming a timed out order never reached the API.
+    pub fn forget(&self, key: &str) {
+        self.submissions.lock().unwrap().remove(key);
+    }
+
+    /// Reserve a key before its order is submitted, or fail if it was
+    /// already submitted within the window.
+    pub(crate) fn begin(&self, client: &Client, account_id: &AccountId, order: &Order, key: &str) -> Result<(), ClientError> {
+        let duplicate = |order_id| ClientError::DuplicateOrder {
+            key: key.to_string(),
+            order_id,
+        };
+
+        {
+            let mut submissions = self.submissions.lock().unwrap();
+
+            let now = client.clock().instant();
+
+            submissions.retain(|_, submission| now.duration_since(submission.at) < self.window);
+
+            match submissions.get_mut(key) {
+                None => {
+                    submissions.insert(key.to_string(), Submission {
+                        at: now,
+                        order_id: None,
+                        in_flight: true,
+                    });
+
+                    return Ok(());
+                }
+                Some(Submission { order_id: Some(order_id), .. }) => return Err(duplicate(Some(*order_id))),
+                Some(Submission { in_flight: true, .. }) => return Err(duplicate(None)),
+                Some(_) if !self.cross_check => return Err(duplicate(None)),
+                // The previous attempt finished with an unknown outcome.
+                Some(submission) => submission.in_flight = true,
+            }
+        }
+
+        match self.find_placed(client, account_id, order) {
+            Ok(Some(order_id)) => {
+                self.complete(key, order_id);
+
+                Err(duplicate(Some(order_id)))
+            }
+            Ok(None) => Ok(()),
+            Err(error) => {
+                self.land(key);
+
+                Err(error)
+            }
+        }
+    }
+
+    /// Record the outcome of submitting the order of a key. Keys of orders
+    /// which certainly didn't reach the API are released.
+    pub(crate) fn finish(&self, key: &str, result: &Result<OrderId, ClientError>) {
+        match result {
+            Ok(order_id) => self.complete(key, *order_id),
+            Err(error) if !may_have_been_placed(error) => self.forget(key),
+            Err(_) => self.land(key),
+        }
+    }
+
+    fn complete(&self, key: &str, order_id: OrderId) {
+        if let Some(submission) = self.submissions.lock().unwrap().get_mut(key) {
+            submission.order_id = Some(order_id);
+            submission.in_flight = false;
+        }
+    }
+
+    /// Mark the attempt of a key finished with an unknown outcome.
+    fn land(&self, key: &str) {
+        if let Some(submission) = self.submissions.lock().unwrap().get_mut(key) {
+            submission.in_flight = false;
+        }
+    }
+
+    /// ID of an order of the account matching `order`, entered within the
+    /// window and not rejected.
+    fn find_placed(&self, client: &Client, account_id: &AccountId, order: &Order) -> Result<Option<OrderId>, ClientError> {
+        let since = client.clock().now() - chrono::Duration::from_std(self.window).unwrap_or_else(|_| chrono::Duration::days(1));
+        let params = GetOrdersParams {
+            from_entered_time: Some(since.format("%Y-%m-%d").to_string()),
+            ..GetOrdersParams::default()
+        };
+
+        Ok(client
+            .get_orders(account_id, params)?
+            .iter()
+            .filter(|placed| placed.status != Some(OrderStatus::Rejected))
+            .filter(|placed| placed.entered_time.is_none_or(|time| time.timestamp() >= since.timestamp()))
+            .find(|placed| same_order(placed, order))
+            .and_then(|placed| placed.order_id))
+    }
+}
+
+/// Whether an order failing with `error` may have reached the API.
+fn may_have_been_placed(error: &ClientError) -> bool {
+    match error {
+        ClientError::NotHttpOk(status, ..) => *status >= 500 || *status == 408,
+        ClientError::MissingOrderId | ClientError::ParseResponse { .. } | ClientError::ReadResponse(_) | ClientError::ResponseTooLarge(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether a placed order has the same type, prices, and legs as `order`.
+fn same_order(placed: &Order, order: &Order) -> bool {
+    placed.order_type == order.order_type
+        && placed.price == order.price
+        && placed.stop_price == order.stop_price
+        && placed.order_leg_collection.len() == order.order_leg_collection.len()
+        && placed.order_leg_collection.iter().zip(&order.order_leg_collection).all(|(placed, leg)| {
+            placed.instruction == leg.instruction && placed.instrument.symbol() == leg.instrument.symbol() && placed.quantity == leg.quantity
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{test_client, Fault, MockTransport},
+        orders::Instruction,
+    };
+    use std::sync::Arc;
+
+    const ORDERS_PATH: &str = "/v1/accounts/123/orders";
+
+    fn client(transport: &Arc<MockTransport>, cross_check: bool) -> Client {
+        let mut client = test_client(transport.clone());
+        let mut guard = IdempotencyGuard::new(Duration::from_secs(60));
+
+        guard.set_cross_check(cross_check);
+        client.set_idempotency_guard(Some(Arc::new(guard)));
+
+        client
+    }
+
+    #[test]
+    fn refuses_duplicates() {
+        let transport = Arc::new(MockTransport::new());
+        let client = client(&transport, false);
+        let order = Order::equity_market(Instruction::Buy, "AAPL", 10.0);
+
+        transport.respond_with_headers("POST", ORDERS_PATH, 201, "", &[("Location", "https://api.tdameritrade.com/v1/accounts/123/orders/42")]);
+        transport.push_fault(Fault::Status(400)).push_fault(Fault::ConnectionReset);
+
+        // Rejected by the API, so the key is released.
+        assert!(matches!(client.place_order_with_key("123", &order, "a"), Err(ClientError::NotHttpOk(400, ..))));
+        assert!(matches!(client.place_order_with_key("123", &order, "a"), Err(ClientError::ReadResponse(_))));
+        assert!(matches!(client.place_order_with_key("123", &order, "a"), Err(ClientError::DuplicateOrder { order_id: None, .. })));
+
+        assert_eq!(client.place_order_with_key("123", &order, "b").unwrap(), OrderId::new(42));
+        assert!(matches!(
+            client.place_order_with_key("123", &order, "b"),
+            Err(ClientError::DuplicateOrder { order_id: Some(order_id), .. }) if order_id == OrderId::new(42)
+        ));
+        assert_eq!(transport.requests().len(), 3);
+    }
+
+    #[test]
+    fn cross_checks_unknown_outcomes() {
+        let transport = Arc::new(MockTransport::new());
+        let client = client(&transport, true);
+        let order = Order::equity_market(Instruction::Buy, "AAPL", 10.0);
+        let placed = r#"[{"orderId": 42, "orderType": "MARKET", "status": "QUEUED", "orderLegCollection": [
+            {"instruction": "BUY", "instrument": {"assetType": "EQUITY", "symbol": "AAPL"}, "quantity": 10}
+        ]}]"#;
+
+        transport.respond("GET", ORDERS_PATH, 200, "[]").push_fault(Fault::ConnectionReset);
+
+        assert!(client.place_order_with_key("123", &order, "a").is_err());
+
+        // Nothing was placed, so the retry goes through.
+        transport
+            .respond_with_headers("POST", ORDERS_PATH, 201, "", &[("Location", "/v1/accounts/123/orders/7")])
+            .push_fault(Fault::Latency(Duration::ZERO))
+            .push_fault(Fault::ConnectionReset);
+
+        assert!(client.place_order_with_key("123", &order, "a").is_err());
+
+        // The order reached the API this time.
+        transport.respond("GET", ORDERS_PATH, 200, placed);
+
+        assert!(matches!(
+            client.place_order_with_key("123", &order, "a"),
+            Err(ClientError::DuplicateOrder { order_id: Some(order_id), .. }) if order_id == OrderId::new(42)
+        ));
+    }
+
+    #[test]
+    fn refuses_keys_in_flight() {
+        let transport = Arc::new(MockTransport::new());
+        let client = client(&transport, true);
+        let guard = IdempotencyGuard::new(Duration::from_secs(60));
+        let mut cross_checking = IdempotencyGuard::new(Duration::from_secs(60));
+        let account_id = AccountId::from("123");
+        let order = Order::equity_market(Instruction::Buy, "AAPL", 10.0);
+
+        cross_checking.set_cross_check(true);
+
+        for guard in [&guard, &cross_checking] {
+            assert!(guard.begin(&client, &account_id, &order, "a").is_ok());
+            assert!(matches!(
+                guard.begin(&client, &account_id, &order, "a"),
+                Err(ClientError::DuplicateOrder { order_id: None, .. })
+            ));
+        }
+
+        // Nothing is cross-checked until the first attempt finishes.
+        assert!(transport.requests().is_empty());
+
+        transport.respond("GET", ORDERS_PATH, 200, "[]");
+        cross_checking.finish("a", &Err(ClientError::MissingOrderId));
+
+        assert!(cross_checking.begin(&client, &account_id, &order, "a").is_ok());
+        assert!(matches!(
+            cross_checking.begin(&client, &account_id, &order, "a"),
+            Err(ClientError::DuplicateOrder { order_id: None, .. })
+        ));
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
@@ -0,0 +1,243 @@
+//! Strongly typed identifiers used by the API.
+//!
+//! These wrap the raw values returned by TDA so that, for example, an order ID
+//! can't accidentally be passed where an account ID is expected.
+//!
+//! Account IDs can be masked in `Debug` and `Display` output with
+//! [`set_mask_account_ids()`](fn.set_mask_account_ids.html), which keeps them
+//! out of logs and screenshots. Serialized IDs, and so requests and stored
+//! records, always use the real ID.
+
+use serde::{
+    de::{self, Visitor},
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+
+use std::{
+    convert::Infallible,
+    fmt,
+    num::ParseIntError,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Number of trailing characters left visible by
+/// [`AccountId::masked()`](struct.AccountId.html#method.masked).
+const UNMASKED_LEN: usize = 4;
+
+static MASK_ACCOUNT_IDS: AtomicBool = AtomicBool::new(false);
+
+/// Mask account IDs in `Debug` and `Display` output across the whole
+/// process.
+///
+/// Masked output can't be read back into the original IDs, so this should
+/// only be enabled for output meant for people.
+pub fn set_mask_account_ids(mask: bool) {
+    MASK_ACCOUNT_IDS.store(mask, Ordering::Relaxed);
+}
+
+/// Whether account IDs are currently masked.
+pub fn mask_account_ids() -> bool {
+    MASK_ACCOUNT_IDS.load(Ordering::Relaxed)
+}
+
+/// Identifier of a brokerage account.
+///
+/// Some endpoints send account IDs as numbers, so both numbers and strings are
+/// accepted when deserializing.
+#[derive(Clone, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct AccountId(String);
+
+impl AccountId {
+    /// Create a new account ID.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// Borrow the account ID as a string slice. This is never masked.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The account ID with all but the last four characters replaced by `*`.
+    /// IDs of four characters or less are masked entirely.
+    pub fn masked(&self) -> String {
+        let chars = self.0.chars().count();
+
+        if chars <= UNMASKED_LEN {
+            return "*".repeat(chars);
+        }
+
+        self.0.chars().enumerate().map(|(index, c)| if index < chars - UNMASKED_LEN { '*' } else { c }).collect()
+    }
+
+    fn display(&self) -> std::borrow::Cow<'_, str> {
+        if mask_account_ids() {
+            self.masked().into()
+        } else {
+            self.0.as_str().into()
+        }
+    }
+}
+
+impl fmt::Debug for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AccountId").field(&self.display()).finish()
+    }
+}
+
+impl Serialize for AccountId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AccountIdVisitor;
+
+        impl<'de> Visitor<'de> for AccountIdVisitor {
+            type Value = AccountId;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an account ID as a string or integer")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(AccountId::from(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(AccountId(v.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(AccountId(v.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(AccountIdVisitor)
+    }
+}
+
+impl AsRef<str> for AccountId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display())
+    }
+}
+
+impl FromStr for AccountId {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl From<&str> for AccountId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl From<String> for AccountId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<&String> for AccountId {
+    fn from(id: &String) -> Self {
+        Self(id.clone())
+    }
+}
+
+impl From<&AccountId> for AccountId {
+    fn from(id: &AccountId) -> Self {
+        id.clone()
+    }
+}
+
+/// Identifier of an order.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct OrderId(i64);
+
+impl OrderId {
+    /// Create a new order ID.
+    pub fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    /// Return the numeric value of the order ID.
+    pub fn value(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for OrderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for OrderId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse().map(Self)
+    }
+}
+
+impl From<i64> for OrderId {
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<&OrderId> for OrderId {
+    fn from(id: &OrderId) -> Self {
+        *id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_id_serde() {
+        let id: AccountId = serde_json::from_str("\"123456789\"").unwrap();
+
+        assert_eq!(id.as_str(), "123456789");
+        assert_eq!(serde_json::to_string(&id).unwrap(), "\"123456789\"");
+
+        let id: AccountId = serde_json::from_str("123456789").unwrap();
+
+        assert_eq!(id.as_str(), "123456789");
+    }
+
+    #[test]
+    fn account_id_masked() {
+        assert_eq!(AccountId::from("123456789").masked(), "*****6789");
+        assert_eq!(AccountId::from("123").masked(), "***");
+    }
+
+    #[test]
+    fn order_id_parse() {
+        let id: OrderId = "4321".parse().unwrap();
+
+        assert_eq!(id, OrderId::new(4321));
+        assert_eq!(id.to_string(), "4321");
+        assert!("abc".parse::<OrderId>().is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Covered call and cash-secured put candidates.
+//!
+//! Selling out of the money calls against shares held, or puts backed by
+//! cash, collects premium. [`covered_calls()`](fn.covered_calls.html) and
+//! [`cash_secured_puts()`](fn.cash_secured_puts.html) go through option
+//! chains for the contracts matching an
+//! [`IncomeCriteria`](struct.IncomeCriteria.html), ranked by annualized
+//! yield, each with a draft order selling it:
+//!
+//! ```no_run
+//! use tda_sdk::{income::IncomeCriteria, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let criteria = IncomeCriteria {
+//!     max_delta: 0.25,
+//!     ..IncomeCriteria::default()
+//! };
+//!
+//! for candidate in client.covered_call_candidates("123456789", &criteria).unwrap().iter().take(5) {
+//!     println!("{} for {:.2} ({:.1}% a year)", candidate.symbol, candidate.premium, candidate.annualized_yield * 100.0);
+//! }
+//!
+//! let puts = client.cash_secured_put_candidates(&["AAPL", "MSFT"], 1.0, &criteria).unwrap();
+//! ```
+//!
+//! Premiums are at the bid, which is what a market order would get. The draft
+//! orders are limit orders at the mark, to be reviewed before placing them.
+
+use crate::{
+    greeks::DEFAULT_MULTIPLIER,
+    instruments::Instrument,
+    orders::{Instruction, Order},
+    responses::{OptionChain, OptionContract, Position},
+};
+use chrono::NaiveDate;
+
+use std::cmp::Ordering;
+
+/// Thresholds candidates have to meet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IncomeCriteria {
+    /// Lowest premium per year, as a fraction of the capital tied up.
+    pub min_annualized_yield: f64,
+    /// Highest delta, regardless of its sign, as a proxy for the odds of
+    /// finishing in the money.
+    pub max_delta: f64,
+    pub min_days_to_expiration: i64,
+    pub max_days_to_expiration: i64,
+    /// Lowest open interest, so the contract can be closed easily.
+    pub min_open_interest: i64,
+}
+
+impl Default for IncomeCriteria {
+    fn default() -> Self {
+        Self {
+            min_annualized_yield: 0.1,
+            max_delta: 0.3,
+            min_days_to_expiration: 7,
+            max_days_to_expiration: 45,
+            min_open_interest: 100,
+        }
+    }
+}
+
+/// Strategy of a candidate.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IncomeStrategy {
+    /// Call sold against shares held.
+    CoveredCall,
+    /// Put sold with cash to buy the shares if assigned.
+    CashSecuredPut,
+}
+
+/// Contract which can be sold for income.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncomeCandidate {
+    pub strategy: IncomeStrategy,
+    pub underlying: String,
+    pub underlying_price: f64,
+    /// Symbol of the contract.
+    pub symbol: String,
+    pub strike: f64,
+    pub expiration: NaiveDate,
+    pub days_to_expiration: i64,
+    pub bid: f64,
+    pub delta: f64,
+    pub contracts: f64,
+    /// Premium collected at the bid for every contract.
+    pub premium: f64,
+    /// Value of the shares covering calls, or cash securing puts.
+    pub capital: f64,
+    /// Premium as a fraction of the capital, until expiration.
+    pub premium_yield: f64,
+    /// Premium yield scaled to a year.
+    pub annualized_yield: f64,
+    /// Limit order selling the contracts at the mark.
+    pub order: Order,
+}
+
+/// Covered calls of the equity positions with chains among `chains`, for as
+/// many contracts as the shares held cover.
+pub fn covered_calls<'a>(positions: impl IntoIterator<Item = &'a Position>, chains: &[OptionChain], criteria: &IncomeCriteria) -> Vec<IncomeCandidate> {
+    let mut candidates = Vec::new();
+
+    for position in positions {
+        let symbol = match &position.instrument {
+            Instrument::Equity { symbol, .. } => symbol,
+            _ => continue,
+        };
+        let contracts = (position.quantity() / DEFAULT_MULTIPLIER).floor();
+
+        if contracts < 1.0 {
+            continue;
+        }
+
+        for chain in chains.iter().filter(|chain| &chain.symbol == symbol) {
+            candidates.extend(candidates_of(chain, IncomeStrategy::CoveredCall, contracts, criteria));
+        }
+    }
+
+    rank(candidates)
+}
+
+/// Cash-secured puts of `contracts` contracts among `chains`.
+pub fn cash_secured_puts(chains: &[OptionChain], contracts: f64, criteria: &IncomeCriteria) -> Vec<IncomeCandidate> {
+    rank(chains.iter().flat_map(|chain| candidates_of(chain, IncomeStrategy::CashSecuredPut, contracts, criteria)).collect())
+}
+
+fn rank(mut candidates: Vec<IncomeCandidate>) -> Vec<IncomeCandidate> {
+    candidates.sort_by(|a, b| b.annualized_yield.partial_cmp(&a.annualized_yield).unwrap_or(Ordering::Equal));
+    candidates
+}
+
+fn candidates_of(chain: &OptionChain, strategy: IncomeStrategy, contracts: f64, criteria: &IncomeCriteria) -> Vec<IncomeCandidate> {
+    let underlying_price = match chain.underlying_price {
+        Some(price) if price > 0.0 => price,
+        _ => return Vec::new(),
+    };
+    let expirations = match strategy {
+        IncomeStrategy::CoveredCall => &chain.call_exp_date_map,
+        IncomeStrategy::CashSecuredPut => &chain.put_exp_date_map,
+    };
+
+    expirations
+        .iter()
+        .filter(|(expiration, _)| (criteria.min_days_to_expiration..=criteria.max_days_to_expiration).contains(&expiration.days_to_expiration))
+        .flat_map(|(expiration, strikes)| strikes.values().flatten().map(move |contract| (expiration, contract)))
+        .filter_map(|(expiration, contract)| {
+            let candidate = candidate(chain, contract, strategy, underlying_price, expiration.date, expiration.days_to_expiration, contracts)?;
+
+            let out_of_the_money = match strategy {
+                IncomeStrategy::CoveredCall => candidate.strike > underlying_price,
+                IncomeStrategy::CashSecuredPut => candidate.strike < underlying_price,
+            };
+
+            let meets_criteria = out_of_the_money
+                && candidate.delta.abs() <= criteria.max_delta
+                && candidate.annualized_yield >= criteria.min_annualized_yield
+                && contract.open_interest.unwrap_or_default() >= criteria.min_open_interest;
+
+            meets_criteria.then_some(candidate)
+        })
+        .collect()
+}
+
+fn candidate(
+    chain: &OptionChain,
+    contract: &OptionContract,
+    strategy: IncomeStrategy,
+    underlying_price: f64,
+    expiration: NaiveDate,
+    days_to_expiration: i64,
+    contracts: f64,
+) -> Option<IncomeCandidate> {
+    let bid = contract.bid.filter(|bid| bid.is_finite() && *bid > 0.0)?;
+    let delta = contract.delta.filter(|delta| delta.is_finite())?;
+    let shares = contracts * contract.multiplier.unwrap_or(DEFAULT_MULTIPLIER);
+    let capital = match strategy {
+        IncomeStrategy::CoveredCall => shares * underlying_price,
+        IncomeStrategy::CashSecuredPut => shares * contract.strike_price,
+    };
+    let premium = bid * shares;
+    let premium_yield = premium / capital;
+    let mark = contract.mark.filter(|mark| mark.is_finite() && *mark >= bid).unwrap_or(bid);
+
+    Some(IncomeCandidate {
+        strategy,
+        underlying: chain.symbol.clone(),
+        underlying_price,
+        symbol: contract.symbol.clone(),
+        strike: contract.strike_price,
+        expiration,
+        days_to_expiration,
+        bid,
+        delta,
+        contracts,
+        premium,
+        capital,
+        premium_yield,
+        annualized_yield: premium_yield * 365.0 / days_to_expiration.max(1) as f64,
+        order: Order::option_limit(Instruction::SellToOpen, &contract.symbol, contracts, mark),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn contract(symbol: &str, put_call: &str, strike: f64, bid: f64, delta: f64) -> serde_json::Value {
+        json!([{
+            "bid": bid,
+            "delta": delta,
+            "mark": bid + 0.05,
+            "multiplier": 100.0,
+            "openInterest": 500,
+            "putCall": put_call,
+            "strikePrice": strike,
+            "symbol": symbol,
+        }])
+    }
+
+    fn chain() -> OptionChain {
+        serde_json::from_value(json!({
+            "callExpDateMap": {
+                "2021-07-16:30": {
+                    "130.0": contract("AAPL_071621C130", "CALL", 130.0, 1.5, 0.3),
+                    "135.0": contract("AAPL_071621C135", "CALL", 135.0, 1.1, 0.18),
+                    "140.0": contract("AAPL_071621C140", "CALL", 140.0, 0.05, 0.04),
+                    "120.0": contract("AAPL_071621C120", "CALL", 120.0, 7.0, 0.7),
+                },
+                "2021-06-18:2": {
+                    "130.0": contract("AAPL_061821C130", "CALL", 130.0, 0.3, 0.1),
+                },
+            },
+            "putExpDateMap": {
+                "2021-07-16:30": {
+                    "120.0": contract("AAPL_071621P120", "PUT", 120.0, 1.2, -0.25),
+                    "125.0": contract("AAPL_071621P125", "PUT", 125.0, 2.6, -0.45),
+                },
+            },
+            "status": "SUCCESS",
+            "symbol": "AAPL",
+            "underlyingPrice": 125.5,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn finds_covered_calls() {
+        let position = Position {
+            average_price: 100.0,
+            current_day_profit_loss: 0.0,
+            current_day_profit_loss_percentage: 0.0,
+            instrument: Instrument::equity("AAPL"),
+            long_quantity: 250.0,
+            market_value: 0.0,
+            settled_long_quantity: None,
+            settled_short_quantity: None,
+            short_quantity: 0.0,
+        };
+
+        let candidates = covered_calls(&[position], &[chain()], &IncomeCriteria::default());
+        let symbols: Vec<&str> = candidates.iter().map(|candidate| candidate.symbol.as_str()).collect();
+
+        // The 140 call yields too little, the 120 is in the money, and the
+        // June call expires too soon.
+        assert_eq!(symbols, vec!["AAPL_071621C130", "AAPL_071621C135"]);
+        assert_eq!(candidates[0].contracts, 2.0);
+        assert_eq!(candidates[0].premium, 300.0);
+        assert!((candidates[0].annualized_yield - 300.0 / 25_100.0 * 365.0 / 30.0).abs() < 1e-12);
+        assert_eq!(candidates[0].order.price, Some(1.55));
+        assert_eq!(candidates[0].order.order_leg_collection[0].instruction, Instruction::SellToOpen);
+    }
+
+    #[test]
+    fn finds_cash_secured_puts() {
+        let candidates = cash_secured_puts(&[chain()], 1.0, &IncomeCriteria::default());
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].symbol, "AAPL_071621P120");
+        assert_eq!(candidates[0].capital, 12_000.0);
+        assert_eq!(candidates[0].strategy, IncomeStrategy::CashSecuredPut);
+    }
+}
@@ -0,0 +1,350 @@
+//! Instruments and a cache of CUSIP and symbol pairs.
+//!
+//! [`Instrument`](enum.Instrument.html) is the instrument of positions and
+//! order legs alike, so a position converts to an order without mapping
+//! fields.
+//!
+//! Transactions reference instruments by CUSIP, while most other endpoints use
+//! symbols. The client fills an [`InstrumentCache`](struct.InstrumentCache.html)
+//! from every instrument it sees, so `resolve_cusip()` only asks the API about
+//! CUSIPs it hasn't seen before. The cache can be saved to a file and loaded
+//! again to survive restarts.
+//!
+//! ```no_run
+//! use tda_sdk::{instruments::InstrumentCache, Client};
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! client.set_instrument_cache(InstrumentCache::load("instruments.json").unwrap_or_default());
+//!
+//! let symbol = client.resolve_cusip("037833100").unwrap();
+//!
+//! client.instrument_cache().save("instruments.json").unwrap();
+//! ```
+
+use crate::{
+    orders::AssetType,
+    responses::InstrumentInfo,
+    schema::{self, ParseMode},
+    symbols::PutCall,
+};
+
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
+use std::{collections::HashMap, fs, io, path::Path};
+
+/// Instrument of a position or an order leg, tagged by its `assetType`.
+///
+/// Instruments of asset types without a typed variant, or which don't match
+/// the expected shape, are kept as
+/// [`Instrument::Unknown`](enum.Instrument.html#variant.Unknown), like
+/// [`Quote::Unknown`](../responses/enum.Quote.html#variant.Unknown). In
+/// [`ParseMode::Strict`](../schema/enum.ParseMode.html#variant.Strict) they fail
+/// to parse instead.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "assetType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Instrument {
+    #[serde(rename_all = "camelCase")]
+    Equity {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cusip: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        symbol: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Option {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cusip: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        option_multiplier: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        put_call: Option<PutCall>,
+        symbol: String,
+        /// `VANILLA`, `BINARY`, or `BARRIER`
+        #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+        option_type: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        underlying_symbol: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    MutualFund {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cusip: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        symbol: String,
+        #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+        fund_type: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    FixedIncome {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cusip: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        factor: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        maturity_date: Option<String>,
+        symbol: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        variable_rate: Option<f64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    CashEquivalent {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cusip: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        symbol: String,
+        /// `SAVINGS` or `MONEY_MARKET_FUND`
+        #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+        cash_equivalent_type: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Index {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cusip: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        symbol: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Currency {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cusip: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        symbol: String,
+    },
+    /// Instrument which could not be parsed into a typed variant, including
+    /// its `assetType`.
+    #[serde(untagged, deserialize_with = "unknown_instrument")]
+    Unknown(Value),
+}
+
+/// Keep an instrument without a typed variant as is, unless parsing strictly.
+fn unknown_instrument<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+
+    if schema::parse_mode() == Some(ParseMode::Strict) {
+        let asset_type = value.get("assetType").and_then(Value::as_str).unwrap_or_default();
+
+        return Err(de::Error::custom(format_args!("unknown or malformed instrument of asset type `{}`", asset_type)));
+    }
+
+    Ok(value)
+}
+
+impl Instrument {
+    /// An equity with only its symbol set.
+    pub fn equity(symbol: &str) -> Self {
+        Instrument::Equity {
+            cusip: None,
+            description: None,
+            symbol: symbol.to_string(),
+        }
+    }
+
+    /// An option contract with only its symbol set.
+    pub fn option(symbol: &str) -> Self {
+        Instrument::Option {
+            cusip: None,
+            description: None,
+            option_multiplier: None,
+            put_call: None,
+            symbol: symbol.to_string(),
+            option_type: None,
+            underlying_symbol: None,
+        }
+    }
+
+    pub fn asset_type(&self) -> AssetType {
+        match self {
+            Instrument::Equity { .. } => AssetType::Equity,
+            Instrument::Option { .. } => AssetType::Option,
+            Instrument::MutualFund { .. } => AssetType::MutualFund,
+            Instrument::FixedIncome { .. } => AssetType::FixedIncome,
+            Instrument::CashEquivalent { .. } => AssetType::CashEquivalent,
+            Instrument::Index { .. } => AssetType::Index,
+            Instrument::Currency { .. } => AssetType::Currency,
+            Instrument::Unknown(_) => AssetType::Unknown,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            Instrument::Equity { symbol, .. }
+            | Instrument::Option { symbol, .. }
+            | Instrument::MutualFund { symbol, .. }
+            | Instrument::FixedIncome { symbol, .. }
+            | Instrument::CashEquivalent { symbol, .. }
+            | Instrument::Index { symbol, .. }
+            | Instrument::Currency { symbol, .. } => symbol,
+            Instrument::Unknown(value) => value.get("symbol").and_then(Value::as_str).unwrap_or_default(),
+        }
+    }
+
+    pub fn cusip(&self) -> Option<&str> {
+        match self {
+            Instrument::Equity { cusip, .. }
+            | Instrument::Option { cusip, .. }
+            | Instrument::MutualFund { cusip, .. }
+            | Instrument::FixedIncome { cusip, .. }
+            | Instrument::CashEquivalent { cusip, .. }
+            | Instrument::Index { cusip, .. }
+            | Instrument::Currency { cusip, .. } => cusip.as_deref(),
+            Instrument::Unknown(value) => value.get("cusip").and_then(Value::as_str),
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Instrument::Equity { description, .. }
+            | Instrument::Option { description, .. }
+            | Instrument::MutualFund { description, .. }
+            | Instrument::FixedIncome { description, .. }
+            | Instrument::CashEquivalent { description, .. }
+            | Instrument::Index { description, .. }
+            | Instrument::Currency { description, .. } => description.as_deref(),
+            Instrument::Unknown(value) => value.get("description").and_then(Value::as_str),
+        }
+    }
+}
+
+/// Two-way map between CUSIPs and symbols.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct InstrumentCache {
+    symbols: HashMap<String, String>,
+}
+
+impl InstrumentCache {
+    /// Load a cache saved with [`save()`](#method.save).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+
+        serde_json::from_str(&json).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Save the cache as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string(self).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        fs::write(path, json)
+    }
+
+    /// Record the CUSIP and symbol of an instrument, if it has a CUSIP.
+    pub fn insert(&mut self, instrument: &InstrumentInfo) {
+        if let Some(cusip) = instrument.cusip.as_ref().filter(|cusip| !cusip.is_empty()) {
+            self.symbols.insert(cusip.clone(), instrument.symbol.clone());
+        }
+    }
+
+    /// Symbol of a CUSIP.
+    pub fn symbol(&self, cusip: &str) -> Option<&str> {
+        self.symbols.get(cusip).map(String::as_str)
+    }
+
+    /// CUSIP of a symbol.
+    pub fn cusip(&self, symbol: &str) -> Option<&str> {
+        self.symbols
+            .iter()
+            .find(|(_, cached)| cached.as_str() == symbol)
+            .map(|(cusip, _)| cusip.as_str())
+    }
+
+    /// Number of cached instruments.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        orders::{Instruction, OrderLeg},
+        responses::{Position, SearchInstrumentsResponse},
+    };
+
+    #[test]
+    fn position_instrument_to_order_leg() {
+        let json = serde_json::json!({
+            "averagePrice": 2.5,
+            "currentDayProfitLoss": 0.0,
+            "currentDayProfitLossPercentage": 0.0,
+            "instrument": {
+                "assetType": "OPTION",
+                "cusip": "0AAPL.AF00130000",
+                "putCall": "CALL",
+                "symbol": "AAPL_011521C130",
+                "type": "VANILLA",
+                "underlyingSymbol": "AAPL",
+            },
+            "longQuantity": 2.0,
+            "marketValue": 500.0,
+            "settledLongQuantity": null,
+            "settledShortQuantity": null,
+            "shortQuantity": 0.0,
+        });
+        let position: Position = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(position.instrument.asset_type(), AssetType::Option);
+        assert_eq!(position.instrument.symbol(), "AAPL_011521C130");
+        assert_eq!(serde_json::to_value(&position).unwrap(), json);
+
+        let leg = OrderLeg {
+            instrument: position.instrument.clone(),
+            ..OrderLeg::option(Instruction::SellToClose, position.instrument.symbol(), position.quantity())
+        };
+
+        assert_eq!(serde_json::to_value(&leg).unwrap()["instrument"], json["instrument"]);
+    }
+
+    #[test]
+    fn unknown_instruments() {
+        let json = serde_json::json!({
+            "assetType": "FUTURE",
+            "cusip": "ES0000000",
+            "symbol": "/ESZ21",
+        });
+        let instrument: Instrument = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(instrument, Instrument::Unknown(json.clone()));
+        assert_eq!(instrument.asset_type(), AssetType::Unknown);
+        assert_eq!(instrument.symbol(), "/ESZ21");
+        assert_eq!(instrument.cusip(), Some("ES0000000"));
+        assert_eq!(serde_json::to_value(&instrument).unwrap(), json);
+
+        let strict = schema::with_parse_mode(ParseMode::Strict, || serde_json::from_value::<Instrument>(json));
+
+        assert!(strict.is_err());
+    }
+
+    #[test]
+    fn cache_instruments() {
+        let instruments: SearchInstrumentsResponse = serde_json::from_str(include_str!("../tests/fixtures/instruments_fundamental.json")).unwrap();
+        let mut cache = InstrumentCache::default();
+
+        for instrument in instruments.values() {
+            cache.insert(instrument);
+        }
+
+        assert_eq!(cache.symbol("037833100"), Some("AAPL"));
+        assert_eq!(cache.cusip("AAPL"), Some("037833100"));
+        assert_eq!(cache.symbol("000000000"), None);
+
+        let path = std::env::temp_dir().join(format!("tda-sdk-instruments-{}.json", std::process::id()));
+
+        cache.save(&path).unwrap();
+        assert_eq!(InstrumentCache::load(&path).unwrap(), cache);
+        fs::remove_file(&path).unwrap();
+    }
+}
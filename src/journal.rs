@@ -0,0 +1,355 @@
+//! Trade journal built from filled orders.
+//!
+//! A [`Journal`](struct.Journal.html) has one entry for each leg of each
+//! filled order, with its executions, average price, and commission, along
+//! with the market around the fill: the candle it happened in and, when
+//! journaling live, the quote at the time. Journals export to JSON or CSV for
+//! review tools and spreadsheets.
+//!
+//! ```no_run
+//! use tda_sdk::{journal::Journal, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let journal = Journal::fetch(&client, "123456789", "2020-05-01").unwrap();
+//!
+//! std::fs::write("journal.csv", journal.to_csv()).unwrap();
+//! ```
+
+use crate::{
+    ids::{AccountId, OrderId},
+    orders::{AssetType, Instruction, Order, OrderType},
+    params::{GetOrdersParams, GetPriceHistoryParams},
+    responses::{Candle, Quote},
+    time::TdaDateTime,
+    Client,
+    ClientError,
+};
+use chrono::Duration;
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+/// Columns of the CSV export, in order.
+const CSV_HEADER: &[&str] = &[
+    "account_id",
+    "order_id",
+    "symbol",
+    "asset_type",
+    "instruction",
+    "order_type",
+    "order_price",
+    "quantity",
+    "average_price",
+    "commission",
+    "entered_time",
+    "first_fill_time",
+    "last_fill_time",
+    "candle_open",
+    "candle_high",
+    "candle_low",
+    "candle_close",
+    "candle_volume",
+    "bid",
+    "ask",
+    "last",
+    "tag",
+];
+
+/// Filled trades, in the order they were added.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Build a journal from orders, skipping the ones without fills.
+    pub fn from_orders(orders: &[Order]) -> Self {
+        Self {
+            entries: orders.iter().flat_map(JournalEntry::from_order).collect(),
+        }
+    }
+
+    /// Fetch the filled orders of an account entered on or after `from` (a
+    /// `yyyy-MM-dd` date), along with the minute candles of the days they
+    /// were filled.
+    ///
+    /// Candles are only fetched for equities, as the API has no price history
+    /// for options.
+    pub fn fetch(client: &Client, account_id: impl Into<AccountId>, from: &str) -> Result<Self, ClientError> {
+        let params = GetOrdersParams {
+            from_entered_time: Some(from.to_string()),
+            status: Some("FILLED".to_string()),
+            ..GetOrdersParams::default()
+        };
+        let mut journal = Self::from_orders(&client.get_orders(account_id, params)?);
+        let mut ranges: HashMap<String, (TdaDateTime, TdaDateTime)> = HashMap::new();
+
+        for entry in journal.entries.iter().filter(|entry| entry.asset_type == AssetType::Equity) {
+            if let (Some(first), Some(last)) = (entry.first_fill_time, entry.last_fill_time) {
+                let range = ranges.entry(entry.symbol.clone()).or_insert((first, last));
+
+                *range = (range.0.min(first), range.1.max(last));
+            }
+        }
+
+        for (symbol, (first, last)) in ranges {
+            let params = GetPriceHistoryParams {
+                frequency_type: Some("minute".to_string()),
+                frequency: Some("1".to_string()),
+                start_date: Some((first.timestamp_millis() - Duration::days(1).num_milliseconds()).into()),
+                end_date: Some((last.timestamp_millis() + Duration::days(1).num_milliseconds()).into()),
+                ..GetPriceHistoryParams::default()
+            };
+            let history = client.get_price_history(&symbol, params)?;
+
+            journal.add_candles(&symbol, &history.candles);
+        }
+
+        Ok(journal)
+    }
+
+    /// Attach to the entries of a symbol the candle their first fill
+    /// happened in. `candles` must be sorted by time, and are assumed to
+    /// span until the next one starts.
+    pub fn add_candles(&mut self, symbol: &str, candles: &[Candle]) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.symbol == symbol) {
+            let fill_time = match entry.first_fill_time {
+                Some(time) => time.timestamp_millis(),
+                None => continue,
+            };
+            let index = candles.partition_point(|candle| candle.datetime <= fill_time);
+
+            if index > 0 {
+                entry.candle = Some(candles[index - 1]);
+            }
+        }
+    }
+
+    /// Attach a quote to the entries of its symbol which don't have one yet,
+    /// such as one received when the order filled.
+    pub fn add_quote(&mut self, symbol: &str, quote: &Quote) {
+        let context = QuoteContext {
+            bid: quote.bid_price(),
+            ask: quote.ask_price(),
+            last: quote.last_price(),
+        };
+
+        for entry in self.entries.iter_mut().filter(|entry| entry.symbol == symbol && entry.quote.is_none()) {
+            entry.quote = Some(context.clone());
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Export one row per entry, with a header. Executions are summarized by
+    /// their first and last times.
+    pub fn to_csv(&self) -> String {
+        let mut csv = CSV_HEADER.join(",") + "\n";
+
+        for entry in &self.entries {
+            let candle = entry.candle.as_ref();
+            let quote = entry.quote.as_ref();
+            let row = [
+                entry.account_id.as_ref().map(ToString::to_string),
+                entry.order_id.as_ref().map(ToString::to_string),
+                Some(entry.symbol.clone()),
+                Some(variant_name(&entry.asset_type)),
+                Some(variant_name(&entry.instruction)),
+                entry.order_type.as_ref().map(variant_name),
+                entry.order_price.map(|price| price.to_string()),
+                Some(entry.quantity.to_string()),
+                Some(entry.average_price.to_string()),
+                Some(entry.commission.to_string()),
+                entry.entered_time.map(|time| time.to_rfc3339()),
+                entry.first_fill_time.map(|time| time.to_rfc3339()),
+                entry.last_fill_time.map(|time| time.to_rfc3339()),
+                candle.map(|candle| candle.open.to_string()),
+                candle.map(|candle| candle.high.to_string()),
+                candle.map(|candle| candle.low.to_string()),
+                candle.map(|candle| candle.close.to_string()),
+                candle.map(|candle| candle.volume.to_string()),
+                quote.and_then(|quote| quote.bid).map(|price| price.to_string()),
+                quote.and_then(|quote| quote.ask).map(|price| price.to_string()),
+                quote.and_then(|quote| quote.last).map(|price| price.to_string()),
+                entry.tag.clone(),
+            ];
+            let fields: Vec<String> = row.iter().map(|field| csv_field(field.as_deref().unwrap_or_default())).collect();
+
+            csv += &fields.join(",");
+            csv += "\n";
+        }
+
+        csv
+    }
+}
+
+/// Fills of one leg of an order.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct JournalEntry {
+    pub account_id: Option<AccountId>,
+    pub order_id: Option<OrderId>,
+    pub symbol: String,
+    pub asset_type: AssetType,
+    pub instruction: Instruction,
+    pub order_type: Option<OrderType>,
+    /// Limit or stop price of the order.
+    pub order_price: Option<f64>,
+    /// Filled quantity.
+    pub quantity: f64,
+    /// Average fill price, weighted by quantity.
+    pub average_price: f64,
+    /// Commission of the executions, split evenly between the legs filled
+    /// together.
+    pub commission: f64,
+    pub entered_time: Option<TdaDateTime>,
+    pub first_fill_time: Option<TdaDateTime>,
+    pub last_fill_time: Option<TdaDateTime>,
+    pub executions: Vec<Execution>,
+    /// Candle the first fill happened in.
+    pub candle: Option<Candle>,
+    /// Quote around the time of the fill.
+    pub quote: Option<QuoteContext>,
+    pub tag: Option<String>,
+}
+
+impl JournalEntry {
+    /// Entries for the filled legs of an order.
+    pub fn from_order(order: &Order) -> Vec<Self> {
+        let mut entries = Vec::new();
+
+        for (index, leg) in order.order_leg_collection.iter().enumerate() {
+            let mut executions = Vec::new();
+            let mut commission = 0.0;
+
+            for activity in order.order_activity_collection.iter().flatten() {
+                let legs = activity.execution_legs.len().max(1) as f64;
+
+                for execution in &activity.execution_legs {
+                    // Single-leg orders don't always number their legs.
+                    let matches = match (execution.leg_id, leg.leg_id) {
+                        (Some(execution_leg), Some(order_leg)) => execution_leg == order_leg,
+                        _ => index == 0,
+                    };
+
+                    if matches {
+                        commission += activity.commission.unwrap_or_default() / legs;
+                        executions.push(Execution {
+                            time: execution.time,
+                            price: execution.price,
+                            quantity: execution.quantity,
+                        });
+                    }
+                }
+            }
+
+            let quantity: f64 = executions.iter().map(|execution| execution.quantity).sum();
+
+            if quantity == 0.0 {
+                continue;
+            }
+
+            let times = executions.iter().filter_map(|execution| execution.time);
+
+            entries.push(Self {
+                account_id: order.account_id.clone(),
+                order_id: order.order_id,
+                symbol: leg.instrument.symbol().to_string(),
+                asset_type: leg.instrument.asset_type(),
+                instruction: leg.instruction,
+                order_type: order.order_type,
+                order_price: order.price.or(order.stop_price),
+                quantity,
+                average_price: executions.iter().map(|execution| execution.price * execution.quantity).sum::<f64>() / quantity,
+                commission,
+                entered_time: order.entered_time,
+                first_fill_time: times.clone().min(),
+                last_fill_time: times.max(),
+                executions,
+                candle: None,
+                quote: None,
+                tag: order.tag.clone(),
+            });
+        }
+
+        entries
+    }
+}
+
+/// Single fill in a [`JournalEntry`](struct.JournalEntry.html).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Execution {
+    pub time: Option<TdaDateTime>,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+/// Quote around the time of a fill.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct QuoteContext {
+    pub bid: Option<f64>,
+    pub ask: Option<f64>,
+    pub last: Option<f64>,
+}
+
+/// API name of an enum variant, such as `BUY_TO_OPEN`.
+fn variant_name(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote, or line break.
+pub(crate) fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orders() -> Vec<Order> {
+        serde_json::from_str(include_str!("../tests/fixtures/orders.json")).unwrap()
+    }
+
+    #[test]
+    fn entries_from_fills() {
+        let mut order = orders().remove(0);
+
+        order.tag = Some("breakout, day 1".to_string());
+
+        let mut journal = Journal::from_orders(&[order]);
+        let entry = &journal.entries[0];
+
+        assert_eq!(journal.entries.len(), 1);
+        assert_eq!(entry.quantity, 10.0);
+        assert_eq!(entry.average_price, 120.4);
+        assert_eq!(entry.commission, 1.5);
+        assert_eq!(entry.first_fill_time.unwrap().to_rfc3339(), "2020-05-04T10:31:03-04:00");
+        assert_eq!(entry.last_fill_time.unwrap().to_rfc3339(), "2020-05-04T10:31:05-04:00");
+
+        let minute = |start: i64, close: f64| Candle {
+            close,
+            datetime: start,
+            ..Candle::default()
+        };
+        let fill_minute = TdaDateTime::parse("2020-05-04T14:31:00+0000").unwrap().timestamp_millis();
+
+        journal.add_candles("AAPL", &[minute(fill_minute - 60_000, 119.0), minute(fill_minute, 120.5), minute(fill_minute + 60_000, 121.0)]);
+
+        assert_eq!(journal.entries[0].candle.unwrap().close, 120.5);
+
+        let csv = journal.to_csv();
+        let row: Vec<&str> = csv.lines().nth(1).unwrap().split(',').collect();
+
+        assert_eq!(csv.lines().next().unwrap().split(',').count(), CSV_HEADER.len());
+        assert_eq!(row[..9], ["123456789", "987654321", "AAPL", "EQUITY", "BUY", "LIMIT", "121", "10", "120.4"]);
+        assert!(csv.ends_with(",\"breakout, day 1\"\n"));
+        assert_eq!(serde_json::from_str::<Journal>(&journal.to_json().unwrap()).unwrap(), journal);
+    }
+}
@@ -0,0 +1,392 @@
+//! Tax lots and wash sales from the trades of an account.
+//!
+//! [`match_lots()`](fn.match_lots.html) replays the buys and sells among
+//! transactions, opening a lot for every buy and closing lots for every sell
+//! in the order of a [`TaxLotMethod`](../orders/enum.TaxLotMethod.html).
+//! [`wash_sales()`](fn.wash_sales.html) then flags the sales at a loss with
+//! shares of the same symbol bought within 30 days before or after them:
+//!
+//! ```no_run
+//! use tda_sdk::{lots, orders::TaxLotMethod, params::GetTransactionsParams, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let transactions = client.get_transactions("123456789", GetTransactionsParams::default()).unwrap();
+//!
+//! for wash_sale in lots::wash_sales(&transactions, TaxLotMethod::Fifo) {
+//!     println!(
+//!         "Sold {} {} at a loss on {}, bought back on {}: {:.2} disallowed",
+//!         wash_sale.quantity, wash_sale.symbol, wash_sale.sold_on, wash_sale.replaced_on, wash_sale.disallowed_loss,
+//!     );
+//! }
+//! ```
+//!
+//! Only long positions are tracked. Sells of more shares than are held, such
+//! as short sales, only close the shares which are held. Transactions from
+//! before the start of the history can't be seen, so accounts holding shares
+//! bought earlier should fetch their full history.
+
+use crate::{orders::TaxLotMethod, responses::Transaction, transactions::TransactionKind};
+use chrono::{Duration, NaiveDate};
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Days before and after a sale at a loss during which a purchase makes it a
+/// wash sale.
+pub const WASH_SALE_DAYS: i64 = 30;
+
+/// Shares bought at once and still held.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lot {
+    pub symbol: String,
+    /// ID of the transaction which bought the shares.
+    pub transaction_id: i64,
+    pub acquired_on: NaiveDate,
+    pub quantity: f64,
+    /// Cost of each share, including fees.
+    pub cost_per_share: f64,
+}
+
+impl Lot {
+    pub fn cost(&self) -> f64 {
+        self.quantity * self.cost_per_share
+    }
+}
+
+/// Shares of a lot closed by a sale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClosedLot {
+    pub symbol: String,
+    /// ID of the transaction which bought the shares.
+    pub opened_by: i64,
+    /// ID of the transaction which sold the shares.
+    pub closed_by: i64,
+    pub acquired_on: NaiveDate,
+    pub sold_on: NaiveDate,
+    pub quantity: f64,
+    /// Cost of the shares, including fees.
+    pub cost: f64,
+    /// Proceeds of the shares, net of fees.
+    pub proceeds: f64,
+}
+
+impl ClosedLot {
+    /// Realized gain, negative for a loss.
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost
+    }
+
+    /// Whether the shares were held for more than a year.
+    pub fn is_long_term(&self) -> bool {
+        self.acquired_on.checked_add_months(chrono::Months::new(12)).is_some_and(|year_later| self.sold_on > year_later)
+    }
+}
+
+/// Lots still held and lots closed by sales.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatchedLots {
+    /// Open lots by symbol, in the order they were bought.
+    pub open: BTreeMap<String, Vec<Lot>>,
+    /// Closed lots, in the order they were sold.
+    pub closed: Vec<ClosedLot>,
+}
+
+/// Buy or sell of shares among transactions.
+#[derive(Clone, Debug, PartialEq)]
+struct Trade {
+    transaction_id: i64,
+    symbol: String,
+    date: NaiveDate,
+    quantity: f64,
+    /// Cash paid for a buy, or received for a sell.
+    amount: f64,
+    buy: bool,
+}
+
+/// Trades among transactions, in the order they happened.
+fn trades<'a>(transactions: impl IntoIterator<Item = &'a Transaction>) -> Vec<Trade> {
+    let mut trades: Vec<(i64, Trade)> = transactions
+        .into_iter()
+        .filter(|transaction| transaction.kind() == TransactionKind::Trade)
+        .filter_map(|transaction| {
+            let item = transaction.transaction_item.as_ref()?;
+            let quantity = item.amount?.abs();
+            let buy = match item.instruction.as_deref()? {
+                "BUY" => true,
+                "SELL" => false,
+                _ => return None,
+            };
+            let time = transaction.transaction_date.as_ref()?;
+
+            let trade = Trade {
+                transaction_id: transaction.transaction_id,
+                symbol: transaction.symbol()?.to_string(),
+                date: time.date(),
+                quantity,
+                amount: transaction.net_amount.abs(),
+                buy,
+            };
+
+            Some((time.timestamp_millis(), trade))
+        })
+        .filter(|(_, trade)| trade.quantity > 0.0)
+        .collect();
+
+    trades.sort_by_key(|(time, trade)| (*time, trade.transaction_id));
+    trades.into_iter().map(|(_, trade)| trade).collect()
+}
+
+/// Index of the open lot a sell closes first. Average cost, specific lots,
+/// minimum tax, and unknown methods can't be replayed from transactions, so
+/// they close lots first in, first out.
+fn next_lot(lots: &[Lot], method: TaxLotMethod) -> usize {
+    let by_cost = |a: &(usize, &Lot), b: &(usize, &Lot)| a.1.cost_per_share.total_cmp(&b.1.cost_per_share);
+
+    match method {
+        TaxLotMethod::Lifo => lots.len() - 1,
+        TaxLotMethod::HighCost => lots.iter().enumerate().max_by(by_cost).map_or(0, |(index, _)| index),
+        TaxLotMethod::LowCost => lots.iter().enumerate().min_by(by_cost).map_or(0, |(index, _)| index),
+        TaxLotMethod::Fifo | TaxLotMethod::AverageCost | TaxLotMethod::SpecificLot | TaxLotMethod::MinimumTax | TaxLotMethod::Unknown => 0,
+    }
+}
+
+/// Replay the trades among transactions into lots.
+pub fn match_lots<'a>(transactions: impl IntoIterator<Item = &'a Transaction>, method: TaxLotMethod) -> MatchedLots {
+    let mut matched = MatchedLots::default();
+
+    for trade in trades(transactions) {
+        let lots = matched.open.entry(trade.symbol.clone()).or_default();
+
+        if trade.buy {
+            lots.push(Lot {
+                symbol: trade.symbol,
+                transaction_id: trade.transaction_id,
+                acquired_on: trade.date,
+                quantity: trade.quantity,
+                cost_per_share: trade.amount / trade.quantity,
+            });
+
+            continue;
+        }
+
+        let proceeds_per_share = trade.amount / trade.quantity;
+        let mut remaining = trade.quantity;
+
+        while remaining > 0.0 && !lots.is_empty() {
+            let index = next_lot(lots, method);
+            let lot = &mut lots[index];
+            let quantity = remaining.min(lot.quantity);
+
+            matched.closed.push(ClosedLot {
+                symbol: trade.symbol.clone(),
+                opened_by: lot.transaction_id,
+                closed_by: trade.transaction_id,
+                acquired_on: lot.acquired_on,
+                sold_on: trade.date,
+                quantity,
+                cost: quantity * lot.cost_per_share,
+                proceeds: quantity * proceeds_per_share,
+            });
+
+            lot.quantity -= quantity;
+            remaining -= quantity;
+
+            if lot.quantity <= 0.0 {
+                lots.remove(index);
+            }
+        }
+    }
+
+    matched.open.retain(|_, lots| !lots.is_empty());
+
+    matched
+}
+
+/// Sale at a loss with replacement shares bought within
+/// [`WASH_SALE_DAYS`](constant.WASH_SALE_DAYS.html) of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WashSale {
+    pub symbol: String,
+    /// ID of the transaction which bought the shares sold at a loss.
+    pub purchase_transaction_id: i64,
+    /// ID of the transaction which sold at a loss.
+    pub sale_transaction_id: i64,
+    pub sold_on: NaiveDate,
+    /// ID of the transaction which bought the replacement shares.
+    pub replacement_transaction_id: i64,
+    pub replaced_on: NaiveDate,
+    /// Shares sold at a loss which were replaced.
+    pub quantity: f64,
+    /// Loss of the replaced shares, which can't be deducted yet but is added
+    /// to the cost of the replacement shares. Positive.
+    pub disallowed_loss: f64,
+}
+
+/// Find potential wash sales among transactions, matching sales to lots with
+/// `method`.
+///
+/// Each replacement share washes at most one share sold at a loss, and
+/// shares sold by the same sale never replace each other, so the shares
+/// bought by a lot never replace the shares of that same lot. The findings
+/// are potential wash sales: purchases in other accounts, such as IRAs, and
+/// of substantially identical securities aren't considered.
+pub fn wash_sales<'a>(transactions: impl IntoIterator<Item = &'a Transaction> + Clone, method: TaxLotMethod) -> Vec<WashSale> {
+    let buys: Vec<Trade> = trades(transactions.clone()).into_iter().filter(|trade| trade.buy).collect();
+    let mut replaceable: HashMap<i64, f64> = buys.iter().map(|buy| (buy.transaction_id, buy.quantity)).collect();
+    let closed = match_lots(transactions, method).closed;
+    let mut wash_sales = Vec::new();
+
+    // Shares of each buy disposed of by each sale, by buy and sale ID.
+    let mut sold_by: HashMap<(i64, i64), f64> = HashMap::new();
+
+    for lot in &closed {
+        *sold_by.entry((lot.opened_by, lot.closed_by)).or_default() += lot.quantity;
+    }
+
+    for lot in closed {
+        let loss_per_share = -lot.gain() / lot.quantity;
+
+        if loss_per_share <= 0.0 {
+            continue;
+        }
+
+        let window = Duration::days(WASH_SALE_DAYS);
+        let mut remaining = lot.quantity;
+
+        for buy in &buys {
+            if remaining <= 0.0 {
+                break;
+            }
+
+            if buy.symbol != lot.symbol || buy.transaction_id == lot.opened_by || buy.date < lot.sold_on - window || buy.date > lot.sold_on + window {
+                continue;
+            }
+
+            let kept = buy.quantity - sold_by.get(&(buy.transaction_id, lot.closed_by)).copied().unwrap_or_default();
+            let available = replaceable.get_mut(&buy.transaction_id).unwrap();
+            let quantity = remaining.min(*available).min(kept);
+
+            if quantity <= 0.0 {
+                continue;
+            }
+
+            *available -= quantity;
+            remaining -= quantity;
+
+            wash_sales.push(WashSale {
+                symbol: lot.symbol.clone(),
+                purchase_transaction_id: lot.opened_by,
+                sale_transaction_id: lot.closed_by,
+                sold_on: lot.sold_on,
+                replacement_transaction_id: buy.transaction_id,
+                replaced_on: buy.date,
+                quantity,
+                disallowed_loss: quantity * loss_per_share,
+            });
+        }
+    }
+
+    wash_sales
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        responses::{TransactionInstrument, TransactionItem},
+        time::TdaDateTime,
+    };
+
+    fn trade(transaction_id: i64, date: &str, instruction: &str, symbol: &str, quantity: f64, price: f64) -> Transaction {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let amount = quantity * price;
+
+        Transaction {
+            net_amount: if instruction == "BUY" { -amount } else { amount },
+            transaction_date: TdaDateTime::from_date(date),
+            transaction_id,
+            transaction_item: Some(TransactionItem {
+                amount: Some(quantity),
+                instruction: Some(instruction.to_string()),
+                instrument: Some(TransactionInstrument {
+                    asset_type: Some("EQUITY".to_string()),
+                    symbol: Some(symbol.to_string()),
+                    ..TransactionInstrument::default()
+                }),
+                price: Some(price),
+                ..TransactionItem::default()
+            }),
+            transaction_sub_type: Some(if instruction == "BUY" { "BY" } else { "SL" }.to_string()),
+            transaction_type: "TRADE".to_string(),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn matches_lots() {
+        let transactions = vec![
+            trade(1, "2019-01-02", "BUY", "AAPL", 10.0, 100.0),
+            trade(2, "2020-03-02", "BUY", "AAPL", 10.0, 150.0),
+            trade(3, "2020-06-01", "SELL", "AAPL", 15.0, 120.0),
+        ];
+
+        let fifo = match_lots(&transactions, TaxLotMethod::Fifo);
+
+        assert_eq!(fifo.closed.len(), 2);
+        assert_eq!(fifo.closed[0].gain(), 200.0);
+        assert!(fifo.closed[0].is_long_term());
+        assert_eq!(fifo.closed[1].gain(), -150.0);
+        assert!(!fifo.closed[1].is_long_term());
+        assert_eq!(fifo.open["AAPL"][0].quantity, 5.0);
+        assert_eq!(fifo.open["AAPL"][0].transaction_id, 2);
+
+        let high_cost = match_lots(&transactions, TaxLotMethod::HighCost);
+
+        assert_eq!(high_cost.closed[0].opened_by, 2);
+        assert_eq!(high_cost.open["AAPL"][0].transaction_id, 1);
+    }
+
+    #[test]
+    fn finds_wash_sales() {
+        let transactions = vec![
+            trade(1, "2020-01-02", "BUY", "MSFT", 10.0, 180.0),
+            trade(2, "2020-03-16", "SELL", "MSFT", 10.0, 140.0),
+            trade(3, "2020-04-01", "BUY", "MSFT", 4.0, 150.0),
+            trade(4, "2020-06-01", "BUY", "MSFT", 6.0, 180.0),
+            trade(5, "2020-03-10", "BUY", "AAPL", 5.0, 250.0),
+            trade(6, "2020-05-01", "SELL", "AAPL", 5.0, 290.0),
+        ];
+
+        let wash_sales = wash_sales(&transactions, TaxLotMethod::Fifo);
+
+        assert_eq!(wash_sales.len(), 1);
+        assert_eq!(wash_sales[0].sale_transaction_id, 2);
+        assert_eq!(wash_sales[0].replacement_transaction_id, 3);
+        assert_eq!(wash_sales[0].quantity, 4.0);
+        assert_eq!(wash_sales[0].disallowed_loss, 160.0);
+    }
+
+    #[test]
+    fn shares_sold_together_do_not_replace_each_other() {
+        let transactions = vec![
+            trade(1, "2020-03-01", "BUY", "MSFT", 10.0, 180.0),
+            trade(2, "2020-03-20", "BUY", "MSFT", 10.0, 170.0),
+            trade(3, "2020-03-25", "SELL", "MSFT", 20.0, 140.0),
+        ];
+
+        assert_eq!(wash_sales(&transactions, TaxLotMethod::Fifo), []);
+
+        // Shares of the second buy still held after the sale do replace
+        // those of the first.
+        let transactions = vec![
+            trade(1, "2020-03-01", "BUY", "MSFT", 10.0, 180.0),
+            trade(2, "2020-03-20", "BUY", "MSFT", 10.0, 170.0),
+            trade(3, "2020-03-25", "SELL", "MSFT", 14.0, 140.0),
+        ];
+        let wash_sales = wash_sales(&transactions, TaxLotMethod::Fifo);
+
+        assert_eq!(wash_sales.len(), 1);
+        assert_eq!(wash_sales[0].purchase_transaction_id, 1);
+        assert_eq!(wash_sales[0].replacement_transaction_id, 2);
+        assert_eq!(wash_sales[0].quantity, 6.0);
+    }
+}
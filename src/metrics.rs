@@ -0,0 +1,323 @@
+//! Prometheus metrics of the SDK.
+//!
+//! With the `metrics` feature enabled, every client records its requests,
+//! errors, rate limiter waits, and token refreshes, and streamer connections
+//! their reconnects, in a process-wide registry. The registry renders them in
+//! the Prometheus text format, and can serve them for scraping:
+//!
+//! ```no_run
+//! use tda_sdk::metrics;
+//!
+//! metrics::serve("0.0.0.0:9184").unwrap();
+//!
+//! // Or include them in the metrics of the app.
+//! let text = metrics::global().render();
+//! ```
+//!
+//! Endpoints are labeled by their path with parameters replaced, such as
+//! `/accounts/{accountId}/orders`, so account IDs and symbols don't end up in
+//! labels.
+
+use crate::ClientError;
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// How long a scrape may take to send its request or read the response
+/// before its connection is dropped.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+static GLOBAL: Metrics = Metrics::new();
+
+/// Registry every client and streamer connection records into.
+pub fn global() -> &'static Metrics {
+    &GLOBAL
+}
+
+/// Serve the global registry over HTTP on a background thread. Every request
+/// gets the rendered metrics, whatever its path.
+///
+/// Each connection is served on its own thread and dropped after
+/// `CONNECTION_TIMEOUT`, so a stalled client can't block other scrapes.
+pub fn serve(addr: impl ToSocketAddrs) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || {
+                        if let Err(error) = respond(stream) {
+                            log::warn!("Failed to serve metrics: {}", error);
+                        }
+                    });
+                }
+                Err(error) => log::warn!("Failed to accept metrics connection: {}", error),
+            }
+        }
+    }))
+}
+
+/// Answer a scrape with the rendered global registry.
+fn respond(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+
+    // The request itself doesn't matter, but has to be read before responding.
+    let mut request = [0; 1024];
+    let _ = stream.read(&mut request)?;
+
+    let body = global().render();
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body,
+    )
+}
+
+/// Counters and histograms of the SDK.
+#[derive(Debug)]
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Requests by method, endpoint, and status.
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// Request durations by endpoint.
+    durations: BTreeMap<String, Histogram>,
+    /// Errors by the kind of `ClientError`.
+    errors: BTreeMap<&'static str, u64>,
+    rate_limit_waits: Histogram,
+    token_refreshes: u64,
+    streamer_reconnects: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                requests: BTreeMap::new(),
+                durations: BTreeMap::new(),
+                errors: BTreeMap::new(),
+                rate_limit_waits: Histogram::new(),
+                token_refreshes: 0,
+                streamer_reconnects: 0,
+            }),
+        }
+    }
+
+    /// Record a response received for a request to `url`.
+    pub fn observe_request(&self, method: &str, url: &str, status: u16, elapsed: Duration) {
+        let endpoint = endpoint(url);
+        let mut state = self.state.lock().unwrap();
+
+        *state.requests.entry((method.to_string(), endpoint.clone(), status)).or_default() += 1;
+        state.durations.entry(endpoint).or_default().observe(elapsed);
+    }
+
+    pub fn observe_error(&self, error: &ClientError) {
+        *self.state.lock().unwrap().errors.entry(error.kind()).or_default() += 1;
+    }
+
+    /// Record how long a request waited on the rate limiter.
+    pub fn observe_rate_limit_wait(&self, waited: Duration) {
+        self.state.lock().unwrap().rate_limit_waits.observe(waited);
+    }
+
+    pub fn inc_token_refreshes(&self) {
+        self.state.lock().unwrap().token_refreshes += 1;
+    }
+
+    pub fn inc_streamer_reconnects(&self) {
+        self.state.lock().unwrap().streamer_reconnects += 1;
+    }
+
+    /// Metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "tda_requests_total", "counter", "Responses received from the API.");
+        for ((method, endpoint, status), count) in &state.requests {
+            let _ = writeln!(out, "tda_requests_total{{method=\"{}\",endpoint=\"{}\",status=\"{}\"}} {}", method, escape(endpoint), status, count);
+        }
+
+        header(&mut out, "tda_request_duration_seconds", "histogram", "Time to receive a response from the API.");
+        for (endpoint, histogram) in &state.durations {
+            histogram.render(&mut out, "tda_request_duration_seconds", &format!("endpoint=\"{}\"", escape(endpoint)));
+        }
+
+        header(&mut out, "tda_errors_total", "counter", "Errors returned by the client, by kind.");
+        for (kind, count) in &state.errors {
+            let _ = writeln!(out, "tda_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+
+        header(&mut out, "tda_rate_limit_wait_seconds", "histogram", "Time requests waited on the rate limiter.");
+        state.rate_limit_waits.render(&mut out, "tda_rate_limit_wait_seconds", "");
+
+        header(&mut out, "tda_token_refreshes_total", "counter", "Access token refreshes.");
+        let _ = writeln!(out, "tda_token_refreshes_total {}", state.token_refreshes);
+
+        header(&mut out, "tda_streamer_reconnects_total", "counter", "Streamer connections resumed after a disconnect.");
+        let _ = writeln!(out, "tda_streamer_reconnects_total {}", state.streamer_reconnects);
+
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+
+        for (count, bound) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, count);
+        }
+
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, separator, self.count);
+
+        let labels = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Path of the endpoint of a URL, with its parameters replaced by
+/// placeholders.
+pub(crate) fn endpoint(url: &str) -> String {
+    let url = url.split('?').next().unwrap_or_default();
+    let path = url.split_once("://").map_or(url, |(_, rest)| rest.find('/').map_or("", |start| &rest[start..]));
+    let path = path.strip_prefix("/trader").unwrap_or(path);
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+    let replaced: Vec<&str> = segments
+        .iter()
+        .enumerate()
+        .map(|(index, segment)| {
+            let previous = index.checked_sub(1).map(|previous| segments[previous]);
+
+            match previous {
+                Some("accounts") => "{accountId}",
+                Some("orders") | Some("savedorders") => "{orderId}",
+                Some("watchlists") => "{watchlistId}",
+                Some("instruments") => "{cusip}",
+                Some("marketdata") if index + 1 < segments.len() => "{symbol}",
+                _ => segment,
+            }
+        })
+        .collect();
+
+    format!("/{}", replaced.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(endpoint("https://api.tdameritrade.com/v1/accounts/123456789/orders?status=FILLED"), "/accounts/{accountId}/orders");
+        assert_eq!(endpoint("https://api.schwabapi.com/trader/v1/accounts/123/watchlists/42"), "/accounts/{accountId}/watchlists/{watchlistId}");
+        assert_eq!(endpoint("https://api.tdameritrade.com/v1/marketdata/AAPL/pricehistory"), "/marketdata/{symbol}/pricehistory");
+        assert_eq!(endpoint("https://api.tdameritrade.com/v1/marketdata/quotes?symbol=AAPL"), "/marketdata/quotes");
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let metrics = Metrics::new();
+
+        metrics.observe_request("GET", "https://api.tdameritrade.com/v1/marketdata/AAPL/quotes", 200, Duration::from_millis(30));
+        metrics.observe_request("GET", "https://api.tdameritrade.com/v1/marketdata/MSFT/quotes", 200, Duration::from_millis(70));
+        metrics.observe_error(&ClientError::TradingDisabled);
+        metrics.observe_rate_limit_wait(Duration::from_secs(2));
+        metrics.inc_token_refreshes();
+
+        let text = metrics.render();
+
+        assert!(text.contains("tda_requests_total{method=\"GET\",endpoint=\"/marketdata/{symbol}/quotes\",status=\"200\"} 2\n"));
+        assert!(text.contains("tda_request_duration_seconds_bucket{endpoint=\"/marketdata/{symbol}/quotes\",le=\"0.05\"} 1\n"));
+        assert!(text.contains("tda_request_duration_seconds_count{endpoint=\"/marketdata/{symbol}/quotes\"} 2\n"));
+        assert!(text.contains("tda_errors_total{kind=\"trading_disabled\"} 1\n"));
+        assert!(text.contains("tda_rate_limit_wait_seconds_bucket{le=\"1\"} 0\n"));
+        assert!(text.contains("tda_rate_limit_wait_seconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(text.contains("tda_token_refreshes_total 1\n"));
+        assert!(text.contains("tda_streamer_reconnects_total 0\n"));
+    }
+
+    #[test]
+    fn serves_while_a_client_stalls() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        serve(("127.0.0.1", port)).unwrap();
+
+        // Connects but never sends its request.
+        let _stalled = TcpStream::connect(("127.0.0.1", port)).unwrap();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("tda_token_refreshes_total"));
+    }
+}
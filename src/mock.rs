@@ -0,0 +1,852 @@
+//! Mock transport for testing apps without the API.
+//!
+//! A [`MockTransport`](struct.MockTransport.html) answers requests with canned
+//! responses, records every request it receives, and can inject latency,
+//! `429`/`500` responses, and malformed JSON to exercise retry and backoff
+//! handling. Random faults come from a seeded generator, so a test sees the
+//! same faults on every run.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use tda_sdk::{
+//!     mock::{Fault, MockTransport},
+//!     AccessToken,
+//!     Client,
+//!     ClientError,
+//! };
+//!
+//! let transport = Arc::new(MockTransport::new());
+//! transport.respond("GET", "/v1/marketdata/AAPL/quotes", 200, r#"{"AAPL": {"assetType": "EQUITY", "symbol": "AAPL"}}"#);
+//! transport.push_fault(Fault::Status(429));
+//!
+//! let token = AccessToken { expires_at: 0, scope: Vec::new(), token: "TOKEN".to_string() };
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", Some(token));
+//! client.set_transport(Some(transport.clone()));
+//!
+//! assert!(matches!(client.get_quote("AAPL"), Err(ClientError::NotHttpOk(429, ..))));
+//! assert!(client.get_quote("AAPL").unwrap().is_some());
+//! assert_eq!(transport.requests().len(), 2);
+//! ```
+
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+
+use std::{
+    collections::VecDeque,
+    io,
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+/// Body of injected `429` responses.
+const RATE_LIMITED_BODY: &str = r#"{"error":"Individual App's transactions per seconds restriction reached. Please contact us with further questions"}"#;
+
+/// Body of injected `500` responses.
+const SERVER_ERROR_BODY: &str = r#"{"error":"Internal server error"}"#;
+
+/// Delay added before every response of a
+/// [`MockTransport`](struct.MockTransport.html).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Latency {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// Uniformly distributed between the two durations.
+    Uniform(Duration, Duration),
+}
+
+/// Random faults injected by a [`MockTransport`](struct.MockTransport.html).
+///
+/// Rates are probabilities between `0.0` and `1.0`, rolled for every request
+/// in the order of the fields. At most one of the status and malformed JSON
+/// faults applies to a request.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Faults {
+    pub latency: Latency,
+    /// Rate of `429 Too Many Requests` responses.
+    pub rate_limited: f64,
+    /// Rate of `500 Internal Server Error` responses.
+    pub server_error: f64,
+    /// Rate of successful responses with their body cut in half.
+    pub malformed_json: f64,
+    /// Seed of the random generator.
+    pub seed: u64,
+}
+
+/// Fault applied to a single request, see
+/// [`MockTransport::push_fault()`](struct.MockTransport.html#method.push_fault).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    /// Delay the response.
+    Latency(Duration),
+    /// Respond with an error status and a matching body.
+    Status(u16),
+    /// Respond with the body of the route cut in half.
+    MalformedJson,
+    /// Fail without a response, like a dropped connection.
+    ConnectionReset,
+}
+
+#[derive(Clone, Debug)]
+struct Route {
+    method: String,
+    path: String,
+    status: u16,
+    body: String,
+    etag: Option<String>,
+    headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<HttpRequest>,
+    faults: Faults,
+    queued: VecDeque<Fault>,
+    rng: u64,
+}
+
+/// Transport answering requests with canned responses.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    state: Mutex<State>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests for a method and URL path with a status and body.
+    /// Routes added later take precedence, and requests without a route get
+    /// a `404` response.
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: &str) -> &Self {
+        self.state.lock().unwrap().routes.push(Route {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            body: body.to_string(),
+            etag: None,
+            headers: Vec::new(),
+        });
+
+        self
+    }
+
+    /// Answer requests for a method and URL path with a body and its `ETag`,
+    /// or with `304 Not Modified` if the request has a matching
+    /// `If-None-Match` header.
+    pub fn respond_with_etag(&self, method: &str, path: &str, body: &str, etag: &str) -> &Self {
+        self.state.lock().unwrap().routes.push(Route {
+            method: method.to_string(),
+            path: path.to_string(),
+            status: 200,
+            body: body.to_string(),
+            etag: Some(etag.to_string()),
+            headers: Vec::new(),
+        });
+
+        self
+    }
+
+    /// Answer requests for a method and URL path with a status, body, and
+    /// headers, such as the `Location` of a new order.
+    pub fn respond_with_headers(&self, method: &str, path: &str, status: u16, body: &str, headers: &[(&str, &str)]) -> &Self {
+        self.state.lock().unwrap().routes.push(Route {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            body: body.to_string(),
+            etag: None,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        });
+
+        self
+    }
+
+    /// Inject random faults into every following request.
+    pub fn set_faults(&self, faults: Faults) -> &Self {
+        let mut state = self.state.lock().unwrap();
+
+        state.rng = faults.seed;
+        state.faults = faults;
+
+        self
+    }
+
+    /// Queue a fault for a single request. Queued faults apply one per
+    /// request in order, replacing the random faults of that request.
+    pub fn push_fault(&self, fault: Fault) -> &Self {
+        self.state.lock().unwrap().queued.push_back(fault);
+
+        self
+    }
+
+    /// Every request received so far.
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+/// Client with an access token sending its requests to `transport`, for
+/// tests.
+#[cfg(test)]
+pub(crate) fn test_client(transport: std::sync::Arc<dyn Transport>) -> crate::Client {
+    let token = crate::AccessToken {
+        expires_at: 0,
+        scope: Vec::new(),
+        token: "TOKEN".to_string(),
+    };
+    let mut client = crate::Client::new("CLIENT_ID", "REFRESH_TOKEN", Some(token));
+
+    client.set_transport(Some(transport));
+
+    client
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+        let (route, faults) = {
+            let mut state = self.state.lock().unwrap();
+
+            state.requests.push(request.clone());
+
+            let route = state
+                .routes
+                .iter()
+                .rev()
+                .find(|route| route.method.eq_ignore_ascii_case(&request.method) && route.path == request.path())
+                .cloned()
+                .unwrap_or_else(|| Route {
+                    method: request.method.clone(),
+                    path: request.path().to_string(),
+                    status: 404,
+                    body: format!(r#"{{"error":"No mock response for {} {}"}}"#, request.method, request.path()),
+                    etag: None,
+                    headers: Vec::new(),
+                });
+
+            (route, state.next_faults())
+        };
+
+        let mut status = route.status;
+        let mut body = route.body;
+
+        if route.etag.is_some() && request.header("If-None-Match") == route.etag.as_deref() {
+            status = 304;
+            body.clear();
+        }
+
+        for fault in faults {
+            match fault {
+                Fault::Latency(delay) => thread::sleep(delay),
+                Fault::Status(fault_status) => {
+                    status = fault_status;
+                    body = match fault_status {
+                        429 => RATE_LIMITED_BODY.to_string(),
+                        500 => SERVER_ERROR_BODY.to_string(),
+                        _ => format!(r#"{{"error":"Injected {} response"}}"#, fault_status),
+                    };
+                }
+                Fault::MalformedJson => {
+                    let mut cut = body.len() / 2;
+
+                    while !body.is_char_boundary(cut) {
+                        cut -= 1;
+                    }
+
+                    body.truncate(cut);
+                }
+                Fault::ConnectionReset => return Err(io::Error::new(io::ErrorKind::ConnectionReset, "injected connection reset")),
+            }
+        }
+
+        let mut response = HttpResponse::new(status, body).with_header("Content-Type", "application/json");
+
+        if let Some(etag) = &route.etag {
+            response = response.with_header("ETag", etag);
+        }
+
+        for (name, value) in &route.headers {
+            response = response.with_header(name, value);
+        }
+
+        Ok(match status {
+            429 => response.with_header("Retry-After", "1"),
+            _ => response,
+        })
+    }
+}
+
+impl State {
+    /// Faults for the next request: the queued one, or random ones.
+    fn next_faults(&mut self) -> Vec<Fault> {
+        if let Some(fault) = self.queued.pop_front() {
+            return vec![fault];
+        }
+
+        let faults = self.faults;
+        let mut next = Vec::new();
+
+        match faults.latency {
+            Latency::None => {}
+            Latency::Fixed(delay) => next.push(Fault::Latency(delay)),
+            Latency::Uniform(min, max) => {
+                let spread = max.saturating_sub(min).as_secs_f64();
+
+                next.push(Fault::Latency(min + Duration::from_secs_f64(spread * self.random())));
+            }
+        }
+
+        if self.random() < faults.rate_limited {
+            next.push(Fault::Status(429));
+        } else if self.random() < faults.server_error {
+            next.push(Fault::Status(500));
+        } else if self.random() < faults.malformed_json {
+            next.push(Fault::MalformedJson);
+        }
+
+        next
+    }
+
+    /// Next random number in `[0, 1)`, from a SplitMix64 generator.
+    fn random(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{Clock, MockClock},
+        orders::{Instruction, Order, OrderCapabilities, OrderStatus},
+        params::{GetQuotesParams, PlaceOrdersParams, QuoteField},
+        schema::{self, ParseMode},
+        watchlists::Watchlist,
+        AccountId,
+        CacheStatus,
+        ClientError,
+    };
+    use serde_json::{json, Value};
+    use std::{sync::Arc, time::Instant};
+
+    const QUOTES_PATH: &str = "/v1/marketdata/quotes";
+
+    /// Records when each request is sent by a mock clock, and takes
+    /// `latency` of it to respond.
+    #[derive(Debug)]
+    struct TimedTransport {
+        inner: Arc<MockTransport>,
+        clock: Arc<MockClock>,
+        latency: Duration,
+        sent_at: Mutex<Vec<Instant>>,
+    }
+
+    impl Transport for TimedTransport {
+        fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+            self.sent_at.lock().unwrap().push(self.clock.instant());
+            self.clock.advance(self.latency);
+            self.inner.send(request)
+        }
+    }
+
+    #[test]
+    fn serves_routes() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport.respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"));
+
+        assert_eq!(client.get_quotes(&["AAPL", "$SPX.X"]).unwrap().found.len(), 3);
+        assert!(matches!(client.get_movers("$DJI", Default::default()), Err(ClientError::NotHttpOk(404, ..))));
+
+        let requests = transport.requests();
+
+        assert_eq!(requests[0].query("symbol").as_deref(), Some("AAPL,$SPX.X"));
+        assert_eq!(requests[0].header("Authorization"), Some("Bearer TOKEN"));
+    }
+
+    #[test]
+    fn response_meta() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport.respond("GET", "/v1/marketdata/AAPL/pricehistory", 200, include_str!("../tests/fixtures/price_history.json"));
+
+        let history = client.get_price_history_with_meta("AAPL", Default::default()).unwrap();
+
+        assert_eq!(history.status, 200);
+        assert_eq!(history.header("content-type"), Some("application/json"));
+        assert_eq!(history.symbol, "AAPL");
+    }
+
+    #[test]
+    fn conditional_requests() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport.respond_with_etag("GET", "/v1/marketdata/EQUITY/hours", include_str!("../tests/fixtures/market_hours.json"), "\"v1\"");
+
+        let hours = client.get_market_hours_if_none_match("EQUITY", Default::default(), None).unwrap().modified().unwrap();
+
+        assert_eq!(hours.etag(), Some("\"v1\""));
+        assert!(hours.contains_key("equity"));
+
+        let status = client.get_market_hours_if_none_match("EQUITY", Default::default(), hours.etag()).unwrap();
+
+        assert_eq!(status, CacheStatus::NotModified);
+        assert_eq!(transport.requests()[1].header("If-None-Match"), Some("\"v1\""));
+    }
+
+    #[test]
+    fn injects_faults() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport
+            .respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"))
+            .push_fault(Fault::Status(500))
+            .push_fault(Fault::MalformedJson)
+            .push_fault(Fault::ConnectionReset);
+
+        assert!(matches!(client.get_quotes(&["AAPL"]), Err(ClientError::NotHttpOk(500, ..))));
+        assert!(matches!(client.get_quotes(&["AAPL"]), Err(ClientError::ParseResponse { .. })));
+        assert!(matches!(client.get_quotes(&["AAPL"]), Err(ClientError::ReadResponse(_))));
+        assert!(client.get_quotes(&["AAPL"]).is_ok());
+    }
+
+    #[test]
+    fn selects_quote_fields() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport.respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"));
+
+        let params = GetQuotesParams {
+            fields: Some(vec![QuoteField::Bid, QuoteField::Last]),
+        };
+        let quotes = client.get_quotes_with(&["AAPL"], params).unwrap();
+        let quote = &quotes.found["AAPL"];
+
+        assert_eq!(transport.requests()[0].query("fields").as_deref(), Some("bidPrice,lastPrice"));
+        assert_eq!(quote.symbol(), Some("AAPL"));
+        assert_eq!(quote.bid_price(), Some(318.7));
+        assert_eq!(quote.last_price(), Some(318.73));
+        assert_eq!(quote.ask_price(), None);
+    }
+
+    #[test]
+    fn injects_headers() {
+        #[derive(Debug)]
+        struct PathHook;
+
+        impl crate::transport::RequestHook for PathHook {
+            fn headers(&self, request: &HttpRequest) -> Vec<(String, String)> {
+                vec![("X-Path".to_string(), request.path().to_string())]
+            }
+        }
+
+        let transport = Arc::new(MockTransport::new());
+        let mut client = test_client(transport.clone());
+
+        transport.respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"));
+        client.set_request_hook(Some(Arc::new(PathHook)));
+        client.get_quotes(&["AAPL"]).unwrap();
+        client.set_request_hook(Some(Arc::new(crate::transport::StaticHeaders::new(&[("X-Api-Key", "KEY"), ("Authorization", "Gateway")]))));
+        client.get_quotes(&["AAPL"]).unwrap();
+
+        let requests = transport.requests();
+
+        assert_eq!(requests[0].header("X-Path"), Some(QUOTES_PATH));
+        assert_eq!(requests[0].header("Authorization"), Some("Bearer TOKEN"));
+        assert_eq!(requests[1].header("X-Api-Key"), Some("KEY"));
+        assert_eq!(requests[1].header("Authorization"), Some("Gateway"));
+    }
+
+    #[test]
+    fn sends_correlation_ids() {
+        let transport = Arc::new(MockTransport::new());
+        let mut client = test_client(transport.clone());
+
+        transport.respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"));
+        crate::correlation::scope("flow-1", || client.get_quotes(&["AAPL"])).unwrap();
+        client.get_quotes(&["AAPL"]).unwrap();
+        client.set_correlation_header(None);
+        client.get_quotes(&["AAPL"]).unwrap();
+
+        let requests = transport.requests();
+
+        assert_eq!(requests[0].header("X-Correlation-ID"), Some("flow-1"));
+        assert!(requests[1].header("X-Correlation-ID").is_some_and(|id| id != "flow-1"));
+        assert_eq!(requests[2].header("X-Correlation-ID"), None);
+    }
+
+    #[test]
+    fn errors_carry_correlation_ids() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        let error = crate::correlation::scope("flow-1", || client.get_all_movers(Default::default())).unwrap_err();
+
+        assert!(matches!(error, ClientError::NotHttpOk(404, ..)));
+        assert_eq!(error.correlation_id(), Some("flow-1"));
+        assert!(transport.requests().iter().all(|request| request.header("X-Correlation-ID") == Some("flow-1")));
+        assert_eq!(transport.requests().len(), 3);
+
+        transport.respond("GET", QUOTES_PATH, 200, "[]");
+
+        let error = client.get_quotes(&["AAPL"]).unwrap_err();
+
+        assert!(matches!(error, ClientError::ParseResponse { .. }));
+        assert_eq!(error.correlation_id(), transport.requests()[3].header("X-Correlation-ID"));
+
+        transport.respond("GET", QUOTES_PATH, 503, "");
+
+        let error = crate::correlation::scope("flow-2", || client.get_quotes(&["AAPL"])).unwrap_err();
+
+        assert!(matches!(error, ClientError::Maintenance(_)));
+        assert_eq!(error.correlation_id(), Some("flow-2"));
+    }
+
+    #[test]
+    fn shuts_down() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport.respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"));
+        client.get_quotes(&["AAPL"]).unwrap();
+
+        let report = client.shutdown(std::time::Duration::from_secs(1));
+
+        assert!(report.drained);
+        assert!(report.audit_flush.is_none());
+        assert!(client.shutdown_signal().is_triggered());
+        assert!(matches!(client.get_quotes(&["AAPL"]), Err(ClientError::ShuttingDown)));
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn shutdown_waits_for_body() {
+        /// Body signaling its first read, then taking a while to arrive.
+        struct SlowBody {
+            body: io::Cursor<Vec<u8>>,
+            started: Option<std::sync::mpsc::Sender<()>>,
+        }
+
+        impl io::Read for SlowBody {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if let Some(started) = self.started.take() {
+                    started.send(()).unwrap();
+                    thread::sleep(Duration::from_millis(200));
+                }
+
+                self.body.read(buf)
+            }
+        }
+
+        #[derive(Debug)]
+        struct SlowTransport(Mutex<Option<std::sync::mpsc::Sender<()>>>);
+
+        impl Transport for SlowTransport {
+            fn send(&self, _: &HttpRequest) -> io::Result<HttpResponse> {
+                Ok(HttpResponse {
+                    body: Box::new(SlowBody {
+                        body: io::Cursor::new(include_bytes!("../tests/fixtures/quotes.json").to_vec()),
+                        started: self.0.lock().unwrap().take(),
+                    }),
+                    ..HttpResponse::new(200, "")
+                })
+            }
+        }
+
+        let (started, reading) = std::sync::mpsc::channel();
+        let client = test_client(Arc::new(SlowTransport(Mutex::new(Some(started)))));
+
+        thread::scope(|scope| {
+            let quotes = scope.spawn(|| client.get_quotes(&["AAPL"]));
+
+            reading.recv().unwrap();
+
+            assert_eq!(client.shutdown_signal().in_flight(), 1);
+            assert!(!client.shutdown(Duration::from_millis(10)).drained);
+            assert!(client.shutdown(Duration::from_secs(5)).drained);
+            assert!(quotes.join().unwrap().is_ok());
+        });
+    }
+
+    #[test]
+    fn limits_response_size() {
+        let transport = Arc::new(MockTransport::new());
+        let mut client = test_client(transport.clone());
+
+        transport
+            .respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"))
+            .respond("GET", "/v1/marketdata/chains", 200, include_str!("../tests/fixtures/option_chain.json"))
+            .respond_with_headers("GET", "/v1/marketdata/AAPL/pricehistory", 200, "{}", &[("Content-Length", "100000")]);
+        client.set_max_response_size(Some(256));
+
+        assert!(matches!(client.get_quotes(&["AAPL"]), Err(ClientError::ResponseTooLarge(256))));
+        assert!(matches!(
+            client.get_option_chain("AAPL", crate::params::GetOptionChainParams::default()),
+            Err(ClientError::ResponseTooLarge(256))
+        ));
+        assert!(matches!(
+            client.get_price_history("AAPL", crate::params::GetPriceHistoryParams::default()),
+            Err(ClientError::ResponseTooLarge(256))
+        ));
+
+        client.set_max_response_size(None);
+
+        assert!(client.get_quotes(&["AAPL"]).is_ok());
+    }
+
+    #[test]
+    fn retries_rejected_batch() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport
+            .respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"))
+            .push_fault(Fault::Status(400));
+
+        let quotes = client.get_quotes(&["AAPL", "MSFT"]).unwrap();
+        let requests = transport.requests();
+
+        assert!(quotes.found.contains_key("AAPL"));
+        assert_eq!(quotes.not_found, ["MSFT"]);
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].query("symbol").as_deref(), Some("MSFT"));
+    }
+
+    #[test]
+    fn bisects_rejected_batch() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let symbols = ["A", "B", "C", "D", "E", "F", "G", "BAD"];
+
+        transport.respond("GET", QUOTES_PATH, 200, "{}");
+
+        // Every batch including the bad symbol is rejected.
+        for _ in 0..3 {
+            transport.push_fault(Fault::Status(400)).push_fault(Fault::Latency(Duration::ZERO));
+        }
+
+        transport.push_fault(Fault::Status(400));
+
+        let quotes = client.get_quotes(&symbols).unwrap();
+        let batches: Vec<_> = transport.requests().iter().map(|request| request.query("symbol").unwrap()).collect();
+
+        assert_eq!(quotes.not_found.last().map(String::as_str), Some("BAD"));
+        assert_eq!(batches, ["A,B,C,D,E,F,G,BAD", "A,B,C,D", "E,F,G,BAD", "E,F", "G,BAD", "G", "BAD"]);
+    }
+
+    #[test]
+    fn trading_disabled() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let order = Order::equity_market(Instruction::Buy, "AAPL", 1.0);
+
+        client.set_trading_enabled(false);
+
+        assert!(matches!(client.place_order("123", &order), Err(ClientError::TradingDisabled)));
+        assert!(matches!(client.create_watchlist("123", &Watchlist::default()), Err(ClientError::TradingDisabled)));
+        assert!(transport.requests().is_empty());
+
+        client.set_trading_enabled(true);
+
+        assert!(matches!(client.place_order("123", &order), Err(ClientError::NotHttpOk(404, ..))));
+    }
+
+    #[test]
+    fn rejects_unsupported_fractional_orders() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let principals = json!({
+            "accounts": [
+                {
+                    "accountCdDomainId": "A000000012345678",
+                    "accountId": "123",
+                    "authorizations": {"fractionalTrading": true, "notionalTrading": false, "stockTrading": true},
+                    "company": "AMER",
+                    "segment": "AMER",
+                },
+                {"accountCdDomainId": "A000000087654321", "accountId": "456", "company": "AMER", "segment": "AMER"},
+            ],
+            "userId": "myuser",
+        });
+
+        transport.respond("GET", "/v1/userprincipals", 200, &principals.to_string());
+
+        let fractional = Order::equity_market(Instruction::Buy, "AAPL", 0.5);
+        let notional = Order::equity_notional(Instruction::Buy, "AAPL", 100.0);
+
+        assert!(matches!(client.place_order("123", &fractional), Err(ClientError::NotHttpOk(404, ..))));
+        assert!(matches!(client.place_order("123", &notional), Err(ClientError::OrderRejected(_))));
+        assert!(matches!(client.place_order("456", &fractional), Err(ClientError::NotHttpOk(404, ..))));
+        assert!(matches!(client.place_order("789", &notional), Err(ClientError::NotHttpOk(404, ..))));
+        assert!(matches!(client.place_order("789", &notional), Err(ClientError::NotHttpOk(404, ..))));
+
+        let requests = transport.requests();
+        let paths = requests.iter().map(|request| request.path()).collect::<Vec<_>>();
+
+        assert_eq!(
+            paths,
+            [
+                "/v1/userprincipals",
+                "/v1/accounts/123/orders",
+                "/v1/accounts/456/orders",
+                "/v1/userprincipals",
+                "/v1/accounts/789/orders",
+                "/v1/accounts/789/orders",
+            ]
+        );
+        assert_eq!(transport.requests()[0].query("fields").as_deref(), Some("preferences"));
+        assert_eq!(client.order_capabilities("456").unwrap(), OrderCapabilities::default());
+        assert_eq!(client.order_capabilities("123").unwrap().notional, Some(false));
+    }
+
+    #[test]
+    fn primary_account_id() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+
+        transport.respond("GET", "/v1/userprincipals", 200, r#"{"accounts": [{"accountCdDomainId": "A000000012345678", "accountId": "123", "company": "AMER", "segment": "AMER"}], "userId": "myuser"}"#);
+
+        assert_eq!(client.primary_account_id().unwrap(), Some(AccountId::from("123")));
+
+        transport.respond("GET", "/v1/userprincipals", 200, r#"{"accounts": [], "userId": "myuser"}"#);
+
+        assert_eq!(client.primary_account_id().unwrap(), None);
+    }
+
+    #[test]
+    fn parse_modes() {
+        let transport = Arc::new(MockTransport::new());
+        let mut client = test_client(transport.clone());
+        let history = json!({"candles": [], "delayed": false, "empty": true, "symbol": "AAPL"});
+
+        transport
+            .respond("GET", "/v1/marketdata/AAPL/pricehistory", 200, &history.to_string())
+            .respond("GET", QUOTES_PATH, 200, include_str!("../tests/fixtures/quotes.json"));
+
+        assert!(client.get_price_history("AAPL", Default::default()).is_ok());
+        assert_eq!(client.get_quotes(&["AAPL", "EUR/USD"]).unwrap().found.len(), 3);
+
+        client.set_parse_mode(ParseMode::Strict);
+
+        match client.get_price_history("AAPL", Default::default()) {
+            Err(ClientError::ParseResponse { source, .. }) => assert!(source.to_string().contains("unknown field `delayed`")),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        assert!(schema::with_parse_mode(ParseMode::Lenient, || client.get_price_history("AAPL", Default::default())).is_ok());
+
+        let quotes = client.get_quotes(&["AAPL", "EUR/USD"]).unwrap();
+
+        assert_eq!(quotes.found.len(), 2);
+        assert!(quotes.failed["EUR/USD"].contains("unknown asset type"));
+    }
+
+    #[test]
+    fn unknown_order_status_in_each_mode() {
+        let transport = Arc::new(MockTransport::new());
+        let mut client = test_client(transport.clone());
+        let mut order: Value = serde_json::from_str::<Vec<Value>>(include_str!("../tests/fixtures/orders.json")).unwrap().remove(0);
+
+        order["status"] = json!("AWAITING_SOMETHING_NEW");
+        transport.respond("GET", "/v1/accounts/123456789/orders/987654321", 200, &order.to_string());
+
+        for mode in [ParseMode::Lenient, ParseMode::Strict] {
+            client.set_parse_mode(mode);
+
+            let order = client.get_order("123456789", 987654321).unwrap();
+
+            assert_eq!(order.status, Some(OrderStatus::Unknown), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn random_faults_are_deterministic() {
+        let statuses = |seed| {
+            let transport = MockTransport::new();
+
+            transport.respond("GET", QUOTES_PATH, 200, "{}").set_faults(Faults {
+                latency: Latency::Uniform(Duration::ZERO, Duration::from_millis(1)),
+                rate_limited: 0.3,
+                server_error: 0.3,
+                seed,
+                ..Faults::default()
+            });
+
+            let request = HttpRequest {
+                method: "GET".to_string(),
+                url: format!("https://api.tdameritrade.com{}", QUOTES_PATH),
+                ..HttpRequest::default()
+            };
+
+            (0..50).map(|_| transport.send(&request).unwrap().status).collect::<Vec<_>>()
+        };
+
+        let first = statuses(7);
+
+        assert_eq!(first, statuses(7));
+        assert!(first.contains(&200) && first.contains(&429) && first.contains(&500));
+    }
+
+    #[test]
+    fn place_orders_spacing_and_stop_on_error() {
+        let mock = Arc::new(MockTransport::new());
+        let clock = Arc::new(MockClock::default());
+        let transport = Arc::new(TimedTransport {
+            inner: mock.clone(),
+            clock: clock.clone(),
+            latency: Duration::from_millis(300),
+            sent_at: Mutex::default(),
+        });
+        let mut client = test_client(transport.clone());
+        let orders = vec![Order::equity_market(Instruction::Buy, "AAPL", 1.0); 3];
+        let params = || PlaceOrdersParams {
+            interval: Some(Duration::from_secs(1)),
+            ..PlaceOrdersParams::default()
+        };
+
+        mock.respond_with_headers("POST", "/v1/accounts/123/orders", 201, "", &[("Location", "/v1/accounts/123/orders/42")]);
+        client.set_clock(clock.clone());
+
+        // Orders are a second apart, including the time taken to place them.
+        let results = client.place_orders("123", &orders, params());
+        let sent_at = transport.sent_at.lock().unwrap().clone();
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(sent_at.len(), 3);
+        assert!(sent_at.windows(2).all(|pair| pair[1] - pair[0] == Duration::from_secs(1)));
+
+        // Without stop_on_error, a failure doesn't stop later orders.
+        mock.push_fault(Fault::Latency(Duration::ZERO)).push_fault(Fault::Status(400));
+
+        let results = client.place_orders("123", &orders, params());
+
+        assert_eq!(results.iter().map(Result::is_ok).collect::<Vec<_>>(), [true, false, true]);
+        assert_eq!(mock.requests().len(), 6);
+
+        // With it, orders after the first failure aren't placed.
+        mock.push_fault(Fault::Latency(Duration::ZERO)).push_fault(Fault::Status(400));
+
+        let results = client.place_orders("123", &orders, PlaceOrdersParams {
+            stop_on_error: true,
+            ..params()
+        });
+
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[1], Err(ClientError::NotHttpOk(400, ..))));
+        assert_eq!(mock.requests().len(), 8);
+    }
+}
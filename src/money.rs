@@ -0,0 +1,287 @@
+//! Formatting and parsing of money amounts.
+//!
+//! [`Money`](struct.Money.html) displays an amount the way a locale writes it,
+//! rounded to the minor unit of its currency, and parses amounts back from
+//! the same strings. Some endpoints send amounts as strings rather than
+//! numbers, sometimes formatted; fields holding them can be parsed with
+//! [`deserialize_amount()`](fn.deserialize_amount.html).
+//!
+//! ```
+//! use tda_sdk::money::{Currency, Locale, Money};
+//!
+//! let money = Money::usd(-1234.5);
+//!
+//! assert_eq!(money.to_string(), "-$1,234.50");
+//! assert_eq!(money.format(Locale::DeDe), "-1.234,50\u{a0}$");
+//! assert_eq!("($1,234.50)".parse::<Money>().unwrap(), money);
+//! assert_eq!(Money::parse("1.234,5 €", Currency::Eur, Locale::DeDe).unwrap().amount.to_string(), "1234.5");
+//! ```
+
+use rust_decimal::{prelude::FromPrimitive, Decimal, RoundingStrategy};
+use serde::{de, Deserializer};
+use thiserror::Error;
+
+use std::{fmt, str::FromStr};
+
+/// Currency of a [`Money`](struct.Money.html) amount.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Currency {
+    #[default]
+    Usd,
+    Cad,
+    Eur,
+    Gbp,
+    Jpy,
+}
+
+impl Currency {
+    /// ISO 4217 code, such as `USD`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Cad => "CAD",
+            Currency::Eur => "EUR",
+            Currency::Gbp => "GBP",
+            Currency::Jpy => "JPY",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Currency::Usd => "$",
+            Currency::Cad => "CA$",
+            Currency::Eur => "€",
+            Currency::Gbp => "£",
+            Currency::Jpy => "¥",
+        }
+    }
+
+    /// Digits after the decimal separator.
+    pub fn decimals(self) -> u32 {
+        match self {
+            Currency::Jpy => 0,
+            _ => 2,
+        }
+    }
+}
+
+/// Conventions for writing amounts.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Locale {
+    /// `$1,234.56`
+    #[default]
+    EnUs,
+    /// `£1,234.56`
+    EnGb,
+    /// `1.234,56 €`
+    DeDe,
+    /// `1 234,56 €`
+    FrFr,
+}
+
+impl Locale {
+    fn group_separator(self) -> &'static str {
+        match self {
+            Locale::EnUs | Locale::EnGb => ",",
+            Locale::DeDe => ".",
+            Locale::FrFr => "\u{202f}",
+        }
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Locale::EnUs | Locale::EnGb => '.',
+            Locale::DeDe | Locale::FrFr => ',',
+        }
+    }
+
+    fn symbol_first(self) -> bool {
+        matches!(self, Locale::EnUs | Locale::EnGb)
+    }
+}
+
+/// Amount of money in a currency.
+///
+/// Displays in the `EnUs` locale; use [`format()`](#method.format) for
+/// others.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Self {
+        Self { amount, currency }
+    }
+
+    /// Dollar amount from the `f64` used by response structs. Amounts which
+    /// aren't finite become zero.
+    pub fn usd(amount: f64) -> Self {
+        Self::new(Decimal::from_f64(amount).unwrap_or_default(), Currency::Usd)
+    }
+
+    /// Write the amount as `locale` does, rounded half away from zero to the
+    /// minor unit of the currency.
+    pub fn format(&self, locale: Locale) -> String {
+        let decimals = self.currency.decimals();
+        let rounded = self.amount.round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero);
+        let digits = format!("{:.*}", decimals as usize, rounded.abs());
+        let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+
+        let mut number = String::new();
+
+        for (index, digit) in integer.chars().enumerate() {
+            if index > 0 && (integer.len() - index) % 3 == 0 {
+                number += locale.group_separator();
+            }
+
+            number.push(digit);
+        }
+
+        if !fraction.is_empty() {
+            number.push(locale.decimal_separator());
+            number += fraction;
+        }
+
+        let sign = match rounded.is_sign_negative() && !rounded.is_zero() {
+            true => "-",
+            false => "",
+        };
+
+        match locale.symbol_first() {
+            true => format!("{}{}{}", sign, self.currency.symbol(), number),
+            false => format!("{}{}\u{a0}{}", sign, number, self.currency.symbol()),
+        }
+    }
+
+    /// Parse an amount written as `locale` does. Currency symbols and codes,
+    /// group separators, and spaces are ignored, and amounts in parentheses
+    /// are negative.
+    pub fn parse(s: &str, currency: Currency, locale: Locale) -> Result<Self, ParseMoneyError> {
+        parse_amount(s, locale).map(|amount| Self::new(amount, currency))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(Locale::EnUs))
+    }
+}
+
+/// Parses dollar amounts in the `EnUs` locale.
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, Currency::Usd, Locale::EnUs)
+    }
+}
+
+/// Errors encountered while parsing a [`Money`](struct.Money.html) amount.
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum ParseMoneyError {
+    /// The string had no digits.
+    #[error("Amount is empty")]
+    Empty,
+
+    /// The string wasn't a number once symbols and separators were removed.
+    #[error("Invalid amount: {0}")]
+    Invalid(String),
+}
+
+pub(crate) fn parse_amount(s: &str, locale: Locale) -> Result<Decimal, ParseMoneyError> {
+    let negative = s.contains('(') && s.contains(')');
+    let mut number = String::new();
+
+    for c in s.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            '-' | '+' => number.push(c),
+            c if c == locale.decimal_separator() => number.push('.'),
+            _ => {}
+        }
+    }
+
+    if !number.chars().any(|c| c.is_ascii_digit()) {
+        return Err(ParseMoneyError::Empty);
+    }
+
+    let amount = Decimal::from_str(&number).map_err(|_| ParseMoneyError::Invalid(s.to_string()))?;
+
+    Ok(if negative { -amount } else { amount })
+}
+
+/// Deserialize an amount sent either as a JSON number or as a string, such
+/// as `"1234.5"` or `"$1,234.50"`.
+pub fn deserialize_amount<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct AmountVisitor;
+
+    impl de::Visitor<'_> for AmountVisitor {
+        type Value = Decimal;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("an amount as a number or a string")
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Decimal, E> {
+            Ok(Decimal::from(value))
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Decimal, E> {
+            Ok(Decimal::from(value))
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<Decimal, E> {
+            Decimal::from_f64(value).ok_or_else(|| E::custom(format!("amount out of range: {}", value)))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Decimal, E> {
+            parse_amount(value, Locale::EnUs).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_any(AmountVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_locales() {
+        let money = Money::usd(1234567.891);
+
+        assert_eq!(money.to_string(), "$1,234,567.89");
+        assert_eq!(money.format(Locale::FrFr), "1\u{202f}234\u{202f}567,89\u{a0}$");
+        assert_eq!(Money::usd(0.005).to_string(), "$0.01");
+        assert_eq!(Money::usd(-0.001).to_string(), "$0.00");
+        assert_eq!(Money::new(Decimal::new(123456, 1), Currency::Jpy).format(Locale::EnUs), "¥12,346");
+        assert_eq!(Money::new(Decimal::new(-99, 0), Currency::Gbp).format(Locale::EnGb), "-£99.00");
+    }
+
+    #[test]
+    fn parse_amounts() {
+        let parse = |s: &str| s.parse::<Money>().map(|money| money.amount.to_string());
+
+        assert_eq!(parse("$1,234.56").unwrap(), "1234.56");
+        assert_eq!(parse("-$0.5").unwrap(), "-0.5");
+        assert_eq!(parse(" (12.00) USD ").unwrap(), "-12.00");
+        assert_eq!(parse("$"), Err(ParseMoneyError::Empty));
+        assert!(matches!(parse("1.2.3"), Err(ParseMoneyError::Invalid(_))));
+
+        #[derive(Deserialize)]
+        struct Balance {
+            #[serde(deserialize_with = "deserialize_amount")]
+            cash: Decimal,
+        }
+
+        for json in [r#"{"cash": 1234.5}"#, r#"{"cash": "1234.50"}"#, r#"{"cash": "$1,234.50"}"#] {
+            assert_eq!(serde_json::from_str::<Balance>(json).unwrap().cash, Decimal::new(12345, 1));
+        }
+    }
+}
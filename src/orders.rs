@@ -0,0 +1,1215 @@
+//! Structs and utilities for building and inspecting orders.
+//!
+//! The same [`Order`](struct.Order.html) struct is used both as the body of
+//! `place_order()` and as the response of the order lookup methods. Fields
+//! which are only ever set by the API are left as `None` when placing an order
+//! and are not serialized.
+
+use crate::{
+    flexible::{flexible_f64, flexible_option_f64},
+    ids::{AccountId, OrderId},
+    instruments::Instrument,
+    time::TdaDateTime,
+    ClientError,
+};
+use serde_json::Value;
+use thiserror::Error;
+
+use std::fmt;
+
+/// An order, either to be placed or as returned by the API.
+///
+/// [API Documentation](https://developer.tdameritrade.com/account-access/apis/post/accounts/%7BaccountId%7D/orders-0)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Order {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<AccountId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancelable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child_order_strategies: Option<Vec<Order>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_time: Option<TdaDateTime>,
+    /// Strategy of a multi-leg option order, which TDA uses to route and
+    /// price it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub complex_order_strategy_type: Option<ComplexOrderStrategyType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<OrderDuration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entered_time: Option<TdaDateTime>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub filled_quantity: Option<f64>,
+    /// Executions and other activity on the order. Only set by the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_activity_collection: Option<Vec<OrderActivity>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<OrderId>,
+    #[serde(default)]
+    pub order_leg_collection: Vec<OrderLeg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_strategy_type: Option<OrderStrategyType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_type: Option<OrderType>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub remaining_quantity: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<Session>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub special_instruction: Option<SpecialInstruction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<OrderStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_description: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub stop_price: Option<f64>,
+    /// Price a trailing stop follows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_price_link_basis: Option<StopPriceLinkBasis>,
+    /// Unit of the `stop_price_offset` of a trailing stop.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_price_link_type: Option<StopPriceLinkType>,
+    /// Distance a trailing stop keeps from the price it follows.
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub stop_price_offset: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Which lots a sell closes, for cost basis. Defaults to the method set
+    /// on the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tax_lot_method: Option<TaxLotMethod>,
+}
+
+impl Order {
+    /// A single-leg equity market order for the regular session.
+    pub fn equity_market(instruction: Instruction, symbol: &str, quantity: f64) -> Self {
+        Self {
+            duration: Some(OrderDuration::Day),
+            order_leg_collection: vec![OrderLeg::equity(instruction, symbol, quantity)],
+            order_strategy_type: Some(OrderStrategyType::Single),
+            order_type: Some(OrderType::Market),
+            session: Some(Session::Normal),
+            ..Self::default()
+        }
+    }
+
+    /// A single-leg equity limit order for the regular session.
+    pub fn equity_limit(instruction: Instruction, symbol: &str, quantity: f64, price: f64) -> Self {
+        Self {
+            order_type: Some(OrderType::Limit),
+            price: Some(price),
+            ..Self::equity_market(instruction, symbol, quantity)
+        }
+    }
+
+    /// A single-leg option market order for the regular session.
+    pub fn option_market(instruction: Instruction, symbol: &str, quantity: f64) -> Self {
+        Self {
+            order_leg_collection: vec![OrderLeg::option(instruction, symbol, quantity)],
+            ..Self::equity_market(instruction, symbol, quantity)
+        }
+    }
+
+    /// A single-leg option limit order for the regular session.
+    pub fn option_limit(instruction: Instruction, symbol: &str, quantity: f64, price: f64) -> Self {
+        Self {
+            order_type: Some(OrderType::Limit),
+            price: Some(price),
+            ..Self::option_market(instruction, symbol, quantity)
+        }
+    }
+
+    /// A single-leg equity market order for `amount` dollars, for accounts
+    /// supporting notional orders.
+    pub fn equity_notional(instruction: Instruction, symbol: &str, amount: f64) -> Self {
+        Self {
+            order_leg_collection: vec![OrderLeg::notional(instruction, symbol, amount)],
+            ..Self::equity_market(instruction, symbol, amount)
+        }
+    }
+
+    /// A multi-leg option order for the regular session, such as a vertical
+    /// spread, priced as a net debit, credit, or even.
+    ///
+    /// ```
+    /// use tda_sdk::orders::{ComplexOrderStrategyType, Instruction, Order, OrderLeg, OrderType};
+    ///
+    /// let spread = Order::option_spread(ComplexOrderStrategyType::Vertical, OrderType::NetDebit, Some(1.25), vec![
+    ///     OrderLeg::option(Instruction::BuyToOpen, "AAPL_011521C130", 1.0),
+    ///     OrderLeg::option(Instruction::SellToOpen, "AAPL_011521C135", 1.0),
+    /// ]);
+    /// ```
+    pub fn option_spread(complex_order_strategy_type: ComplexOrderStrategyType, order_type: OrderType, price: Option<f64>, legs: Vec<OrderLeg>) -> Self {
+        Self {
+            complex_order_strategy_type: Some(complex_order_strategy_type),
+            duration: Some(OrderDuration::Day),
+            order_leg_collection: legs,
+            order_strategy_type: Some(OrderStrategyType::Single),
+            order_type: Some(order_type),
+            price,
+            session: Some(Session::Normal),
+            ..Self::default()
+        }
+    }
+
+    /// An equity bracket: a limit entry which, once filled, places its
+    /// take-profit and stop-loss exits, canceling the other when one fills.
+    ///
+    /// ```
+    /// use tda_sdk::orders::{BracketSpec, Instruction, Order, OrderStrategyType};
+    ///
+    /// let mut spec = BracketSpec::new(Instruction::Buy, "AAPL", 10.0, 100.0);
+    /// spec.set_take_profit_percent(6.0).set_stop_loss_percent(3.0);
+    ///
+    /// let order = Order::bracket(&spec).unwrap();
+    /// let exits = &order.child_order_strategies.as_ref().unwrap()[0];
+    ///
+    /// assert_eq!(order.order_strategy_type, Some(OrderStrategyType::Trigger));
+    /// assert_eq!(exits.order_strategy_type, Some(OrderStrategyType::Oco));
+    /// assert_eq!(exits.child_order_strategies.as_ref().unwrap()[1].stop_price, Some(97.0));
+    /// ```
+    pub fn bracket(spec: &BracketSpec) -> Result<Self, BracketError> {
+        spec.validate()?;
+
+        let exit_instruction = match spec.instruction {
+            Instruction::SellShort => Instruction::BuyToCover,
+            _ => Instruction::Sell,
+        };
+        let exit = |order_type| Order {
+            duration: Some(spec.exit_duration),
+            order_type: Some(order_type),
+            ..Order::equity_market(exit_instruction, &spec.symbol, spec.quantity)
+        };
+
+        let mut exits = Vec::new();
+
+        if let Some(price) = spec.take_profit_price() {
+            exits.push(Order {
+                price: Some(price),
+                ..exit(OrderType::Limit)
+            });
+        }
+
+        if let Some(price) = spec.stop_loss_price() {
+            exits.push(match (spec.trailing, spec.stop_loss) {
+                (true, Some(BracketExit::Percent(percent))) => Order {
+                    stop_price_link_basis: Some(StopPriceLinkBasis::Mark),
+                    stop_price_link_type: Some(StopPriceLinkType::Percent),
+                    stop_price_offset: Some(percent),
+                    ..exit(OrderType::TrailingStop)
+                },
+                (true, _) => Order {
+                    stop_price_link_basis: Some(StopPriceLinkBasis::Mark),
+                    stop_price_link_type: Some(StopPriceLinkType::Value),
+                    stop_price_offset: Some(round_price((spec.entry - price).abs())),
+                    ..exit(OrderType::TrailingStop)
+                },
+                (false, _) => Order {
+                    stop_price: Some(price),
+                    ..exit(OrderType::Stop)
+                },
+            });
+        }
+
+        let child = match exits.len() {
+            1 => exits.remove(0),
+            _ => Order {
+                child_order_strategies: Some(exits),
+                order_strategy_type: Some(OrderStrategyType::Oco),
+                ..Order::default()
+            },
+        };
+
+        Ok(Order {
+            child_order_strategies: Some(vec![child]),
+            order_strategy_type: Some(OrderStrategyType::Trigger),
+            ..Order::equity_limit(spec.instruction, &spec.symbol, spec.quantity, spec.entry)
+        })
+    }
+
+    /// Whether the order, or any of its child orders, trades fractions of a
+    /// share.
+    pub fn is_fractional(&self) -> bool {
+        self.order_leg_collection.iter().any(OrderLeg::is_fractional) || self.child_order_strategies.iter().flatten().any(Order::is_fractional)
+    }
+
+    /// Whether the order, or any of its child orders, trades a dollar amount.
+    pub fn is_notional(&self) -> bool {
+        self.order_leg_collection.iter().any(OrderLeg::is_notional) || self.child_order_strategies.iter().flatten().any(Order::is_notional)
+    }
+
+    /// All execution legs of the order's `EXECUTION` activities.
+    pub fn executions(&self) -> impl Iterator<Item = &ExecutionLeg> {
+        self.order_activity_collection
+            .iter()
+            .flatten()
+            .filter(|activity| activity.activity_type == "EXECUTION")
+            .flat_map(|activity| activity.execution_legs.iter())
+    }
+
+    /// Quantity-weighted average price of all executions, or `None` if the
+    /// order hasn't been filled at all.
+    pub fn average_fill_price(&self) -> Option<f64> {
+        let (quantity, value) = self
+            .executions()
+            .fold((0.0, 0.0), |(quantity, value), leg| (quantity + leg.quantity, value + leg.quantity * leg.price));
+
+        if quantity == 0.0 {
+            return None;
+        }
+
+        Some(value / quantity)
+    }
+
+    /// Sum of the commissions reported on the order's executions, or `None`
+    /// if no execution reported one.
+    pub fn total_commission(&self) -> Option<f64> {
+        self.order_activity_collection
+            .iter()
+            .flatten()
+            .filter(|activity| activity.activity_type == "EXECUTION")
+            .filter_map(|activity| activity.commission)
+            .fold(None, |total, commission| Some(total.unwrap_or(0.0) + commission))
+    }
+
+    /// The order as indented JSON, exactly as it would be sent to the API.
+    pub fn to_pretty_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("orders always serialize")
+    }
+
+    /// Fields which differ between this order and `other`, such as an order
+    /// and its replacement, compared as they would be sent to the API.
+    pub fn diff(&self, other: &Order) -> OrderDiff {
+        let mut changes = Vec::new();
+        let old = serde_json::to_value(self).expect("orders always serialize");
+        let new = serde_json::to_value(other).expect("orders always serialize");
+
+        diff_values("", Some(&old), Some(&new), &mut changes);
+
+        OrderDiff { changes }
+    }
+}
+
+/// Field changes between two orders, see
+/// [`Order::diff()`](struct.Order.html#method.diff).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrderDiff {
+    pub changes: Vec<FieldChange>,
+}
+
+impl OrderDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Shows one change per line.
+impl fmt::Display for OrderDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Change of a single field in an [`OrderDiff`](struct.OrderDiff.html).
+///
+/// The path uses the API field names, such as
+/// `orderLegCollection[0].quantity`. The old value is `None` for added fields,
+/// and the new value is `None` for removed ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// Shows `path: old -> new`, `+ path: new`, or `- path: old`.
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.path, old, new),
+            (None, Some(new)) => write!(f, "+ {}: {}", self.path, new),
+            (Some(old), None) => write!(f, "- {}: {}", self.path, old),
+            (None, None) => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// Collect the differences between two JSON values, descending into objects
+/// and arrays.
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    _ => format!("{}.{}", path, key),
+                };
+
+                diff_values(&path, old.get(key), new.get(key), changes);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for index in 0..old.len().max(new.len()) {
+                diff_values(&format!("{}[{}]", path, index), old.get(index), new.get(index), changes);
+            }
+        }
+        (old, new) if old != new => changes.push(FieldChange {
+            path: path.to_string(),
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// Activity item in [`Order`](struct.Order.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderActivity {
+    /// Choices: `EXECUTION` or `ORDER_ACTION`
+    pub activity_type: String,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub commission: Option<f64>,
+    #[serde(default)]
+    pub execution_legs: Vec<ExecutionLeg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_type: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub order_remaining_quantity: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+}
+
+/// Execution leg item in [`OrderActivity`](struct.OrderActivity.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionLeg {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leg_id: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub mismarked_quantity: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub quantity: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<TdaDateTime>,
+}
+
+/// Individual leg in [`Order`](struct.Order.html)
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderLeg {
+    pub instruction: Instruction,
+    pub instrument: Instrument,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leg_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_leg_type: Option<AssetType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_effect: Option<PositionEffect>,
+    /// Shares, or the dollar amount of a notional order.
+    #[serde(deserialize_with = "flexible_f64")]
+    pub quantity: f64,
+    /// Whether the quantity is in shares or dollars. Shares if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity_type: Option<QuantityType>,
+}
+
+impl OrderLeg {
+    /// A leg trading `quantity` shares of an equity.
+    ///
+    /// Equity instructions are `Buy`, `Sell`, `BuyToCover`, and `SellShort`.
+    pub fn equity(instruction: Instruction, symbol: &str, quantity: f64) -> Self {
+        Self {
+            instruction,
+            instrument: Instrument::equity(symbol),
+            leg_id: None,
+            order_leg_type: None,
+            position_effect: None,
+            quantity,
+            quantity_type: None,
+        }
+    }
+
+    /// A leg trading `amount` dollars of an equity.
+    pub fn notional(instruction: Instruction, symbol: &str, amount: f64) -> Self {
+        Self {
+            quantity_type: Some(QuantityType::Dollars),
+            ..Self::equity(instruction, symbol, amount)
+        }
+    }
+
+    /// Whether the quantity is a dollar amount.
+    pub fn is_notional(&self) -> bool {
+        self.quantity_type == Some(QuantityType::Dollars)
+    }
+
+    /// Whether the quantity is a fraction of a share.
+    pub fn is_fractional(&self) -> bool {
+        !self.is_notional() && self.quantity.fract() != 0.0
+    }
+
+    /// A leg trading `quantity` contracts of an option.
+    ///
+    /// Option instructions are `BuyToOpen`, `BuyToClose`, `SellToOpen`, and
+    /// `SellToClose`.
+    pub fn option(instruction: Instruction, symbol: &str, quantity: f64) -> Self {
+        Self {
+            instrument: Instrument::option(symbol),
+            ..Self::equity(instruction, symbol, quantity)
+        }
+    }
+}
+
+/// Entry and exits of a bracket order, see
+/// [`Order::bracket()`](struct.Order.html#method.bracket).
+#[derive(Clone, Debug, PartialEq)]
+pub struct BracketSpec {
+    /// `Buy` to enter long, or `SellShort` to enter short.
+    pub instruction: Instruction,
+    pub symbol: String,
+    pub quantity: f64,
+    /// Limit price of the entry.
+    pub entry: f64,
+    pub take_profit: Option<BracketExit>,
+    pub stop_loss: Option<BracketExit>,
+    /// Whether the stop loss trails the mark, keeping the distance between
+    /// the entry and the initial stop from the mark as it moves in favor of
+    /// the position.
+    pub trailing: bool,
+    /// How long the exits stay working, `GoodTillCancel` by default.
+    pub exit_duration: OrderDuration,
+}
+
+/// Price of a bracket exit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BracketExit {
+    /// Distance from the entry, as a percentage of it.
+    Percent(f64),
+    Price(f64),
+}
+
+/// Invalid [`BracketSpec`](struct.BracketSpec.html).
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum BracketError {
+    #[error("Brackets enter with BUY or SELL_SHORT, not {0:?}")]
+    InvalidInstruction(Instruction),
+
+    #[error("Invalid bracket {0}")]
+    InvalidValue(&'static str),
+
+    #[error("Bracket has no take-profit or stop-loss exit")]
+    NoExits,
+
+    /// The take profit isn't above the entry of a long position, or below
+    /// the entry of a short one.
+    #[error("Take profit {price} is on the wrong side of the entry {entry}")]
+    TakeProfitWrongSide { entry: f64, price: f64 },
+
+    /// The stop loss isn't below the entry of a long position, or above the
+    /// entry of a short one.
+    #[error("Stop loss {price} is on the wrong side of the entry {entry}")]
+    StopLossWrongSide { entry: f64, price: f64 },
+}
+
+impl BracketSpec {
+    /// Bracket entering at `entry` without exits yet.
+    pub fn new(instruction: Instruction, symbol: &str, quantity: f64, entry: f64) -> Self {
+        Self {
+            instruction,
+            symbol: symbol.to_string(),
+            quantity,
+            entry,
+            take_profit: None,
+            stop_loss: None,
+            trailing: false,
+            exit_duration: OrderDuration::GoodTillCancel,
+        }
+    }
+
+    /// Take profits `percent` percent away from the entry.
+    pub fn set_take_profit_percent(&mut self, percent: f64) -> &mut Self {
+        self.take_profit = Some(BracketExit::Percent(percent));
+        self
+    }
+
+    pub fn set_take_profit_price(&mut self, price: f64) -> &mut Self {
+        self.take_profit = Some(BracketExit::Price(price));
+        self
+    }
+
+    /// Stop out `percent` percent away from the entry.
+    pub fn set_stop_loss_percent(&mut self, percent: f64) -> &mut Self {
+        self.stop_loss = Some(BracketExit::Percent(percent));
+        self
+    }
+
+    pub fn set_stop_loss_price(&mut self, price: f64) -> &mut Self {
+        self.stop_loss = Some(BracketExit::Price(price));
+        self
+    }
+
+    pub fn set_trailing(&mut self, trailing: bool) -> &mut Self {
+        self.trailing = trailing;
+        self
+    }
+
+    pub fn set_exit_duration(&mut self, duration: OrderDuration) -> &mut Self {
+        self.exit_duration = duration;
+        self
+    }
+
+    /// Price of the take profit, if any.
+    pub fn take_profit_price(&self) -> Option<f64> {
+        self.take_profit.map(|exit| self.exit_price(exit, 1.0))
+    }
+
+    /// Initial price of the stop loss, if any.
+    pub fn stop_loss_price(&self) -> Option<f64> {
+        self.stop_loss.map(|exit| self.exit_price(exit, -1.0))
+    }
+
+    /// Check the bracket makes sense: a long or short entry, positive
+    /// quantity and prices, and exits on the correct side of the entry.
+    pub fn validate(&self) -> Result<(), BracketError> {
+        if !matches!(self.instruction, Instruction::Buy | Instruction::SellShort) {
+            return Err(BracketError::InvalidInstruction(self.instruction));
+        }
+
+        if !(self.quantity.is_finite() && self.quantity > 0.0) {
+            return Err(BracketError::InvalidValue("quantity"));
+        }
+
+        if !(self.entry.is_finite() && self.entry > 0.0) {
+            return Err(BracketError::InvalidValue("entry"));
+        }
+
+        if self.take_profit.is_none() && self.stop_loss.is_none() {
+            return Err(BracketError::NoExits);
+        }
+
+        let long = self.instruction == Instruction::Buy;
+        let above_entry = |price: f64| price > self.entry;
+
+        if let Some(price) = self.take_profit_price() {
+            if !(price.is_finite() && price > 0.0) || above_entry(price) != long {
+                return Err(BracketError::TakeProfitWrongSide { entry: self.entry, price });
+            }
+        }
+
+        if let Some(price) = self.stop_loss_price() {
+            if !(price.is_finite() && price > 0.0) || price == self.entry || above_entry(price) == long {
+                return Err(BracketError::StopLossWrongSide { entry: self.entry, price });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Price of `exit`, with percentages away from the entry in the
+    /// `direction` of a profit (`1.0`) or loss (`-1.0`).
+    fn exit_price(&self, exit: BracketExit, direction: f64) -> f64 {
+        match exit {
+            BracketExit::Percent(percent) => {
+                let side = if self.instruction == Instruction::SellShort { -1.0 } else { 1.0 };
+
+                round_price(self.entry * (1.0 + side * direction * percent / 100.0))
+            }
+            BracketExit::Price(price) => price,
+        }
+    }
+}
+
+/// Settings new orders start from, such as the defaults of an account's
+/// [`Preferences`](../responses/struct.Preferences.html).
+///
+/// ```
+/// use tda_sdk::orders::{OrderDefaults, OrderDuration, OrderType};
+///
+/// let defaults = OrderDefaults {
+///     order_type: OrderType::Limit,
+///     duration: OrderDuration::GoodTillCancel,
+///     quantity: Some(100.0),
+///     ..OrderDefaults::default()
+/// };
+///
+/// let order = defaults.equity_order("AAPL", None, Some(120.5)).unwrap();
+///
+/// assert_eq!(order.price, Some(120.5));
+/// assert_eq!(order.order_leg_collection[0].quantity, 100.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrderDefaults {
+    pub instruction: Instruction,
+    pub order_type: OrderType,
+    pub duration: OrderDuration,
+    pub session: Session,
+    /// Quantity of orders which don't set one.
+    pub quantity: Option<f64>,
+    pub tax_lot_method: Option<TaxLotMethod>,
+}
+
+impl Default for OrderDefaults {
+    /// The defaults of the `equity_*()` constructors of
+    /// [`Order`](struct.Order.html): a buy at market for the day.
+    fn default() -> Self {
+        Self {
+            instruction: Instruction::Buy,
+            order_type: OrderType::Market,
+            duration: OrderDuration::Day,
+            session: Session::Normal,
+            quantity: None,
+            tax_lot_method: None,
+        }
+    }
+}
+
+impl OrderDefaults {
+    /// Single-leg equity order of `quantity`, or of the default quantity,
+    /// with `price` as its limit price, stop price, or both, depending on
+    /// the order type. `None` without a quantity.
+    pub fn equity_order(&self, symbol: &str, quantity: Option<f64>, price: Option<f64>) -> Option<Order> {
+        let quantity = quantity.or(self.quantity)?;
+        let limit = matches!(self.order_type, OrderType::Limit | OrderType::StopLimit);
+        let stop = matches!(self.order_type, OrderType::Stop | OrderType::StopLimit);
+
+        Some(Order {
+            duration: Some(self.duration),
+            order_type: Some(self.order_type),
+            price: price.filter(|_| limit),
+            session: Some(self.session),
+            stop_price: price.filter(|_| stop),
+            tax_lot_method: self.tax_lot_method,
+            ..Order::equity_market(self.instruction, symbol, quantity)
+        })
+    }
+
+    /// Set the duration, session, and tax lot method of `order` if it leaves
+    /// them unset.
+    pub fn apply(&self, order: &mut Order) {
+        order.duration.get_or_insert(self.duration);
+        order.session.get_or_insert(self.session);
+
+        if order.tax_lot_method.is_none() {
+            order.tax_lot_method = self.tax_lot_method;
+        }
+    }
+}
+
+/// Kinds of orders beyond whole shares an account supports, see
+/// [`Client::order_capabilities()`](../struct.Client.html#method.order_capabilities).
+/// Each is `None` if the API doesn't say, leaving it to the API to accept or
+/// reject such orders.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct OrderCapabilities {
+    /// Orders for fractions of a share.
+    pub fractional: Option<bool>,
+    /// Orders for a dollar amount.
+    pub notional: Option<bool>,
+}
+
+impl OrderCapabilities {
+    /// Fail with `ClientError::OrderRejected` if the order is fractional or
+    /// notional and the account is known not to support it.
+    pub fn check(&self, order: &Order) -> Result<(), ClientError> {
+        if order.is_notional() && self.notional == Some(false) {
+            return Err(ClientError::OrderRejected("account doesn't support notional orders".to_string()));
+        }
+
+        if order.is_fractional() && self.fractional == Some(false) {
+            return Err(ClientError::OrderRejected("account doesn't support fractional shares".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Round `price` to cents, or to hundredths of a cent below a dollar, as
+/// orders are priced.
+pub(crate) fn round_price(price: f64) -> f64 {
+    let scale = if price.abs() < 1.0 { 10_000.0 } else { 100.0 };
+
+    (price * scale).round() / scale
+}
+
+/// How long an order stays working.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderDuration {
+    Day,
+    GoodTillCancel,
+    FillOrKill,
+    ImmediateOrCancel,
+    EndOfWeek,
+    EndOfMonth,
+    NextEndOfMonth,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Strategy of a multi-leg option order.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ComplexOrderStrategyType {
+    None,
+    Covered,
+    Vertical,
+    BackRatio,
+    Calendar,
+    Diagonal,
+    Straddle,
+    Strangle,
+    CollarSynthetic,
+    Butterfly,
+    Condor,
+    IronCondor,
+    VerticalRoll,
+    CollarWithStock,
+    DoubleDiagonal,
+    UnbalancedButterfly,
+    UnbalancedCondor,
+    UnbalancedIronCondor,
+    UnbalancedVerticalRoll,
+    Custom,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Trading session an order works in.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Session {
+    Normal,
+    /// Pre-market session.
+    Am,
+    /// After-hours session.
+    Pm,
+    /// All sessions, from pre-market through after-hours.
+    Seamless,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Execution condition of an order.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SpecialInstruction {
+    /// Fill the whole quantity at once, or not at all.
+    AllOrNone,
+    /// Don't reduce the price of the order when the stock pays a dividend.
+    DoNotReduce,
+    AllOrNoneDoNotReduce,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Method choosing the lots a sell closes.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TaxLotMethod {
+    Fifo,
+    Lifo,
+    HighCost,
+    LowCost,
+    AverageCost,
+    SpecificLot,
+    /// Lots minimizing the tax owed, only a default for mutual funds.
+    MinimumTax,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Pricing of an order.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderType {
+    Market,
+    Limit,
+    Stop,
+    StopLimit,
+    TrailingStop,
+    TrailingStopLimit,
+    MarketOnClose,
+    Exercise,
+    /// Multi-leg order priced as the net debit of its legs.
+    NetDebit,
+    /// Multi-leg order priced as the net credit of its legs.
+    NetCredit,
+    /// Multi-leg order priced at even.
+    NetZero,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Unit of the quantity of an order leg.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum QuantityType {
+    /// Every share held, when closing a position.
+    AllShares,
+    Dollars,
+    Shares,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Price a trailing stop follows.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StopPriceLinkBasis {
+    Manual,
+    Base,
+    Trigger,
+    Last,
+    Bid,
+    Ask,
+    AskBid,
+    Mark,
+    Average,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Unit of the offset of a trailing stop.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StopPriceLinkType {
+    Value,
+    Percent,
+    Tick,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// How an order relates to the orders it is placed with.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderStrategyType {
+    Single,
+    /// One cancels the other: filling one child order cancels the rest.
+    Oco,
+    /// The child orders are placed once the order fills.
+    Trigger,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Status of an order.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderStatus {
+    AwaitingParentOrder,
+    AwaitingCondition,
+    AwaitingStopCondition,
+    AwaitingManualReview,
+    Accepted,
+    AwaitingUrOut,
+    PendingActivation,
+    Queued,
+    Working,
+    Rejected,
+    PendingCancel,
+    Canceled,
+    PendingReplace,
+    Replaced,
+    Filled,
+    Expired,
+    New,
+    AwaitingReleaseTime,
+    PendingAcknowledgement,
+    PendingRecall,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// What an order leg does.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Instruction {
+    Buy,
+    Sell,
+    BuyToCover,
+    SellShort,
+    BuyToOpen,
+    BuyToClose,
+    SellToOpen,
+    SellToClose,
+    Exchange,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+impl Instruction {
+    /// Whether the instruction buys, increasing the position.
+    pub fn is_buy(self) -> bool {
+        matches!(self, Instruction::Buy | Instruction::BuyToCover | Instruction::BuyToOpen | Instruction::BuyToClose)
+    }
+}
+
+/// Whether an option leg opens or closes a position.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PositionEffect {
+    Opening,
+    Closing,
+    Automatic,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Type of an instrument.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AssetType {
+    Equity,
+    Option,
+    Index,
+    MutualFund,
+    CashEquivalent,
+    FixedIncome,
+    Currency,
+    /// Value added by the API after this version of the crate, which can't
+    /// be sent back.
+    #[serde(other)]
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_aggregation() {
+        let orders: Vec<Order> = serde_json::from_str(include_str!("../tests/fixtures/orders.json")).unwrap();
+        let filled = &orders[0];
+
+        assert_eq!(filled.status, Some(OrderStatus::Filled));
+        assert_eq!(filled.order_leg_collection[0].position_effect, Some(PositionEffect::Opening));
+        assert_eq!(filled.average_fill_price(), Some(120.4));
+        assert_eq!(filled.total_commission(), Some(1.5));
+        assert_eq!(filled.executions().count(), 2);
+
+        let working = &orders[1];
+
+        assert_eq!(working.average_fill_price(), None);
+        assert_eq!(working.total_commission(), None);
+    }
+
+    #[test]
+    fn unknown_enum_values() {
+        let order: Order = serde_json::from_value(serde_json::json!({
+            "duration": "GOOD_TILL_NEXT_FULL_MOON",
+            "orderType": "LIMIT",
+            "session": "NORMAL",
+            "status": "AWAITING_STOP_CONDITION",
+            "orderStrategyType": "SINGLE",
+            "orderLegCollection": [{
+                "instruction": "BUY",
+                "quantity": 1.0,
+                "instrument": { "assetType": "EQUITY", "symbol": "AAPL" }
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(order.duration, Some(OrderDuration::Unknown));
+        assert_eq!(order.status, Some(OrderStatus::AwaitingStopCondition));
+
+        let status: OrderStatus = serde_json::from_str("\"SOME_NEW_STATUS\"").unwrap();
+        let instruction: Instruction = serde_json::from_str("\"SWAP\"").unwrap();
+
+        assert_eq!(status, OrderStatus::Unknown);
+        assert_eq!(instruction, Instruction::Unknown);
+    }
+
+    #[test]
+    fn diff_orders() {
+        let order = Order::equity_limit(Instruction::Buy, "AAPL", 10.0, 120.5);
+        let replacement = Order {
+            price: Some(121.0),
+            stop_price: Some(119.0),
+            session: None,
+            ..Order::equity_limit(Instruction::Buy, "AAPL", 15.0, 120.5)
+        };
+
+        assert!(order.diff(&order).is_empty());
+        assert_eq!(
+            order.diff(&replacement).to_string(),
+            "orderLegCollection[0].quantity: 10.0 -> 15.0\nprice: 120.5 -> 121.0\n- session: \"NORMAL\"\n+ stopPrice: 119.0\n",
+        );
+        assert!(order.to_pretty_json().contains("\n  \"orderType\": \"LIMIT\",\n"));
+    }
+
+    #[test]
+    fn bracket_orders() {
+        let mut spec = BracketSpec::new(Instruction::SellShort, "TSLA", 5.0, 200.0);
+        spec.set_take_profit_percent(10.0).set_stop_loss_price(210.0).set_trailing(true);
+
+        let order = Order::bracket(&spec).unwrap();
+        let oco = &order.child_order_strategies.as_ref().unwrap()[0];
+        let exits = oco.child_order_strategies.as_ref().unwrap();
+        let json = serde_json::to_value(&exits[1]).unwrap();
+
+        assert_eq!(order.price, Some(200.0));
+        assert_eq!(exits[0].price, Some(180.0));
+        assert_eq!(exits[0].order_leg_collection[0].instruction, Instruction::BuyToCover);
+        assert_eq!(exits[0].duration, Some(OrderDuration::GoodTillCancel));
+        assert_eq!(json["orderType"], "TRAILING_STOP");
+        assert_eq!(json["stopPriceLinkType"], "VALUE");
+        assert_eq!(json["stopPriceOffset"], 10.0);
+
+        let mut spec = BracketSpec::new(Instruction::Buy, "AAPL", 10.0, 100.0);
+        spec.set_stop_loss_percent(2.5).set_trailing(true);
+
+        let order = Order::bracket(&spec).unwrap();
+        let stop = &order.child_order_strategies.as_ref().unwrap()[0];
+
+        assert_eq!(stop.order_strategy_type, Some(OrderStrategyType::Single));
+        assert_eq!(stop.stop_price_link_type, Some(StopPriceLinkType::Percent));
+        assert_eq!(stop.stop_price_offset, Some(2.5));
+    }
+
+    #[test]
+    fn trailing_short_brackets() {
+        let mut spec = BracketSpec::new(Instruction::SellShort, "TSLA", 5.0, 200.0);
+        spec.set_take_profit_price(180.0).set_stop_loss_percent(4.0).set_trailing(true);
+
+        let order = Order::bracket(&spec).unwrap();
+        let oco = &order.child_order_strategies.as_ref().unwrap()[0];
+        let exits = oco.child_order_strategies.as_ref().unwrap();
+
+        assert_eq!(order.order_leg_collection[0].instruction, Instruction::SellShort);
+        assert_eq!(oco.order_strategy_type, Some(OrderStrategyType::Oco));
+        assert_eq!(exits[0].order_type, Some(OrderType::Limit));
+        assert_eq!(exits[0].order_leg_collection[0].instruction, Instruction::BuyToCover);
+        assert_eq!(exits[1].order_type, Some(OrderType::TrailingStop));
+        assert_eq!(exits[1].order_leg_collection[0].instruction, Instruction::BuyToCover);
+        assert_eq!(exits[1].stop_price_link_basis, Some(StopPriceLinkBasis::Mark));
+        assert_eq!(exits[1].stop_price_link_type, Some(StopPriceLinkType::Percent));
+        assert_eq!(exits[1].stop_price_offset, Some(4.0));
+        assert_eq!(exits[1].stop_price, None);
+        assert_eq!(spec.stop_loss_price(), Some(208.0));
+
+        spec.set_trailing(false);
+
+        let order = Order::bracket(&spec).unwrap();
+        let exits = order.child_order_strategies.as_ref().unwrap()[0].child_order_strategies.clone().unwrap();
+
+        assert_eq!(exits[1].order_type, Some(OrderType::Stop));
+        assert_eq!(exits[1].order_leg_collection[0].instruction, Instruction::BuyToCover);
+        assert_eq!(exits[1].stop_price, Some(208.0));
+    }
+
+    #[test]
+    fn validate_brackets() {
+        let mut spec = BracketSpec::new(Instruction::Buy, "AAPL", 10.0, 100.0);
+
+        assert_eq!(spec.validate(), Err(BracketError::NoExits));
+
+        spec.set_take_profit_price(95.0);
+        assert_eq!(spec.validate(), Err(BracketError::TakeProfitWrongSide { entry: 100.0, price: 95.0 }));
+
+        spec.set_take_profit_price(105.0).set_stop_loss_price(101.0);
+        assert_eq!(spec.validate(), Err(BracketError::StopLossWrongSide { entry: 100.0, price: 101.0 }));
+
+        spec.set_stop_loss_percent(150.0);
+        assert!(matches!(spec.validate(), Err(BracketError::StopLossWrongSide { .. })));
+
+        spec.set_stop_loss_percent(5.0);
+        assert_eq!(spec.validate(), Ok(()));
+        assert_eq!(spec.stop_loss_price(), Some(95.0));
+
+        spec.instruction = Instruction::Sell;
+        assert_eq!(Order::bracket(&spec), Err(BracketError::InvalidInstruction(Instruction::Sell)));
+    }
+
+    #[test]
+    fn serialize_option_spread() {
+        let order = Order::option_spread(ComplexOrderStrategyType::IronCondor, OrderType::NetCredit, Some(0.85), vec![
+            OrderLeg::option(Instruction::SellToOpen, "SPY_011521P300", 1.0),
+            OrderLeg::option(Instruction::BuyToOpen, "SPY_011521P295", 1.0),
+        ]);
+        let json = serde_json::to_value(&order).unwrap();
+
+        assert_eq!(json["complexOrderStrategyType"], "IRON_CONDOR");
+        assert_eq!(json["orderType"], "NET_CREDIT");
+        assert_eq!(json["orderLegCollection"][0]["instruction"], "SELL_TO_OPEN");
+        assert_eq!(json["orderLegCollection"][1]["instrument"]["assetType"], "OPTION");
+    }
+
+    #[test]
+    fn serialize_special_instruction_and_tax_lot_method() {
+        let order = Order {
+            special_instruction: Some(SpecialInstruction::AllOrNoneDoNotReduce),
+            tax_lot_method: Some(TaxLotMethod::HighCost),
+            ..Order::equity_limit(Instruction::Sell, "AAPL", 10.0, 130.0)
+        };
+        let json = serde_json::to_value(&order).unwrap();
+
+        assert_eq!(json["specialInstruction"], "ALL_OR_NONE_DO_NOT_REDUCE");
+        assert_eq!(json["taxLotMethod"], "HIGH_COST");
+    }
+
+    #[test]
+    fn serialize_limit_order() {
+        let order = Order::equity_limit(Instruction::Buy, "AAPL", 10.0, 120.5);
+
+        assert_eq!(
+            serde_json::to_value(&order).unwrap(),
+            serde_json::json!({
+                "duration": "DAY",
+                "orderLegCollection": [{
+                    "instruction": "BUY",
+                    "instrument": { "assetType": "EQUITY", "symbol": "AAPL" },
+                    "quantity": 10.0,
+                }],
+                "orderStrategyType": "SINGLE",
+                "orderType": "LIMIT",
+                "price": 120.5,
+                "session": "NORMAL",
+            }),
+        );
+    }
+}
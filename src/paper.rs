@@ -0,0 +1,193 @@
+//! Simulated broker for paper trading and backtests.
+//!
+//! [`PaperBroker`](struct.PaperBroker.html) implements
+//! [`OrderSink`](../strategy/trait.OrderSink.html), so a
+//! [`Strategy`](../strategy/trait.Strategy.html) can trade against it exactly
+//! as it would against a live account. Orders are queued when placed and are
+//! filled against the next bar of their symbol, which keeps strategies from
+//! trading at prices they have already seen.
+
+use crate::{
+    ids::{AccountId, OrderId},
+    orders::{Instruction, Order, OrderType},
+    responses::Candle,
+    strategy::{Fill, OrderSink},
+    ClientError,
+};
+
+use std::collections::HashMap;
+
+/// Account ID reported on fills from a [`PaperBroker`](struct.PaperBroker.html).
+pub const PAPER_ACCOUNT_ID: &str = "PAPER";
+
+/// A simulated account which fills orders against bars.
+///
+/// Supported order types are `MARKET`, `LIMIT`, and `STOP`, each with a single
+/// leg. Market orders fill at the open of the next bar, while limit and stop
+/// orders fill at their price (or a better open) once the bar trades through
+/// it. Orders which aren't filled stay pending until they are, or until
+/// [`cancel_all()`](#method.cancel_all) is called.
+#[derive(Clone, Debug, Default)]
+pub struct PaperBroker {
+    /// Cash in the account. Buying more than the cash available is allowed,
+    /// and leaves a negative balance.
+    pub cash: f64,
+
+    /// Flat commission charged on every fill.
+    pub commission: f64,
+
+    /// Quantity held, keyed by symbol. Short positions are negative.
+    pub positions: HashMap<String, f64>,
+
+    next_order_id: i64,
+    pending: Vec<(OrderId, Order)>,
+}
+
+impl PaperBroker {
+    /// Create a paper account holding `cash`.
+    pub fn new(cash: f64) -> Self {
+        Self {
+            cash,
+            ..Self::default()
+        }
+    }
+
+    /// Orders which haven't been filled yet.
+    pub fn pending_orders(&self) -> impl Iterator<Item = (OrderId, &Order)> {
+        self.pending.iter().map(|(order_id, order)| (*order_id, order))
+    }
+
+    /// Cancel all pending orders.
+    pub fn cancel_all(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Value of the cash and positions, using the `prices` of each symbol.
+    /// Positions without a price are ignored.
+    pub fn equity(&self, prices: &HashMap<String, f64>) -> f64 {
+        self.positions
+            .iter()
+            .filter_map(|(symbol, quantity)| Some(quantity * prices.get(symbol)?))
+            .sum::<f64>()
+            + self.cash
+    }
+
+    /// Fill the pending orders for `symbol` which trade within `bar`.
+    pub fn process_bar(&mut self, symbol: &str, bar: &Candle) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let mut pending = Vec::new();
+
+        for (order_id, order) in std::mem::take(&mut self.pending) {
+            let leg = &order.order_leg_collection[0];
+
+            if leg.instrument.symbol() != symbol {
+                pending.push((order_id, order));
+                continue;
+            }
+
+            let buy = leg.instruction.is_buy();
+
+            match fill_price(&order, buy, bar) {
+                Some(price) => {
+                    let signed_quantity = if buy { leg.quantity } else { -leg.quantity };
+
+                    *self.positions.entry(symbol.to_string()).or_default() += signed_quantity;
+                    self.positions.retain(|_, quantity| *quantity != 0.0);
+                    self.cash -= signed_quantity * price + self.commission;
+
+                    fills.push(Fill {
+                        account_id: AccountId::from(PAPER_ACCOUNT_ID),
+                        order_id,
+                        symbol: symbol.to_string(),
+                        instruction: fill_instruction(leg.instruction),
+                        quantity: leg.quantity,
+                        price,
+                    });
+                }
+                None => pending.push((order_id, order)),
+            }
+        }
+
+        self.pending = pending;
+
+        fills
+    }
+}
+
+impl OrderSink for PaperBroker {
+    fn place_order(&mut self, order: &Order) -> Result<OrderId, ClientError> {
+        let supported = matches!(order.order_type, Some(OrderType::Market) | Some(OrderType::Limit) | Some(OrderType::Stop));
+
+        if !supported || order.order_leg_collection.len() != 1 {
+            return Err(ClientError::OrderRejected("paper broker only supports single-leg market, limit, and stop orders".to_string()));
+        }
+
+        self.next_order_id += 1;
+
+        let order_id = OrderId::new(self.next_order_id);
+
+        self.pending.push((order_id, order.clone()));
+
+        Ok(order_id)
+    }
+}
+
+/// Convert an order instruction to the spelling used by fill messages.
+fn fill_instruction(instruction: Instruction) -> String {
+    format!("{:?}", instruction)
+}
+
+fn fill_price(order: &Order, buy: bool, bar: &Candle) -> Option<f64> {
+    match (order.order_type?, buy) {
+        (OrderType::Market, _) => Some(bar.open),
+        (OrderType::Limit, true) => order.price.filter(|price| bar.low <= *price).map(|price| price.min(bar.open)),
+        (OrderType::Limit, false) => order.price.filter(|price| bar.high >= *price).map(|price| price.max(bar.open)),
+        (OrderType::Stop, true) => order.stop_price.filter(|price| bar.high >= *price).map(|price| price.max(bar.open)),
+        (OrderType::Stop, false) => order.stop_price.filter(|price| bar.low <= *price).map(|price| price.min(bar.open)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle { open, high, low, close, ..Candle::default() }
+    }
+
+    #[test]
+    fn market_and_limit_fills() {
+        let mut broker = PaperBroker::new(10_000.0);
+
+        broker.commission = 1.0;
+        broker.place_order(&Order::equity_market(Instruction::Buy, "AAPL", 10.0)).unwrap();
+        broker.place_order(&Order::equity_limit(Instruction::Sell, "AAPL", 10.0, 110.0)).unwrap();
+
+        let fills = broker.process_bar("AAPL", &bar(100.0, 105.0, 95.0, 102.0));
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 100.0);
+        assert_eq!(fills[0].instruction, "Buy");
+        assert_eq!(broker.cash, 8_999.0);
+        assert_eq!(broker.pending_orders().count(), 1);
+
+        // Gapping through the limit fills at the better open.
+        let fills = broker.process_bar("AAPL", &bar(112.0, 115.0, 111.0, 114.0));
+
+        assert_eq!(fills[0].price, 112.0);
+        assert_eq!(broker.cash, 10_118.0);
+        assert!(broker.positions.is_empty());
+    }
+
+    #[test]
+    fn unsupported_orders() {
+        let mut broker = PaperBroker::new(0.0);
+        let order = Order {
+            order_type: Some(OrderType::TrailingStop),
+            ..Order::equity_market(Instruction::Buy, "AAPL", 1.0)
+        };
+
+        assert!(broker.place_order(&order).is_err());
+    }
+}
@@ -1,9 +1,59 @@
 //! Structs and utilities for building API request parameters.
 
+use chrono::{DateTime, NaiveDate, Utc};
+
+use std::{fmt, time::Duration};
+
+/// A point in time accepted by date parameters, which the API expects as
+/// milliseconds since epoch.
+///
+/// Dates are taken as midnight UTC.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DateOrMillis {
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+    Millis(i64),
+}
+
+impl DateOrMillis {
+    /// Milliseconds since epoch.
+    pub fn millis(&self) -> i64 {
+        match self {
+            DateOrMillis::Date(date) => date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis(),
+            DateOrMillis::DateTime(date_time) => date_time.timestamp_millis(),
+            DateOrMillis::Millis(millis) => *millis,
+        }
+    }
+}
+
+impl fmt::Display for DateOrMillis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.millis())
+    }
+}
+
+impl From<NaiveDate> for DateOrMillis {
+    fn from(date: NaiveDate) -> Self {
+        DateOrMillis::Date(date)
+    }
+}
+
+impl From<DateTime<Utc>> for DateOrMillis {
+    fn from(date_time: DateTime<Utc>) -> Self {
+        DateOrMillis::DateTime(date_time)
+    }
+}
+
+impl From<i64> for DateOrMillis {
+    fn from(millis: i64) -> Self {
+        DateOrMillis::Millis(millis)
+    }
+}
+
 /// Parameters for the `get_account()` method.
 ///
 /// [API Documentation](https://developer.tdameritrade.com/account-access/apis/get/accounts/%7BaccountId%7D-0)
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct GetAccountParams {
     /// Balances displayed by default, additional fields can be added here by adding `positions` or `orders`
     ///
@@ -11,18 +61,10 @@ pub struct GetAccountParams {
     pub fields: Option<String>,
 }
 
-impl Default for GetAccountParams {
-    fn default() -> Self {
-        Self {
-            fields: None,
-        }
-    }
-}
-
 /// Parameters for the `get_accounts()` method.
 ///
 /// [API Documentation](https://developer.tdameritrade.com/account-access/apis/get/accounts-0)
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct GetAccountsParams {
     /// Balances displayed by default, additional fields can be added here by adding `positions` or `orders`
     ///
@@ -30,18 +72,45 @@ pub struct GetAccountsParams {
     pub fields: Option<String>,
 }
 
-impl Default for GetAccountsParams {
-    fn default() -> Self {
-        Self {
-            fields: None,
-        }
-    }
+/// Parameters for the `get_orders()` method.
+///
+/// [API Documentation](https://developer.tdameritrade.com/account-access/apis/get/accounts/%7BaccountId%7D/orders-0)
+#[derive(Debug, Default)]
+pub struct GetOrdersParams {
+    /// Only return orders entered on or after this date, formatted as
+    /// `yyyy-MM-dd`. Must be within 60 days of today.
+    pub from_entered_time: Option<String>,
+
+    /// The maximum number of orders to retrieve.
+    pub max_results: Option<i64>,
+
+    /// Only return orders with this status.
+    ///
+    /// Choices: `AWAITING_PARENT_ORDER`, `AWAITING_CONDITION`,
+    /// `AWAITING_MANUAL_REVIEW`, `ACCEPTED`, `AWAITING_UR_OUT`,
+    /// `PENDING_ACTIVATION`, `QUEUED`, `WORKING`, `REJECTED`,
+    /// `PENDING_CANCEL`, `CANCELED`, `PENDING_REPLACE`, `REPLACED`, `FILLED`,
+    /// or `EXPIRED`
+    pub status: Option<String>,
+
+    /// Only return orders entered on or before this date, formatted as
+    /// `yyyy-MM-dd`.
+    pub to_entered_time: Option<String>,
+}
+
+/// Parameters for the `get_market_hours()` method.
+///
+/// [API Documentation](https://developer.tdameritrade.com/market-hours/apis/get/marketdata/%7Bmarket%7D/hours)
+#[derive(Debug, Default)]
+pub struct GetMarketHoursParams {
+    /// Date to get the hours of, defaults to the current day
+    pub date: Option<NaiveDate>,
 }
 
 /// Parameters for the `get_movers()` method.
 ///
 /// [API Documentation](https://developer.tdameritrade.com/movers/apis/get/marketdata/%7Bindex%7D/movers)
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct GetMoversParams {
     /// To return movers with the specified directions of up or down
     ///
@@ -54,24 +123,51 @@ pub struct GetMoversParams {
     pub direction: Option<String>,
 }
 
-impl Default for GetMoversParams {
-    fn default() -> Self {
-        Self {
-            change: None,
-            direction: None,
-        }
-    }
+/// Parameters for the `get_option_chain()` method.
+///
+/// [API Documentation](https://developer.tdameritrade.com/option-chains/apis/get/marketdata/chains)
+#[derive(Clone, Debug, Default)]
+pub struct GetOptionChainParams {
+    /// Type of contracts to return.
+    ///
+    /// Choices: `CALL`, `PUT`, or `ALL` (default)
+    pub contract_type: Option<String>,
+
+    /// Only return expirations after this date.
+    pub from_date: Option<NaiveDate>,
+
+    /// Include quotes for the options in the chain.
+    pub include_quotes: Option<bool>,
+
+    /// Strike interval for spread strategy chains.
+    pub interval: Option<String>,
+
+    /// Return options with this strike price only.
+    pub strike: Option<String>,
+
+    /// The number of strikes to return above and below the at-the-money price.
+    pub strike_count: Option<i64>,
+
+    /// Choices: `ITM`, `NTM`, `OTM`, `SAK`, `SBK`, `SNK`, or `ALL` (default)
+    pub range: Option<String>,
+
+    /// Choices: `SINGLE` (default), `ANALYTICAL`, `COVERED`, `VERTICAL`,
+    /// `CALENDAR`, `STRANGLE`, `STRADDLE`, `BUTTERFLY`, `CONDOR`, `DIAGONAL`,
+    /// `COLLAR`, or `ROLL`
+    pub strategy: Option<String>,
+
+    /// Only return expirations before this date.
+    pub to_date: Option<NaiveDate>,
 }
 
 /// Parameters for the `get_price_history()` method.
 ///
 /// [API Documentation](https://developer.tdameritrade.com/price-history/apis/get/marketdata/%7Bsymbol%7D/pricehistory)
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct GetPriceHistoryParams {
-    /// End date as milliseconds since epoch. If startDate and endDate are
-    /// provided, period should not be provided. Default is previous trading
-    /// day.
-    pub end_date: Option<String>,
+    /// End date. If startDate and endDate are provided, period should not be
+    /// provided. Default is previous trading day.
+    pub end_date: Option<DateOrMillis>,
 
     /// The type of frequency with which a new candle is formed.
     ///
@@ -130,21 +226,278 @@ pub struct GetPriceHistoryParams {
     /// `ytd`: 1*
     pub period: Option<String>,
 
-    /// Start date as milliseconds since epoch. If startDate and endDate are
-    /// provided, period should not be provided.
-    pub start_date: Option<String>,
+    /// Start date. If startDate and endDate are provided, period should not
+    /// be provided.
+    pub start_date: Option<DateOrMillis>,
 }
 
-impl Default for GetPriceHistoryParams {
-    fn default() -> Self {
+/// Valid periods by period type.
+const DAY_PERIODS: &[u32] = &[1, 2, 3, 4, 5, 10];
+const MONTH_PERIODS: &[u32] = &[1, 2, 3, 6];
+const YEAR_PERIODS: &[u32] = &[1, 2, 3, 5, 10, 15, 20];
+
+/// Valid frequencies of minute candles.
+const MINUTE_FREQUENCIES: &[u32] = &[1, 5, 10, 15, 30];
+
+/// Presets of the valid combinations of period and frequency. Periods and
+/// intervals without a valid value of their own are rounded up to the next
+/// one, or down to the largest, so the history covers at least what was
+/// asked for.
+///
+/// ```
+/// use tda_sdk::params::GetPriceHistoryParams;
+///
+/// let params = GetPriceHistoryParams::minute_bars(7, 5);
+///
+/// assert_eq!(params.period.as_deref(), Some("10"));
+/// assert_eq!(params.frequency.as_deref(), Some("5"));
+/// ```
+impl GetPriceHistoryParams {
+    /// Candles of `interval` minutes over the last `days` days, up to 10.
+    pub fn minute_bars(days: u32, interval: u32) -> Self {
+        Self::preset("day", round_up(days, DAY_PERIODS), "minute", round_up(interval, MINUTE_FREQUENCIES))
+    }
+
+    /// Daily candles over the last `months` months, up to 6.
+    pub fn daily_for_months(months: u32) -> Self {
+        Self::preset("month", round_up(months, MONTH_PERIODS), "daily", 1)
+    }
+
+    /// Weekly candles over the last `months` months, up to 6.
+    pub fn weekly_for_months(months: u32) -> Self {
+        Self::preset("month", round_up(months, MONTH_PERIODS), "weekly", 1)
+    }
+
+    /// Daily candles over the last `years` years, up to 20.
+    pub fn daily_for_years(years: u32) -> Self {
+        Self::preset("year", round_up(years, YEAR_PERIODS), "daily", 1)
+    }
+
+    /// Weekly candles over the last `years` years, up to 20.
+    pub fn weekly_for_years(years: u32) -> Self {
+        Self::preset("year", round_up(years, YEAR_PERIODS), "weekly", 1)
+    }
+
+    /// Monthly candles over the last `years` years, up to 20.
+    pub fn monthly_for_years(years: u32) -> Self {
+        Self::preset("year", round_up(years, YEAR_PERIODS), "monthly", 1)
+    }
+
+    /// Daily candles since the start of the year.
+    pub fn daily_year_to_date() -> Self {
+        Self::preset("ytd", 1, "daily", 1)
+    }
+
+    /// Weekly candles since the start of the year.
+    pub fn weekly_year_to_date() -> Self {
+        Self::preset("ytd", 1, "weekly", 1)
+    }
+
+    fn preset(period_type: &str, period: u32, frequency_type: &str, frequency: u32) -> Self {
         Self {
-            end_date: None,
-            frequency_type: None,
-            frequency: None,
-            need_extended_hours_data: None,
-            period_type: None,
-            period: None,
-            start_date: None,
+            period_type: Some(period_type.to_string()),
+            period: Some(period.to_string()),
+            frequency_type: Some(frequency_type.to_string()),
+            frequency: Some(frequency.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Smallest of the `valid` values at least `value`, or else the largest.
+fn round_up(value: u32, valid: &[u32]) -> u32 {
+    valid.iter().copied().find(|&valid| valid >= value).unwrap_or(valid[valid.len() - 1])
+}
+
+/// Quote field which can be requested on its own, from the quotes endpoint
+/// or the `QUOTE` service of the streamer. The symbol is always included.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum QuoteField {
+    Bid,
+    Ask,
+    Last,
+    BidSize,
+    AskSize,
+    TotalVolume,
+    LastSize,
+    TradeTime,
+    QuoteTime,
+    High,
+    Low,
+    Close,
+    Open,
+    NetChange,
+}
+
+impl QuoteField {
+    /// Every field.
+    pub const ALL: [QuoteField; 14] = [
+        QuoteField::Bid,
+        QuoteField::Ask,
+        QuoteField::Last,
+        QuoteField::BidSize,
+        QuoteField::AskSize,
+        QuoteField::TotalVolume,
+        QuoteField::LastSize,
+        QuoteField::TradeTime,
+        QuoteField::QuoteTime,
+        QuoteField::High,
+        QuoteField::Low,
+        QuoteField::Close,
+        QuoteField::Open,
+        QuoteField::NetChange,
+    ];
+
+    /// Name of the field in quotes of the quotes endpoint.
+    pub fn name(self) -> &'static str {
+        match self {
+            QuoteField::Bid => "bidPrice",
+            QuoteField::Ask => "askPrice",
+            QuoteField::Last => "lastPrice",
+            QuoteField::BidSize => "bidSize",
+            QuoteField::AskSize => "askSize",
+            QuoteField::TotalVolume => "totalVolume",
+            QuoteField::LastSize => "lastSize",
+            QuoteField::TradeTime => "tradeTimeInLong",
+            QuoteField::QuoteTime => "quoteTimeInLong",
+            QuoteField::High => "highPrice",
+            QuoteField::Low => "lowPrice",
+            QuoteField::Close => "closePrice",
+            QuoteField::Open => "openPrice",
+            QuoteField::NetChange => "netChange",
+        }
+    }
+
+    /// Number of the field in the `QUOTE` service of the streamer.
+    pub fn streamer_number(self) -> u8 {
+        match self {
+            QuoteField::Bid => 1,
+            QuoteField::Ask => 2,
+            QuoteField::Last => 3,
+            QuoteField::BidSize => 4,
+            QuoteField::AskSize => 5,
+            QuoteField::TotalVolume => 8,
+            QuoteField::LastSize => 9,
+            QuoteField::TradeTime => 10,
+            QuoteField::QuoteTime => 11,
+            QuoteField::High => 12,
+            QuoteField::Low => 13,
+            QuoteField::Close => 15,
+            QuoteField::Open => 28,
+            QuoteField::NetChange => 29,
+        }
+    }
+
+    /// Comma separated streamer numbers of `fields`, starting with the symbol
+    /// and without duplicates, as subscriptions take them.
+    pub fn streamer_fields(fields: &[QuoteField]) -> String {
+        let mut numbers: Vec<u8> = fields.iter().map(|field| field.streamer_number()).collect();
+
+        numbers.push(0);
+        numbers.sort_unstable();
+        numbers.dedup();
+
+        numbers.iter().map(u8::to_string).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Parameters for the `get_quotes_with()` method.
+#[derive(Clone, Debug, Default)]
+pub struct GetQuotesParams {
+    /// Fields to keep, or all of them if `None`. Parsed quotes are trimmed
+    /// to them, along with their symbol and asset type. They're also sent as
+    /// the `fields` parameter, but the TDA endpoint ignores it, so the
+    /// response is as large as without them.
+    pub fields: Option<Vec<QuoteField>>,
+}
+
+/// Parameters for the `get_transactions()` method.
+///
+/// [API Documentation](https://developer.tdameritrade.com/transaction-history/apis/get/accounts/%7BaccountId%7D/transactions-0)
+#[derive(Clone, Debug, Default)]
+pub struct GetTransactionsParams {
+    /// Only return transactions on or before this date.
+    pub end_date: Option<NaiveDate>,
+
+    /// Only return transactions on or after this date. The API returns up to
+    /// a year of history.
+    pub start_date: Option<NaiveDate>,
+
+    /// Only return transactions for this symbol.
+    pub symbol: Option<String>,
+
+    /// Only return transactions of this type.
+    ///
+    /// Choices: `ALL` (default), `TRADE`, `BUY_ONLY`, `SELL_ONLY`,
+    /// `CASH_IN_OR_CASH_OUT`, `CHECKING`, `DIVIDEND`, `INTEREST`, or `OTHER`
+    pub transaction_type: Option<String>,
+}
+
+/// Parameters for the `place_orders()` method.
+#[derive(Debug, Default)]
+pub struct PlaceOrdersParams {
+    /// Minimum time between two order submissions. Default is
+    /// [`DEFAULT_ORDER_INTERVAL`](../constant.DEFAULT_ORDER_INTERVAL.html).
+    pub interval: Option<Duration>,
+
+    /// Stop submitting orders after the first one fails. Default is `false`.
+    pub stop_on_error: bool,
+}
+
+/// Parameters for the `search_instruments()` method.
+///
+/// [API Documentation](https://developer.tdameritrade.com/instruments/apis/get/instruments)
+#[derive(Debug, Default)]
+pub struct SearchInstrumentsParams {
+    /// The type of request.
+    ///
+    /// Choices: `symbol-search`, `symbol-regex`, `desc-search`, `desc-regex`,
+    /// or `fundamental`
+    pub projection: Option<String>,
+}
+
+/// Parameters for the `get_user_principals()` method.
+///
+/// [API Documentation](https://developer.tdameritrade.com/user-principal/apis/get/userprincipals-0)
+#[derive(Debug, Default)]
+pub struct GetUserPrincipalsParams {
+    /// A comma separated list of additional fields to return.
+    ///
+    /// Choices: `streamerSubscriptionKeys`, `streamerConnectionInfo`,
+    /// `preferences`, or `surrogateIds`
+    pub fields: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_or_millis() {
+        let date = NaiveDate::from_ymd_opt(2021, 6, 18).unwrap();
+        let date_time = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+        assert_eq!(DateOrMillis::from(date).millis(), 1_623_974_400_000);
+        assert_eq!(DateOrMillis::from(date_time).millis(), 1_623_974_400_000);
+        assert_eq!(DateOrMillis::from(1_623_974_400_000).to_string(), "1623974400000");
+    }
+
+    #[test]
+    fn price_history_presets() {
+        let presets = [
+            (GetPriceHistoryParams::daily_for_years(5), ["year", "5", "daily", "1"]),
+            (GetPriceHistoryParams::daily_for_years(4), ["year", "5", "daily", "1"]),
+            (GetPriceHistoryParams::monthly_for_years(30), ["year", "20", "monthly", "1"]),
+            (GetPriceHistoryParams::minute_bars(10, 5), ["day", "10", "minute", "5"]),
+            (GetPriceHistoryParams::minute_bars(0, 7), ["day", "1", "minute", "10"]),
+            (GetPriceHistoryParams::weekly_for_months(4), ["month", "6", "weekly", "1"]),
+            (GetPriceHistoryParams::daily_year_to_date(), ["ytd", "1", "daily", "1"]),
+        ];
+
+        for (params, expected) in presets {
+            let actual = [params.period_type, params.period, params.frequency_type, params.frequency];
+
+            assert_eq!(actual, expected.map(|value| Some(value.to_string())));
         }
     }
 }
@@ -0,0 +1,276 @@
+//! Client-side request rate limiting.
+//!
+//! The API allows a limited number of requests per minute for each app key
+//! (client ID), across every user of the app. A
+//! [`RateLimiter`](struct.RateLimiter.html) can be shared between every client
+//! using the same app key to stay under the limit.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use tda_sdk::{rate_limit::RateLimiter, Client};
+//!
+//! let limiter = Arc::new(RateLimiter::per_minute(120));
+//!
+//! let mut alice = Client::new("CLIENT_ID", "ALICE_REFRESH_TOKEN", None);
+//! let mut bob = Client::new("CLIENT_ID", "BOB_REFRESH_TOKEN", None);
+//!
+//! alice.set_rate_limiter(Some(limiter.clone()));
+//! bob.set_rate_limiter(Some(limiter));
+//! ```
+//!
+//! Order placement is held to a stricter standard: apps sending bursts of
+//! orders get flagged. An [`OrderThrottle`](struct.OrderThrottle.html) only
+//! applies to trading endpoints, on top of the rate limiter, and lets a few
+//! orders through at once before settling to a steady rate:
+//!
+//! ```
+//! use std::sync::Arc;
+//! use tda_sdk::{rate_limit::{OrderThrottle, ThrottleMode}, Client};
+//!
+//! let mut throttle = OrderThrottle::new(3, 20);
+//! throttle.set_mode(ThrottleMode::Reject);
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! client.set_order_throttle(Some(Arc::new(throttle)));
+//! ```
+
+use crate::{
+    clock::{Clock, SystemClock},
+    ClientError,
+};
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Requests per minute allowed for each app key by the API.
+pub const DEFAULT_REQUESTS_PER_MINUTE: usize = 120;
+
+/// Limits the number of requests sent within a sliding window.
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_requests: usize,
+    period: Duration,
+    sent: Mutex<VecDeque<Instant>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    /// Allow up to `max_requests` requests within any `period`.
+    pub fn new(max_requests: usize, period: Duration) -> Self {
+        Self {
+            max_requests: max_requests.max(1),
+            period,
+            sent: Mutex::new(VecDeque::with_capacity(max_requests)),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Allow up to `max_requests` requests within any minute.
+    pub fn per_minute(max_requests: usize) -> Self {
+        Self::new(max_requests, Duration::from_secs(60))
+    }
+
+    /// Set the clock requests are timed with. Defaults to the system clock.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+        self.sent.get_mut().unwrap().clear();
+
+        self
+    }
+
+    /// Block until a request may be sent, and record it. Returns how long the
+    /// caller was blocked.
+    ///
+    /// Callers are let through one at a time, so a blocked caller also holds
+    /// back the callers behind it.
+    pub fn acquire(&self) -> Duration {
+        let mut sent = self.sent.lock().unwrap();
+        let mut waited = Duration::default();
+
+        loop {
+            let now = self.clock.instant();
+
+            while sent.front().is_some_and(|at| now.duration_since(*at) >= self.period) {
+                sent.pop_front();
+            }
+
+            if sent.len() < self.max_requests {
+                sent.push_back(now);
+
+                return waited;
+            }
+
+            let wait = self.period - now.duration_since(sent[0]);
+
+            self.clock.sleep(wait);
+            waited += wait;
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::per_minute(DEFAULT_REQUESTS_PER_MINUTE)
+    }
+}
+
+/// Orders an [`OrderThrottle`](struct.OrderThrottle.html) lets through at
+/// once by default.
+pub const DEFAULT_ORDER_BURST: usize = 4;
+
+/// Orders per minute an [`OrderThrottle`](struct.OrderThrottle.html) lets
+/// through by default once its burst is used up.
+pub const DEFAULT_ORDERS_PER_MINUTE: usize = 30;
+
+/// What an [`OrderThrottle`](struct.OrderThrottle.html) does with an order
+/// over its limit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ThrottleMode {
+    /// Block until the order may be sent.
+    #[default]
+    Wait,
+
+    /// Fail the order with `ClientError::OrderThrottled`.
+    Reject,
+}
+
+/// Limits order placement with a token bucket: up to `burst` orders may be
+/// sent at once, and the bucket refills at a steady number of orders per
+/// minute.
+#[derive(Debug)]
+pub struct OrderThrottle {
+    burst: f64,
+    interval: Duration,
+    mode: ThrottleMode,
+    bucket: Mutex<(f64, Instant)>,
+    clock: Arc<dyn Clock>,
+}
+
+impl OrderThrottle {
+    /// Allow bursts of up to `burst` orders, refilled at `per_minute` orders
+    /// per minute.
+    pub fn new(burst: usize, per_minute: usize) -> Self {
+        let burst = burst.max(1) as f64;
+        let clock = Arc::new(SystemClock);
+
+        Self {
+            burst,
+            interval: Duration::from_secs(60) / per_minute.max(1) as u32,
+            mode: ThrottleMode::default(),
+            bucket: Mutex::new((burst, clock.instant())),
+            clock,
+        }
+    }
+
+    /// Set whether orders over the limit wait or fail. Defaults to waiting.
+    pub fn set_mode(&mut self, mode: ThrottleMode) -> &mut Self {
+        self.mode = mode;
+
+        self
+    }
+
+    /// Set the clock the bucket refills with, which fills it up again.
+    /// Defaults to the system clock.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        *self.bucket.get_mut().unwrap() = (self.burst, clock.instant());
+        self.clock = clock;
+
+        self
+    }
+
+    /// Take an order from the bucket if one is available, or return how long
+    /// until one is.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let (tokens, refilled_at) = &mut *bucket;
+        let now = self.clock.instant();
+
+        *tokens = (*tokens + now.duration_since(*refilled_at).as_secs_f64() / self.interval.as_secs_f64()).min(self.burst);
+        *refilled_at = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+
+            return Ok(());
+        }
+
+        Err(self.interval.mul_f64(1.0 - *tokens))
+    }
+
+    /// Block until an order may be sent, and take it from the bucket. Returns
+    /// how long the caller was blocked.
+    pub fn acquire(&self) -> Duration {
+        let mut waited = Duration::default();
+
+        while let Err(wait) = self.try_acquire() {
+            self.clock.sleep(wait);
+            waited += wait;
+        }
+
+        waited
+    }
+
+    /// Let an order through according to the mode of the throttle.
+    pub(crate) fn check(&self) -> Result<Duration, ClientError> {
+        match self.mode {
+            ThrottleMode::Wait => Ok(self.acquire()),
+            ThrottleMode::Reject => self.try_acquire().map(|_| Duration::default()).map_err(ClientError::OrderThrottled),
+        }
+    }
+}
+
+impl Default for OrderThrottle {
+    fn default() -> Self {
+        Self::new(DEFAULT_ORDER_BURST, DEFAULT_ORDERS_PER_MINUTE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn limits_requests() {
+        let limiter = RateLimiter::new(2, Duration::from_millis(50));
+
+        assert_eq!(limiter.acquire(), Duration::default());
+        assert_eq!(limiter.acquire(), Duration::default());
+        assert!(limiter.acquire() > Duration::default());
+    }
+
+    #[test]
+    fn throttles_orders() {
+        let mut throttle = OrderThrottle::new(2, 60);
+
+        throttle.set_mode(ThrottleMode::Reject);
+
+        assert!(throttle.check().is_ok());
+        assert!(throttle.check().is_ok());
+
+        match throttle.check() {
+            Err(ClientError::OrderThrottled(wait)) => assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1)),
+            result => panic!("expected the order to be throttled, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn refills_with_clock() {
+        let clock = Arc::new(MockClock::default());
+        let mut throttle = OrderThrottle::new(1, 60);
+
+        throttle.set_clock(clock.clone());
+
+        assert_eq!(throttle.try_acquire(), Ok(()));
+        assert_eq!(throttle.try_acquire(), Err(Duration::from_secs(1)));
+
+        clock.advance(Duration::from_millis(250));
+
+        assert_eq!(throttle.try_acquire(), Err(Duration::from_millis(750)));
+        assert_eq!(throttle.acquire(), Duration::from_millis(750));
+        assert_eq!(throttle.try_acquire(), Err(Duration::from_secs(1)));
+    }
+}
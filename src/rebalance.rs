@@ -0,0 +1,383 @@
+//! Structs and utilities for rebalancing a portfolio towards target weights.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use tda_sdk::rebalance::{rebalance, Holdings, RebalanceParams};
+//!
+//! let holdings = Holdings {
+//!     cash: 1_000.0,
+//!     positions: vec![("AAPL".to_string(), 10.0)].into_iter().collect(),
+//!     ..Holdings::default()
+//! };
+//! let prices: HashMap<String, f64> = vec![
+//!     ("AAPL".to_string(), 100.0),
+//!     ("MSFT".to_string(), 50.0),
+//! ].into_iter().collect();
+//! let params = RebalanceParams {
+//!     targets: vec![("AAPL".to_string(), 0.5), ("MSFT".to_string(), 0.5)].into_iter().collect(),
+//!     ..RebalanceParams::default()
+//! };
+//!
+//! // AAPL is already at its target weight, so only MSFT is bought.
+//! let orders = rebalance(&holdings, &prices, &params).unwrap();
+//!
+//! assert_eq!(orders.len(), 1);
+//! assert_eq!(orders[0].order_leg_collection[0].quantity, 20.0);
+//! ```
+
+use crate::{
+    greeks::DEFAULT_MULTIPLIER,
+    instruments::Instrument,
+    orders::{Instruction, Order},
+    responses::{GetQuotesResponse, SecuritiesAccount},
+};
+use thiserror::Error;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Cash and positions to rebalance.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Holdings {
+    /// Cash available to buy with.
+    pub cash: f64,
+
+    /// Quantity held, keyed by symbol, negative for short positions.
+    pub positions: HashMap<String, f64>,
+
+    /// Contract multiplier of each option symbol, held or targeted. Their
+    /// quantities are in contracts and their prices per share, and they're
+    /// traded with option orders.
+    pub multipliers: HashMap<String, f64>,
+}
+
+impl Holdings {
+    /// Build holdings from an account fetched with the `positions` field.
+    pub fn from_account(account: &SecuritiesAccount) -> Self {
+        let mut holdings = Self {
+            cash: account.current_balances().cash_balance,
+            ..Self::default()
+        };
+
+        for position in account.positions() {
+            let symbol = position.instrument.symbol().to_string();
+
+            if let Instrument::Option { option_multiplier, .. } = &position.instrument {
+                holdings.multipliers.insert(symbol.clone(), option_multiplier.unwrap_or(DEFAULT_MULTIPLIER));
+            }
+
+            *holdings.positions.entry(symbol).or_default() += position.quantity();
+        }
+
+        holdings
+    }
+}
+
+/// How target share counts are rounded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ShareRounding {
+    /// Round towards zero to whole shares, so the targets are never
+    /// overspent, long or short.
+    #[default]
+    Down,
+
+    /// Round to the nearest whole share.
+    Nearest,
+
+    /// Don't round, for accounts which support fractional shares.
+    Fractional,
+}
+
+/// Parameters for the [`rebalance()`](fn.rebalance.html) function.
+#[derive(Clone, Debug, Default)]
+pub struct RebalanceParams {
+    /// Target weight of each symbol, as a fraction of the portfolio,
+    /// negative for short positions. The positive weights must not add up to
+    /// more than `1.0`. Held symbols without a target are closed.
+    pub targets: HashMap<String, f64>,
+
+    /// Fraction of the portfolio to keep in cash, at least `0.0` and less
+    /// than `1.0`. Targets are weights of the rest.
+    pub cash_buffer: f64,
+
+    /// Symbols which are never bought or sold. Their value still counts
+    /// towards the size of the portfolio.
+    pub do_not_trade: HashSet<String>,
+
+    /// How target share counts are rounded.
+    pub rounding: ShareRounding,
+
+    /// Trades worth less than this are skipped.
+    pub min_trade_value: f64,
+}
+
+/// Errors encountered while computing a rebalance.
+#[derive(Debug, Error, PartialEq)]
+pub enum RebalanceError {
+    /// The cash buffer is negative, or `1.0` or more.
+    #[error("Cash buffer must be at least 0 and less than 1, got {0}")]
+    InvalidCashBuffer(f64),
+
+    /// The positive target weights add up to more than `1.0`.
+    #[error("Positive target weights must add up to at most 1")]
+    InvalidWeights,
+
+    /// No usable price was given for a symbol which is held or targeted.
+    #[error("Missing price for {0}")]
+    MissingPrice(String),
+}
+
+/// Compute the market orders which bring `holdings` to the target weights.
+///
+/// Sells are returned before buys, so that the proceeds are available by the
+/// time the buys are placed. Long positions are bought and sold, and short
+/// positions sold short and bought to cover; a position which crosses zero
+/// is closed by one order and opened by another. Symbols already within
+/// `min_trade_value` of their target are left alone.
+pub fn rebalance(holdings: &Holdings, prices: &HashMap<String, f64>, params: &RebalanceParams) -> Result<Vec<Order>, RebalanceError> {
+    if !(0.0..1.0).contains(&params.cash_buffer) {
+        return Err(RebalanceError::InvalidCashBuffer(params.cash_buffer));
+    }
+
+    if params.targets.values().filter(|weight| **weight > 0.0).sum::<f64>() > 1.0 + f64::EPSILON {
+        return Err(RebalanceError::InvalidWeights);
+    }
+
+    // Price of a share, or of a contract for options.
+    let price_of = |symbol: &str| match prices.get(symbol) {
+        Some(price) if *price > 0.0 => Ok(*price * holdings.multipliers.get(symbol).copied().unwrap_or(1.0)),
+        _ => Err(RebalanceError::MissingPrice(symbol.to_string())),
+    };
+
+    let mut equity = holdings.cash;
+
+    for (symbol, quantity) in &holdings.positions {
+        equity += quantity * price_of(symbol)?;
+    }
+
+    let investable = equity * (1.0 - params.cash_buffer);
+    let symbols: BTreeSet<&String> = holdings.positions.keys().chain(params.targets.keys()).collect();
+    let mut sells = Vec::new();
+    let mut buys = Vec::new();
+
+    for symbol in symbols {
+        if params.do_not_trade.contains(symbol) {
+            continue;
+        }
+
+        let price = price_of(symbol)?;
+        let current = holdings.positions.get(symbol).copied().unwrap_or_default();
+        let target_value = params.targets.get(symbol).copied().unwrap_or_default() * investable;
+        let target = match params.rounding {
+            ShareRounding::Down => (target_value / price).trunc(),
+            ShareRounding::Nearest => (target_value / price).round(),
+            ShareRounding::Fractional => target_value / price,
+        };
+        let delta = target - current;
+
+        if delta == 0.0 || delta.abs() * price < params.min_trade_value {
+            continue;
+        }
+
+        let order = |instruction, quantity| match holdings.multipliers.contains_key(symbol.as_str()) {
+            true => Order::option_market(option_instruction(instruction), symbol, quantity),
+            false => Order::equity_market(instruction, symbol, quantity),
+        };
+
+        if delta < 0.0 {
+            if current > 0.0 {
+                sells.push(order(Instruction::Sell, current - target.max(0.0)));
+            }
+
+            if target < 0.0 {
+                sells.push(order(Instruction::SellShort, current.min(0.0) - target));
+            }
+        } else {
+            if current < 0.0 {
+                buys.push(order(Instruction::BuyToCover, target.min(0.0) - current));
+            }
+
+            if target > 0.0 {
+                buys.push(order(Instruction::Buy, target - current.max(0.0)));
+            }
+        }
+    }
+
+    sells.extend(buys);
+
+    Ok(sells)
+}
+
+/// Option equivalent of an equity instruction: shorting and covering open
+/// and close short contracts.
+fn option_instruction(instruction: Instruction) -> Instruction {
+    match instruction {
+        Instruction::Buy => Instruction::BuyToOpen,
+        Instruction::Sell => Instruction::SellToClose,
+        Instruction::SellShort => Instruction::SellToOpen,
+        Instruction::BuyToCover => Instruction::BuyToClose,
+        other => other,
+    }
+}
+
+/// Collect the last price of each quote, for use with
+/// [`rebalance()`](fn.rebalance.html).
+pub fn prices_from_quotes(quotes: &GetQuotesResponse) -> HashMap<String, f64> {
+    quotes
+        .iter()
+        .filter_map(|(symbol, quote)| Some((symbol.clone(), quote.last_price()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, f64)]) -> HashMap<String, f64> {
+        entries.iter().map(|(symbol, value)| (symbol.to_string(), *value)).collect()
+    }
+
+    fn legs(orders: &[Order]) -> Vec<(Instruction, String, f64)> {
+        orders
+            .iter()
+            .map(|order| {
+                let leg = &order.order_leg_collection[0];
+
+                (leg.instruction, leg.instrument.symbol().to_string(), leg.quantity)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sells_before_buys() {
+        let holdings = Holdings {
+            cash: 0.0,
+            positions: map(&[("AAPL", 10.0), ("GE", 100.0)]),
+            ..Holdings::default()
+        };
+        let prices = map(&[("AAPL", 100.0), ("GE", 10.0), ("MSFT", 50.0)]);
+        let params = RebalanceParams {
+            targets: map(&[("AAPL", 0.5), ("MSFT", 0.5)]),
+            ..RebalanceParams::default()
+        };
+
+        let orders = rebalance(&holdings, &prices, &params).unwrap();
+
+        assert_eq!(legs(&orders), vec![
+            (Instruction::Sell, "GE".to_string(), 100.0),
+            (Instruction::Buy, "MSFT".to_string(), 20.0),
+        ]);
+    }
+
+    #[test]
+    fn cash_buffer_and_do_not_trade() {
+        let holdings = Holdings {
+            cash: 1_000.0,
+            positions: map(&[("TSLA", 1.0)]),
+            ..Holdings::default()
+        };
+        let prices = map(&[("SPY", 30.0), ("TSLA", 1_000.0)]);
+        let params = RebalanceParams {
+            targets: map(&[("SPY", 1.0)]),
+            cash_buffer: 0.1,
+            do_not_trade: vec!["TSLA".to_string()].into_iter().collect(),
+            min_trade_value: 100.0,
+            ..RebalanceParams::default()
+        };
+
+        let orders = rebalance(&holdings, &prices, &params).unwrap();
+
+        // 90% of 2000 is 1800, which buys 60 shares of SPY.
+        assert_eq!(legs(&orders), vec![(Instruction::Buy, "SPY".to_string(), 60.0)]);
+    }
+
+    #[test]
+    fn short_positions() {
+        let holdings = Holdings {
+            cash: 10_000.0,
+            positions: map(&[("AMC", -100.0), ("GME", -50.0), ("SPY", 10.0), ("TSLA", -10.0)]),
+            ..Holdings::default()
+        };
+        let prices = map(&[("AMC", 10.0), ("GME", 20.0), ("SPY", 100.0), ("TSLA", 50.0)]);
+        let params = RebalanceParams {
+            targets: map(&[("AMC", -0.2), ("GME", -0.05), ("SPY", -0.05), ("TSLA", 0.05)]),
+            ..RebalanceParams::default()
+        };
+
+        let orders = rebalance(&holdings, &prices, &params).unwrap();
+
+        // The portfolio is worth 10000 - 1000 - 1000 + 1000 - 500 = 8500.
+        assert_eq!(legs(&orders), vec![
+            (Instruction::SellShort, "AMC".to_string(), 70.0),
+            (Instruction::Sell, "SPY".to_string(), 10.0),
+            (Instruction::SellShort, "SPY".to_string(), 4.0),
+            (Instruction::BuyToCover, "GME".to_string(), 29.0),
+            (Instruction::BuyToCover, "TSLA".to_string(), 10.0),
+            (Instruction::Buy, "TSLA".to_string(), 8.0),
+        ]);
+    }
+
+    #[test]
+    fn option_contracts() {
+        let holdings = Holdings {
+            cash: 10_000.0,
+            positions: map(&[("AAPL_011521C130", 1.0)]),
+            multipliers: map(&[("AAPL_011521C130", 100.0)]),
+        };
+        let prices = map(&[("AAPL_011521C130", 5.0)]);
+        let params = RebalanceParams {
+            targets: map(&[("AAPL_011521C130", 0.2)]),
+            ..RebalanceParams::default()
+        };
+
+        let orders = rebalance(&holdings, &prices, &params).unwrap();
+
+        // The contract is worth 500, so 20% of 10500 is 4 contracts.
+        assert_eq!(legs(&orders), vec![(Instruction::BuyToOpen, "AAPL_011521C130".to_string(), 3.0)]);
+        assert!(matches!(orders[0].order_leg_collection[0].instrument, Instrument::Option { .. }));
+
+        let params = RebalanceParams {
+            targets: map(&[("AAPL_011521C130", -0.2)]),
+            ..RebalanceParams::default()
+        };
+
+        let orders = rebalance(&holdings, &prices, &params).unwrap();
+
+        assert_eq!(legs(&orders), vec![
+            (Instruction::SellToClose, "AAPL_011521C130".to_string(), 1.0),
+            (Instruction::SellToOpen, "AAPL_011521C130".to_string(), 4.0),
+        ]);
+
+        let holdings = Holdings {
+            positions: map(&[("AAPL_011521C130", -1.0)]),
+            ..holdings
+        };
+        let orders = rebalance(&holdings, &prices, &RebalanceParams::default()).unwrap();
+
+        assert_eq!(legs(&orders), vec![(Instruction::BuyToClose, "AAPL_011521C130".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn invalid_params() {
+        let holdings = Holdings::default();
+        let params = RebalanceParams {
+            targets: map(&[("AAPL", 0.7), ("MSFT", 0.7)]),
+            ..RebalanceParams::default()
+        };
+
+        assert_eq!(rebalance(&holdings, &HashMap::new(), &params), Err(RebalanceError::InvalidWeights));
+
+        let params = RebalanceParams {
+            targets: map(&[("AAPL", 1.0)]),
+            ..RebalanceParams::default()
+        };
+
+        assert_eq!(rebalance(&holdings, &HashMap::new(), &params), Err(RebalanceError::MissingPrice("AAPL".to_string())));
+
+        let params = RebalanceParams {
+            cash_buffer: 1.0,
+            ..RebalanceParams::default()
+        };
+
+        assert_eq!(rebalance(&holdings, &HashMap::new(), &params), Err(RebalanceError::InvalidCashBuffer(1.0)));
+    }
+}
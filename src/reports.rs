@@ -0,0 +1,227 @@
+//! Reports for tax preparation.
+//!
+//! [`realized_gains()`](fn.realized_gains.html) summarizes the gains realized
+//! in a year by symbol and term, the way brokers report them on a 1099-B, so
+//! they can be checked against the documents of the broker:
+//!
+//! ```no_run
+//! use tda_sdk::{orders::TaxLotMethod, params::GetTransactionsParams, reports, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let transactions = client.get_transactions("123456789", GetTransactionsParams::default()).unwrap();
+//! let report = reports::realized_gains(&transactions, 2020, TaxLotMethod::Fifo);
+//!
+//! println!("Short term: {:.2}, long term: {:.2}", report.short_term().gain(), report.long_term().gain());
+//!
+//! std::fs::write("realized_gains_2020.csv", report.to_csv()).unwrap();
+//! ```
+//!
+//! Lots are matched over every transaction given, so the history should go
+//! back to when the shares sold in the year were bought. See the
+//! [`lots`](../lots/index.html) module for what is and isn't tracked.
+
+use crate::{
+    journal::csv_field,
+    lots::{self, ClosedLot},
+    orders::TaxLotMethod,
+    responses::Transaction,
+};
+use chrono::Datelike;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Columns of the CSV export, in order.
+const CSV_HEADER: &[&str] = &["symbol", "term", "quantity", "proceeds", "cost", "wash_sale_loss_disallowed", "gain"];
+
+/// Holding period of sold shares.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Term {
+    /// Held for a year or less.
+    ShortTerm,
+    /// Held for more than a year.
+    LongTerm,
+}
+
+impl Term {
+    fn name(self) -> &'static str {
+        match self {
+            Self::ShortTerm => "short_term",
+            Self::LongTerm => "long_term",
+        }
+    }
+}
+
+/// Sales of a symbol and term added up.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RealizedGain {
+    pub quantity: f64,
+    pub proceeds: f64,
+    pub cost: f64,
+    /// Losses which can't be deducted because of wash sales. Positive.
+    pub wash_sale_loss_disallowed: f64,
+}
+
+impl RealizedGain {
+    /// Gain to report, with the disallowed losses added back.
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost + self.wash_sale_loss_disallowed
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.quantity += other.quantity;
+        self.proceeds += other.proceeds;
+        self.cost += other.cost;
+        self.wash_sale_loss_disallowed += other.wash_sale_loss_disallowed;
+    }
+}
+
+/// Gains realized in a year.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RealizedGainsReport {
+    pub year: i32,
+    /// Gains by symbol and term, sorted by symbol.
+    pub gains: BTreeMap<(String, Term), RealizedGain>,
+    /// Lots closed during the year, in the order they were sold.
+    pub lots: Vec<ClosedLot>,
+}
+
+impl RealizedGainsReport {
+    /// Gains of every symbol held for a year or less.
+    pub fn short_term(&self) -> RealizedGain {
+        self.total(Term::ShortTerm)
+    }
+
+    /// Gains of every symbol held for more than a year.
+    pub fn long_term(&self) -> RealizedGain {
+        self.total(Term::LongTerm)
+    }
+
+    fn total(&self, term: Term) -> RealizedGain {
+        let mut total = RealizedGain::default();
+
+        for gain in self.gains.iter().filter(|((_, gain_term), _)| *gain_term == term).map(|(_, gain)| gain) {
+            total.add(gain);
+        }
+
+        total
+    }
+
+    /// Export one row per symbol and term, with a header.
+    pub fn to_csv(&self) -> String {
+        let mut csv = CSV_HEADER.join(",") + "\n";
+
+        for ((symbol, term), gain) in &self.gains {
+            let row = [
+                symbol.clone(),
+                term.name().to_string(),
+                gain.quantity.to_string(),
+                format!("{:.2}", gain.proceeds),
+                format!("{:.2}", gain.cost),
+                format!("{:.2}", gain.wash_sale_loss_disallowed),
+                format!("{:.2}", gain.gain()),
+            ];
+            let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+
+            csv += &fields.join(",");
+            csv += "\n";
+        }
+
+        csv
+    }
+}
+
+/// Summarize the gains realized in `year`, matching sales to lots with
+/// `method`. Losses of wash sales are reported as disallowed.
+pub fn realized_gains<'a>(transactions: impl IntoIterator<Item = &'a Transaction> + Clone, year: i32, method: TaxLotMethod) -> RealizedGainsReport {
+    let mut disallowed: HashMap<(i64, i64), f64> = HashMap::new();
+
+    for wash_sale in lots::wash_sales(transactions.clone(), method) {
+        *disallowed.entry((wash_sale.purchase_transaction_id, wash_sale.sale_transaction_id)).or_default() += wash_sale.disallowed_loss;
+    }
+
+    let mut report = RealizedGainsReport { year, ..RealizedGainsReport::default() };
+
+    for lot in lots::match_lots(transactions, method).closed {
+        if lot.sold_on.year() != year {
+            continue;
+        }
+
+        let term = if lot.is_long_term() { Term::LongTerm } else { Term::ShortTerm };
+        let gain = report.gains.entry((lot.symbol.clone(), term)).or_default();
+
+        gain.add(&RealizedGain {
+            quantity: lot.quantity,
+            proceeds: lot.proceeds,
+            cost: lot.cost,
+            wash_sale_loss_disallowed: disallowed.remove(&(lot.opened_by, lot.closed_by)).unwrap_or_default(),
+        });
+
+        report.lots.push(lot);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        responses::{TransactionInstrument, TransactionItem},
+        time::TdaDateTime,
+    };
+    use chrono::NaiveDate;
+
+    fn trade(transaction_id: i64, date: &str, instruction: &str, symbol: &str, quantity: f64, price: f64) -> Transaction {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let amount = quantity * price;
+
+        Transaction {
+            net_amount: if instruction == "BUY" { -amount } else { amount },
+            transaction_date: TdaDateTime::from_date(date),
+            transaction_id,
+            transaction_item: Some(TransactionItem {
+                amount: Some(quantity),
+                instruction: Some(instruction.to_string()),
+                instrument: Some(TransactionInstrument {
+                    symbol: Some(symbol.to_string()),
+                    ..TransactionInstrument::default()
+                }),
+                price: Some(price),
+                ..TransactionItem::default()
+            }),
+            transaction_type: "TRADE".to_string(),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn realized_gains_by_term() {
+        let transactions = vec![
+            trade(1, "2019-01-02", "BUY", "AAPL", 10.0, 100.0),
+            trade(2, "2020-03-02", "BUY", "AAPL", 10.0, 150.0),
+            trade(3, "2020-06-01", "SELL", "AAPL", 15.0, 120.0),
+            trade(4, "2020-06-15", "BUY", "AAPL", 5.0, 125.0),
+            trade(5, "2019-05-01", "BUY", "MSFT", 4.0, 120.0),
+            trade(6, "2019-11-01", "SELL", "MSFT", 4.0, 140.0),
+        ];
+
+        let report = realized_gains(&transactions, 2020, TaxLotMethod::Fifo);
+
+        assert_eq!(report.lots.len(), 2);
+        assert_eq!(report.long_term().gain(), 200.0);
+        assert_eq!(report.short_term(), RealizedGain {
+            quantity: 5.0,
+            proceeds: 600.0,
+            cost: 750.0,
+            wash_sale_loss_disallowed: 150.0,
+        });
+
+        let csv = report.to_csv();
+
+        assert_eq!(csv.lines().next().unwrap().split(',').count(), CSV_HEADER.len());
+        assert_eq!(csv.lines().nth(1).unwrap(), "AAPL,short_term,5,600.00,750.00,150.00,0.00");
+        assert_eq!(csv.lines().nth(2).unwrap(), "AAPL,long_term,10,1200.00,1000.00,0.00,200.00");
+
+        assert_eq!(realized_gains(&transactions, 2019, TaxLotMethod::Fifo).gains[&("MSFT".to_string(), Term::ShortTerm)].gain(), 80.0);
+    }
+}
@@ -1,164 +1,2179 @@
 //! Structs and utilities for handling API response data.
 
+use crate::{
+    flexible::{epoch_millis, flexible_f64, flexible_option_f64},
+    ids::AccountId,
+    instruments::Instrument,
+    orders::{self, OrderCapabilities, OrderDefaults},
+    schema::{self, ParseMode},
+    time::TdaDateTime,
+    transactions::{classify, TransactionKind},
+    ClientError,
+};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+    sync::mpsc::Receiver,
+};
+
 /// Response returned by the `get_access_token()` method.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct AccessTokenResponse {
     pub access_token: String,
     pub scope: String,
     pub expires_in: i64,
+    /// New refresh token, only included when one was requested with
+    /// `renew_refresh_token()`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// Seconds until the new refresh token expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token_expires_in: Option<i64>,
 }
 
 /// Response returned by the `get_price_history()` method.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct GetPriceHistoryResponse {
     pub candles: Vec<Candle>,
     pub empty: bool,
     pub symbol: String,
 }
 
+impl GetPriceHistoryResponse {
+    /// Split the candles into one vector per field, which is friendlier to
+    /// numeric analysis than a vector of candles.
+    pub fn into_columns(self) -> CandleColumns {
+        let mut columns = CandleColumns::with_capacity(self.candles.len());
+
+        for candle in self.candles {
+            columns.close.push(candle.close);
+            columns.datetime.push(candle.datetime);
+            columns.high.push(candle.high);
+            columns.low.push(candle.low);
+            columns.open.push(candle.open);
+            columns.volume.push(candle.volume);
+        }
+
+        columns
+    }
+}
+
+/// Candles of a [`GetPriceHistoryResponse`](struct.GetPriceHistoryResponse.html)
+/// stored as parallel vectors, all of the same length.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CandleColumns {
+    pub close: Vec<f64>,
+    /// Milliseconds since epoch.
+    pub datetime: Vec<i64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub open: Vec<f64>,
+    pub volume: Vec<i64>,
+}
+
+impl CandleColumns {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            close: Vec::with_capacity(capacity),
+            datetime: Vec::with_capacity(capacity),
+            high: Vec::with_capacity(capacity),
+            low: Vec::with_capacity(capacity),
+            open: Vec::with_capacity(capacity),
+            volume: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Number of candles.
+    pub fn len(&self) -> usize {
+        self.datetime.len()
+    }
+
+    /// Whether there are no candles.
+    pub fn is_empty(&self) -> bool {
+        self.datetime.is_empty()
+    }
+
+    /// Prices and volume as a matrix with one row per candle, and columns
+    /// ordered open, high, low, close, volume.
+    ///
+    /// Only available with the `ndarray` feature enabled.
+    #[cfg(feature = "ndarray")]
+    pub fn to_ohlcv(&self) -> ndarray::Array2<f64> {
+        ndarray::Array2::from_shape_fn((self.len(), 5), |(row, column)| match column {
+            0 => self.open[row],
+            1 => self.high[row],
+            2 => self.low[row],
+            3 => self.close[row],
+            _ => self.volume[row] as f64,
+        })
+    }
+}
+
 /// Individual candle item in [`GetPriceHistoryResponse`](struct.GetPriceHistoryResponse.html).
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct Candle {
+    #[serde(deserialize_with = "flexible_f64")]
     pub close: f64,
-    pub datetime: usize,
+    /// Milliseconds since epoch, even if the API sent seconds.
+    #[serde(deserialize_with = "epoch_millis")]
+    pub datetime: i64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub high: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub low: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub open: f64,
     pub volume: i64,
 }
 
+/// Response returned by the `get_market_hours()` method, keyed by market and
+/// then by product.
+pub type GetMarketHoursResponse = HashMap<String, HashMap<String, MarketHours>>;
+
+/// Hours of a market product on a single day.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketHours {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    pub date: NaiveDate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange: Option<String>,
+    pub is_open: bool,
+    pub market_type: String,
+    pub product: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_name: Option<String>,
+    /// Missing when the market is closed for the day.
+    pub session_hours: Option<SessionHours>,
+}
+
+impl MarketHours {
+    /// Session a timestamp in milliseconds falls into, or `None` if it is
+    /// outside of every session of the day.
+    pub fn session_at(&self, millis: i64) -> Option<Session> {
+        let hours = self.session_hours.as_ref()?;
+        let sessions = [
+            (Session::Pre, &hours.pre_market),
+            (Session::Regular, &hours.regular_market),
+            (Session::Post, &hours.post_market),
+        ];
+
+        sessions
+            .iter()
+            .find(|(_, intervals)| intervals.iter().any(|interval| interval.contains(millis)))
+            .map(|(session, _)| *session)
+    }
+}
+
+/// Session hours item in [`MarketHours`](struct.MarketHours.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionHours {
+    #[serde(default)]
+    pub pre_market: Vec<SessionInterval>,
+    #[serde(default)]
+    pub regular_market: Vec<SessionInterval>,
+    #[serde(default)]
+    pub post_market: Vec<SessionInterval>,
+}
+
+/// Start and end of a session in [`SessionHours`](struct.SessionHours.html).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SessionInterval {
+    pub start: TdaDateTime,
+    pub end: TdaDateTime,
+}
+
+impl SessionInterval {
+    /// Whether a timestamp in milliseconds is within the interval. The end is
+    /// exclusive, so a candle starting at the close belongs to the next
+    /// session.
+    pub fn contains(&self, millis: i64) -> bool {
+        self.start.timestamp_millis() <= millis && millis < self.end.timestamp_millis()
+    }
+}
+
+/// Trading session of a day.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Session {
+    Pre,
+    Regular,
+    Post,
+}
+
 /// Individual response item returned by the `get_movers()` method.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Mover {
+    #[serde(alias = "netChange")]
+    #[serde(deserialize_with = "flexible_f64")]
     pub change: f64,
     pub description: String,
     pub direction: String,
+    /// Index the mover was requested for, such as `$DJI`. Set by the client,
+    /// not the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    #[serde(alias = "lastPrice")]
+    #[serde(deserialize_with = "flexible_f64")]
     pub last: f64,
     pub symbol: String,
+    #[serde(alias = "volume")]
     pub total_volume: i64,
 }
 
+/// Response returned by the `get_quotes()` method, keyed by symbol.
+pub type GetQuotesResponse = HashMap<String, Quote>;
+
+/// Quotes returned by the `get_quotes()` method, split by whether each
+/// requested symbol got one.
+///
+/// The API leaves symbols it doesn't know out of its response, and sometimes
+/// returns an error in place of a quote, so one bad symbol doesn't fail the
+/// whole batch.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotesResult {
+    /// Quotes keyed by symbol.
+    pub found: GetQuotesResponse,
+    /// Requested symbols the API returned nothing for.
+    pub not_found: Vec<String>,
+    /// Symbols the API returned an error or an unparseable quote for, with
+    /// the reason.
+    pub failed: HashMap<String, String>,
+}
+
+impl QuotesResult {
+    /// Sort the raw quotes of a response for `symbols`.
+    pub fn from_values(symbols: &[&str], values: HashMap<String, Value>) -> Self {
+        let mut result = Self::default();
+
+        for (symbol, value) in values {
+            match value.get("error").and_then(Value::as_str) {
+                Some(error) => {
+                    result.failed.insert(symbol, error.to_string());
+                }
+                None => match Quote::try_from_value(value) {
+                    Ok(quote) => {
+                        result.found.insert(symbol, quote);
+                    }
+                    Err(error) => {
+                        result.failed.insert(symbol, error.to_string());
+                    }
+                },
+            }
+        }
+
+        result.not_found = symbols
+            .iter()
+            .filter(|symbol| !result.contains(symbol) && !result.contains(&symbol.to_uppercase()))
+            .map(|symbol| symbol.to_string())
+            .collect();
+
+        result
+    }
+
+    /// Whether every requested symbol got a quote.
+    pub fn is_complete(&self) -> bool {
+        self.not_found.is_empty() && self.failed.is_empty()
+    }
+
+    /// Add the quotes of another response, such as one for a subset of the
+    /// symbols.
+    pub fn merge(&mut self, other: QuotesResult) {
+        self.found.extend(other.found);
+        self.not_found.extend(other.not_found);
+        self.failed.extend(other.failed);
+    }
+
+    fn contains(&self, symbol: &str) -> bool {
+        self.found.contains_key(symbol) || self.failed.contains_key(symbol)
+    }
+}
+
+/// Quote for a single symbol returned by the `get_quote()` and `get_quotes()`
+/// methods.
+///
+/// Quotes are parsed leniently: if a quote has an asset type without a typed
+/// variant, or doesn't match the expected shape, it is kept as
+/// [`Quote::Unknown`](enum.Quote.html#variant.Unknown) instead of failing the
+/// whole response. In [`ParseMode::Strict`](../schema/enum.ParseMode.html#variant.Strict)
+/// such quotes fail to parse instead.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Quote {
+    Equity(EquityQuote),
+    Etf(EquityQuote),
+    Index(IndexQuote),
+    MutualFund(MutualFundQuote),
+    Option(OptionQuote),
+    /// Quote of an asset type which could not be parsed into a typed variant.
+    ///
+    /// Quotes the API doesn't send at all, even after a rejected batch is
+    /// split to find the bad symbols, aren't `Unknown`: they're listed in
+    /// [`QuotesResult::not_found`](struct.QuotesResult.html#structfield.not_found).
+    Unknown(Value),
+}
+
+impl Quote {
+    /// Parse a raw quote, falling back to `Quote::Unknown` if it doesn't match
+    /// its typed variant.
+    pub fn from_value(value: Value) -> Self {
+        Self::try_from_value(value.clone()).unwrap_or(Quote::Unknown(value))
+    }
+
+    /// Parse a raw quote, failing if it doesn't match its typed variant.
+    /// Quotes of asset types without a typed variant are `Quote::Unknown`,
+    /// unless parsed in [`ParseMode::Strict`](../schema/enum.ParseMode.html#variant.Strict),
+    /// which also fails on fields the variant doesn't know.
+    pub fn try_from_value(value: Value) -> Result<Self, serde_json::Error> {
+        /// Parse the fields of a typed variant, which don't include the
+        /// asset type tagging it.
+        fn variant<T: de::DeserializeOwned>(mut value: Value) -> Result<T, serde_json::Error> {
+            if let Some(object) = value.as_object_mut() {
+                object.remove("assetType");
+            }
+
+            schema::deserialize(value)
+        }
+
+        let asset_type = value.get("assetType").and_then(Value::as_str).unwrap_or_default().to_string();
+
+        match asset_type.as_str() {
+            "EQUITY" => variant(value).map(Quote::Equity),
+            "ETF" => variant(value).map(Quote::Etf),
+            "INDEX" => variant(value).map(Quote::Index),
+            "MUTUAL_FUND" => variant(value).map(Quote::MutualFund),
+            "OPTION" => variant(value).map(Quote::Option),
+            _ if schema::parse_mode() == Some(ParseMode::Strict) => Err(de::Error::custom(format_args!("unknown asset type `{}`", asset_type))),
+            _ => Ok(Quote::Unknown(value)),
+        }
+    }
+
+    /// Asset type of the quote, as reported by the API.
+    pub fn asset_type(&self) -> Option<&str> {
+        match self {
+            Quote::Equity(_) => Some("EQUITY"),
+            Quote::Etf(_) => Some("ETF"),
+            Quote::Index(_) => Some("INDEX"),
+            Quote::MutualFund(_) => Some("MUTUAL_FUND"),
+            Quote::Option(_) => Some("OPTION"),
+            Quote::Unknown(value) => value.get("assetType").and_then(Value::as_str),
+        }
+    }
+
+    /// Symbol of the quote.
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => Some(&quote.symbol),
+            Quote::Index(quote) => Some(&quote.symbol),
+            Quote::MutualFund(quote) => Some(&quote.symbol),
+            Quote::Option(quote) => Some(&quote.symbol),
+            Quote::Unknown(value) => value.get("symbol").and_then(Value::as_str),
+        }
+    }
+
+    /// Price of the last trade, or the net asset value for mutual funds.
+    pub fn last_price(&self) -> Option<f64> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote.last_price,
+            Quote::Index(quote) => quote.last_price,
+            Quote::MutualFund(quote) => quote.nav.or(quote.close_price),
+            Quote::Option(quote) => quote.last_price,
+            Quote::Unknown(value) => value.get("lastPrice").and_then(Value::as_f64),
+        }
+    }
+
+    /// Best bid, for quotes which have one.
+    pub fn bid_price(&self) -> Option<f64> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote.bid_price,
+            Quote::Option(quote) => quote.bid_price,
+            Quote::Index(_) | Quote::MutualFund(_) => None,
+            Quote::Unknown(value) => value.get("bidPrice").and_then(Value::as_f64),
+        }
+    }
+
+    /// Best ask, for quotes which have one.
+    pub fn ask_price(&self) -> Option<f64> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote.ask_price,
+            Quote::Option(quote) => quote.ask_price,
+            Quote::Index(_) | Quote::MutualFund(_) => None,
+            Quote::Unknown(value) => value.get("askPrice").and_then(Value::as_f64),
+        }
+    }
+
+    /// Closing price of the previous trading day.
+    pub fn close_price(&self) -> Option<f64> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote.close_price,
+            Quote::Index(quote) => quote.close_price,
+            Quote::MutualFund(quote) => quote.close_price,
+            Quote::Option(quote) => quote.close_price,
+            Quote::Unknown(value) => value.get("closePrice").and_then(Value::as_f64),
+        }
+    }
+
+    /// Shares or contracts traded today.
+    pub fn total_volume(&self) -> Option<i64> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote.total_volume,
+            Quote::Index(quote) => quote.total_volume,
+            Quote::MutualFund(quote) => quote.total_volume,
+            Quote::Option(quote) => quote.total_volume,
+            Quote::Unknown(value) => value.get("totalVolume").and_then(Value::as_i64),
+        }
+    }
+
+    /// Highest price of the last 52 weeks, for quotes which have one.
+    pub fn fifty_two_week_high(&self) -> Option<f64> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote.fifty_two_week_high,
+            Quote::Index(quote) => quote.fifty_two_week_high,
+            Quote::MutualFund(quote) => quote.fifty_two_week_high,
+            Quote::Option(_) => None,
+            Quote::Unknown(value) => value.get("52WkHigh").and_then(Value::as_f64),
+        }
+    }
+
+    /// Lowest price of the last 52 weeks, for quotes which have one.
+    pub fn fifty_two_week_low(&self) -> Option<f64> {
+        match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote.fifty_two_week_low,
+            Quote::Index(quote) => quote.fifty_two_week_low,
+            Quote::MutualFund(quote) => quote.fifty_two_week_low,
+            Quote::Option(_) => None,
+            Quote::Unknown(value) => value.get("52WkLow").and_then(Value::as_f64),
+        }
+    }
+
+    /// Whether the quote is real-time.
+    ///
+    /// Quotes which don't report the `delayed` flag are only considered
+    /// real-time if the account is entitled to real-time quotes, so data is
+    /// never labelled real-time without the API saying so.
+    pub fn is_realtime(&self) -> bool {
+        let (delayed, realtime_entitled) = match self {
+            Quote::Equity(quote) | Quote::Etf(quote) => (quote.delayed, quote.realtime_entitled),
+            Quote::Index(quote) => (quote.delayed, quote.realtime_entitled),
+            Quote::MutualFund(quote) => (quote.delayed, quote.realtime_entitled),
+            Quote::Option(quote) => (quote.delayed, quote.realtime_entitled),
+            Quote::Unknown(value) => (
+                value.get("delayed").and_then(Value::as_bool),
+                value.get("realtimeEntitled").and_then(Value::as_bool),
+            ),
+        };
+
+        match delayed {
+            Some(delayed) => !delayed,
+            None => realtime_entitled == Some(true),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Quote {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+
+        match schema::parse_mode() {
+            Some(ParseMode::Strict) => Quote::try_from_value(value).map_err(de::Error::custom),
+            _ => Ok(Quote::from_value(value)),
+        }
+    }
+}
+
+impl Serialize for Quote {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        /// Borrowed copy of the typed variants, tagged the same way the API
+        /// tags them.
+        #[derive(Serialize)]
+        #[serde(tag = "assetType", rename_all = "SCREAMING_SNAKE_CASE")]
+        enum Tagged<'a> {
+            Equity(&'a EquityQuote),
+            Etf(&'a EquityQuote),
+            Index(&'a IndexQuote),
+            MutualFund(&'a MutualFundQuote),
+            Option(&'a OptionQuote),
+        }
+
+        match self {
+            Quote::Equity(quote) => Tagged::Equity(quote).serialize(serializer),
+            Quote::Etf(quote) => Tagged::Etf(quote).serialize(serializer),
+            Quote::Index(quote) => Tagged::Index(quote).serialize(serializer),
+            Quote::MutualFund(quote) => Tagged::MutualFund(quote).serialize(serializer),
+            Quote::Option(quote) => Tagged::Option(quote).serialize(serializer),
+            Quote::Unknown(value) => value.serialize(serializer),
+        }
+    }
+}
+
+/// Equity and ETF item in [`Quote`](enum.Quote.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EquityQuote {
+    #[serde(rename = "52WkHigh")]
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub fifty_two_week_high: Option<f64>,
+    #[serde(rename = "52WkLow")]
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub fifty_two_week_low: Option<f64>,
+    pub ask_id: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub ask_price: Option<f64>,
+    pub ask_size: Option<i64>,
+    pub bid_id: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub bid_price: Option<f64>,
+    pub bid_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub close_price: Option<f64>,
+    /// Whether the quote is delayed rather than real-time.
+    pub delayed: Option<bool>,
+    pub description: Option<String>,
+    pub digits: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub div_amount: Option<f64>,
+    pub div_date: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub div_yield: Option<f64>,
+    pub exchange: Option<String>,
+    pub exchange_name: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub high_price: Option<f64>,
+    pub last_id: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub last_price: Option<f64>,
+    pub last_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub low_price: Option<f64>,
+    pub marginable: Option<bool>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub mark: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub net_change: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub open_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub pe_ratio: Option<f64>,
+    pub quote_time_in_long: Option<i64>,
+    /// Whether the account is entitled to real-time quotes.
+    pub realtime_entitled: Option<bool>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub regular_market_last_price: Option<f64>,
+    pub regular_market_last_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub regular_market_net_change: Option<f64>,
+    pub regular_market_trade_time_in_long: Option<i64>,
+    pub security_status: Option<String>,
+    pub shortable: Option<bool>,
+    pub symbol: String,
+    pub total_volume: Option<i64>,
+    pub trade_time_in_long: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub volatility: Option<f64>,
+}
+
+/// Index item in [`Quote`](enum.Quote.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexQuote {
+    #[serde(rename = "52WkHigh")]
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub fifty_two_week_high: Option<f64>,
+    #[serde(rename = "52WkLow")]
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub fifty_two_week_low: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub close_price: Option<f64>,
+    /// Whether the quote is delayed rather than real-time.
+    pub delayed: Option<bool>,
+    pub description: Option<String>,
+    pub digits: Option<i64>,
+    pub exchange: Option<String>,
+    pub exchange_name: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub high_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub last_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub low_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub net_change: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub open_price: Option<f64>,
+    /// Whether the account is entitled to real-time quotes.
+    pub realtime_entitled: Option<bool>,
+    pub security_status: Option<String>,
+    pub symbol: String,
+    pub total_volume: Option<i64>,
+    pub trade_time_in_long: Option<i64>,
+}
+
+/// Mutual fund item in [`Quote`](enum.Quote.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MutualFundQuote {
+    #[serde(rename = "52WkHigh")]
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub fifty_two_week_high: Option<f64>,
+    #[serde(rename = "52WkLow")]
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub fifty_two_week_low: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub close_price: Option<f64>,
+    /// Whether the quote is delayed rather than real-time.
+    pub delayed: Option<bool>,
+    pub description: Option<String>,
+    pub digits: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub div_amount: Option<f64>,
+    pub div_date: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub div_yield: Option<f64>,
+    pub exchange: Option<String>,
+    pub exchange_name: Option<String>,
+    #[serde(rename = "nAV")]
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub nav: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub net_change: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub pe_ratio: Option<f64>,
+    /// Whether the account is entitled to real-time quotes.
+    pub realtime_entitled: Option<bool>,
+    pub security_status: Option<String>,
+    pub symbol: String,
+    pub total_volume: Option<i64>,
+    pub trade_time_in_long: Option<i64>,
+}
+
+/// Option item in [`Quote`](enum.Quote.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionQuote {
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub ask_price: Option<f64>,
+    pub ask_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub bid_price: Option<f64>,
+    pub bid_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub close_price: Option<f64>,
+    pub contract_type: Option<String>,
+    pub days_to_expiration: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub delta: Option<f64>,
+    /// Whether the quote is delayed rather than real-time.
+    pub delayed: Option<bool>,
+    pub description: Option<String>,
+    pub digits: Option<i64>,
+    pub exchange: Option<String>,
+    pub exchange_name: Option<String>,
+    pub expiration_day: Option<i64>,
+    pub expiration_month: Option<i64>,
+    pub expiration_year: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub gamma: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub high_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub last_price: Option<f64>,
+    pub last_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub low_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub mark: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub money_intrinsic_value: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub multiplier: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub net_change: Option<f64>,
+    pub open_interest: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub open_price: Option<f64>,
+    pub quote_time_in_long: Option<i64>,
+    /// Whether the account is entitled to real-time quotes.
+    pub realtime_entitled: Option<bool>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub rho: Option<f64>,
+    pub security_status: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub strike_price: Option<f64>,
+    pub symbol: String,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub theoretical_option_value: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub theta: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub time_value: Option<f64>,
+    pub total_volume: Option<i64>,
+    pub trade_time_in_long: Option<i64>,
+    pub underlying: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub underlying_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub vega: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub volatility: Option<f64>,
+}
+
+/// Response returned by the `get_option_chain()` method.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionChain {
+    pub call_exp_date_map: ExpirationMap,
+    pub days_to_expiration: Option<f64>,
+    pub interest_rate: Option<f64>,
+    pub interval: Option<f64>,
+    pub is_delayed: Option<bool>,
+    pub is_index: Option<bool>,
+    pub number_of_contracts: Option<i64>,
+    pub put_exp_date_map: ExpirationMap,
+    pub status: String,
+    pub strategy: Option<String>,
+    pub symbol: String,
+    pub underlying_price: Option<f64>,
+    pub volatility: Option<f64>,
+}
+
+impl OptionChain {
+    /// Every contract of the chain, calls first, each sorted by expiration
+    /// and then by strike.
+    pub fn contracts(&self) -> impl Iterator<Item = &OptionContract> {
+        self.call_exp_date_map
+            .values()
+            .chain(self.put_exp_date_map.values())
+            .flat_map(|strikes| strikes.values())
+            .flatten()
+    }
+}
+
+/// Contracts of an [`OptionChain`](struct.OptionChain.html), sorted by
+/// expiration and then by strike.
+pub type ExpirationMap = BTreeMap<ExpirationDate, BTreeMap<Decimal, Vec<OptionContract>>>;
+
+/// Expiration key of an [`ExpirationMap`](type.ExpirationMap.html).
+///
+/// The API writes these as `YYYY-MM-DD:DTE`, where `DTE` is the number of
+/// days to expiration.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ExpirationDate {
+    pub date: NaiveDate,
+    pub days_to_expiration: i64,
+}
+
+impl fmt::Display for ExpirationDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.date.format("%Y-%m-%d"), self.days_to_expiration)
+    }
+}
+
+impl FromStr for ExpirationDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid expiration date: {}", s);
+        let (date, days_to_expiration) = s.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())?,
+            days_to_expiration: days_to_expiration.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ExpirationDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for ExpirationDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Individual contract in an [`ExpirationMap`](type.ExpirationMap.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionContract {
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub ask: Option<f64>,
+    pub ask_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub bid: Option<f64>,
+    pub bid_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub close_price: Option<f64>,
+    pub days_to_expiration: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub delta: Option<f64>,
+    pub description: Option<String>,
+    pub exchange_name: Option<String>,
+    pub expiration_date: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub gamma: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub high_price: Option<f64>,
+    pub in_the_money: Option<bool>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub last: Option<f64>,
+    pub last_size: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub low_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub mark: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub multiplier: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub net_change: Option<f64>,
+    pub non_standard: Option<bool>,
+    pub open_interest: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub open_price: Option<f64>,
+    pub put_call: String,
+    pub quote_time_in_long: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub rho: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub strike_price: f64,
+    pub symbol: String,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub theoretical_option_value: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub theta: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub time_value: Option<f64>,
+    pub total_volume: Option<i64>,
+    pub trade_time_in_long: Option<i64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub vega: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub volatility: Option<f64>,
+}
+
+/// Iterator over the contracts of an option chain, returned by the
+/// `option_contracts()` method.
+pub struct OptionContracts {
+    receiver: Receiver<Result<OptionContract, ClientError>>,
+}
+
+impl OptionContracts {
+    pub(crate) fn new(receiver: Receiver<Result<OptionContract, ClientError>>) -> Self {
+        Self { receiver }
+    }
+}
+
+impl Iterator for OptionContracts {
+    type Item = Result<OptionContract, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Deserializes an option chain one contract at a time, handing each contract
+/// to a callback instead of building the whole chain in memory.
+///
+/// The callback returns `false` to stop reading early.
+pub(crate) struct OptionContractSeed<'a> {
+    on_contract: &'a mut dyn FnMut(OptionContract) -> bool,
+    level: ChainLevel,
+}
+
+/// Level of the chain being read by an
+/// [`OptionContractSeed`](struct.OptionContractSeed.html).
+#[derive(Clone, Copy)]
+enum ChainLevel {
+    Chain,
+    Expirations,
+    Strikes,
+    Contracts,
+}
+
+impl<'a> OptionContractSeed<'a> {
+    pub(crate) fn new(on_contract: &'a mut dyn FnMut(OptionContract) -> bool) -> Self {
+        Self {
+            on_contract,
+            level: ChainLevel::Chain,
+        }
+    }
+
+    fn nested(&mut self, level: ChainLevel) -> OptionContractSeed<'_> {
+        OptionContractSeed {
+            on_contract: &mut *self.on_contract,
+            level,
+        }
+    }
+}
+
+impl<'de> de::DeserializeSeed<'de> for OptionContractSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        match self.level {
+            ChainLevel::Contracts => deserializer.deserialize_seq(self),
+            _ => deserializer.deserialize_map(self),
+        }
+    }
+}
+
+impl<'de> de::Visitor<'de> for OptionContractSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an option chain")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match (self.level, key.as_str()) {
+                (ChainLevel::Chain, "callExpDateMap") | (ChainLevel::Chain, "putExpDateMap") => {
+                    map.next_value_seed(self.nested(ChainLevel::Expirations))?
+                }
+                (ChainLevel::Chain, _) => {
+                    map.next_value::<de::IgnoredAny>()?;
+                }
+                (ChainLevel::Expirations, _) => map.next_value_seed(self.nested(ChainLevel::Strikes))?,
+                _ => map.next_value_seed(self.nested(ChainLevel::Contracts))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(contract) = seq.next_element::<OptionContract>()? {
+            if !(self.on_contract)(contract) {
+                return Err(de::Error::custom("stopped reading option contracts"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Response returned by the `search_instruments()` method, keyed by symbol.
+pub type SearchInstrumentsResponse = HashMap<String, InstrumentInfo>;
+
+/// Individual instrument returned by the `search_instruments()` method.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstrumentInfo {
+    pub asset_type: String,
+    pub cusip: Option<String>,
+    pub description: Option<String>,
+    pub exchange: Option<String>,
+    /// Only present when searching with the `fundamental` projection.
+    pub fundamental: Option<Fundamental>,
+    pub symbol: String,
+}
+
+/// Fundamental data of an instrument, returned by the `get_fundamentals()`
+/// method.
+///
+/// The API leaves out fields it has no data for, such as for funds and
+/// recent listings, so missing fields are `0.0` or empty.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Fundamental {
+    pub beta: f64,
+    pub book_value_per_share: f64,
+    pub current_ratio: f64,
+    pub div_growth_rate3_year: f64,
+    pub dividend_amount: f64,
+    /// Ex-dividend date, formatted as `YYYY-MM-DD HH:MM:SS.sss`.
+    pub dividend_date: String,
+    pub dividend_pay_amount: f64,
+    /// Dividend payment date, formatted as `YYYY-MM-DD HH:MM:SS.sss`.
+    pub dividend_pay_date: String,
+    pub dividend_yield: f64,
+    pub eps_change: f64,
+    #[serde(rename = "epsChangePercentTTM")]
+    pub eps_change_percent_ttm: f64,
+    pub eps_change_year: f64,
+    #[serde(rename = "epsTTM")]
+    pub eps_ttm: f64,
+    #[serde(rename = "grossMarginMRQ")]
+    pub gross_margin_mrq: f64,
+    #[serde(rename = "grossMarginTTM")]
+    pub gross_margin_ttm: f64,
+    pub high52: f64,
+    pub interest_coverage: f64,
+    pub low52: f64,
+    pub lt_debt_to_equity: f64,
+    pub market_cap: f64,
+    pub market_cap_float: f64,
+    #[serde(rename = "netProfitMarginMRQ")]
+    pub net_profit_margin_mrq: f64,
+    #[serde(rename = "netProfitMarginTTM")]
+    pub net_profit_margin_ttm: f64,
+    #[serde(rename = "operatingMarginMRQ")]
+    pub operating_margin_mrq: f64,
+    #[serde(rename = "operatingMarginTTM")]
+    pub operating_margin_ttm: f64,
+    pub pb_ratio: f64,
+    pub pcf_ratio: f64,
+    pub pe_ratio: f64,
+    pub peg_ratio: f64,
+    pub pr_ratio: f64,
+    pub quick_ratio: f64,
+    pub return_on_assets: f64,
+    pub return_on_equity: f64,
+    pub return_on_investment: f64,
+    pub rev_change_in: f64,
+    #[serde(rename = "revChangeTTM")]
+    pub rev_change_ttm: f64,
+    pub rev_change_year: f64,
+    pub shares_outstanding: f64,
+    pub short_int_day_to_cover: f64,
+    pub short_int_to_float: f64,
+    pub symbol: String,
+    pub total_debt_to_capital: f64,
+    pub total_debt_to_equity: f64,
+    pub vol10_day_avg: f64,
+    pub vol1_day_avg: f64,
+    pub vol3_month_avg: f64,
+}
+
+impl Fundamental {
+    /// Dividend details pulled out of the fundamental data.
+    pub fn dividend_info(&self) -> DividendInfo {
+        DividendInfo {
+            symbol: self.symbol.clone(),
+            amount: self.dividend_amount,
+            dividend_yield: self.dividend_yield,
+            ex_date: parse_fundamental_date(&self.dividend_date),
+            pay_amount: self.dividend_pay_amount,
+            pay_date: parse_fundamental_date(&self.dividend_pay_date),
+            growth_rate_3_year: self.div_growth_rate3_year,
+        }
+    }
+
+    /// Earnings details pulled out of the fundamental data.
+    pub fn earnings_info(&self) -> EarningsInfo {
+        EarningsInfo {
+            symbol: self.symbol.clone(),
+            eps_ttm: self.eps_ttm,
+            eps_change: self.eps_change,
+            eps_change_percent_ttm: self.eps_change_percent_ttm,
+            eps_change_year: self.eps_change_year,
+            pe_ratio: self.pe_ratio,
+        }
+    }
+}
+
+/// Dividend details returned by the `get_dividend_info()` method.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct DividendInfo {
+    pub symbol: String,
+    /// Annual dividend amount.
+    pub amount: f64,
+    /// Dividend yield, in percent.
+    pub dividend_yield: f64,
+    /// Most recent ex-dividend date, if the symbol pays a dividend.
+    pub ex_date: Option<NaiveDate>,
+    /// Amount of the most recent dividend payment.
+    pub pay_amount: f64,
+    /// Date of the most recent dividend payment.
+    pub pay_date: Option<NaiveDate>,
+    /// Dividend growth rate over the last three years, in percent.
+    pub growth_rate_3_year: f64,
+}
+
+/// Earnings details from [`Fundamental::earnings_info()`](struct.Fundamental.html#method.earnings_info).
+///
+/// TDA does not report upcoming earnings dates, only trailing figures.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct EarningsInfo {
+    pub symbol: String,
+    /// Earnings per share over the trailing twelve months.
+    pub eps_ttm: f64,
+    pub eps_change: f64,
+    pub eps_change_percent_ttm: f64,
+    pub eps_change_year: f64,
+    pub pe_ratio: f64,
+}
+
+/// Parse the date part of a fundamental date string.
+///
+/// Returns `None` for the blank strings TDA sends for missing dates.
+fn parse_fundamental_date(date: &str) -> Option<NaiveDate> {
+    let date = date.trim();
+
+    NaiveDate::parse_from_str(date.get(..10).unwrap_or(date), "%Y-%m-%d").ok()
+}
+
+/// Response returned by the `get_preferences()` method.
+///
+/// Settings the API reports as `NONE` are `None`.
+///
+/// [API Documentation](https://developer.tdameritrade.com/account-access/apis/get/accounts/%7BaccountId%7D/preferences-0)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Preferences {
+    /// How long the API keeps a session, such as `TWO_HOURS`.
+    pub auth_token_timeout: Option<String>,
+    pub default_advanced_tool_launch: Option<String>,
+    #[serde(with = "none_string")]
+    pub default_equity_order_duration: Option<orders::OrderDuration>,
+    #[serde(with = "none_string")]
+    pub default_equity_order_leg_instruction: Option<orders::Instruction>,
+    #[serde(with = "none_string")]
+    pub default_equity_order_market_session: Option<orders::Session>,
+    #[serde(with = "none_string")]
+    pub default_equity_order_price_link_type: Option<orders::StopPriceLinkType>,
+    #[serde(with = "none_string")]
+    pub default_equity_order_type: Option<orders::OrderType>,
+    /// Zero if there is no default quantity.
+    pub default_equity_quantity: i64,
+    pub direct_equity_routing: bool,
+    pub direct_options_routing: bool,
+    #[serde(with = "none_string")]
+    pub equity_tax_lot_method: Option<orders::TaxLotMethod>,
+    /// Whether orders are placed without a confirmation step on the
+    /// website.
+    pub express_trading: bool,
+    #[serde(with = "none_string")]
+    pub mutual_fund_tax_lot_method: Option<orders::TaxLotMethod>,
+    #[serde(with = "none_string")]
+    pub option_tax_lot_method: Option<orders::TaxLotMethod>,
+}
+
+impl Preferences {
+    /// Defaults of new equity orders, as set in the preferences. Settings
+    /// without a preference keep the defaults of
+    /// [`OrderDefaults`](../orders/struct.OrderDefaults.html).
+    pub fn order_defaults(&self) -> OrderDefaults {
+        let defaults = OrderDefaults::default();
+
+        OrderDefaults {
+            instruction: self.default_equity_order_leg_instruction.unwrap_or(defaults.instruction),
+            order_type: self.default_equity_order_type.unwrap_or(defaults.order_type),
+            duration: self.default_equity_order_duration.unwrap_or(defaults.duration),
+            session: self.default_equity_order_market_session.unwrap_or(defaults.session),
+            quantity: Some(self.default_equity_quantity).filter(|quantity| *quantity > 0).map(|quantity| quantity as f64),
+            tax_lot_method: self.equity_tax_lot_method,
+        }
+    }
+}
+
+/// Enum settings which the API sends as `NONE` when unset.
+mod none_string {
+    use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    pub fn serialize<T: Serialize, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_str("NONE"),
+        }
+    }
+
+    pub fn deserialize<'de, T: DeserializeOwned, D: Deserializer<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(None),
+            Value::String(value) if value == "NONE" => Ok(None),
+            value => serde_json::from_value(value).map(Some).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Response returned by the `get_user_principals()` method.
+///
+/// The streamer fields are only present when requested with the
+/// `streamerSubscriptionKeys` and `streamerConnectionInfo` fields.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPrincipals {
+    pub access_level: Option<String>,
+    #[serde(default)]
+    pub accounts: Vec<PrincipalAccount>,
+    pub primary_account_id: Option<AccountId>,
+    pub streamer_info: Option<StreamerInfo>,
+    pub streamer_subscription_keys: Option<StreamerSubscriptionKeys>,
+    pub token_expiration_time: Option<String>,
+    pub user_id: String,
+}
+
+impl UserPrincipals {
+    /// Account the user logs in to the streamer with: the primary account,
+    /// or else the first one.
+    pub fn primary_account(&self) -> Option<&PrincipalAccount> {
+        self.accounts
+            .iter()
+            .find(|account| Some(&account.account_id) == self.primary_account_id.as_ref())
+            .or_else(|| self.accounts.first())
+    }
+
+    /// Linked account with the ID `account_id`, if any.
+    pub fn account(&self, account_id: &AccountId) -> Option<&PrincipalAccount> {
+        self.accounts.iter().find(|account| &account.account_id == account_id)
+    }
+
+    /// Keys to subscribe to the streamer with, empty unless requested with
+    /// the `streamerSubscriptionKeys` field.
+    pub fn subscription_keys(&self) -> Vec<&str> {
+        self.streamer_subscription_keys
+            .iter()
+            .flat_map(|keys| &keys.keys)
+            .map(|key| key.key.as_str())
+            .collect()
+    }
+}
+
+/// Account item in [`UserPrincipals`](struct.UserPrincipals.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrincipalAccount {
+    pub account_cd_domain_id: String,
+    pub account_id: AccountId,
+    /// Entitlements of the account on the streamer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acl: Option<String>,
+    /// What the account may trade. Only present when requested with the
+    /// `preferences` field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorizations: Option<AccountAuthorizations>,
+    pub company: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Name the user gave the account, such as `Roth IRA`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub segment: String,
+}
+
+impl PrincipalAccount {
+    /// Name to show for the account: its display name, or else its masked
+    /// ID.
+    pub fn name(&self) -> String {
+        self.display_name.clone().unwrap_or_else(|| self.account_id.masked())
+    }
+
+    /// Kinds of orders beyond whole shares the account supports, unknown if
+    /// its authorizations weren't requested.
+    pub fn order_capabilities(&self) -> OrderCapabilities {
+        self.authorizations.as_ref().map(AccountAuthorizations::order_capabilities).unwrap_or_default()
+    }
+}
+
+/// Authorizations item in [`PrincipalAccount`](struct.PrincipalAccount.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AccountAuthorizations {
+    pub advanced_margin: bool,
+    pub apex: bool,
+    /// Whether orders may be for fractions of a share. Not part of TDA's
+    /// documented authorizations, so `None` unless the API sends it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fractional_trading: Option<bool>,
+    pub level_two_quotes: bool,
+    pub margin_trading: bool,
+    /// Whether orders may be for a dollar amount rather than a quantity. Not
+    /// part of TDA's documented authorizations, so `None` unless the API
+    /// sends it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notional_trading: Option<bool>,
+    pub option_trading_level: Option<String>,
+    pub scottrade_account: bool,
+    pub stock_trading: bool,
+    pub streamer_access: bool,
+    pub streaming_news: bool,
+}
+
+impl AccountAuthorizations {
+    pub fn order_capabilities(&self) -> OrderCapabilities {
+        OrderCapabilities {
+            fractional: self.fractional_trading,
+            notional: self.notional_trading,
+        }
+    }
+}
+
+/// Streamer connection item in [`UserPrincipals`](struct.UserPrincipals.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamerInfo {
+    pub access_level: String,
+    pub acl: String,
+    pub app_id: String,
+    pub streamer_binary_url: Option<String>,
+    pub streamer_socket_url: String,
+    pub token: String,
+    /// Timestamp of the streamer token, formatted as
+    /// `yyyy-MM-dd'T'HH:mm:ssZ`.
+    pub token_timestamp: String,
+    pub user_group: String,
+}
+
+/// Streamer subscription keys item in [`UserPrincipals`](struct.UserPrincipals.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StreamerSubscriptionKeys {
+    pub keys: Vec<StreamerSubscriptionKey>,
+}
+
+/// Individual key in [`StreamerSubscriptionKeys`](struct.StreamerSubscriptionKeys.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StreamerSubscriptionKey {
+    pub key: String,
+}
+
 /// Individual response item returned by the `get_account()` and
 /// `get_accounts()` methods.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Account {
     pub securities_account: SecuritiesAccount,
 }
 
+impl Account {
+    /// ID of the account.
+    pub fn account_id(&self) -> &AccountId {
+        self.securities_account.account_id()
+    }
+
+    /// ID of the account with all but the last four characters masked, for
+    /// logs and screenshots.
+    pub fn masked_id(&self) -> String {
+        self.account_id().masked()
+    }
+
+    /// Kind of the account, for the restrictions which come with it.
+    pub fn kind(&self) -> AccountKind {
+        self.securities_account.kind()
+    }
+
+    /// Positions of the account, empty unless it was fetched with the
+    /// `positions` field.
+    pub fn positions(&self) -> &[Position] {
+        self.securities_account.positions()
+    }
+}
+
 /// Securities Account item in [`Account`](struct.Account.html)
-#[derive(Clone, Debug, Deserialize, Serialize)]
+///
+/// Cash, margin, and retirement accounts all parse to `MarginAccount`.
+/// Retirement accounts are told apart by their `iraType`, and their IRA
+/// sections parse leniently: a malformed section is `None`, logged, rather
+/// than failing the account, unless parsed in
+/// [`ParseMode::Strict`](../schema/enum.ParseMode.html#variant.Strict).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(untagged)]
 pub enum SecuritiesAccount {
     #[serde(rename_all = "camelCase")]
     MarginAccount {
         r#type: String,
-        account_id: String,
+        #[serde(alias = "accountNumber")]
+        account_id: AccountId,
+        /// Only present for retirement accounts.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ira_type: Option<IraType>,
+        #[serde(default, deserialize_with = "lenient_section", skip_serializing_if = "Option::is_none")]
+        contribution_info: Option<ContributionInfo>,
+        #[serde(default, deserialize_with = "lenient_section", skip_serializing_if = "Option::is_none")]
+        withdrawal_restrictions: Option<WithdrawalRestrictions>,
+        #[serde(default)]
         round_trips: usize,
+        #[serde(default)]
         is_day_trader: bool,
+        #[serde(default)]
         is_closing_only_restricted: bool,
+        #[serde(default)]
         initial_balances: InitialBalances,
+        #[serde(default)]
         current_balances: CurrentBalances,
+        #[serde(default)]
         projected_balances: ProjectedBalances,
+        /// Only present when requested with the `positions` field.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        positions: Option<Vec<Position>>,
     },
 }
 
-/// Initial Balances item in [`SecuritiesAccount`](enum.SecuritiesAccount.html)
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Parse an optional section of an account, logging and skipping it if it's
+/// malformed outside of strict parse mode.
+fn lenient_section<'de, T: de::DeserializeOwned, D: Deserializer<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    match serde_json::from_value(value) {
+        Ok(section) => Ok(Some(section)),
+        Err(error) if schema::parse_mode() == Some(ParseMode::Strict) => Err(de::Error::custom(error)),
+        Err(error) => {
+            log::warn!("Skipping malformed account section: {}", error);
+
+            Ok(None)
+        }
+    }
+}
+
+impl SecuritiesAccount {
+    /// ID of the account.
+    pub fn account_id(&self) -> &AccountId {
+        match self {
+            SecuritiesAccount::MarginAccount { account_id, .. } => account_id,
+        }
+    }
+
+    /// Type of the account as reported by the API, `CASH` or `MARGIN`.
+    pub fn account_type(&self) -> &str {
+        match self {
+            SecuritiesAccount::MarginAccount { r#type, .. } => r#type,
+        }
+    }
+
+    /// Kind of the account, for the restrictions which come with it.
+    pub fn kind(&self) -> AccountKind {
+        match self {
+            SecuritiesAccount::MarginAccount { ira_type: Some(ira_type), .. } => AccountKind::Retirement(*ira_type),
+            SecuritiesAccount::MarginAccount { r#type, .. } if r#type == "CASH" => AccountKind::Cash,
+            SecuritiesAccount::MarginAccount { .. } => AccountKind::Margin,
+        }
+    }
+
+    /// Contributions to the account, if it's a retirement account.
+    pub fn contribution_info(&self) -> Option<&ContributionInfo> {
+        match self {
+            SecuritiesAccount::MarginAccount { contribution_info, .. } => contribution_info.as_ref(),
+        }
+    }
+
+    /// Withdrawal restrictions of the account, if it's a retirement account.
+    pub fn withdrawal_restrictions(&self) -> Option<&WithdrawalRestrictions> {
+        match self {
+            SecuritiesAccount::MarginAccount { withdrawal_restrictions, .. } => withdrawal_restrictions.as_ref(),
+        }
+    }
+
+    /// Whether the account is flagged as a pattern day trader.
+    pub fn is_day_trader(&self) -> bool {
+        match self {
+            SecuritiesAccount::MarginAccount { is_day_trader, .. } => *is_day_trader,
+        }
+    }
+
+    pub fn initial_balances(&self) -> &InitialBalances {
+        match self {
+            SecuritiesAccount::MarginAccount { initial_balances, .. } => initial_balances,
+        }
+    }
+
+    pub fn current_balances(&self) -> &CurrentBalances {
+        match self {
+            SecuritiesAccount::MarginAccount { current_balances, .. } => current_balances,
+        }
+    }
+
+    pub fn projected_balances(&self) -> &ProjectedBalances {
+        match self {
+            SecuritiesAccount::MarginAccount { projected_balances, .. } => projected_balances,
+        }
+    }
+
+    /// Positions of the account, empty unless it was fetched with the
+    /// `positions` field.
+    pub fn positions(&self) -> &[Position] {
+        match self {
+            SecuritiesAccount::MarginAccount { positions, .. } => positions.as_deref().unwrap_or_default(),
+        }
+    }
+}
+
+/// Kind of an account, returned by [`Account::kind()`](struct.Account.html#method.kind).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccountKind {
+    Cash,
+    Margin,
+    /// Individual retirement account, with or without limited margin.
+    Retirement(IraType),
+}
+
+impl AccountKind {
+    pub fn is_retirement(&self) -> bool {
+        matches!(self, AccountKind::Retirement(_))
+    }
+
+    /// Whether the account can sell short. Retirement accounts can't, even
+    /// with limited margin.
+    pub fn can_short(&self) -> bool {
+        matches!(self, AccountKind::Margin)
+    }
+}
+
+/// Kind of individual retirement account.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IraType {
+    Traditional,
+    Roth,
+    Rollover,
+    Sep,
+    Simple,
+    /// Kind without a variant of its own, such as an inherited IRA.
+    #[serde(other)]
+    Other,
+}
+
+/// Contributions to a retirement [`SecuritiesAccount`](enum.SecuritiesAccount.html).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ContributionInfo {
+    pub tax_year: Option<i32>,
+    #[serde(deserialize_with = "flexible_option_f64")]
+    pub current_year_contributions: Option<f64>,
+    #[serde(deserialize_with = "flexible_option_f64")]
+    pub prior_year_contributions: Option<f64>,
+    /// Most that can be contributed for the tax year.
+    #[serde(deserialize_with = "flexible_option_f64")]
+    pub contribution_limit: Option<f64>,
+}
+
+impl ContributionInfo {
+    /// What can still be contributed for the tax year.
+    pub fn remaining(&self) -> Option<f64> {
+        Some((self.contribution_limit? - self.current_year_contributions.unwrap_or_default()).max(0.0))
+    }
+}
+
+/// Withdrawal restrictions of a retirement [`SecuritiesAccount`](enum.SecuritiesAccount.html).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WithdrawalRestrictions {
+    /// Date withdrawals stop being charged the early withdrawal penalty.
+    pub penalty_free_date: Option<NaiveDate>,
+    /// Distribution which must be taken this year.
+    #[serde(deserialize_with = "flexible_option_f64")]
+    pub required_minimum_distribution: Option<f64>,
+    #[serde(deserialize_with = "flexible_option_f64")]
+    pub distributions_year_to_date: Option<f64>,
+    /// Whether taxes must be withheld from withdrawals.
+    pub withholding_required: Option<bool>,
+}
+
+impl WithdrawalRestrictions {
+    /// Part of the required minimum distribution not yet taken this year.
+    pub fn remaining_required_distribution(&self) -> Option<f64> {
+        Some((self.required_minimum_distribution? - self.distributions_year_to_date.unwrap_or_default()).max(0.0))
+    }
+}
+
+/// Position item in [`SecuritiesAccount`](enum.SecuritiesAccount.html)
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
+pub struct Position {
+    #[serde(deserialize_with = "flexible_f64")]
+    pub average_price: f64,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub current_day_profit_loss: f64,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub current_day_profit_loss_percentage: f64,
+    pub instrument: Instrument,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub long_quantity: f64,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub market_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub settled_long_quantity: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub settled_short_quantity: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub short_quantity: f64,
+}
+
+impl Position {
+    /// Net quantity held, negative for short positions.
+    pub fn quantity(&self) -> f64 {
+        self.long_quantity - self.short_quantity
+    }
+}
+
+/// Initial Balances item in [`SecuritiesAccount`](enum.SecuritiesAccount.html)
+///
+/// Which balances are reported depends on the kind of account: cash accounts
+/// have no margin fields, and IRAs no short ones. Missing balances are `0.0`
+/// or `None`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct InitialBalances {
+    #[serde(deserialize_with = "flexible_f64")]
     pub account_value: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub accrued_interest: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub available_funds_non_marginable_trade: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub bond_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub buying_power: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub cash_available_for_trading: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_available_for_withdrawal: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub cash_balance: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_debit_call_value: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub cash_receipts: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub day_trading_buying_power: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub day_trading_buying_power_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub day_trading_equity_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub equity: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub equity_percentage: Option<f64>,
     pub is_in_call: bool,
+    #[serde(deserialize_with = "flexible_f64")]
     pub liquidation_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub long_margin_value: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub long_option_market_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub long_stock_value: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub maintenance_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub maintenance_requirement: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub margin: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub margin_balance: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub margin_equity: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub money_market_fund: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub mutual_fund_value: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub pending_deposits: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub reg_t_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub short_balance: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub short_margin_value: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub short_option_market_value: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub short_stock_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub total_cash: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub unsettled_cash: Option<f64>,
 }
 
 /// Current Balances item in [`SecuritiesAccount`](enum.SecuritiesAccount.html)
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
+///
+/// Which balances are reported depends on the kind of account: cash accounts
+/// have no margin fields, and IRAs no short ones. Missing balances are `0.0`
+/// or `None`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct CurrentBalances {
+    #[serde(deserialize_with = "flexible_f64")]
     pub accrued_interest: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub available_funds: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub available_funds_non_marginable_trade: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub bond_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub buying_power: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub buying_power_non_marginable_trade: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_available_for_trading: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_available_for_withdrawal: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub cash_balance: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_debit_call_value: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub cash_receipts: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub day_trading_buying_power: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub equity: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub equity_percentage: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub liquidation_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub long_margin_value: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub long_market_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub long_non_marginable_market_value: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub long_option_market_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub maintenance_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub maintenance_requirement: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub margin_balance: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub money_market_fund: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub mutual_fund_value: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub pending_deposits: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub reg_t_call: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub savings: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub short_balance: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub short_margin_value: Option<f64>,
+    #[serde(deserialize_with = "flexible_f64")]
     pub short_market_value: f64,
+    #[serde(deserialize_with = "flexible_f64")]
     pub short_option_market_value: f64,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub sma: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub total_cash: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub unsettled_cash: Option<f64>,
 }
 
 /// Projected Balances item in [`SecuritiesAccount`](enum.SecuritiesAccount.html)
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
+///
+/// Which balances are reported depends on the kind of account: cash accounts
+/// have no margin fields, and IRAs no short ones. Missing balances are `0.0`
+/// or `None`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct ProjectedBalances {
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub available_funds: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub available_funds_non_marginable_trade: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub buying_power: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_available_for_trading: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub cash_available_for_withdrawal: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub day_trading_buying_power: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub day_trading_buying_power_call: Option<f64>,
     pub is_in_call: Option<bool>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub maintenance_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub reg_t_call: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
     pub stock_buying_power: Option<f64>,
 }
+
+/// Response returned by the `get_transactions()` method.
+pub type GetTransactionsResponse = Vec<Transaction>;
+
+/// Transaction of an account, such as a trade, dividend, or transfer.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    pub cash_balance_effect_flag: Option<bool>,
+    pub description: Option<String>,
+    /// Fees charged, keyed by kind, such as `commission` or `secFee`.
+    #[serde(default)]
+    pub fees: BTreeMap<String, f64>,
+    #[serde(deserialize_with = "flexible_f64")]
+    pub net_amount: f64,
+    pub order_date: Option<TdaDateTime>,
+    pub order_id: Option<String>,
+    pub settlement_date: Option<NaiveDate>,
+    pub sub_account: Option<String>,
+    pub transaction_date: Option<TdaDateTime>,
+    pub transaction_id: i64,
+    pub transaction_item: Option<TransactionItem>,
+    /// Code of the kind of transaction, such as `BY` for a purchase or `DV`
+    /// for a dividend.
+    pub transaction_sub_type: Option<String>,
+    /// Such as `TRADE`, `DIVIDEND_OR_INTEREST`, or `ELECTRONIC_FUND`.
+    #[serde(rename = "type")]
+    pub transaction_type: String,
+}
+
+impl Transaction {
+    /// What the transaction is. See
+    /// [`transactions::classify()`](../transactions/fn.classify.html).
+    pub fn kind(&self) -> TransactionKind {
+        classify(self).kind
+    }
+
+    /// Symbol of the instrument involved, if any.
+    pub fn symbol(&self) -> Option<&str> {
+        self.transaction_item.as_ref()?.instrument.as_ref()?.symbol.as_deref()
+    }
+
+    /// Fees of every kind added up.
+    pub fn total_fees(&self) -> f64 {
+        self.fees.values().sum()
+    }
+}
+
+/// Item of a [`Transaction`](struct.Transaction.html), for transactions which
+/// involve an instrument.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionItem {
+    pub account_id: Option<AccountId>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub amount: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub cost: Option<f64>,
+    pub instruction: Option<String>,
+    pub instrument: Option<TransactionInstrument>,
+    pub position_effect: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64")]
+    pub price: Option<f64>,
+}
+
+/// Instrument of a [`TransactionItem`](struct.TransactionItem.html).
+///
+/// Kept apart from [`Instrument`](../instruments/enum.Instrument.html) as
+/// transactions leave out fields, such as the symbol of cash movements.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionInstrument {
+    pub asset_type: Option<String>,
+    pub cusip: Option<String>,
+    pub description: Option<String>,
+    pub option_expiration_date: Option<TdaDateTime>,
+    pub put_call: Option<String>,
+    pub symbol: Option<String>,
+    pub underlying_symbol: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+
+    /// Parse a golden file, serialize it back, and make sure nothing was lost.
+    fn assert_round_trip<T>(golden: &str) -> T
+    where
+        T: DeserializeOwned + Serialize + PartialEq + std::fmt::Debug,
+    {
+        let parsed: T = serde_json::from_str(golden).unwrap();
+        let serialized = serde_json::to_string(&parsed).unwrap();
+        let reparsed: T = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(parsed, reparsed);
+        assert_eq!(
+            serde_json::from_str::<Value>(golden).unwrap(),
+            serde_json::from_str::<Value>(&serialized).unwrap(),
+        );
+
+        parsed
+    }
+
+    #[test]
+    fn transactions_round_trip() {
+        let transactions: GetTransactionsResponse = assert_round_trip(include_str!("../tests/fixtures/transactions.json"));
+        let item = transactions[0].transaction_item.as_ref().unwrap();
+
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[0].transaction_sub_type.as_deref(), Some("BY"));
+        assert_eq!(item.instrument.as_ref().unwrap().symbol.as_deref(), Some("AAPL"));
+        assert_eq!(transactions[1].net_amount, 20.5);
+    }
+
+    #[test]
+    fn access_token_round_trip() {
+        let token: AccessTokenResponse = assert_round_trip(include_str!("../tests/fixtures/access_token.json"));
+
+        assert_eq!(token.expires_in, 1800);
+    }
+
+    #[test]
+    fn access_token_with_refresh_token_round_trip() {
+        let response: AccessTokenResponse = assert_round_trip(include_str!("../tests/fixtures/access_token_offline.json"));
+
+        assert_eq!(response.refresh_token_expires_in, Some(7_776_000));
+    }
+
+    #[test]
+    fn accounts_round_trip() {
+        let accounts: Vec<Account> = assert_round_trip(include_str!("../tests/fixtures/accounts.json"));
+
+        assert_eq!(accounts.len(), 1);
+    }
+
+    #[test]
+    fn accounts_by_type() {
+        let accounts: Vec<Account> = serde_json::from_str(include_str!("../tests/fixtures/accounts_by_type.json")).unwrap();
+        let [cash, ira, day_trader] = [&accounts[0].securities_account, &accounts[1].securities_account, &accounts[2].securities_account];
+
+        assert_eq!(cash.kind(), AccountKind::Cash);
+        assert_eq!(cash.current_balances().buying_power, None);
+        assert_eq!(cash.current_balances().cash_available_for_trading, Some(1200.0));
+        assert_eq!(cash.projected_balances().is_in_call, None);
+
+        assert_eq!(ira.kind(), AccountKind::Retirement(IraType::Roth));
+        assert!(!ira.kind().can_short());
+        assert_eq!(ira.current_balances().short_market_value, 0.0);
+        assert_eq!(ira.current_balances().sma, None);
+        assert_eq!(ira.current_balances().liquidation_value, 48000.0);
+
+        assert_eq!(ira.contribution_info().unwrap().remaining(), Some(1500.0));
+        assert_eq!(ira.withdrawal_restrictions().unwrap().penalty_free_date, NaiveDate::from_ymd_opt(2041, 3, 15));
+        assert_eq!(ira.withdrawal_restrictions().unwrap().remaining_required_distribution(), None);
+        assert_eq!(cash.contribution_info(), None);
+
+        assert_eq!(day_trader.kind(), AccountKind::Margin);
+        assert!(day_trader.is_day_trader());
+        assert_eq!(day_trader.projected_balances().day_trading_buying_power_call, Some(4500.0));
+    }
+
+    #[test]
+    fn malformed_ira_sections() {
+        let mut json: Value = serde_json::from_str(include_str!("../tests/fixtures/accounts_by_type.json")).unwrap();
+        json[1]["securitiesAccount"]["contributionInfo"] = serde_json::json!({ "taxYear": "last year" });
+
+        let accounts: Vec<Account> = serde_json::from_value(json.clone()).unwrap();
+        let ira = &accounts[1].securities_account;
+
+        assert_eq!(ira.kind(), AccountKind::Retirement(IraType::Roth));
+        assert_eq!(ira.contribution_info(), None);
+        assert!(ira.withdrawal_restrictions().is_some());
+
+        let strict = schema::with_parse_mode(ParseMode::Strict, || serde_json::from_value::<Vec<Account>>(json));
+
+        assert!(strict.is_err());
+    }
+
+    #[test]
+    fn instruments_round_trip() {
+        let instruments: SearchInstrumentsResponse = assert_round_trip(include_str!("../tests/fixtures/instruments_fundamental.json"));
+        let fundamental = instruments["AAPL"].fundamental.as_ref().unwrap();
+        let dividend = fundamental.dividend_info();
+
+        assert_eq!(dividend.ex_date, NaiveDate::from_ymd_opt(2020, 5, 8));
+        assert_eq!(dividend.pay_date, None);
+        assert_eq!(fundamental.earnings_info().eps_ttm, 12.728);
+    }
+
+    #[test]
+    fn partial_fundamentals() {
+        let fundamental: Fundamental = serde_json::from_str(r#"{"symbol": "SPY", "peRatio": 21.5, "dividendDate": "2020-06-19 00:00:00.000"}"#).unwrap();
+
+        assert_eq!(fundamental.pe_ratio, 21.5);
+        assert_eq!(fundamental.eps_ttm, 0.0);
+        assert_eq!(fundamental.dividend_info().ex_date, NaiveDate::from_ymd_opt(2020, 6, 19));
+        assert_eq!(fundamental.dividend_info().pay_date, None);
+    }
+
+    #[test]
+    fn movers_round_trip() {
+        let movers: Vec<Mover> = assert_round_trip(include_str!("../tests/fixtures/movers.json"));
+
+        assert_eq!(movers[0].symbol, "BA");
+    }
+
+    #[test]
+    fn price_history_columns() {
+        let response: GetPriceHistoryResponse = serde_json::from_str(include_str!("../tests/fixtures/price_history.json")).unwrap();
+        let candles = response.candles.clone();
+        let columns = response.into_columns();
+
+        assert_eq!(columns.len(), candles.len());
+        assert_eq!(columns.close[0], candles[0].close);
+        assert_eq!(columns.volume[columns.len() - 1], candles[candles.len() - 1].volume);
+
+        #[cfg(feature = "ndarray")]
+        assert_eq!(columns.to_ohlcv()[[0, 3]], candles[0].close);
+    }
+
+    #[test]
+    fn price_history_round_trip() {
+        let history: GetPriceHistoryResponse = assert_round_trip(include_str!("../tests/fixtures/price_history.json"));
+
+        assert_eq!(history.candles.len(), 2);
+    }
+
+    #[test]
+    fn quoted_numbers() {
+        let quote = Quote::from_value(serde_json::json!({
+            "assetType": "EQUITY",
+            "symbol": "AAPL",
+            "bidPrice": "318.5",
+            "askPrice": 318.75,
+            "lastPrice": "1,318.6",
+        }));
+
+        assert_eq!(quote.bid_price(), Some(318.5));
+        assert_eq!(quote.last_price(), Some(1318.6));
+
+        let candle: Candle = serde_json::from_str(r#"{"open": "1", "high": "2.5", "low": 0.5, "close": "2", "volume": 10, "datetime": 0}"#).unwrap();
+
+        assert_eq!(candle.high, 2.5);
+
+        let candle: Candle = serde_json::from_str(r#"{"open": 1, "high": 2, "low": 1, "close": 2, "volume": 10, "datetime": 1588600800}"#).unwrap();
+
+        assert_eq!(candle.datetime, 1588600800000);
+
+        let candle: Candle = serde_json::from_str(r#"{"open": 1, "high": 2, "low": 1, "close": 2, "volume": 10, "datetime": -86400}"#).unwrap();
+
+        assert_eq!(candle.datetime, -86_400_000);
+    }
+
+    #[test]
+    fn user_principals_round_trip() {
+        let principals: UserPrincipals = assert_round_trip(include_str!("../tests/fixtures/user_principals.json"));
+
+        assert_eq!(principals.accounts[0].account_id.as_str(), "123456789");
+        assert_eq!(principals.primary_account().unwrap().name(), "Individual");
+        assert_eq!(principals.account(&AccountId::from("987654321")).unwrap().name(), "*****4321");
+        assert_eq!(principals.subscription_keys(), ["SUBSCRIPTION_KEY"]);
+    }
+
+    #[test]
+    fn preferences_round_trip() {
+        let preferences: Preferences = assert_round_trip(include_str!("../tests/fixtures/preferences.json"));
+        let defaults = preferences.order_defaults();
+
+        assert_eq!(preferences.default_equity_order_leg_instruction, None);
+        assert_eq!(preferences.mutual_fund_tax_lot_method, Some(orders::TaxLotMethod::MinimumTax));
+        assert!(preferences.express_trading);
+        assert_eq!(defaults.instruction, orders::Instruction::Buy);
+        assert_eq!(defaults.order_type, orders::OrderType::Limit);
+        assert_eq!(defaults.duration, orders::OrderDuration::GoodTillCancel);
+        assert_eq!(defaults.quantity, Some(100.0));
+        assert_eq!(defaults.tax_lot_method, Some(orders::TaxLotMethod::HighCost));
+    }
+
+    #[test]
+    fn option_chain_round_trip() {
+        let chain: OptionChain = assert_round_trip(include_str!("../tests/fixtures/option_chain.json"));
+        let expirations: Vec<String> = chain.call_exp_date_map.keys().map(ToString::to_string).collect();
+
+        assert_eq!(expirations, vec!["2021-06-18:5", "2021-07-16:33"]);
+
+        let strikes: Vec<&Decimal> = chain.call_exp_date_map.values().next().unwrap().keys().collect();
+
+        assert_eq!(strikes, vec![&Decimal::new(95, 0), &Decimal::new(1225, 1)]);
+    }
+
+    #[test]
+    fn option_contract_seed() {
+        use serde::de::DeserializeSeed;
+
+        let mut symbols = Vec::new();
+        let mut on_contract = |contract: OptionContract| {
+            symbols.push(contract.symbol);
+
+            true
+        };
+        let deserializer = &mut serde_json::Deserializer::from_str(include_str!("../tests/fixtures/option_chain.json"));
+
+        OptionContractSeed::new(&mut on_contract).deserialize(deserializer).unwrap();
+
+        assert_eq!(symbols, vec!["AAPL_071621C122.5", "AAPL_061821C122.5", "AAPL_061821C95"]);
+    }
+
+    #[test]
+    fn option_contract_nan_greeks() {
+        let contract: OptionContract = serde_json::from_str(r#"{"putCall":"CALL","symbol":"AAPL_061821C120","strikePrice":120.0,"delta":"NaN","volatility":-999.0}"#).unwrap();
+
+        assert_eq!(contract.delta, None);
+        assert_eq!(contract.volatility, Some(-999.0));
+    }
+
+    #[test]
+    fn market_hours_round_trip() {
+        let response: GetMarketHoursResponse = assert_round_trip(include_str!("../tests/fixtures/market_hours.json"));
+        let hours = &response["equity"]["EQ"];
+
+        // 2020-05-05 09:30 and 16:00 EDT.
+        assert_eq!(hours.session_at(1_588_685_400_000 - 1), Some(Session::Pre));
+        assert_eq!(hours.session_at(1_588_685_400_000), Some(Session::Regular));
+        assert_eq!(hours.session_at(1_588_708_800_000), Some(Session::Post));
+        assert_eq!(hours.session_at(0), None);
+    }
+
+    #[test]
+    fn quotes_round_trip() {
+        let quotes: GetQuotesResponse = assert_round_trip(include_str!("../tests/fixtures/quotes.json"));
+
+        assert!(matches!(quotes["AAPL"], Quote::Equity(_)));
+        assert!(matches!(quotes["$SPX.X"], Quote::Index(_)));
+        assert!(matches!(quotes["EUR/USD"], Quote::Unknown(_)));
+        assert_eq!(quotes["EUR/USD"].symbol(), Some("EUR/USD"));
+        assert!(quotes["AAPL"].is_realtime());
+        assert!(!quotes["$SPX.X"].is_realtime());
+        assert!(!quotes["EUR/USD"].is_realtime());
+    }
+
+    #[test]
+    fn partial_quotes() {
+        let values = serde_json::from_str(
+            r#"{
+                "AAPL": {"assetType": "EQUITY", "symbol": "AAPL", "lastPrice": 318.73},
+                "MSFT": {"assetType": "EQUITY", "symbol": 42},
+                "BAD!": {"error": "Invalid symbol"}
+            }"#,
+        )
+        .unwrap();
+        let result = QuotesResult::from_values(&["aapl", "MSFT", "BAD!", "NOPE"], values);
+
+        assert_eq!(result.found["AAPL"].last_price(), Some(318.73));
+        assert_eq!(result.not_found, ["NOPE"]);
+        assert_eq!(result.failed["BAD!"], "Invalid symbol");
+        assert!(result.failed.contains_key("MSFT"));
+        assert!(!result.is_complete());
+    }
+
+    #[test]
+    fn quote_falls_back_to_unknown() {
+        let quote: Quote = serde_json::from_str(r#"{"assetType": "EQUITY", "symbol": 42}"#).unwrap();
+
+        assert!(matches!(quote, Quote::Unknown(_)));
+        assert_eq!(quote.asset_type(), Some("EQUITY"));
+    }
+
+    #[test]
+    fn schwab_accounts() {
+        let tda: Vec<Account> = serde_json::from_str(include_str!("../tests/fixtures/accounts.json")).unwrap();
+        let schwab: Vec<Account> = serde_json::from_str(include_str!("../tests/fixtures/schwab_accounts.json")).unwrap();
+
+        assert_eq!(tda, schwab);
+    }
+
+    #[test]
+    fn schwab_movers() {
+        let movers: Vec<Mover> = serde_json::from_str(include_str!("../tests/fixtures/schwab_movers.json")).unwrap();
+
+        assert_eq!(movers[0].last, 141.11);
+        assert_eq!(movers[0].total_volume, 29618142);
+    }
+}
@@ -0,0 +1,597 @@
+//! Scans of large symbol universes.
+//!
+//! A [`Scanner`](struct.Scanner.html) fetches the data a scan needs for
+//! every symbol, with a bounded number of requests in flight, and runs a
+//! function over each symbol's [`SymbolData`](struct.SymbolData.html).
+//! Symbols the function returns a value for are hits:
+//!
+//! ```no_run
+//! use tda_sdk::{scanner::Scanner, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let mut scanner = Scanner::new();
+//!
+//! scanner.set_concurrency(8).set_fundamentals(true).set_progress(|progress| {
+//!     eprintln!("{}/{} {}", progress.done, progress.total, progress.symbol);
+//! });
+//!
+//! let report = scanner.rank(&client, &["AAPL", "MSFT", "KO", "T"], |data| {
+//!     let fundamental = data.fundamental.as_ref()?;
+//!
+//!     (fundamental.pe_ratio > 0.0 && fundamental.pe_ratio < 20.0).then(|| fundamental.dividend_yield)
+//! });
+//!
+//! for hit in &report.hits {
+//!     println!("{}: {:.2}%", hit.symbol, hit.value);
+//! }
+//! ```
+//!
+//! [`gaps()`](struct.Scanner.html#method.gaps) is a ready-made scan for
+//! stocks gapping before the open:
+//!
+//! ```no_run
+//! use tda_sdk::{scanner::{GapCriteria, Scanner}, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let report = Scanner::new().gaps(&client, &["AAPL", "MSFT", "TSLA"], &GapCriteria::default());
+//!
+//! for gap in &report.hits {
+//!     println!("{} {:+.1}% at {}", gap.symbol, gap.value.gap * 100.0, gap.value.price);
+//! }
+//! ```
+//!
+//! Quotes are fetched in batches before the scan. Fundamentals and price
+//! history change slowly, so they are kept in a
+//! [`ScanCache`](struct.ScanCache.html), which can be shared by scanners to
+//! scan the same universe again without fetching them again.
+
+use crate::{
+    correlation,
+    enriched::{self, QuoteEnriched},
+    params::GetPriceHistoryParams,
+    responses::{Candle, Fundamental, Quote},
+    time::MARKET_TIMEZONE,
+    Client, ClientError,
+};
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Requests in flight at once, by default.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Symbols per quotes request.
+pub const QUOTE_BATCH_SIZE: usize = 200;
+
+/// How long fundamentals and price history are cached, by default.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Data of a symbol passed to the scan function. Only the data the scanner
+/// was set to fetch is filled in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SymbolData {
+    pub symbol: String,
+    pub quote: Option<Quote>,
+    pub fundamental: Option<Fundamental>,
+    pub candles: Vec<Candle>,
+}
+
+/// Symbol the scan function returned a value for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScanHit<T> {
+    pub symbol: String,
+    pub value: T,
+}
+
+/// Result of a scan.
+#[derive(Debug)]
+pub struct ScanReport<T> {
+    /// Hits, in the order of the universe, or by score for
+    /// [`rank()`](struct.Scanner.html#method.rank).
+    pub hits: Vec<ScanHit<T>>,
+    /// Symbols whose data couldn't be fetched, with the error. Symbols of a
+    /// failed quotes batch share its error.
+    pub failed: Vec<(String, Arc<ClientError>)>,
+}
+
+/// Progress of a scan, reported after each symbol.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanProgress<'a> {
+    pub symbol: &'a str,
+    /// Symbols done, including this one.
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Thresholds of the gap scan.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GapCriteria {
+    /// Smallest gap, up or down, as a fraction of the previous close.
+    pub min_gap: f64,
+    /// Lowest price, to leave out penny stocks.
+    pub min_price: f64,
+    /// Lowest volume traded so far today.
+    pub min_volume: i64,
+}
+
+impl Default for GapCriteria {
+    fn default() -> Self {
+        Self {
+            min_gap: 0.03,
+            min_price: 1.0,
+            min_volume: 0,
+        }
+    }
+}
+
+/// Price of a stock compared to its previous close.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gap {
+    pub previous_close: f64,
+    /// Last price, including extended hours trades.
+    pub price: f64,
+    /// Price change as a fraction of the previous close, negative for gaps
+    /// down.
+    pub gap: f64,
+    /// Volume traded so far today, including extended hours.
+    pub volume: Option<i64>,
+}
+
+impl Gap {
+    /// Gap of an equity or ETF quote. Before the open, the last price of a
+    /// quote is the last extended hours trade.
+    pub fn from_quote(quote: &Quote) -> Option<Self> {
+        let quote = match quote {
+            Quote::Equity(quote) | Quote::Etf(quote) => quote,
+            _ => return None,
+        };
+        let previous_close = quote.close_price.filter(|close| close.is_finite() && *close > 0.0)?;
+        let price = quote.last_price.filter(|price| price.is_finite() && *price > 0.0)?;
+
+        Some(Self {
+            previous_close,
+            price,
+            gap: price / previous_close - 1.0,
+            volume: quote.total_volume,
+        })
+    }
+
+    /// Whether the price is above the previous close.
+    pub fn is_up(&self) -> bool {
+        self.gap > 0.0
+    }
+}
+
+/// Fundamentals and price history of symbols, shared between scans.
+#[derive(Debug)]
+pub struct ScanCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    fetched_at: Instant,
+    fundamental: Option<Option<Fundamental>>,
+    candles: Option<Vec<Candle>>,
+}
+
+impl ScanCache {
+    /// Cache keeping data for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Forget everything.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    fn get(&self, symbol: &str, now: Instant) -> Option<CacheEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(symbol)
+            .filter(|entry| now.saturating_duration_since(entry.fetched_at) < self.ttl)
+            .cloned()
+    }
+
+    fn insert(&self, symbol: &str, entry: CacheEntry) {
+        self.entries.lock().unwrap().insert(symbol.to_string(), entry);
+    }
+}
+
+impl Default for ScanCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_TTL)
+    }
+}
+
+type ProgressFn = dyn Fn(ScanProgress<'_>) + Send + Sync;
+
+type ScanResult<T> = Result<Option<T>, Arc<ClientError>>;
+
+/// Runs a function over the data of many symbols.
+pub struct Scanner {
+    concurrency: usize,
+    quotes: bool,
+    fundamentals: bool,
+    price_history: Option<GetPriceHistoryParams>,
+    cache: Arc<ScanCache>,
+    progress: Option<Box<ProgressFn>>,
+}
+
+impl fmt::Debug for Scanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scanner")
+            .field("concurrency", &self.concurrency)
+            .field("quotes", &self.quotes)
+            .field("fundamentals", &self.fundamentals)
+            .field("price_history", &self.price_history)
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+    /// Scanner fetching quotes only.
+    pub fn new() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            quotes: true,
+            fundamentals: false,
+            price_history: None,
+            cache: Arc::default(),
+            progress: None,
+        }
+    }
+
+    /// Set how many symbols are fetched at once. The rate limiter of the
+    /// client still applies.
+    pub fn set_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = concurrency.max(1);
+
+        self
+    }
+
+    /// Set whether quotes are fetched. Defaults to `true`.
+    pub fn set_quotes(&mut self, quotes: bool) -> &mut Self {
+        self.quotes = quotes;
+
+        self
+    }
+
+    /// Set whether fundamentals are fetched. Defaults to `false`.
+    pub fn set_fundamentals(&mut self, fundamentals: bool) -> &mut Self {
+        self.fundamentals = fundamentals;
+
+        self
+    }
+
+    /// Fetch price history with `params`, or not at all with `None`, the
+    /// default.
+    pub fn set_price_history(&mut self, params: Option<GetPriceHistoryParams>) -> &mut Self {
+        self.price_history = params;
+
+        self
+    }
+
+    /// Share a cache with other scanners.
+    pub fn set_cache(&mut self, cache: Arc<ScanCache>) -> &mut Self {
+        self.cache = cache;
+
+        self
+    }
+
+    /// Call `progress` after each symbol, from the thread which scanned it.
+    pub fn set_progress(&mut self, progress: impl Fn(ScanProgress<'_>) + Send + Sync + 'static) -> &mut Self {
+        self.progress = Some(Box::new(progress));
+
+        self
+    }
+
+    /// Run `scan` over every symbol, keeping the values it returns as hits.
+    pub fn scan<T, F>(&self, client: &Client, symbols: &[&str], scan: F) -> ScanReport<T>
+    where
+        T: Send,
+        F: Fn(&SymbolData) -> Option<T> + Sync,
+    {
+        let mut quotes: HashMap<String, Quote> = HashMap::new();
+        let mut quote_errors: HashMap<String, Arc<ClientError>> = HashMap::new();
+
+        if self.quotes {
+            for batch in symbols.chunks(QUOTE_BATCH_SIZE) {
+                match client.get_quotes(batch) {
+                    Ok(result) => quotes.extend(result.found),
+                    Err(error) => {
+                        let error = Arc::new(error);
+
+                        quote_errors.extend(batch.iter().map(|symbol| (symbol.to_string(), error.clone())));
+                    }
+                }
+            }
+        }
+
+        let quotes = Mutex::new(quotes);
+        let quote_errors = Mutex::new(quote_errors);
+        let results: Mutex<Vec<Option<ScanResult<T>>>> = Mutex::new((0..symbols.len()).map(|_| None).collect());
+        let next = AtomicUsize::new(0);
+        let done = AtomicUsize::new(0);
+
+        let correlation_id = correlation::current();
+
+        thread::scope(|scope| {
+            for _ in 0..self.concurrency.min(symbols.len()) {
+                scope.spawn(|| correlation::within(correlation_id.as_deref(), || loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let symbol = match symbols.get(index) {
+                        Some(symbol) => *symbol,
+                        None => break,
+                    };
+
+                    let result = match quote_errors.lock().unwrap().remove(symbol) {
+                        Some(error) => Err(error),
+                        None => {
+                            // Quotes are keyed by the API's uppercase symbol.
+                            let quote = {
+                                let mut quotes = quotes.lock().unwrap();
+
+                                quotes.remove(symbol).or_else(|| quotes.remove(&symbol.to_uppercase()))
+                            };
+
+                            self.fetch(client, symbol, quote).map(|data| scan(&data)).map_err(Arc::new)
+                        }
+                    };
+
+                    results.lock().unwrap()[index] = Some(result);
+
+                    if let Some(progress) = &self.progress {
+                        progress(ScanProgress {
+                            symbol,
+                            done: done.fetch_add(1, Ordering::SeqCst) + 1,
+                            total: symbols.len(),
+                        });
+                    }
+                }));
+            }
+        });
+
+        let mut report = ScanReport {
+            hits: Vec::new(),
+            failed: Vec::new(),
+        };
+
+        for (symbol, result) in symbols.iter().zip(results.into_inner().unwrap()) {
+            match result {
+                Some(Ok(Some(value))) => report.hits.push(ScanHit {
+                    symbol: symbol.to_string(),
+                    value,
+                }),
+                Some(Err(error)) => report.failed.push((symbol.to_string(), error)),
+                Some(Ok(None)) | None => {}
+            }
+        }
+
+        report
+    }
+
+    /// Like [`scan()`](#method.scan), with hits sorted by the score `scan`
+    /// returns, highest first.
+    pub fn rank<F>(&self, client: &Client, symbols: &[&str], scan: F) -> ScanReport<f64>
+    where
+        F: Fn(&SymbolData) -> Option<f64> + Sync,
+    {
+        let mut report = self.scan(client, symbols, scan);
+
+        report.hits.sort_by(|a, b| b.value.total_cmp(&a.value));
+
+        report
+    }
+
+    /// Stocks among `symbols` gapping from their previous close by at least
+    /// the criteria, largest gaps first, up or down. Only needs quotes, so it
+    /// fetches nothing else whatever the scanner is set to fetch.
+    pub fn gaps(&self, client: &Client, symbols: &[&str], criteria: &GapCriteria) -> ScanReport<Gap> {
+        let scanner = Self {
+            concurrency: self.concurrency,
+            quotes: true,
+            fundamentals: false,
+            price_history: None,
+            cache: self.cache.clone(),
+            progress: None,
+        };
+
+        let mut report = scanner.scan(client, symbols, |data| {
+            let gap = Gap::from_quote(data.quote.as_ref()?)?;
+
+            let meets_criteria = gap.gap.abs() >= criteria.min_gap
+                && gap.price >= criteria.min_price
+                && gap.volume.unwrap_or_default() >= criteria.min_volume;
+
+            meets_criteria.then_some(gap)
+        });
+
+        report.hits.sort_by(|a, b| b.value.gap.abs().total_cmp(&a.value.gap.abs()));
+
+        report
+    }
+
+    /// Quotes of `symbols` enriched with a year of daily candles, in the
+    /// order of the universe. See the [`enriched`](../enriched/index.html)
+    /// module.
+    pub fn enriched_quotes(&self, client: &Client, symbols: &[&str]) -> ScanReport<QuoteEnriched> {
+        let scanner = Self {
+            concurrency: self.concurrency,
+            quotes: true,
+            fundamentals: false,
+            price_history: Some(enriched::price_history_params()),
+            cache: self.cache.clone(),
+            progress: None,
+        };
+        let today = client.clock().now().with_timezone(&MARKET_TIMEZONE).date_naive();
+
+        scanner.scan(client, symbols, |data| Some(QuoteEnriched::new(data.quote.clone()?, &data.candles, today)))
+    }
+
+    fn fetch(&self, client: &Client, symbol: &str, quote: Option<Quote>) -> Result<SymbolData, ClientError> {
+        let now = client.clock().instant();
+        let mut entry = self.cache.get(symbol, now).unwrap_or(CacheEntry {
+            fetched_at: now,
+            fundamental: None,
+            candles: None,
+        });
+        let mut fetched = false;
+
+        if self.fundamentals && entry.fundamental.is_none() {
+            entry.fundamental = Some(client.get_fundamentals(symbol)?);
+            fetched = true;
+        }
+
+        if let (Some(params), None) = (&self.price_history, &entry.candles) {
+            entry.candles = Some(client.get_price_history(symbol, params.clone())?.candles);
+            fetched = true;
+        }
+
+        if fetched {
+            self.cache.insert(symbol, entry.clone());
+        }
+
+        Ok(SymbolData {
+            symbol: symbol.to_string(),
+            quote,
+            fundamental: entry.fundamental.flatten().filter(|_| self.fundamentals),
+            candles: entry.candles.filter(|_| self.price_history.is_some()).unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{test_client, MockTransport};
+
+    #[test]
+    fn scans_with_bounded_concurrency() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let progress = Arc::new(AtomicUsize::new(0));
+        let mut scanner = Scanner::new();
+
+        transport.respond("GET", "/v1/marketdata/quotes", 200, include_str!("../tests/fixtures/quotes.json"));
+        transport.respond("GET", "/v1/marketdata/AAPL/pricehistory", 200, include_str!("../tests/fixtures/price_history.json"));
+        transport.respond("GET", "/v1/marketdata/MSFT/pricehistory", 404, "");
+
+        let counter = progress.clone();
+
+        scanner
+            .set_concurrency(2)
+            .set_price_history(Some(GetPriceHistoryParams::default()))
+            .set_progress(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+
+        let report = scanner.rank(&client, &["MSFT", "AAPL"], |data| Some(data.candles.last()?.close));
+
+        assert_eq!(report.hits, vec![ScanHit {
+            symbol: "AAPL".to_string(),
+            value: 320.11,
+        }]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "MSFT");
+        assert_eq!(progress.load(Ordering::SeqCst), 2);
+
+        // Price history comes from the cache the second time.
+        let requests = transport.requests().len();
+
+        scanner.scan(&client, &["AAPL"], |data| data.quote.as_ref().map(|_| ()));
+
+        assert_eq!(transport.requests().len(), requests + 1);
+    }
+
+    #[test]
+    fn scans_in_correlation_scope() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let mut scanner = Scanner::new();
+
+        transport.respond("GET", "/v1/marketdata/quotes", 200, include_str!("../tests/fixtures/quotes.json"));
+        scanner.set_concurrency(2).set_price_history(Some(GetPriceHistoryParams::default()));
+
+        let report = correlation::scope("scan-1", || scanner.scan(&client, &["MSFT", "AAPL"], |_| Some(())));
+
+        assert_eq!(report.failed.len(), 2);
+        assert!(report.failed.iter().all(|(_, error)| error.correlation_id() == Some("scan-1")));
+        assert!(transport.requests().iter().all(|request| request.header("X-Correlation-ID") == Some("scan-1")));
+    }
+
+    #[test]
+    fn finds_gaps() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let quote = |symbol: &str, close: f64, last: f64| {
+            serde_json::json!({
+                "assetType": "EQUITY",
+                "closePrice": close,
+                "lastPrice": last,
+                "symbol": symbol,
+                "totalVolume": 50_000,
+            })
+        };
+        let quotes = serde_json::json!({
+            "AAPL": quote("AAPL", 100.0, 104.0),
+            "MSFT": quote("MSFT", 200.0, 201.0),
+            "PENY": quote("PENY", 0.5, 0.8),
+            "TSLA": quote("TSLA", 400.0, 360.0),
+        });
+
+        transport.respond("GET", "/v1/marketdata/quotes", 200, &quotes.to_string());
+
+        let report = Scanner::new().gaps(&client, &["AAPL", "MSFT", "PENY", "TSLA"], &GapCriteria::default());
+        let gappers: Vec<&str> = report.hits.iter().map(|hit| hit.symbol.as_str()).collect();
+
+        assert_eq!(gappers, vec!["TSLA", "AAPL"]);
+        assert!((report.hits[0].value.gap + 0.1).abs() < 1e-12);
+        assert!(!report.hits[0].value.is_up());
+        assert!(report.hits[1].value.is_up());
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn matches_lowercase_symbols() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let quotes = serde_json::json!({
+            "TSLA": {
+                "assetType": "EQUITY",
+                "closePrice": 400.0,
+                "lastPrice": 360.0,
+                "symbol": "TSLA",
+                "totalVolume": 50_000,
+            },
+        });
+
+        transport.respond("GET", "/v1/marketdata/quotes", 200, &quotes.to_string());
+
+        let report = Scanner::new().gaps(&client, &["tsla"], &GapCriteria::default());
+
+        assert_eq!(report.hits.len(), 1);
+        assert!(report.failed.is_empty());
+    }
+}
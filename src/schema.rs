@@ -0,0 +1,252 @@
+//! Detect changes to the shape of API responses.
+//!
+//! serde ignores fields it doesn't know, and fills in missing optional ones,
+//! so responses keep parsing when TDA adds or removes fields, and the data
+//! silently goes missing. These helpers make the changes visible:
+//! [`unknown_fields()`](fn.unknown_fields.html) lists the fields of a response
+//! that a type drops, and [`SchemaDiff`](struct.SchemaDiff.html) compares the
+//! fields of a response with the ones recorded earlier.
+//!
+//! Fields are identified by their path, such as `candles[].open`, with array
+//! elements written as `[]`.
+//!
+//! ```
+//! use tda_sdk::{responses::GetPriceHistoryResponse, schema::unknown_fields};
+//!
+//! let json = r#"{"candles": [], "empty": true, "symbol": "AAPL", "delayed": false}"#;
+//!
+//! let unknown = unknown_fields::<GetPriceHistoryResponse>(json).unwrap();
+//!
+//! assert_eq!(unknown.into_iter().collect::<Vec<_>>(), ["delayed"]);
+//! ```
+//!
+//! Clients parse responses leniently by default, which suits production. To
+//! validate responses against the types instead, such as in CI, parse them in
+//! [`ParseMode::Strict`](enum.ParseMode.html#variant.Strict), either for a
+//! whole client or for the calls in a [`with_parse_mode()`](fn.with_parse_mode.html)
+//! scope:
+//!
+//! ```no_run
+//! use tda_sdk::{schema::{self, ParseMode}, Client};
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! client.set_parse_mode(ParseMode::Strict);
+//!
+//! // Fails if the response has a field `Account` doesn't know.
+//! let accounts = client.get_accounts(Default::default());
+//!
+//! let movers = schema::with_parse_mode(ParseMode::Lenient, || client.get_movers("$DJI", Default::default()));
+//! ```
+
+use serde::{
+    de::{DeserializeOwned, Deserializer, Error as _},
+    Serialize,
+};
+use serde_json::Value;
+
+use std::{cell::Cell, collections::BTreeSet, fmt};
+
+thread_local! {
+    static SCOPED_MODE: Cell<Option<ParseMode>> = const { Cell::new(None) };
+}
+
+/// How strictly responses are parsed.
+///
+/// Either mode parses enum values added by the API after this version of the
+/// crate, such as a new order status, as the enum's `Unknown` variant, so
+/// check for `Unknown` where a new value matters.
+///
+/// Strict mode can't detect missing fields: optional fields, and fields with
+/// a default, are filled in with `None` or their defaults in either mode, so
+/// a field the API stops sending looks the same as one that is empty. Use
+/// [`SchemaDiff`](struct.SchemaDiff.html) to notice fields going missing.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ParseMode {
+    /// Ignore fields the types don't know, and keep quotes and instruments
+    /// which don't match a typed variant as `Quote::Unknown` and
+    /// `Instrument::Unknown`.
+    #[default]
+    Lenient,
+    /// Fail on fields the types don't know, and on quotes and instruments of
+    /// unknown asset types or which don't match their typed variant.
+    Strict,
+}
+
+/// Run `f` with every response it parses on this thread parsed in `mode`,
+/// whatever the mode of the client. Scopes can be nested, and the outer mode
+/// is restored when `f` returns or panics.
+pub fn with_parse_mode<T>(mode: ParseMode, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<ParseMode>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED_MODE.with(|scoped| scoped.set(self.0));
+        }
+    }
+
+    let _restore = Restore(SCOPED_MODE.with(|scoped| scoped.replace(Some(mode))));
+
+    f()
+}
+
+/// Mode of the enclosing [`with_parse_mode()`](fn.with_parse_mode.html)
+/// scope on this thread, if any.
+pub fn parse_mode() -> Option<ParseMode> {
+    SCOPED_MODE.with(Cell::get)
+}
+
+/// Deserialize `T`, failing in strict mode if the input has a field `T`
+/// doesn't know.
+pub(crate) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: serde::Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    if parse_mode() != Some(ParseMode::Strict) {
+        return T::deserialize(deserializer);
+    }
+
+    let mut unknown = None;
+    let value = serde_ignored::deserialize(deserializer, |path| {
+        unknown.get_or_insert_with(|| ignored_path(&path));
+    })?;
+
+    match unknown {
+        Some(path) => Err(D::Error::custom(format_args!("unknown field `{}`", path))),
+        None => Ok(value),
+    }
+}
+
+/// Path of an ignored field, written the way `field_paths()` writes it.
+fn ignored_path(path: &serde_ignored::Path<'_>) -> String {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, .. } => format!("{}[]", ignored_path(parent)),
+        Path::Map { parent, key } => match ignored_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+/// Paths of every field in a JSON value, including fields set to `null`.
+pub fn field_paths(value: &Value) -> BTreeSet<String> {
+    let mut paths = BTreeSet::new();
+
+    collect_paths(value, "", &mut paths);
+
+    paths
+}
+
+fn collect_paths(value: &Value, prefix: &str, paths: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let path = match prefix {
+                    "" => key.clone(),
+                    _ => format!("{}.{}", prefix, key),
+                };
+
+                collect_paths(value, &path, paths);
+                paths.insert(path);
+            }
+        }
+        Value::Array(array) => {
+            let path = format!("{}[]", prefix);
+
+            for value in array {
+                collect_paths(value, &path, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Paths of the fields of a response that are lost when it is parsed as `T`,
+/// because `T` doesn't know them.
+pub fn unknown_fields<T>(json: &str) -> Result<BTreeSet<String>, serde_json::Error>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let raw: Value = serde_json::from_str(json)?;
+    let parsed: T = serde_json::from_value(raw.clone())?;
+    let known = field_paths(&serde_json::to_value(&parsed)?);
+
+    Ok(field_paths(&raw).difference(&known).cloned().collect())
+}
+
+/// Fields added to and removed from a response, compared with an earlier one.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SchemaDiff {
+    pub added: BTreeSet<String>,
+    pub removed: BTreeSet<String>,
+}
+
+impl SchemaDiff {
+    /// Compare the field paths of a response with the expected ones.
+    pub fn new(expected: &BTreeSet<String>, actual: &BTreeSet<String>) -> Self {
+        Self {
+            added: actual.difference(expected).cloned().collect(),
+            removed: expected.difference(actual).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Shows the diff with one field per line, prefixed with `+` or `-`.
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for path in &self.added {
+            writeln!(f, "+ {}", path)?;
+        }
+
+        for path in &self.removed {
+            writeln!(f, "- {}", path)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_and_diff() {
+        let expected = field_paths(&serde_json::json!({"candles": [{"open": 1.0}], "symbol": "AAPL"}));
+        let actual = field_paths(&serde_json::json!({"candles": [{"open": 1.0, "vwap": 1.0}], "empty": null}));
+
+        assert_eq!(expected.iter().collect::<Vec<_>>(), ["candles", "candles[].open", "symbol"]);
+
+        let diff = SchemaDiff::new(&expected, &actual);
+
+        assert!(!diff.is_empty());
+        assert_eq!(diff.to_string(), "+ candles[].vwap\n+ empty\n- symbol\n");
+    }
+
+    #[test]
+    fn strict_parse_mode() {
+        use crate::responses::GetPriceHistoryResponse;
+
+        let json = r#"{"candles": [{"open": 1, "high": 1, "low": 1, "close": 1, "volume": 1, "datetime": 0, "vwap": 1}], "empty": false, "symbol": "AAPL"}"#;
+        let parse = || deserialize::<GetPriceHistoryResponse, _>(&mut serde_json::Deserializer::from_str(json));
+
+        assert!(parse().is_ok());
+        assert_eq!(parse_mode(), None);
+
+        with_parse_mode(ParseMode::Strict, || {
+            assert!(parse().unwrap_err().to_string().contains("unknown field `candles[].vwap`"));
+            assert!(with_parse_mode(ParseMode::Lenient, parse).is_ok());
+            assert_eq!(parse_mode(), Some(ParseMode::Strict));
+        });
+
+        assert_eq!(parse_mode(), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Graceful shutdown of a client and the loops around it.
+//!
+//! [`Client::shutdown()`](../struct.Client.html#method.shutdown) stops the
+//! client from sending new requests, waits for those in flight to finish, and
+//! flushes the audit sink. Background loops, such as threads polling alerts
+//! or delivering streamer events, watch the client's
+//! [`Shutdown`](struct.Shutdown.html) signal to know when to stop:
+//!
+//! ```no_run
+//! use std::{thread, time::Duration};
+//! use tda_sdk::Client;
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let signal = client.shutdown_signal();
+//!
+//! let poller = thread::spawn(move || {
+//!     while !signal.is_triggered() {
+//!         // Poll something.
+//!         signal.wait_triggered(Duration::from_secs(5));
+//!     }
+//! });
+//!
+//! let report = client.shutdown(Duration::from_secs(10));
+//!
+//! poller.join().unwrap();
+//! assert!(report.drained);
+//! ```
+
+use crate::ClientError;
+
+use std::{
+    io::{self, Read},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Shutdown signal of a client, counting its requests in flight.
+#[derive(Debug, Default)]
+pub struct Shutdown {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    triggered: bool,
+    in_flight: usize,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signal a shutdown. New requests fail with `ClientError::ShuttingDown`.
+    pub fn trigger(&self) {
+        self.state.lock().unwrap().triggered = true;
+        self.changed.notify_all();
+    }
+
+    /// Whether a shutdown was signaled.
+    pub fn is_triggered(&self) -> bool {
+        self.state.lock().unwrap().triggered
+    }
+
+    /// Wait until a shutdown is signaled, for at most `timeout`, returning
+    /// whether it was. Lets loops sleep between iterations without delaying
+    /// the shutdown.
+    pub fn wait_triggered(&self, timeout: Duration) -> bool {
+        let state = self.state.lock().unwrap();
+        let (state, _) = self.changed.wait_timeout_while(state, timeout, |state| !state.triggered).unwrap();
+
+        state.triggered
+    }
+
+    /// Requests in flight.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    /// Wait until no request is in flight, for at most `timeout`, returning
+    /// whether none is.
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+
+        while state.in_flight > 0 {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining == Duration::default() {
+                return false;
+            }
+
+            state = self.changed.wait_timeout(state, remaining).unwrap().0;
+        }
+
+        true
+    }
+
+    /// Count a request in flight until the returned guard is dropped, or fail
+    /// if a shutdown was signaled.
+    pub(crate) fn begin_request(self: &Arc<Self>) -> Result<InFlight, ClientError> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.triggered {
+            return Err(ClientError::ShuttingDown);
+        }
+
+        state.in_flight += 1;
+
+        Ok(InFlight(self.clone()))
+    }
+}
+
+/// Request in flight, counted until dropped.
+pub(crate) struct InFlight(Arc<Shutdown>);
+
+impl InFlight {
+    /// Keep counting the request in flight until its response body is
+    /// dropped, so a shutdown waits for the body to be read too.
+    pub(crate) fn hold_until_read(self, body: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+        Box::new(InFlightBody { body, _in_flight: self })
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().in_flight -= 1;
+        self.0.changed.notify_all();
+    }
+}
+
+/// Response body of a request in flight.
+struct InFlightBody {
+    body: Box<dyn Read + Send>,
+    _in_flight: InFlight,
+}
+
+impl Read for InFlightBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+/// Outcome of a shutdown.
+#[derive(Debug)]
+pub struct ShutdownReport {
+    /// Whether every request in flight finished before the timeout.
+    pub drained: bool,
+    /// Requests still in flight when the shutdown returned.
+    pub in_flight: usize,
+    /// Result of flushing the audit sink, if the client has one.
+    pub audit_flush: Option<io::Result<()>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn drains_requests() {
+        let shutdown = Arc::new(Shutdown::new());
+        let request = shutdown.begin_request().unwrap();
+
+        assert_eq!(shutdown.in_flight(), 1);
+        assert!(!shutdown.wait_triggered(Duration::from_millis(1)));
+
+        shutdown.trigger();
+
+        assert!(matches!(shutdown.begin_request(), Err(ClientError::ShuttingDown)));
+        assert!(!shutdown.wait_idle(Duration::from_millis(10)));
+
+        let waiter = {
+            let shutdown = shutdown.clone();
+
+            thread::spawn(move || shutdown.wait_idle(Duration::from_secs(5)))
+        };
+
+        drop(request);
+
+        assert!(waiter.join().unwrap());
+        assert!(shutdown.wait_triggered(Duration::default()));
+    }
+}
@@ -0,0 +1,163 @@
+//! Volatility measures and position sizing.
+//!
+//! Sizes are in whole shares, rounded down so a position never risks more
+//! than asked, and capped at what the equity can buy without margin. Risk per
+//! trade is a fraction of the equity (`0.01` risks 1%).
+//!
+//! ```
+//! use tda_sdk::{responses::GetPriceHistoryResponse, sizing};
+//!
+//! let history: GetPriceHistoryResponse = serde_json::from_str(r#"{
+//!     "candles": [
+//!         {"open": 100.0, "high": 102.0, "low": 99.0, "close": 101.0, "volume": 1000, "datetime": 0},
+//!         {"open": 101.0, "high": 104.0, "low": 100.0, "close": 103.0, "volume": 1000, "datetime": 1},
+//!         {"open": 103.0, "high": 103.5, "low": 100.5, "close": 101.0, "volume": 1000, "datetime": 2}
+//!     ],
+//!     "empty": false,
+//!     "symbol": "AAPL"
+//! }"#).unwrap();
+//!
+//! let atr = sizing::average_true_range(&history.candles, 2).unwrap();
+//!
+//! // Risk 1% of 50,000 with a stop two ATRs below an entry at 101.
+//! let shares = sizing::atr_position_size(50_000.0, 0.01, 101.0, atr, 2.0);
+//!
+//! assert_eq!(shares, 71.0);
+//! ```
+
+use crate::responses::{Candle, SecuritiesAccount};
+
+/// Annualized historical volatility of `candles`: the sample standard
+/// deviation of the log returns of the closes, scaled by the square root of
+/// `periods_per_year`, such as
+/// [`TRADING_DAYS_PER_YEAR`](../stats/constant.TRADING_DAYS_PER_YEAR.html)
+/// for daily candles. `None` with fewer than three candles.
+pub fn historical_volatility(candles: &[Candle], periods_per_year: f64) -> Option<f64> {
+    let returns: Vec<f64> = candles
+        .windows(2)
+        .filter(|pair| pair[0].close > 0.0 && pair[1].close > 0.0)
+        .map(|pair| (pair[1].close / pair[0].close).ln())
+        .collect();
+
+    if returns.len() < 2 {
+        return None;
+    }
+
+    let count = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / count;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (count - 1.0);
+
+    Some(variance.sqrt() * periods_per_year.sqrt())
+}
+
+/// True range of each candle after the first: the largest of its range and
+/// its distances from the previous close.
+pub fn true_ranges(candles: &[Candle]) -> Vec<f64> {
+    candles
+        .windows(2)
+        .map(|pair| {
+            let (previous, candle) = (&pair[0], &pair[1]);
+
+            (candle.high - candle.low)
+                .max((candle.high - previous.close).abs())
+                .max((candle.low - previous.close).abs())
+        })
+        .collect()
+}
+
+/// Average true range over `period` candles, with Wilder's smoothing. `None`
+/// with fewer than `period + 1` candles.
+pub fn average_true_range(candles: &[Candle], period: usize) -> Option<f64> {
+    let ranges = true_ranges(candles);
+
+    if period == 0 || ranges.len() < period {
+        return None;
+    }
+
+    let initial = ranges[..period].iter().sum::<f64>() / period as f64;
+
+    Some(ranges[period..].iter().fold(initial, |atr, range| (atr * (period - 1) as f64 + range) / period as f64))
+}
+
+/// Liquidation value of an account, the equity to size positions against.
+pub fn account_equity(account: &SecuritiesAccount) -> f64 {
+    account.current_balances().liquidation_value
+}
+
+/// Shares to buy at `price` so that a stop `stop_distance` away loses
+/// `risk_per_trade` of `equity`.
+pub fn risk_position_size(equity: f64, risk_per_trade: f64, price: f64, stop_distance: f64) -> f64 {
+    if price <= 0.0 || stop_distance <= 0.0 {
+        return 0.0;
+    }
+
+    let shares = (equity * risk_per_trade / stop_distance).min(equity / price);
+
+    shares.max(0.0).floor()
+}
+
+/// Shares to buy at `price` with a stop `atr_multiple` average true ranges
+/// away, so that hitting the stop loses `risk_per_trade` of `equity`.
+pub fn atr_position_size(equity: f64, risk_per_trade: f64, price: f64, atr: f64, atr_multiple: f64) -> f64 {
+    risk_position_size(equity, risk_per_trade, price, atr * atr_multiple)
+}
+
+/// Shares to buy at `price` so the position contributes `target_volatility`
+/// to the volatility of `equity`, given the annualized `volatility` of the
+/// symbol.
+pub fn volatility_position_size(equity: f64, target_volatility: f64, price: f64, volatility: f64) -> f64 {
+    if price <= 0.0 || volatility <= 0.0 {
+        return 0.0;
+    }
+
+    let value = (equity * target_volatility / volatility).min(equity);
+
+    (value / price).max(0.0).floor()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::TRADING_DAYS_PER_YEAR;
+
+    fn candle(high: f64, low: f64, close: f64) -> Candle {
+        Candle {
+            close,
+            datetime: 0,
+            high,
+            low,
+            open: close,
+            volume: 0,
+        }
+    }
+
+    #[test]
+    fn volatility_and_atr() {
+        let candles = [candle(11.0, 9.0, 10.0), candle(12.0, 10.0, 11.0), candle(11.5, 9.5, 10.0), candle(13.0, 10.5, 12.0)];
+
+        // The gap from 10 to 13 makes the last true range 3 instead of 2.5.
+        assert_eq!(true_ranges(&candles), [2.0, 2.0, 3.0]);
+        assert_eq!(average_true_range(&candles, 3), Some(7.0 / 3.0));
+        assert_eq!(average_true_range(&candles, 2), Some(2.5));
+        assert_eq!(average_true_range(&candles, 4), None);
+
+        let flat = [candle(10.0, 10.0, 10.0), candle(10.0, 10.0, 10.0), candle(10.0, 10.0, 10.0)];
+
+        assert_eq!(historical_volatility(&flat, TRADING_DAYS_PER_YEAR), Some(0.0));
+        assert!(historical_volatility(&candles, TRADING_DAYS_PER_YEAR).unwrap() > 1.0);
+        assert_eq!(historical_volatility(&candles[..2], TRADING_DAYS_PER_YEAR), None);
+    }
+
+    #[test]
+    fn position_sizes() {
+        // Risking 500 with a 2.5 stop is 200 shares.
+        assert_eq!(atr_position_size(50_000.0, 0.01, 100.0, 1.25, 2.0), 200.0);
+
+        // Capped at what the equity buys.
+        assert_eq!(risk_position_size(10_000.0, 0.02, 100.0, 0.5), 100.0);
+        assert_eq!(risk_position_size(10_000.0, 0.02, 100.0, 0.0), 0.0);
+
+        // A 40% volatility symbol targeted at 10% gets a quarter of the equity.
+        assert_eq!(volatility_position_size(100_000.0, 0.1, 50.0, 0.4), 500.0);
+    }
+}
@@ -0,0 +1,186 @@
+//! Performance metrics for equity curves and fills.
+//!
+//! All ratios are returned as fractions (`0.1` is 10%). Returns are simple
+//! period-over-period returns, and the Sharpe ratio assumes a risk-free rate
+//! of zero.
+
+use crate::strategy::Fill;
+
+use std::collections::HashMap;
+
+/// Trading days in a year, for annualizing daily returns.
+pub const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+/// Summary of the performance of an equity curve and its fills.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PerformanceReport {
+    /// Return from the first to the last point of the equity curve.
+    pub total_return: f64,
+
+    /// Annualized Sharpe ratio, or `None` if the returns have no variance.
+    pub sharpe_ratio: Option<f64>,
+
+    /// Largest drop from a peak of the equity curve.
+    pub max_drawdown: f64,
+
+    /// Fraction of closing trades with a positive profit, or `None` if no
+    /// trade was closed.
+    pub win_rate: Option<f64>,
+
+    /// Profit or loss of each closing trade, in the order they were closed.
+    pub closed_trades: Vec<f64>,
+}
+
+impl PerformanceReport {
+    /// Compute every metric. `periods_per_year` is the number of equity curve
+    /// points in a year, such as
+    /// [`TRADING_DAYS_PER_YEAR`](constant.TRADING_DAYS_PER_YEAR.html) for
+    /// daily points.
+    pub fn new(equity: &[f64], fills: &[Fill], periods_per_year: f64) -> Self {
+        let closed_trades = closed_trades(fills);
+
+        Self {
+            total_return: total_return(equity),
+            sharpe_ratio: sharpe_ratio(&returns(equity), periods_per_year),
+            max_drawdown: max_drawdown(equity),
+            win_rate: win_rate(&closed_trades),
+            closed_trades,
+        }
+    }
+}
+
+/// Return from the first to the last point of `equity`, or `0.0` if there are
+/// fewer than two points.
+pub fn total_return(equity: &[f64]) -> f64 {
+    match (equity.first(), equity.last()) {
+        (Some(first), Some(last)) if *first != 0.0 => last / first - 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Return of each period of `equity`.
+pub fn returns(equity: &[f64]) -> Vec<f64> {
+    equity.windows(2).map(|pair| pair[1] / pair[0] - 1.0).collect()
+}
+
+/// Annualized Sharpe ratio of `returns`, using the sample standard deviation.
+pub fn sharpe_ratio(returns: &[f64], periods_per_year: f64) -> Option<f64> {
+    if returns.len() < 2 {
+        return None;
+    }
+
+    let count = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / count;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (count - 1.0);
+
+    if variance == 0.0 {
+        return None;
+    }
+
+    Some(mean / variance.sqrt() * periods_per_year.sqrt())
+}
+
+/// Largest drop from a peak of `equity`, as a fraction of the peak.
+pub fn max_drawdown(equity: &[f64]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut drawdown: f64 = 0.0;
+
+    for value in equity {
+        peak = peak.max(*value);
+
+        if peak > 0.0 {
+            drawdown = drawdown.max(1.0 - value / peak);
+        }
+    }
+
+    drawdown
+}
+
+/// Profit or loss of every fill which reduced a position, using the average
+/// cost of the position.
+pub fn closed_trades(fills: &[Fill]) -> Vec<f64> {
+    // Signed quantity and average cost of each symbol.
+    let mut positions: HashMap<&str, (f64, f64)> = HashMap::new();
+    let mut trades = Vec::new();
+
+    for fill in fills {
+        let (quantity, cost) = positions.entry(&fill.symbol).or_default();
+        let signed = match fill.instruction.as_str() {
+            "Buy" | "BuyToCover" | "BUY" | "BUY_TO_COVER" => fill.quantity,
+            _ => -fill.quantity,
+        };
+
+        if *quantity == 0.0 || quantity.signum() == signed.signum() {
+            *cost = (*cost * quantity.abs() + fill.price * signed.abs()) / (quantity.abs() + signed.abs());
+            *quantity += signed;
+            continue;
+        }
+
+        let closed = signed.abs().min(quantity.abs());
+
+        trades.push((fill.price - *cost) * closed * quantity.signum());
+        *quantity += signed;
+
+        // Flipping through zero opens a new position at the fill price.
+        if quantity.signum() == signed.signum() {
+            *cost = fill.price;
+        }
+    }
+
+    trades
+}
+
+/// Fraction of `trades` with a positive profit.
+pub fn win_rate(trades: &[f64]) -> Option<f64> {
+    if trades.is_empty() {
+        return None;
+    }
+
+    Some(trades.iter().filter(|pnl| **pnl > 0.0).count() as f64 / trades.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(instruction: &str, quantity: f64, price: f64) -> Fill {
+        Fill {
+            symbol: "AAPL".to_string(),
+            instruction: instruction.to_string(),
+            quantity,
+            price,
+            ..Fill::default()
+        }
+    }
+
+    #[test]
+    fn equity_metrics() {
+        let equity = [100.0, 110.0, 99.0, 120.0];
+
+        assert!((total_return(&equity) - 0.2).abs() < 1e-12);
+        assert!((max_drawdown(&equity) - 0.1).abs() < 1e-12);
+        assert_eq!(returns(&equity).len(), 3);
+        assert!(sharpe_ratio(&returns(&equity), TRADING_DAYS_PER_YEAR).unwrap() > 0.0);
+        assert_eq!(sharpe_ratio(&[0.01, 0.01], TRADING_DAYS_PER_YEAR), None);
+    }
+
+    #[test]
+    fn trade_metrics() {
+        let fills = [
+            fill("Buy", 10.0, 100.0),
+            fill("Buy", 10.0, 110.0),
+            fill("Sell", 5.0, 120.0),
+            fill("Sell", 25.0, 100.0),
+            fill("BuyToCover", 10.0, 90.0),
+        ];
+
+        // Average cost is 105, so the sells close +75 and -75, and the short
+        // opened at 100 is covered for +100.
+        assert_eq!(closed_trades(&fills), vec![75.0, -75.0, 100.0]);
+
+        let report = PerformanceReport::new(&[100.0, 100.0], &fills, TRADING_DAYS_PER_YEAR);
+
+        assert_eq!(report.win_rate, Some(2.0 / 3.0));
+        assert_eq!(report.sharpe_ratio, None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Persistence of market and account data.
+//!
+//! Apps collecting data tend to design the same tables. With the `sqlite`
+//! feature enabled, the [`sqlite`](sqlite/index.html) module provides them for
+//! candles, quotes, transactions, and orders, with upserts so data can be
+//! fetched again without creating duplicates.
+//!
+//! Candles can be kept up to date with
+//! [`Client::sync_price_history()`](../struct.Client.html#method.sync_price_history),
+//! which only requests candles newer than the latest one in a
+//! [`CandleStore`](trait.CandleStore.html):
+//!
+//! ```no_run
+//! use tda_sdk::{storage::MemoryCandleStore, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let mut store = MemoryCandleStore::new();
+//!
+//! for symbol in &["AAPL", "MSFT", "SPY"] {
+//!     let added = client.sync_price_history(symbol, &mut store).unwrap();
+//!
+//!     println!("{}: {} new candles", symbol, added);
+//! }
+//! ```
+
+use crate::{responses::Candle, ClientError};
+use thiserror::Error;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+};
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Storage of candles by symbol, which price history can be synced into.
+pub trait CandleStore {
+    type Error;
+
+    /// Time of the latest candle of a symbol, in milliseconds since the
+    /// epoch.
+    fn latest_candle_datetime(&self, symbol: &str) -> Result<Option<i64>, Self::Error>;
+
+    /// Insert candles of a symbol, replacing those with the same time.
+    fn upsert_candles(&mut self, symbol: &str, candles: &[Candle]) -> Result<usize, Self::Error>;
+}
+
+/// Candle store which only lives in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCandleStore {
+    candles: HashMap<String, BTreeMap<i64, Candle>>,
+}
+
+impl MemoryCandleStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Candles of a symbol, sorted by time.
+    pub fn candles(&self, symbol: &str) -> Vec<Candle> {
+        self.candles.get(symbol).map(|candles| candles.values().copied().collect()).unwrap_or_default()
+    }
+}
+
+impl CandleStore for MemoryCandleStore {
+    type Error = Infallible;
+
+    fn latest_candle_datetime(&self, symbol: &str) -> Result<Option<i64>, Self::Error> {
+        Ok(self.candles.get(symbol).and_then(|candles| candles.keys().next_back().copied()))
+    }
+
+    fn upsert_candles(&mut self, symbol: &str, candles: &[Candle]) -> Result<usize, Self::Error> {
+        let stored = self.candles.entry(symbol.to_string()).or_default();
+
+        for candle in candles {
+            stored.insert(candle.datetime, *candle);
+        }
+
+        Ok(candles.len())
+    }
+}
+
+/// Errors encountered while syncing price history into a store.
+#[derive(Debug, Error)]
+pub enum SyncError<E> {
+    /// The price history couldn't be fetched.
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    /// The store failed to read or write candles.
+    #[error("Failed to store candles: {0}")]
+    Store(E),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{test_client, MockTransport};
+    use std::sync::Arc;
+
+    #[test]
+    fn syncs_only_newer_candles() {
+        let transport = Arc::new(MockTransport::new());
+        let client = test_client(transport.clone());
+        let mut store = MemoryCandleStore::new();
+
+        transport.respond("GET", "/v1/marketdata/AAPL/pricehistory", 200, include_str!("../tests/fixtures/price_history.json"));
+
+        assert_eq!(client.sync_price_history("AAPL", &mut store).unwrap(), 2);
+        assert_eq!(store.latest_candle_datetime("AAPL").unwrap(), Some(1_588_222_800_000));
+
+        // The latest candle is fetched again, but isn't new.
+        assert_eq!(client.sync_price_history("AAPL", &mut store).unwrap(), 0);
+        assert_eq!(store.candles("AAPL").len(), 2);
+
+        let requests = transport.requests();
+
+        assert!(requests[0].url.contains("period=20"));
+        assert!(!requests[1].url.contains("period="));
+        assert!(requests[1].url.contains("startDate=1588222800000"));
+        assert!(requests[1].url.contains("endDate="));
+    }
+}
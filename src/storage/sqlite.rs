@@ -0,0 +1,397 @@
+//! SQLite tables for candles, quotes, transactions, and orders.
+//!
+//! Only available with the `sqlite` feature enabled.
+//!
+//! ```no_run
+//! use tda_sdk::{
+//!     params::{GetPriceHistoryParams, GetTransactionsParams},
+//!     storage::sqlite::SqliteStore,
+//!     Client,
+//! };
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let mut store = SqliteStore::open("market.db").unwrap();
+//!
+//! let history = client.get_price_history("AAPL", GetPriceHistoryParams::default()).unwrap();
+//! store.upsert_candles("AAPL", &history.candles).unwrap();
+//!
+//! let transactions = client.get_transactions("123456789", GetTransactionsParams::default()).unwrap();
+//! store.upsert_transactions(&"123456789".into(), &transactions).unwrap();
+//! ```
+//!
+//! Columns useful for querying are stored on their own, and transactions,
+//! orders, and quotes are also kept whole as JSON in a `data` column, so
+//! nothing the API returned is lost. The schema is in
+//! [`SCHEMA`](constant.SCHEMA.html).
+
+use super::CandleStore;
+use crate::{
+    ids::AccountId,
+    orders::Order,
+    responses::{Candle, GetQuotesResponse, Transaction},
+    time::TdaDateTime,
+};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use thiserror::Error;
+
+use std::path::Path;
+
+/// Tables created by [`SqliteStore`](struct.SqliteStore.html) if they don't
+/// exist. Times are stored as RFC 3339 in UTC, or as milliseconds since the
+/// epoch where the API uses those.
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS candles (
+    symbol TEXT NOT NULL,
+    datetime INTEGER NOT NULL,
+    open REAL NOT NULL,
+    high REAL NOT NULL,
+    low REAL NOT NULL,
+    close REAL NOT NULL,
+    volume INTEGER NOT NULL,
+    PRIMARY KEY (symbol, datetime)
+);
+
+CREATE TABLE IF NOT EXISTS quotes (
+    symbol TEXT NOT NULL,
+    quoted_at INTEGER NOT NULL,
+    last_price REAL,
+    bid_price REAL,
+    ask_price REAL,
+    close_price REAL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (symbol, quoted_at)
+);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    transaction_id INTEGER PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    type TEXT NOT NULL,
+    sub_type TEXT,
+    transaction_date TEXT,
+    settlement_date TEXT,
+    symbol TEXT,
+    net_amount REAL NOT NULL,
+    data TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS transactions_by_account ON transactions (account_id, transaction_date);
+
+CREATE TABLE IF NOT EXISTS orders (
+    order_id INTEGER PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    status TEXT,
+    entered_time TEXT,
+    close_time TEXT,
+    data TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS orders_by_account ON orders (account_id, entered_time);
+";
+
+/// Connection to a SQLite database with the tables of
+/// [`SCHEMA`](constant.SCHEMA.html).
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Open a database file, creating it and its tables if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SqliteError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Open a database which only lives in memory, such as for tests.
+    pub fn open_in_memory() -> Result<Self, SqliteError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Use an existing connection, creating the tables if needed.
+    pub fn from_connection(connection: Connection) -> Result<Self, SqliteError> {
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(Self { connection })
+    }
+
+    /// Connection to the database, for queries of its own.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Insert candles of a symbol, replacing those with the same time.
+    /// Returns the number of candles written.
+    pub fn upsert_candles(&mut self, symbol: &str, candles: &[Candle]) -> Result<usize, SqliteError> {
+        let transaction = self.connection.transaction()?;
+
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO candles (symbol, datetime, open, high, low, close, volume) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT (symbol, datetime) DO UPDATE SET
+                     open = excluded.open, high = excluded.high, low = excluded.low, close = excluded.close, volume = excluded.volume",
+            )?;
+
+            for candle in candles {
+                statement.execute(params![symbol, candle.datetime, candle.open, candle.high, candle.low, candle.close, candle.volume])?;
+            }
+        }
+
+        transaction.commit()?;
+
+        Ok(candles.len())
+    }
+
+    /// Candles of a symbol between two times in milliseconds since the
+    /// epoch, inclusive, sorted by time.
+    pub fn candles(&self, symbol: &str, from: Option<usize>, to: Option<usize>) -> Result<Vec<Candle>, SqliteError> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT datetime, open, high, low, close, volume FROM candles
+             WHERE symbol = ?1 AND datetime >= ?2 AND datetime <= ?3 ORDER BY datetime",
+        )?;
+        let from = from.map_or(i64::MIN, |from| from as i64);
+        let to = to.map_or(i64::MAX, |to| to as i64);
+
+        let candles = statement
+            .query_map(params![symbol, from, to], |row| {
+                Ok(Candle {
+                    datetime: row.get(0)?,
+                    open: row.get(1)?,
+                    high: row.get(2)?,
+                    low: row.get(3)?,
+                    close: row.get(4)?,
+                    volume: row.get(5)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(candles)
+    }
+
+    /// Time of the latest candle of a symbol, in milliseconds since the
+    /// epoch.
+    pub fn latest_candle_datetime(&self, symbol: &str) -> Result<Option<i64>, SqliteError> {
+        let datetime: Option<i64> = self
+            .connection
+            .query_row("SELECT MAX(datetime) FROM candles WHERE symbol = ?1", params![symbol], |row| row.get(0))
+            .optional()?
+            .flatten();
+
+        Ok(datetime)
+    }
+
+    /// Insert quotes taken at `quoted_at`, replacing those of the same symbol
+    /// and time. Returns the number of quotes written.
+    pub fn upsert_quotes(&mut self, quotes: &GetQuotesResponse, quoted_at: DateTime<Utc>) -> Result<usize, SqliteError> {
+        let transaction = self.connection.transaction()?;
+
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO quotes (symbol, quoted_at, last_price, bid_price, ask_price, close_price, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT (symbol, quoted_at) DO UPDATE SET
+                     last_price = excluded.last_price, bid_price = excluded.bid_price, ask_price = excluded.ask_price,
+                     close_price = excluded.close_price, data = excluded.data",
+            )?;
+
+            for (symbol, quote) in quotes {
+                statement.execute(params![
+                    symbol,
+                    quoted_at.timestamp_millis(),
+                    quote.last_price(),
+                    quote.bid_price(),
+                    quote.ask_price(),
+                    quote.close_price(),
+                    serde_json::to_string(quote)?,
+                ])?;
+            }
+        }
+
+        transaction.commit()?;
+
+        Ok(quotes.len())
+    }
+
+    /// Insert transactions of an account, replacing those with the same ID.
+    /// Returns the number of transactions written.
+    pub fn upsert_transactions(&mut self, account_id: &AccountId, transactions: &[Transaction]) -> Result<usize, SqliteError> {
+        let db_transaction = self.connection.transaction()?;
+
+        {
+            let mut statement = db_transaction.prepare_cached(
+                "INSERT INTO transactions (transaction_id, account_id, type, sub_type, transaction_date, settlement_date, symbol, net_amount, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT (transaction_id) DO UPDATE SET
+                     account_id = excluded.account_id, type = excluded.type, sub_type = excluded.sub_type,
+                     transaction_date = excluded.transaction_date, settlement_date = excluded.settlement_date,
+                     symbol = excluded.symbol, net_amount = excluded.net_amount, data = excluded.data",
+            )?;
+
+            for transaction in transactions {
+                let symbol = transaction
+                    .transaction_item
+                    .as_ref()
+                    .and_then(|item| item.instrument.as_ref())
+                    .and_then(|instrument| instrument.symbol.as_deref());
+
+                statement.execute(params![
+                    transaction.transaction_id,
+                    account_id.as_str(),
+                    transaction.transaction_type,
+                    transaction.transaction_sub_type,
+                    transaction.transaction_date.as_ref().map(utc_string),
+                    transaction.settlement_date.map(|date| date.format("%Y-%m-%d").to_string()),
+                    symbol,
+                    transaction.net_amount,
+                    serde_json::to_string(transaction)?,
+                ])?;
+            }
+        }
+
+        db_transaction.commit()?;
+
+        Ok(transactions.len())
+    }
+
+    /// Transactions of an account, sorted by date.
+    pub fn transactions(&self, account_id: &AccountId) -> Result<Vec<Transaction>, SqliteError> {
+        self.query_data("SELECT data FROM transactions WHERE account_id = ?1 ORDER BY transaction_date, transaction_id", account_id)
+    }
+
+    /// Insert orders of an account, replacing those with the same ID. Orders
+    /// without an ID are skipped. Returns the number of orders written.
+    pub fn upsert_orders(&mut self, account_id: &AccountId, orders: &[Order]) -> Result<usize, SqliteError> {
+        let transaction = self.connection.transaction()?;
+        let mut written = 0;
+
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO orders (order_id, account_id, status, entered_time, close_time, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (order_id) DO UPDATE SET
+                     account_id = excluded.account_id, status = excluded.status, entered_time = excluded.entered_time,
+                     close_time = excluded.close_time, data = excluded.data",
+            )?;
+
+            for order in orders {
+                let order_id = match order.order_id {
+                    Some(order_id) => order_id,
+                    None => continue,
+                };
+                let status = order
+                    .status
+                    .map(serde_json::to_value)
+                    .transpose()?
+                    .and_then(|status| status.as_str().map(str::to_string));
+
+                statement.execute(params![
+                    order_id.value(),
+                    account_id.as_str(),
+                    status,
+                    order.entered_time.as_ref().map(utc_string),
+                    order.close_time.as_ref().map(utc_string),
+                    serde_json::to_string(order)?,
+                ])?;
+
+                written += 1;
+            }
+        }
+
+        transaction.commit()?;
+
+        Ok(written)
+    }
+
+    /// Orders of an account, sorted by the time they were entered.
+    pub fn orders(&self, account_id: &AccountId) -> Result<Vec<Order>, SqliteError> {
+        self.query_data("SELECT data FROM orders WHERE account_id = ?1 ORDER BY entered_time, order_id", account_id)
+    }
+
+    fn query_data<T: serde::de::DeserializeOwned>(&self, sql: &str, account_id: &AccountId) -> Result<Vec<T>, SqliteError> {
+        let mut statement = self.connection.prepare_cached(sql)?;
+        let rows = statement.query_map(params![account_id.as_str()], |row| row.get::<_, String>(0))?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+}
+
+impl CandleStore for SqliteStore {
+    type Error = SqliteError;
+
+    fn latest_candle_datetime(&self, symbol: &str) -> Result<Option<i64>, Self::Error> {
+        SqliteStore::latest_candle_datetime(self, symbol)
+    }
+
+    fn upsert_candles(&mut self, symbol: &str, candles: &[Candle]) -> Result<usize, Self::Error> {
+        SqliteStore::upsert_candles(self, symbol, candles)
+    }
+}
+
+fn utc_string(time: &TdaDateTime) -> String {
+    time.with_timezone(&Utc).to_rfc3339()
+}
+
+/// Errors encountered while reading or writing the database.
+#[derive(Debug, Error)]
+pub enum SqliteError {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// A value couldn't be converted to or from the JSON of a `data` column.
+    #[error("Failed to convert stored JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::GetTransactionsResponse;
+
+    fn candle(datetime: i64, close: f64) -> Candle {
+        Candle {
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 100,
+            datetime,
+        }
+    }
+
+    #[test]
+    fn upserts_candles() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+
+        store.upsert_candles("AAPL", &[candle(1, 1.0), candle(2, 2.0)]).unwrap();
+        store.upsert_candles("AAPL", &[candle(2, 2.5), candle(3, 3.0)]).unwrap();
+
+        assert_eq!(store.candles("AAPL", None, None).unwrap(), vec![candle(1, 1.0), candle(2, 2.5), candle(3, 3.0)]);
+        assert_eq!(store.candles("AAPL", Some(2), Some(2)).unwrap(), vec![candle(2, 2.5)]);
+        assert_eq!(store.latest_candle_datetime("AAPL").unwrap(), Some(3));
+        assert_eq!(store.latest_candle_datetime("MSFT").unwrap(), None);
+    }
+
+    #[test]
+    fn upserts_account_data() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let account_id = AccountId::from("123456789");
+        let transactions: GetTransactionsResponse = serde_json::from_str(include_str!("../../tests/fixtures/transactions.json")).unwrap();
+        let orders: Vec<Order> = serde_json::from_str(include_str!("../../tests/fixtures/orders.json")).unwrap();
+        let quotes: GetQuotesResponse = serde_json::from_str(include_str!("../../tests/fixtures/quotes.json")).unwrap();
+
+        store.upsert_transactions(&account_id, &transactions).unwrap();
+        store.upsert_transactions(&account_id, &transactions[..1]).unwrap();
+        store.upsert_orders(&account_id, &orders).unwrap();
+        store.upsert_quotes(&quotes, Utc::now()).unwrap();
+
+        let stored = store.transactions(&account_id).unwrap();
+        let symbol: Option<String> = store
+            .connection()
+            .query_row("SELECT symbol FROM transactions WHERE type = 'TRADE'", [], |row| row.get(0))
+            .unwrap();
+        let quote_count: i64 = store.connection().query_row("SELECT COUNT(*) FROM quotes", [], |row| row.get(0)).unwrap();
+
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[0].transaction_type, "ELECTRONIC_FUND");
+        assert_eq!(symbol.as_deref(), Some("AAPL"));
+        assert_eq!(store.orders(&account_id).unwrap().len(), orders.iter().filter(|order| order.order_id.is_some()).count());
+        assert_eq!(quote_count, quotes.len() as i64);
+    }
+}
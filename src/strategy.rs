@@ -0,0 +1,206 @@
+//! Callback API for writing simple trading bots.
+//!
+//! Implement [`Strategy`](trait.Strategy.html) and hand it to
+//! [`run()`](fn.run.html), which streams quotes, bars, and fills for an
+//! account and places whatever orders the strategy submits. The runner is only
+//! available with the `streamer` feature enabled.
+//!
+//! ```no_run
+//! # #[cfg(feature = "streamer")]
+//! # {
+//! use tda_sdk::{
+//!     orders::{Instruction, Order},
+//!     strategy::{run, OrderSink, Strategy},
+//!     streamer::LevelOneQuote,
+//!     Client,
+//! };
+//!
+//! struct BuyTheDip {
+//!     bought: bool,
+//! }
+//!
+//! impl Strategy for BuyTheDip {
+//!     fn on_quote(&mut self, quote: &LevelOneQuote, orders: &mut dyn OrderSink) {
+//!         if !self.bought && quote.net_change.unwrap_or_default() < -5.0 {
+//!             self.bought = orders.place_order(&Order::equity_market(Instruction::Buy, &quote.symbol, 1.0)).is_ok();
+//!         }
+//!     }
+//! }
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! client.set_access_token(&Some(client.get_access_token().unwrap().into()));
+//!
+//! run(&client, "123456789", &["AAPL"], &mut BuyTheDip { bought: false }).unwrap();
+//! # }
+//! ```
+
+use crate::{
+    ids::{AccountId, OrderId},
+    orders::Order,
+    responses::Candle,
+    streamer::LevelOneQuote,
+    Client,
+    ClientError,
+};
+
+/// Callbacks invoked by a strategy runner. Every callback has an empty default
+/// implementation, so only the relevant ones need to be written.
+pub trait Strategy {
+    /// Called with the full, merged quote whenever a symbol's quote changes.
+    fn on_quote(&mut self, _quote: &LevelOneQuote, _orders: &mut dyn OrderSink) {}
+
+    /// Called whenever a bar closes for a symbol.
+    fn on_bar(&mut self, _symbol: &str, _bar: &Candle, _orders: &mut dyn OrderSink) {}
+
+    /// Called whenever an order in the account is filled.
+    fn on_fill(&mut self, _fill: &Fill, _orders: &mut dyn OrderSink) {}
+}
+
+/// Destination for the orders submitted by a [`Strategy`](trait.Strategy.html).
+pub trait OrderSink {
+    /// Place an order and return its ID.
+    fn place_order(&mut self, order: &Order) -> Result<OrderId, ClientError>;
+}
+
+/// Places orders in a live account.
+pub struct LiveOrders<'a> {
+    client: &'a Client,
+    account_id: AccountId,
+}
+
+impl<'a> LiveOrders<'a> {
+    /// Create a sink placing orders in `account_id`.
+    pub fn new(client: &'a Client, account_id: impl Into<AccountId>) -> Self {
+        Self {
+            client,
+            account_id: account_id.into(),
+        }
+    }
+}
+
+impl OrderSink for LiveOrders<'_> {
+    fn place_order(&mut self, order: &Order) -> Result<OrderId, ClientError> {
+        self.client.place_order(&self.account_id, order)
+    }
+}
+
+/// An order fill.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fill {
+    pub account_id: AccountId,
+    pub order_id: OrderId,
+    pub symbol: String,
+    /// Choices: `Buy`, `Sell`, `BuyToCover`, or `SellShort`
+    pub instruction: String,
+    pub quantity: f64,
+    pub price: f64,
+}
+
+impl Fill {
+    /// Parse the XML body of an `OrderFill` account activity message.
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        Some(Self {
+            account_id: AccountId::from(xml_tag(xml, "AccountKey")?),
+            order_id: xml_tag(xml, "OrderKey")?.parse().ok()?,
+            symbol: xml_tag(xml, "Symbol")?.to_string(),
+            instruction: xml_tag(xml, "OrderInstructions")?.to_string(),
+            quantity: xml_tag(xml, "Quantity")?.parse().ok()?,
+            price: xml_tag(xml, "ExecutionPrice")?.parse().ok()?,
+        })
+    }
+}
+
+/// Text of the last `<tag>` element in `xml`.
+///
+/// Fill messages repeat some tags (such as `Quantity`) for the order and the
+/// execution, and the execution always comes last.
+fn xml_tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.rfind(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+
+    Some(xml[start..end].trim())
+}
+
+#[cfg(feature = "streamer")]
+pub use self::runner::run;
+
+#[cfg(feature = "streamer")]
+mod runner {
+    use super::{LiveOrders, Strategy};
+    use crate::{
+        ids::AccountId,
+        params::GetUserPrincipalsParams,
+        streamer::{LevelOneQuote, StreamerClient, StreamerError, StreamerEvent, StreamerSession},
+        Client,
+    };
+
+    use std::collections::HashMap;
+
+    /// Stream quotes and bars for `symbols` and fills for `account_id` into
+    /// `strategy`, placing its orders in the same account.
+    ///
+    /// Blocks until the connection fails.
+    pub fn run<S: Strategy>(client: &Client, account_id: impl Into<AccountId>, symbols: &[&str], strategy: &mut S) -> Result<(), StreamerError> {
+        let account_id = account_id.into();
+        let principals = client.get_user_principals(GetUserPrincipalsParams {
+            fields: Some("streamerSubscriptionKeys,streamerConnectionInfo".to_string()),
+        })?;
+        let mut streamer = StreamerClient::connect(StreamerSession::new(&principals)?)?;
+        let mut orders = LiveOrders::new(client, &account_id);
+        let mut quotes: HashMap<String, LevelOneQuote> = HashMap::new();
+
+        let requests = [
+            streamer.session().subscribe_quotes(symbols),
+            streamer.session().subscribe_chart(symbols),
+        ];
+
+        for request in requests.iter().chain(streamer.session().subscribe_account_activity().as_ref()) {
+            streamer.send(request)?;
+        }
+
+        loop {
+            for event in streamer.read()? {
+                match event {
+                    StreamerEvent::Quote(update) => {
+                        let quote = quotes.entry(update.symbol.clone()).or_insert_with(|| LevelOneQuote {
+                            symbol: update.symbol.clone(),
+                            ..LevelOneQuote::default()
+                        });
+
+                        quote.merge(&update);
+                        strategy.on_quote(quote, &mut orders);
+                    }
+                    StreamerEvent::Bar { symbol, bar } => strategy.on_bar(&symbol, &bar, &mut orders),
+                    StreamerEvent::AccountActivity(activity) => {
+                        if let Some(fill) = activity.fill().filter(|fill| fill.account_id == account_id) {
+                            strategy.on_fill(&fill, &mut orders);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_from_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?><OrderFillMessage xmlns="urn:xmlns:beb.ameritrade.com"><OrderGroupID><Firm>310</Firm><Branch>864</Branch><ClientKey>123456789</ClientKey><AccountKey>123456789</AccountKey><SubAccountType>Margin</SubAccountType></OrderGroupID><ActivityTimestamp>2020-05-04T10:00:00.000-05:00</ActivityTimestamp><Order><OrderKey>4321</OrderKey><Security><CUSIP>037833100</CUSIP><Symbol>AAPL</Symbol><SecurityType>Common Stock</SecurityType></Security><OrderPricing><Limit>121</Limit></OrderPricing><OrderType>Limit</OrderType><OrderDuration>Day</OrderDuration><OrderInstructions>Buy</OrderInstructions><OriginalQuantity>10</OriginalQuantity></Order><OrderCompletionCode>NormalCompletion</OrderCompletionCode><ExecutionInformation><Type>Bought</Type><Timestamp>2020-05-04T10:00:00.000-05:00</Timestamp><Quantity>10</Quantity><ExecutionPrice>120.5</ExecutionPrice><AveragePriceIndicator>false</AveragePriceIndicator><LeavesQuantity>0</LeavesQuantity></ExecutionInformation></OrderFillMessage>"#;
+
+        assert_eq!(Fill::from_xml(xml), Some(Fill {
+            account_id: AccountId::from("123456789"),
+            order_id: OrderId::new(4321),
+            symbol: "AAPL".to_string(),
+            instruction: "Buy".to_string(),
+            quantity: 10.0,
+            price: 120.5,
+        }));
+        assert_eq!(Fill::from_xml("<OrderCancelReplaceRequestMessage/>"), None);
+    }
+}
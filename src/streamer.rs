@@ -0,0 +1,1335 @@
+//! Structs and utilities for the websocket streamer.
+//!
+//! The streamer pushes quotes, chart bars, and account activity over a single
+//! websocket connection. Requests are built by a
+//! [`StreamerSession`](struct.StreamerSession.html) from the `streamerInfo` of
+//! the user principals, and incoming messages are decoded with
+//! [`parse_message()`](fn.parse_message.html).
+//!
+//! The websocket connection itself is only available with the `streamer`
+//! feature enabled. Its events can be handed to a slower consumer through a
+//! bounded [`EventQueue`](struct.EventQueue.html), which decides what gives
+//! when the consumer falls behind:
+//!
+//! ```no_run
+//! # #[cfg(feature = "streamer")]
+//! # fn run(mut client: tda_sdk::streamer::StreamerClient) {
+//! use std::{sync::Arc, thread};
+//! use tda_sdk::streamer::{EventQueue, OverflowPolicy};
+//!
+//! let queue = Arc::new(EventQueue::new(10_000, OverflowPolicy::CoalesceQuotes));
+//! let reader = queue.clone();
+//!
+//! thread::spawn(move || client.deliver(&reader));
+//!
+//! for event in queue.iter() {
+//!     println!("{:?}", event);
+//! }
+//! # }
+//! ```
+//!
+//! [API Documentation](https://developer.tdameritrade.com/content/streaming-data)
+
+use crate::{
+    flexible::normalize_epoch_millis,
+    ids::AccountId,
+    params::QuoteField,
+    responses::{Candle, UserPrincipals},
+    strategy::Fill,
+    ClientError,
+};
+use chrono::DateTime;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex},
+};
+
+/// Fields requested by [`StreamerSession::subscribe_quotes()`](struct.StreamerSession.html#method.subscribe_quotes).
+pub const QUOTE_FIELDS: &str = "0,1,2,3,4,5,8,9,10,11,12,13,15,28,29";
+
+/// Fields requested by [`StreamerSession::subscribe_chart()`](struct.StreamerSession.html#method.subscribe_chart).
+pub const CHART_FIELDS: &str = "0,1,2,3,4,5,6,7,8";
+
+/// Fields requested by [`StreamerSession::subscribe_chart_futures()`](struct.StreamerSession.html#method.subscribe_chart_futures).
+pub const CHART_FUTURES_FIELDS: &str = "0,1,2,3,4,5,6";
+
+/// Fields requested by [`StreamerSession::subscribe_futures_active_symbols()`](struct.StreamerSession.html#method.subscribe_futures_active_symbols).
+pub const FUTURES_ACTIVE_SYMBOL_FIELDS: &str = "0,34,35";
+
+/// Fields requested by [`StreamerSession::subscribe_account_activity()`](struct.StreamerSession.html#method.subscribe_account_activity).
+pub const ACCOUNT_ACTIVITY_FIELDS: &str = "0,1,2,3";
+
+/// Builds the requests sent to the streamer.
+///
+/// Every request needs the account and app ID it was logged in with, and a
+/// unique request ID, so the session keeps track of them.
+#[derive(Clone, Debug)]
+pub struct StreamerSession {
+    account_id: AccountId,
+    app_id: String,
+    credential: String,
+    next_request_id: u64,
+    subscription_key: Option<String>,
+    subscriptions: SubscriptionState,
+    token: String,
+    url: String,
+}
+
+impl StreamerSession {
+    /// Create a session from user principals fetched with the
+    /// `streamerSubscriptionKeys` and `streamerConnectionInfo` fields.
+    pub fn new(principals: &UserPrincipals) -> Result<Self, StreamerError> {
+        let info = principals.streamer_info.as_ref().ok_or(StreamerError::MissingStreamerInfo)?;
+        let account = principals.primary_account().ok_or(StreamerError::MissingStreamerInfo)?;
+        let timestamp = DateTime::parse_from_str(&info.token_timestamp, "%Y-%m-%dT%H:%M:%S%z")
+            .map_err(|_| StreamerError::InvalidTokenTimestamp(info.token_timestamp.clone()))?;
+
+        let credential = [
+            ("userid", account.account_id.as_str()),
+            ("token", &info.token),
+            ("company", &account.company),
+            ("segment", &account.segment),
+            ("cddomain", &account.account_cd_domain_id),
+            ("usergroup", &info.user_group),
+            ("accesslevel", &info.access_level),
+            ("authorized", "Y"),
+            ("timestamp", &timestamp.timestamp_millis().to_string()),
+            ("appid", &info.app_id),
+            ("acl", &info.acl),
+        ]
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, url_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+        let subscription_key = principals.subscription_keys().first().map(|key| key.to_string());
+
+        Ok(Self {
+            account_id: account.account_id.clone(),
+            app_id: info.app_id.clone(),
+            credential,
+            next_request_id: 0,
+            subscription_key,
+            subscriptions: SubscriptionState::default(),
+            token: info.token.clone(),
+            url: format!("wss://{}/ws", info.streamer_socket_url),
+        })
+    }
+
+    /// Websocket URL of the streamer.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Account the session is logged in with.
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    /// The `LOGIN` request, which must be sent before any other.
+    pub fn login(&mut self) -> Value {
+        self.request("ADMIN", "LOGIN", json!({
+            "credential": self.credential,
+            "token": self.token,
+            "version": "1.0",
+        }))
+    }
+
+    /// The `LOGOUT` request.
+    pub fn logout(&mut self) -> Value {
+        self.request("ADMIN", "LOGOUT", json!({}))
+    }
+
+    /// Subscribe to level one equity quotes.
+    pub fn subscribe_quotes(&mut self, symbols: &[&str]) -> Value {
+        self.subscribe("QUOTE", &symbols.join(","), QUOTE_FIELDS)
+    }
+
+    /// Subscribe to level one equity quotes with only the given fields,
+    /// along with the symbol.
+    pub fn subscribe_quote_fields(&mut self, symbols: &[&str], fields: &[QuoteField]) -> Value {
+        self.subscribe("QUOTE", &symbols.join(","), &QuoteField::streamer_fields(fields))
+    }
+
+    /// Subscribe to one minute equity chart bars.
+    pub fn subscribe_chart(&mut self, symbols: &[&str]) -> Value {
+        self.subscribe("CHART_EQUITY", &symbols.join(","), CHART_FIELDS)
+    }
+
+    /// Subscribe to one minute futures chart bars. Futures symbols must name
+    /// a contract (`/ESM21`), see
+    /// [`subscribe_futures_active_symbols()`](#method.subscribe_futures_active_symbols).
+    pub fn subscribe_chart_futures(&mut self, symbols: &[&str]) -> Value {
+        self.subscribe("CHART_FUTURES", &symbols.join(","), CHART_FUTURES_FIELDS)
+    }
+
+    /// Subscribe to the active contract of futures roots such as `/ES`.
+    ///
+    /// The streamer answers with a
+    /// [`StreamerEvent::FuturesActiveSymbol`](enum.StreamerEvent.html#variant.FuturesActiveSymbol)
+    /// for each root.
+    pub fn subscribe_futures_active_symbols(&mut self, roots: &[&str]) -> Value {
+        self.subscribe("LEVELONE_FUTURES", &roots.join(","), FUTURES_ACTIVE_SYMBOL_FIELDS)
+    }
+
+    /// Subscribe to account activity, which includes order fills.
+    ///
+    /// Returns `None` if the principals had no streamer subscription key.
+    pub fn subscribe_account_activity(&mut self) -> Option<Value> {
+        let key = self.subscription_key.clone()?;
+
+        Some(self.subscribe("ACCT_ACTIVITY", &key, ACCOUNT_ACTIVITY_FIELDS))
+    }
+
+    /// A `SUBS` request for any service.
+    ///
+    /// `SUBS` replaces any earlier subscription to the same service, and the
+    /// session's [`subscriptions()`](#method.subscriptions) are updated to
+    /// match.
+    pub fn subscribe(&mut self, service: &str, keys: &str, fields: &str) -> Value {
+        self.subscriptions.record(Subscription {
+            service: service.to_string(),
+            keys: keys.split(',').map(str::trim).filter(|key| !key.is_empty()).map(str::to_string).collect(),
+            fields: fields.to_string(),
+        });
+
+        self.request(service, "SUBS", json!({ "keys": keys, "fields": fields }))
+    }
+
+    /// An `ADD` request, which adds symbols to the subscription to `service`
+    /// with its current fields, or the default fields of the service if it
+    /// has no subscription yet.
+    pub fn add_symbols(&mut self, service: &str, symbols: &[&str]) -> Value {
+        let fields = self
+            .subscriptions
+            .get(service)
+            .map_or_else(|| default_fields(service).to_string(), |subscription| subscription.fields.clone());
+        let mut subscription = self.subscriptions.get(service).cloned().unwrap_or_else(|| Subscription {
+            service: service.to_string(),
+            keys: Vec::new(),
+            fields: fields.clone(),
+        });
+
+        for symbol in symbols {
+            if !subscription.keys.iter().any(|key| key == symbol) {
+                subscription.keys.push(symbol.to_string());
+            }
+        }
+
+        self.subscriptions.record(subscription);
+        self.request(service, "ADD", json!({ "keys": symbols.join(","), "fields": fields }))
+    }
+
+    /// An `UNSUBS` request for some symbols of the subscription to `service`.
+    /// The subscription is dropped once none of its symbols are left.
+    pub fn remove_symbols(&mut self, service: &str, symbols: &[&str]) -> Value {
+        if let Some(mut subscription) = self.subscriptions.get(service).cloned() {
+            subscription.keys.retain(|key| !symbols.contains(&key.as_str()));
+            self.subscriptions.record(subscription);
+        }
+
+        self.request(service, "UNSUBS", json!({ "keys": symbols.join(",") }))
+    }
+
+    /// A `SUBS` request changing the fields of the subscription to `service`,
+    /// keeping its symbols, or `None` if there is no such subscription.
+    pub fn set_fields(&mut self, service: &str, fields: &str) -> Option<Value> {
+        let keys = self.subscriptions.get(service)?.keys.join(",");
+
+        Some(self.subscribe(service, &keys, fields))
+    }
+
+    /// Cancel the subscription to `service`.
+    pub fn unsubscribe(&mut self, service: &str) -> Value {
+        let keys = self.subscriptions.symbols(service).join(",");
+
+        self.subscriptions.subscriptions.retain(|subscription| subscription.service != service);
+        self.request(service, "UNSUBS", json!({ "keys": keys }))
+    }
+
+    /// Subscriptions requested through this session.
+    pub fn subscriptions(&self) -> &SubscriptionState {
+        &self.subscriptions
+    }
+
+    /// The `SUBS` requests which restore every subscription in `state`.
+    ///
+    /// Account activity is resubscribed with this session's subscription key,
+    /// since the saved one may have expired.
+    pub fn resume(&mut self, state: &SubscriptionState) -> Vec<Value> {
+        state
+            .subscriptions
+            .iter()
+            .map(|subscription| {
+                let keys = match (subscription.service.as_str(), &self.subscription_key) {
+                    ("ACCT_ACTIVITY", Some(key)) => key.clone(),
+                    _ => subscription.keys.join(","),
+                };
+
+                self.subscribe(&subscription.service, &keys, &subscription.fields)
+            })
+            .collect()
+    }
+
+    fn request(&mut self, service: &str, command: &str, parameters: Value) -> Value {
+        let request_id = self.next_request_id;
+
+        self.next_request_id += 1;
+
+        json!({
+            "requests": [{
+                "service": service,
+                "command": command,
+                "requestid": request_id.to_string(),
+                "account": self.account_id.as_str(),
+                "source": self.app_id,
+                "parameters": parameters,
+            }]
+        })
+    }
+}
+
+/// Subscriptions of a streamer session, which can be saved and used to
+/// resume them after a restart.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SubscriptionState {
+    pub subscriptions: Vec<Subscription>,
+}
+
+impl SubscriptionState {
+    /// Subscription to `service`, if any.
+    pub fn get(&self, service: &str) -> Option<&Subscription> {
+        self.subscriptions.iter().find(|subscription| subscription.service == service)
+    }
+
+    /// Symbols subscribed to on `service`.
+    pub fn symbols(&self, service: &str) -> &[String] {
+        self.get(service).map_or(&[], |subscription| &subscription.keys)
+    }
+
+    fn record(&mut self, subscription: Subscription) {
+        self.subscriptions.retain(|existing| existing.service != subscription.service);
+
+        if !subscription.keys.is_empty() {
+            self.subscriptions.push(subscription);
+        }
+    }
+}
+
+/// Individual subscription in [`SubscriptionState`](struct.SubscriptionState.html)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Subscription {
+    pub service: String,
+    pub keys: Vec<String>,
+    pub fields: String,
+}
+
+/// A single decoded item of a streamer message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamerEvent {
+    /// Level one quote update. Only the fields which changed are set.
+    Quote(LevelOneQuote),
+
+    /// Chart bar for a symbol.
+    Bar { symbol: String, bar: Candle },
+
+    /// Account activity, such as order fills.
+    AccountActivity(AccountActivity),
+
+    /// Active contract of a futures root, such as `/ESM21` for `/ES`.
+    FuturesActiveSymbol {
+        root: String,
+        active_symbol: String,
+        /// Expiration of the active contract, in milliseconds since epoch.
+        expiration: Option<i64>,
+    },
+
+    /// Response to a request sent by the client.
+    Response {
+        service: String,
+        command: String,
+        code: i64,
+        message: String,
+    },
+
+    /// Heartbeat sent by the streamer to keep the connection alive.
+    Heartbeat,
+
+    /// Anything this crate doesn't decode.
+    Other(Value),
+}
+
+/// Level one equity quote from the `QUOTE` service.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelOneQuote {
+    pub symbol: String,
+    pub ask_price: Option<f64>,
+    pub ask_size: Option<f64>,
+    pub bid_price: Option<f64>,
+    pub bid_size: Option<f64>,
+    pub close_price: Option<f64>,
+    pub high_price: Option<f64>,
+    pub last_price: Option<f64>,
+    pub last_size: Option<f64>,
+    pub low_price: Option<f64>,
+    pub net_change: Option<f64>,
+    pub open_price: Option<f64>,
+    pub quote_time: Option<i64>,
+    pub total_volume: Option<f64>,
+    pub trade_time: Option<i64>,
+}
+
+impl LevelOneQuote {
+    /// Copy the fields set in `update` over this quote.
+    ///
+    /// The streamer only sends fields which changed, so merging each update
+    /// into the previous quote gives the full picture.
+    pub fn merge(&mut self, update: &LevelOneQuote) {
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(if update.$field.is_some() {
+                    self.$field = update.$field;
+                })*
+            };
+        }
+
+        merge!(
+            ask_price, ask_size, bid_price, bid_size, close_price, high_price, last_price, last_size, low_price,
+            net_change, open_price, quote_time, total_volume, trade_time
+        );
+    }
+
+    fn from_content(content: &Map<String, Value>) -> Self {
+        Self {
+            symbol: field_str(content, "key").unwrap_or_default(),
+            bid_price: field_f64(content, "1"),
+            ask_price: field_f64(content, "2"),
+            last_price: field_f64(content, "3"),
+            bid_size: field_f64(content, "4"),
+            ask_size: field_f64(content, "5"),
+            total_volume: field_f64(content, "8"),
+            last_size: field_f64(content, "9"),
+            trade_time: field_f64(content, "10").map(|time| time as i64),
+            quote_time: field_f64(content, "11").map(|time| time as i64),
+            high_price: field_f64(content, "12"),
+            low_price: field_f64(content, "13"),
+            close_price: field_f64(content, "15"),
+            open_price: field_f64(content, "28"),
+            net_change: field_f64(content, "29"),
+        }
+    }
+}
+
+/// Account activity from the `ACCT_ACTIVITY` service.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountActivity {
+    pub account: String,
+    /// Type of the message, such as `OrderFill`, `OrderEntryRequest`, or
+    /// `SUBSCRIBED`.
+    pub message_type: String,
+    /// XML body of the message.
+    pub message_data: String,
+}
+
+impl AccountActivity {
+    /// The fill described by an `OrderFill` message.
+    pub fn fill(&self) -> Option<Fill> {
+        if self.message_type != "OrderFill" {
+            return None;
+        }
+
+        Fill::from_xml(&self.message_data)
+    }
+}
+
+/// What an [`EventQueue`](struct.EventQueue.html) does with an event when it
+/// is full.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Wait for the consumer to make room, holding back the streamer.
+    #[default]
+    Block,
+
+    /// Drop the oldest queued event to make room.
+    DropOldest,
+
+    /// Merge a quote into the queued quote of the same symbol, so the
+    /// consumer gets the latest values without every tick. Other events, and
+    /// quotes of symbols not queued, wait for room.
+    CoalesceQuotes,
+}
+
+/// Bounded queue of streamer events between the connection and a consumer.
+///
+/// Shared between threads, typically in an `Arc`.
+#[derive(Debug)]
+pub struct EventQueue {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    events: VecDeque<StreamerEvent>,
+    dropped: u64,
+    closed: bool,
+}
+
+impl EventQueue {
+    /// Queue holding up to `capacity` events.
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+            state: Mutex::default(),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Add an event, applying the overflow policy if the queue is full.
+    /// Returns `false` if the queue was closed and the event discarded.
+    pub fn push(&self, event: StreamerEvent) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if self.policy == OverflowPolicy::CoalesceQuotes && state.events.len() >= self.capacity {
+            if let StreamerEvent::Quote(update) = &event {
+                let queued = state.events.iter_mut().find_map(|queued| match queued {
+                    StreamerEvent::Quote(quote) if quote.symbol == update.symbol => Some(quote),
+                    _ => None,
+                });
+
+                if let Some(quote) = queued {
+                    quote.merge(update);
+
+                    return !state.closed;
+                }
+            }
+        }
+
+        while !state.closed && state.events.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.events.pop_front();
+                    state.dropped += 1;
+                }
+                OverflowPolicy::Block | OverflowPolicy::CoalesceQuotes => state = self.changed.wait(state).unwrap(),
+            }
+        }
+
+        if state.closed {
+            return false;
+        }
+
+        state.events.push_back(event);
+        self.changed.notify_all();
+
+        true
+    }
+
+    /// Take the oldest event, blocking until one arrives. Returns `None` once
+    /// the queue is closed and empty.
+    pub fn pop(&self) -> Option<StreamerEvent> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                self.changed.notify_all();
+
+                return Some(event);
+            }
+
+            if state.closed {
+                return None;
+            }
+
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Take every queued event without blocking.
+    pub fn drain(&self) -> Vec<StreamerEvent> {
+        let events = self.state.lock().unwrap().events.drain(..).collect();
+
+        self.changed.notify_all();
+
+        events
+    }
+
+    /// Events until the queue is closed and empty.
+    pub fn iter(&self) -> impl Iterator<Item = StreamerEvent> + '_ {
+        std::iter::from_fn(move || self.pop())
+    }
+
+    /// Stop accepting events. Queued events can still be taken.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of events dropped to make room.
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+}
+
+/// Decode a text message received from the streamer.
+pub fn parse_message(message: &str) -> Result<Vec<StreamerEvent>, serde_json::Error> {
+    let message: Map<String, Value> = serde_json::from_str(message)?;
+    let mut events = Vec::new();
+
+    for item in message.get("response").and_then(Value::as_array).into_iter().flatten() {
+        let content = item.get("content");
+
+        events.push(StreamerEvent::Response {
+            service: item.get("service").and_then(Value::as_str).unwrap_or_default().to_string(),
+            command: item.get("command").and_then(Value::as_str).unwrap_or_default().to_string(),
+            code: content.and_then(|content| content.get("code")).and_then(Value::as_i64).unwrap_or_default(),
+            message: content.and_then(|content| content.get("msg")).and_then(Value::as_str).unwrap_or_default().to_string(),
+        });
+    }
+
+    for item in message.get("notify").and_then(Value::as_array).into_iter().flatten() {
+        if item.get("heartbeat").is_some() {
+            events.push(StreamerEvent::Heartbeat);
+        } else {
+            events.push(StreamerEvent::Other(item.clone()));
+        }
+    }
+
+    for item in message.get("data").and_then(Value::as_array).into_iter().flatten() {
+        let service = item.get("service").and_then(Value::as_str).unwrap_or_default();
+        let contents = item.get("content").and_then(Value::as_array);
+
+        for content in contents.into_iter().flatten().filter_map(Value::as_object) {
+            events.push(match service {
+                "QUOTE" => StreamerEvent::Quote(LevelOneQuote::from_content(content)),
+                "CHART_EQUITY" => StreamerEvent::Bar {
+                    symbol: field_str(content, "key").unwrap_or_default(),
+                    bar: Candle {
+                        open: field_f64(content, "1").unwrap_or_default(),
+                        high: field_f64(content, "2").unwrap_or_default(),
+                        low: field_f64(content, "3").unwrap_or_default(),
+                        close: field_f64(content, "4").unwrap_or_default(),
+                        volume: field_f64(content, "5").unwrap_or_default() as i64,
+                        datetime: normalize_epoch_millis(field_f64(content, "7").unwrap_or_default()),
+                    },
+                },
+                "CHART_FUTURES" => StreamerEvent::Bar {
+                    symbol: field_str(content, "key").unwrap_or_default(),
+                    bar: Candle {
+                        datetime: normalize_epoch_millis(field_f64(content, "1").unwrap_or_default()),
+                        open: field_f64(content, "2").unwrap_or_default(),
+                        high: field_f64(content, "3").unwrap_or_default(),
+                        low: field_f64(content, "4").unwrap_or_default(),
+                        close: field_f64(content, "5").unwrap_or_default(),
+                        volume: field_f64(content, "6").unwrap_or_default() as i64,
+                    },
+                },
+                "LEVELONE_FUTURES" if content.contains_key("34") => StreamerEvent::FuturesActiveSymbol {
+                    root: field_str(content, "key").unwrap_or_default(),
+                    active_symbol: field_str(content, "34").unwrap_or_default(),
+                    expiration: field_f64(content, "35").map(|expiration| expiration as i64),
+                },
+                "ACCT_ACTIVITY" => StreamerEvent::AccountActivity(AccountActivity {
+                    account: field_str(content, "1").unwrap_or_default(),
+                    message_type: field_str(content, "2").unwrap_or_default(),
+                    message_data: field_str(content, "3").unwrap_or_default(),
+                }),
+                _ => StreamerEvent::Other(Value::Object(content.clone())),
+            });
+        }
+    }
+
+    Ok(events)
+}
+
+/// Represents all possible errors encountered while streaming.
+#[derive(Debug, Error)]
+pub enum StreamerError {
+    /// The user principals were fetched without the streamer fields.
+    #[error("User principals do not include streamer info")]
+    MissingStreamerInfo,
+
+    /// The streamer token timestamp could not be parsed.
+    #[error("Invalid streamer token timestamp: {0}")]
+    InvalidTokenTimestamp(String),
+
+    /// The streamer rejected the login request.
+    #[error("Streamer login failed with code {0}: {1}")]
+    Login(i64, String),
+
+    /// A REST request made while setting up the stream failed.
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    /// A message from the streamer was not valid JSON.
+    #[error("Failed to parse streamer message: {0}")]
+    ParseMessage(#[from] serde_json::Error),
+
+    /// The streamer didn't send the active contract of these futures roots
+    /// within the messages read while resolving them.
+    #[error("Could not resolve futures roots: {}", .0.join(", "))]
+    UnresolvedFutures(Vec<String>),
+
+    /// The websocket connection failed.
+    #[cfg(feature = "streamer")]
+    #[error("Websocket error: {0}")]
+    WebSocket(#[from] Box<tungstenite::Error>),
+}
+
+#[cfg(feature = "streamer")]
+pub use self::connection::StreamerClient;
+
+#[cfg(feature = "streamer")]
+mod connection {
+    use super::{parse_message, EventQueue, StreamerError, StreamerEvent, StreamerSession, SubscriptionState};
+    use crate::shutdown::Shutdown;
+    use serde_json::Value;
+    use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+    use std::{collections::HashMap, io, net::TcpStream, time::Duration};
+
+    /// How long closing waits for the streamer to acknowledge.
+    const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// How many messages resolving futures roots reads before giving up on
+    /// the roots the streamer hasn't answered for.
+    const RESOLVE_MAX_READS: usize = 100;
+
+    /// Websocket connection to the streamer.
+    ///
+    /// The connection is closed with [`close()`](#method.close), or when the
+    /// client is dropped, logging out first. Connections dropped without
+    /// logging out leave a session open on the streamer, which can reject
+    /// the next login for a while.
+    pub struct StreamerClient {
+        session: StreamerSession,
+        socket: WebSocket<MaybeTlsStream<TcpStream>>,
+        closed: bool,
+    }
+
+    impl StreamerClient {
+        /// Connect to the streamer and log in.
+        pub fn connect(mut session: StreamerSession) -> Result<Self, StreamerError> {
+            let (socket, _) = tungstenite::connect(session.url()).map_err(Box::new)?;
+            let login = session.login();
+            let mut client = Self { session, socket, closed: false };
+
+            client.send(&login)?;
+
+            loop {
+                for event in client.read()? {
+                    if let StreamerEvent::Response { command, code, message, .. } = event {
+                        if command == "LOGIN" {
+                            return match code {
+                                0 => Ok(client),
+                                _ => Err(StreamerError::Login(code, message)),
+                            };
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Connect to the streamer, log in, and restore the subscriptions
+        /// saved in `state`.
+        pub fn resume(session: StreamerSession, state: &SubscriptionState) -> Result<Self, StreamerError> {
+            let mut client = Self::connect(session)?;
+
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().inc_streamer_reconnects();
+
+            for request in client.session.resume(state) {
+                client.send(&request)?;
+            }
+
+            Ok(client)
+        }
+
+        /// Subscriptions currently active on this connection.
+        pub fn subscriptions(&self) -> &SubscriptionState {
+            self.session.subscriptions()
+        }
+
+        /// Session used to build requests for this connection.
+        pub fn session(&mut self) -> &mut StreamerSession {
+            &mut self.session
+        }
+
+        /// Send a request built by the session.
+        pub fn send(&mut self, request: &Value) -> Result<(), StreamerError> {
+            self.socket.send(Message::text(request.to_string())).map_err(Box::new)?;
+
+            Ok(())
+        }
+
+        /// Subscribe to `service`, replacing any earlier subscription to it.
+        pub fn subscribe(&mut self, service: &str, keys: &str, fields: &str) -> Result<(), StreamerError> {
+            let request = self.session.subscribe(service, keys, fields);
+
+            self.send(&request)
+        }
+
+        /// Cancel the subscription to `service`.
+        pub fn unsubscribe(&mut self, service: &str) -> Result<(), StreamerError> {
+            let request = self.session.unsubscribe(service);
+
+            self.send(&request)
+        }
+
+        /// Add symbols to the subscription to `service`.
+        pub fn add_symbols(&mut self, service: &str, symbols: &[&str]) -> Result<(), StreamerError> {
+            let request = self.session.add_symbols(service, symbols);
+
+            self.send(&request)
+        }
+
+        /// Remove symbols from the subscription to `service`.
+        pub fn remove_symbols(&mut self, service: &str, symbols: &[&str]) -> Result<(), StreamerError> {
+            let request = self.session.remove_symbols(service, symbols);
+
+            self.send(&request)
+        }
+
+        /// Change the fields of the subscription to `service`. Does nothing
+        /// if there is no such subscription.
+        pub fn set_fields(&mut self, service: &str, fields: &str) -> Result<(), StreamerError> {
+            match self.session.set_fields(service, fields) {
+                Some(request) => self.send(&request),
+                None => Ok(()),
+            }
+        }
+
+        /// Block until the next message arrives and decode it.
+        ///
+        /// Control frames are answered automatically and yield no events.
+        pub fn read(&mut self) -> Result<Vec<StreamerEvent>, StreamerError> {
+            match self.socket.read().map_err(Box::new)? {
+                Message::Text(text) => Ok(parse_message(text.as_str())?),
+                _ => Ok(Vec::new()),
+            }
+        }
+
+        /// Read events into a queue until the connection fails or the queue
+        /// is closed. The queue is closed when this returns, so its consumer
+        /// stops once it is drained.
+        pub fn deliver(&mut self, queue: &EventQueue) -> Result<(), StreamerError> {
+            let result = (|| loop {
+                for event in self.read()? {
+                    if !queue.push(event) {
+                        return Ok(());
+                    }
+                }
+            })();
+
+            queue.close();
+
+            result
+        }
+
+        /// Like [`deliver()`](#method.deliver), also returning once `shutdown`
+        /// is triggered, checked as messages arrive. The connection stays
+        /// open, to be closed with [`close()`](#method.close).
+        pub fn deliver_until(&mut self, queue: &EventQueue, shutdown: &Shutdown) -> Result<(), StreamerError> {
+            let result = (|| loop {
+                if shutdown.is_triggered() {
+                    return Ok(());
+                }
+
+                for event in self.read()? {
+                    if !queue.push(event) {
+                        return Ok(());
+                    }
+                }
+            })();
+
+            queue.close();
+
+            result
+        }
+
+        /// Resolve futures roots such as `/ES` to their active contract, so
+        /// that they can be used with
+        /// [`subscribe_chart_futures()`](struct.StreamerSession.html#method.subscribe_chart_futures).
+        ///
+        /// Blocks until every root is resolved, or fails with
+        /// `StreamerError::UnresolvedFutures` if some still aren't after
+        /// `RESOLVE_MAX_READS` messages. Events for other services received in
+        /// the meantime are returned alongside the symbols so they aren't
+        /// lost, and any existing `LEVELONE_FUTURES` subscription is restored
+        /// afterwards, either way.
+        pub fn resolve_futures(&mut self, roots: &[&str]) -> Result<(HashMap<String, String>, Vec<StreamerEvent>), StreamerError> {
+            let previous = self.session.subscriptions().clone();
+            let request = self.session.subscribe_futures_active_symbols(roots);
+            let mut symbols = HashMap::new();
+            let mut skipped = Vec::new();
+
+            self.send(&request)?;
+
+            for _ in 0..RESOLVE_MAX_READS {
+                if roots.iter().all(|root| symbols.contains_key(*root)) {
+                    break;
+                }
+
+                for event in self.read()? {
+                    match event {
+                        StreamerEvent::FuturesActiveSymbol { root, active_symbol, .. } => {
+                            symbols.insert(root, active_symbol);
+                        }
+                        event => skipped.push(event),
+                    }
+                }
+            }
+
+            let restore = match previous.subscriptions.iter().find(|subscription| subscription.service == "LEVELONE_FUTURES") {
+                Some(subscription) => self.session.subscribe(&subscription.service, &subscription.keys.join(","), &subscription.fields),
+                None => self.session.unsubscribe("LEVELONE_FUTURES"),
+            };
+
+            self.send(&restore)?;
+
+            let unresolved: Vec<String> = roots.iter().filter(|root| !symbols.contains_key(**root)).map(|root| root.to_string()).collect();
+
+            if !unresolved.is_empty() {
+                return Err(StreamerError::UnresolvedFutures(unresolved));
+            }
+
+            Ok((symbols, skipped))
+        }
+
+        /// Log out and close the connection, waiting a few seconds for the
+        /// streamer to acknowledge.
+        pub fn close(mut self) -> Result<(), StreamerError> {
+            self.logout_and_close()
+        }
+
+        fn logout_and_close(&mut self) -> Result<(), StreamerError> {
+            if self.closed {
+                return Ok(());
+            }
+
+            self.closed = true;
+
+            let logout = self.session.logout();
+
+            self.send(&logout)?;
+            self.socket.close(None).map_err(Box::new)?;
+            self.set_read_timeout(CLOSE_TIMEOUT);
+
+            // The close handshake completes once the streamer answers with a
+            // close frame of its own, which reading waits for.
+            loop {
+                match self.socket.read() {
+                    Ok(_) => {}
+                    Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => return Ok(()),
+                    Err(tungstenite::Error::Io(error)) if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Ok(()),
+                    Err(error) => return Err(Box::new(error).into()),
+                }
+            }
+        }
+
+        fn set_read_timeout(&self, timeout: Duration) {
+            let stream = match self.socket.get_ref() {
+                MaybeTlsStream::Plain(stream) => stream,
+                MaybeTlsStream::Rustls(stream) => &stream.sock,
+                _ => return,
+            };
+
+            let _ = stream.set_read_timeout(Some(timeout));
+        }
+    }
+
+    impl Drop for StreamerClient {
+        fn drop(&mut self) {
+            if let Err(error) = self.logout_and_close() {
+                log::debug!("Failed to log out of the streamer: {}", error);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::responses::UserPrincipals;
+        use std::{net::TcpListener, sync::mpsc, thread};
+
+        #[test]
+        fn logs_out_on_drop() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let (sender, receiver) = mpsc::channel();
+
+            let server = thread::spawn(move || {
+                let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+
+                loop {
+                    match socket.read() {
+                        Ok(Message::Text(text)) if text.contains("LOGIN") && !text.contains("LOGOUT") => {
+                            let response = r#"{"response":[{"service":"ADMIN","requestid":"0","command":"LOGIN","content":{"code":0,"msg":"ok"}}]}"#;
+
+                            socket.send(Message::text(response)).unwrap();
+                        }
+                        Ok(Message::Text(text)) => sender.send(text.to_string()).unwrap(),
+                        Ok(Message::Close(_)) => sender.send("CLOSE".to_string()).unwrap(),
+                        Ok(_) => {}
+                        Err(_) => break,
+                    }
+                }
+            });
+
+            let principals: UserPrincipals = serde_json::from_str(include_str!("../tests/fixtures/user_principals.json")).unwrap();
+            let mut session = StreamerSession::new(&principals).unwrap();
+
+            session.url = format!("ws://127.0.0.1:{}", port);
+            drop(StreamerClient::connect(session).unwrap());
+            server.join().unwrap();
+
+            let received: Vec<String> = receiver.iter().collect();
+
+            assert_eq!(received.len(), 2);
+            assert!(received[0].contains("LOGOUT"));
+            assert_eq!(received[1], "CLOSE");
+        }
+
+        #[test]
+        fn gives_up_on_unresolved_futures() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+
+            thread::spawn(move || {
+                let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+
+                loop {
+                    match socket.read() {
+                        Ok(Message::Text(text)) if text.contains("LOGIN") && !text.contains("LOGOUT") => {
+                            let response = r#"{"response":[{"service":"ADMIN","requestid":"0","command":"LOGIN","content":{"code":0,"msg":"ok"}}]}"#;
+
+                            socket.send(Message::text(response)).unwrap();
+                        }
+                        // Only ever answers for /ES.
+                        Ok(Message::Text(text)) if text.contains("\"SUBS\"") => {
+                            let data = r#"{"data":[{"service":"LEVELONE_FUTURES","timestamp":1620000000000,"command":"SUBS","content":[{"key":"/ES","34":"/ESM21","35":1623974400000}]}]}"#;
+
+                            for _ in 0..=RESOLVE_MAX_READS {
+                                socket.send(Message::text(data)).unwrap();
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(_) => break,
+                    }
+                }
+            });
+
+            let principals: UserPrincipals = serde_json::from_str(include_str!("../tests/fixtures/user_principals.json")).unwrap();
+            let mut session = StreamerSession::new(&principals).unwrap();
+
+            session.url = format!("ws://127.0.0.1:{}", port);
+
+            let mut client = StreamerClient::connect(session).unwrap();
+
+            assert!(matches!(
+                client.resolve_futures(&["/ES", "/NQ"]),
+                Err(StreamerError::UnresolvedFutures(roots)) if roots == ["/NQ"]
+            ));
+        }
+    }
+}
+
+/// Fields requested for `service` when no others were given.
+fn default_fields(service: &str) -> &'static str {
+    match service {
+        "QUOTE" => QUOTE_FIELDS,
+        "CHART_EQUITY" => CHART_FIELDS,
+        "CHART_FUTURES" => CHART_FUTURES_FIELDS,
+        "LEVELONE_FUTURES" => FUTURES_ACTIVE_SYMBOL_FIELDS,
+        "ACCT_ACTIVITY" => ACCOUNT_ACTIVITY_FIELDS,
+        _ => "0",
+    }
+}
+
+fn field_str(content: &Map<String, Value>, field: &str) -> Option<String> {
+    content.get(field).and_then(Value::as_str).map(str::to_string)
+}
+
+fn field_f64(content: &Map<String, Value>, field: &str) -> Option<f64> {
+    content.get(field).and_then(Value::as_f64)
+}
+
+/// Percent-encode a value for the login credential.
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'*' => (b as char).to_string(),
+            b' ' => "+".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principals() -> UserPrincipals {
+        serde_json::from_str(include_str!("../tests/fixtures/user_principals.json")).unwrap()
+    }
+
+    #[test]
+    fn login_request() {
+        let mut session = StreamerSession::new(&principals()).unwrap();
+        let login = session.login();
+        let request = &login["requests"][0];
+
+        assert_eq!(session.url(), "wss://streamer-ws.tdameritrade.com/ws");
+        assert_eq!(request["service"], "ADMIN");
+        assert_eq!(request["requestid"], "0");
+        assert_eq!(request["account"], "123456789");
+        assert_eq!(request["source"], "MYAPPID");
+
+        let credential = request["parameters"]["credential"].as_str().unwrap();
+
+        assert!(credential.starts_with("userid=123456789&token=STREAMER_TOKEN&company=AMER"));
+        assert!(credential.contains("&timestamp=1588600800000&"));
+        assert_eq!(session.subscribe_quotes(&["AAPL", "MSFT"])["requests"][0]["requestid"], "1");
+    }
+
+    #[test]
+    fn resume_subscriptions() {
+        let mut session = StreamerSession::new(&principals()).unwrap();
+
+        session.subscribe_quotes(&["AAPL", "MSFT"]);
+        session.subscribe_chart(&["AAPL"]);
+        session.subscribe_quotes(&["SPY"]);
+        session.subscribe_account_activity();
+
+        let saved = serde_json::to_string(session.subscriptions()).unwrap();
+        let state: SubscriptionState = serde_json::from_str(&saved).unwrap();
+        let services: Vec<&str> = state.subscriptions.iter().map(|subscription| subscription.service.as_str()).collect();
+
+        assert_eq!(services, vec!["CHART_EQUITY", "QUOTE", "ACCT_ACTIVITY"]);
+
+        let mut restarted = StreamerSession::new(&principals()).unwrap();
+        let requests = restarted.resume(&state);
+
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1]["requests"][0]["parameters"]["keys"], "SPY");
+        assert_eq!(requests[1]["requests"][0]["parameters"]["fields"], QUOTE_FIELDS);
+        assert_eq!(restarted.subscriptions(), &state);
+    }
+
+    #[test]
+    fn missing_streamer_info() {
+        let principals = UserPrincipals {
+            streamer_info: None,
+            ..principals()
+        };
+
+        assert!(matches!(StreamerSession::new(&principals), Err(StreamerError::MissingStreamerInfo)));
+    }
+
+    #[test]
+    fn parse_data() {
+        let events = parse_message(r#"{"data":[
+            {"service":"QUOTE","timestamp":1588600800000,"command":"SUBS","content":[{"key":"AAPL","1":120.1,"2":120.2,"3":120.15}]},
+            {"service":"CHART_EQUITY","timestamp":1588600800000,"command":"SUBS","content":[{"key":"AAPL","1":120.0,"2":121.0,"3":119.5,"4":120.5,"5":1000.0,"6":1,"7":1588600800000,"8":18386}]}
+        ]}"#).unwrap();
+
+        assert_eq!(events, vec![
+            StreamerEvent::Quote(LevelOneQuote {
+                symbol: "AAPL".to_string(),
+                bid_price: Some(120.1),
+                ask_price: Some(120.2),
+                last_price: Some(120.15),
+                ..LevelOneQuote::default()
+            }),
+            StreamerEvent::Bar {
+                symbol: "AAPL".to_string(),
+                bar: Candle {
+                    open: 120.0,
+                    high: 121.0,
+                    low: 119.5,
+                    close: 120.5,
+                    volume: 1000,
+                    datetime: 1588600800000,
+                },
+            },
+        ]);
+    }
+
+    #[test]
+    fn parse_futures() {
+        let events = parse_message(r#"{"data":[
+            {"service":"LEVELONE_FUTURES","timestamp":1620000000000,"command":"SUBS","content":[{"key":"/ES","34":"/ESM21","35":1623974400000}]},
+            {"service":"CHART_FUTURES","timestamp":1620000000000,"command":"SUBS","content":[{"key":"/ESM21","1":1620000000000,"2":4180.0,"3":4185.25,"4":4179.5,"5":4183.0,"6":1200.0}]}
+        ]}"#).unwrap();
+
+        assert_eq!(events[0], StreamerEvent::FuturesActiveSymbol {
+            root: "/ES".to_string(),
+            active_symbol: "/ESM21".to_string(),
+            expiration: Some(1623974400000),
+        });
+
+        match &events[1] {
+            StreamerEvent::Bar { symbol, bar } => {
+                assert_eq!(symbol, "/ESM21");
+                assert_eq!(bar.high, 4185.25);
+                assert_eq!(bar.datetime, 1620000000000);
+            }
+            event => panic!("expected a bar, got {:?}", event),
+        }
+    }
+
+    #[test]
+    fn unsubscribe() {
+        let mut session = StreamerSession::new(&principals()).unwrap();
+
+        session.subscribe_futures_active_symbols(&["/ES", "/CL"]);
+
+        let request = session.unsubscribe("LEVELONE_FUTURES");
+
+        assert_eq!(request["requests"][0]["command"], "UNSUBS");
+        assert_eq!(request["requests"][0]["parameters"]["keys"], "/ES,/CL");
+        assert!(session.subscriptions().subscriptions.is_empty());
+    }
+
+    #[test]
+    fn subscribe_quote_fields() {
+        let mut session = StreamerSession::new(&principals()).unwrap();
+
+        let request = session.subscribe_quote_fields(&["AAPL"], &[QuoteField::Last, QuoteField::Bid, QuoteField::Last]);
+
+        assert_eq!(request["requests"][0]["parameters"]["fields"], "0,1,3");
+        assert_eq!(QuoteField::streamer_fields(&QuoteField::ALL), QUOTE_FIELDS);
+    }
+
+    #[test]
+    fn manage_subscriptions() {
+        let mut session = StreamerSession::new(&principals()).unwrap();
+
+        let request = session.add_symbols("QUOTE", &["AAPL", "MSFT"]);
+
+        assert_eq!(request["requests"][0]["command"], "ADD");
+        assert_eq!(request["requests"][0]["parameters"]["fields"], QUOTE_FIELDS);
+
+        session.add_symbols("QUOTE", &["MSFT", "SPY"]);
+
+        assert_eq!(session.subscriptions().symbols("QUOTE"), ["AAPL", "MSFT", "SPY"]);
+
+        let request = session.remove_symbols("QUOTE", &["AAPL"]);
+
+        assert_eq!(request["requests"][0]["command"], "UNSUBS");
+        assert_eq!(request["requests"][0]["parameters"]["keys"], "AAPL");
+        assert_eq!(session.subscriptions().symbols("QUOTE"), ["MSFT", "SPY"]);
+
+        let request = session.set_fields("QUOTE", "0,1,2").unwrap();
+
+        assert_eq!(request["requests"][0]["command"], "SUBS");
+        assert_eq!(request["requests"][0]["parameters"]["keys"], "MSFT,SPY");
+        assert_eq!(session.subscriptions().get("QUOTE").unwrap().fields, "0,1,2");
+        assert!(session.set_fields("CHART_EQUITY", "0,1").is_none());
+
+        session.remove_symbols("QUOTE", &["MSFT", "SPY"]);
+
+        assert!(session.subscriptions().get("QUOTE").is_none());
+    }
+
+    #[test]
+    fn parse_response_and_notify() {
+        let events = parse_message(r#"{"response":[{"service":"ADMIN","requestid":"0","command":"LOGIN","timestamp":1588600800000,"content":{"code":3,"msg":"Login denied"}}]}"#).unwrap();
+
+        assert_eq!(events, vec![StreamerEvent::Response {
+            service: "ADMIN".to_string(),
+            command: "LOGIN".to_string(),
+            code: 3,
+            message: "Login denied".to_string(),
+        }]);
+
+        let events = parse_message(r#"{"notify":[{"heartbeat":"1588600800000"}]}"#).unwrap();
+
+        assert_eq!(events, vec![StreamerEvent::Heartbeat]);
+    }
+
+    fn quote(symbol: &str, last_price: f64) -> StreamerEvent {
+        StreamerEvent::Quote(LevelOneQuote {
+            symbol: symbol.to_string(),
+            last_price: Some(last_price),
+            ..LevelOneQuote::default()
+        })
+    }
+
+    #[test]
+    fn queue_overflow() {
+        let queue = EventQueue::new(2, OverflowPolicy::DropOldest);
+
+        queue.push(quote("AAPL", 1.0));
+        queue.push(quote("MSFT", 2.0));
+        queue.push(StreamerEvent::Heartbeat);
+
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.drain(), vec![quote("MSFT", 2.0), StreamerEvent::Heartbeat]);
+
+        let queue = EventQueue::new(2, OverflowPolicy::CoalesceQuotes);
+
+        queue.push(quote("AAPL", 1.0));
+        queue.push(StreamerEvent::Heartbeat);
+        queue.push(quote("AAPL", 1.5));
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop(), Some(quote("AAPL", 1.5)));
+    }
+
+    #[test]
+    fn queue_blocks_until_consumed() {
+        let queue = std::sync::Arc::new(EventQueue::new(1, OverflowPolicy::Block));
+        let producer = queue.clone();
+
+        let handle = std::thread::spawn(move || {
+            for price in 0..3 {
+                producer.push(quote("AAPL", price as f64));
+            }
+
+            producer.close();
+        });
+
+        let events: Vec<StreamerEvent> = queue.iter().collect();
+
+        handle.join().unwrap();
+
+        assert_eq!(events, vec![quote("AAPL", 0.0), quote("AAPL", 1.0), quote("AAPL", 2.0)]);
+        assert_eq!(queue.dropped(), 0);
+        assert!(!queue.push(StreamerEvent::Heartbeat));
+    }
+
+    #[test]
+    fn merge_quotes() {
+        let mut quote = LevelOneQuote {
+            symbol: "AAPL".to_string(),
+            bid_price: Some(120.1),
+            ask_price: Some(120.2),
+            ..LevelOneQuote::default()
+        };
+
+        quote.merge(&LevelOneQuote {
+            symbol: "AAPL".to_string(),
+            ask_price: Some(120.3),
+            ..LevelOneQuote::default()
+        });
+
+        assert_eq!(quote.bid_price, Some(120.1));
+        assert_eq!(quote.ask_price, Some(120.3));
+    }
+}
@@ -0,0 +1,355 @@
+//! Structs and utilities for normalizing ticker symbols.
+//!
+//! TDA isn't consistent about how share classes and preferred shares are
+//! written: REST endpoints expect `BRK.B`, while the streamer expects `BRK/B`.
+//! [`Symbol`](enum.Symbol.html) accepts any of the common spellings and can
+//! format itself for whichever endpoint it's being sent to.
+//!
+//! ```
+//! use tda_sdk::Symbol;
+//!
+//! let symbol: Symbol = "brk/b".parse().unwrap();
+//!
+//! assert_eq!(symbol.to_rest(), "BRK.B");
+//! assert_eq!(symbol.to_streamer(), "BRK/B");
+//! ```
+
+use chrono::NaiveDate;
+use thiserror::Error;
+
+use std::{fmt, str::FromStr};
+
+/// A normalized ticker symbol.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Symbol {
+    /// Common stock, optionally with a share class (`BRK.B`).
+    Equity {
+        root: String,
+        class: Option<String>,
+    },
+
+    /// Preferred stock, optionally with a series (`BAC-PL`).
+    Preferred {
+        root: String,
+        series: Option<String>,
+    },
+
+    /// Option contract.
+    Option(OptionSymbol),
+
+    /// Indexes (`$SPX.X`), futures (`/ES`), and anything else which is passed
+    /// through untouched.
+    Other(String),
+}
+
+impl Symbol {
+    /// Parse a symbol from any of its common spellings.
+    pub fn parse(symbol: &str) -> Result<Self, ParseSymbolError> {
+        let symbol = symbol.trim().to_uppercase();
+
+        if symbol.is_empty() {
+            return Err(ParseSymbolError::Empty);
+        }
+
+        if symbol.starts_with('$') || symbol.starts_with('/') {
+            return Ok(Symbol::Other(symbol));
+        }
+
+        if symbol.contains('_') {
+            return OptionSymbol::parse_tda(&symbol).map(Symbol::Option);
+        }
+
+        if let Some(option) = OptionSymbol::parse_occ(&symbol) {
+            return Ok(Symbol::Option(option));
+        }
+
+        if let Some((root, series)) = split_preferred(&symbol) {
+            return Ok(Symbol::Preferred { root, series });
+        }
+
+        let separator = symbol.find(['.', '/', '-', ' ']);
+
+        match separator {
+            Some(index) => {
+                let root = symbol[..index].trim().to_string();
+                let class = symbol[index + 1..].trim().to_string();
+
+                if root.is_empty() || class.is_empty() || !is_alphabetic(&class) {
+                    return Ok(Symbol::Other(symbol));
+                }
+
+                Ok(Symbol::Equity { root, class: Some(class) })
+            }
+            None => Ok(Symbol::Equity { root: symbol, class: None }),
+        }
+    }
+
+    /// Format the symbol for REST endpoints (quotes, price history, orders).
+    pub fn to_rest(&self) -> String {
+        self.format('.')
+    }
+
+    /// Format the symbol for streamer subscriptions.
+    pub fn to_streamer(&self) -> String {
+        self.format('/')
+    }
+
+    fn format(&self, class_separator: char) -> String {
+        match self {
+            Symbol::Equity { root, class: Some(class) } => format!("{}{}{}", root, class_separator, class),
+            Symbol::Equity { root, class: None } => root.clone(),
+            Symbol::Preferred { root, series } => format!("{}-P{}", root, series.as_deref().unwrap_or("")),
+            Symbol::Option(option) => option.to_tda(),
+            Symbol::Other(symbol) => symbol.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_rest())
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = ParseSymbolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Symbol::parse(s)
+    }
+}
+
+/// Whether an option contract is a put or a call.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PutCall {
+    Put,
+    Call,
+}
+
+/// Symbol of an option contract.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OptionSymbol {
+    underlying: String,
+    expiration: NaiveDate,
+    put_call: PutCall,
+    /// Strike price in thousandths of a dollar, as encoded by OCC symbols.
+    strike_thousandths: u64,
+}
+
+impl OptionSymbol {
+    /// Create a new option symbol.
+    pub fn new(underlying: &str, expiration: NaiveDate, put_call: PutCall, strike: f64) -> Self {
+        Self {
+            underlying: underlying.trim().to_uppercase(),
+            expiration,
+            put_call,
+            strike_thousandths: (strike * 1000.0).round() as u64,
+        }
+    }
+
+    /// Symbol of the underlying security.
+    pub fn underlying(&self) -> &str {
+        &self.underlying
+    }
+
+    /// Expiration date of the contract.
+    pub fn expiration(&self) -> NaiveDate {
+        self.expiration
+    }
+
+    /// Whether the contract is a put or a call.
+    pub fn put_call(&self) -> PutCall {
+        self.put_call
+    }
+
+    /// Strike price of the contract.
+    pub fn strike(&self) -> f64 {
+        self.strike_thousandths as f64 / 1000.0
+    }
+
+    /// Format the symbol the way TDA does (`AAPL_061821C120`).
+    pub fn to_tda(&self) -> String {
+        let whole = self.strike_thousandths / 1000;
+        let fraction = self.strike_thousandths % 1000;
+        let strike = if fraction == 0 {
+            whole.to_string()
+        } else {
+            format!("{}.{:03}", whole, fraction).trim_end_matches('0').to_string()
+        };
+
+        format!(
+            "{}_{}{}{}",
+            self.underlying,
+            self.expiration.format("%m%d%y"),
+            put_call_char(self.put_call),
+            strike,
+        )
+    }
+
+    /// Format the symbol as a padded OCC symbol (`AAPL  210618C00120000`).
+    pub fn to_occ(&self) -> String {
+        format!(
+            "{:<6}{}{}{:08}",
+            self.underlying,
+            self.expiration.format("%y%m%d"),
+            put_call_char(self.put_call),
+            self.strike_thousandths,
+        )
+    }
+
+    /// Parse a TDA option symbol such as `AAPL_061821C120`.
+    fn parse_tda(symbol: &str) -> Result<Self, ParseSymbolError> {
+        let invalid = || ParseSymbolError::InvalidOption(symbol.to_string());
+
+        let (underlying, rest) = symbol.split_once('_').ok_or_else(invalid)?;
+
+        if underlying.is_empty() || rest.len() < 8 || !rest.is_ascii() {
+            return Err(invalid());
+        }
+
+        let expiration = NaiveDate::parse_from_str(&rest[..6], "%m%d%y").map_err(|_| invalid())?;
+        let put_call = parse_put_call(&rest[6..7]).ok_or_else(invalid)?;
+        let strike: f64 = rest[7..].parse().map_err(|_| invalid())?;
+
+        Ok(Self::new(underlying, expiration, put_call, strike))
+    }
+
+    /// Parse an OCC option symbol, with or without the padding after the
+    /// underlying.
+    fn parse_occ(symbol: &str) -> Option<Self> {
+        if symbol.len() < 16 || !symbol.is_ascii() {
+            return None;
+        }
+
+        let (underlying, rest) = symbol.split_at(symbol.len() - 15);
+        let underlying = underlying.trim();
+
+        if underlying.is_empty() || !rest[..6].bytes().all(|b| b.is_ascii_digit()) || !rest[7..].bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let expiration = NaiveDate::parse_from_str(&rest[..6], "%y%m%d").ok()?;
+        let put_call = parse_put_call(&rest[6..7])?;
+        let strike_thousandths = rest[7..].parse().ok()?;
+
+        Some(Self {
+            underlying: underlying.to_string(),
+            expiration,
+            put_call,
+            strike_thousandths,
+        })
+    }
+}
+
+impl fmt::Display for OptionSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_tda())
+    }
+}
+
+/// Errors encountered while parsing a [`Symbol`](enum.Symbol.html).
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum ParseSymbolError {
+    /// The symbol was empty.
+    #[error("Symbol is empty")]
+    Empty,
+
+    /// The symbol looked like an option symbol but could not be parsed.
+    #[error("Invalid option symbol: {0}")]
+    InvalidOption(String),
+}
+
+fn is_alphabetic(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn parse_put_call(s: &str) -> Option<PutCall> {
+    match s {
+        "P" => Some(PutCall::Put),
+        "C" => Some(PutCall::Call),
+        _ => None,
+    }
+}
+
+fn put_call_char(put_call: PutCall) -> char {
+    match put_call {
+        PutCall::Put => 'P',
+        PutCall::Call => 'C',
+    }
+}
+
+/// Split a preferred share symbol into its root and optional series.
+///
+/// Accepts `BAC-PL`, `BAC.PR.L`, `BAC.PRL`, `BAC/PR/L`, `BAC PRL`, and
+/// `BAC^L`.
+fn split_preferred(symbol: &str) -> Option<(String, Option<String>)> {
+    let markers = ["-P", ".PR.", ".PR", "/PR/", "/PR", " PR", "^"];
+
+    for marker in &markers {
+        if let Some(index) = symbol.find(marker) {
+            let root = &symbol[..index];
+            let series = &symbol[index + marker.len()..];
+
+            if root.is_empty() || !is_alphabetic(root) || series.len() > 2 || !is_alphabetic(series) {
+                continue;
+            }
+
+            let series = if series.is_empty() { None } else { Some(series.to_string()) };
+
+            return Some((root.to_string(), series));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_classes() {
+        for input in &["BRK.B", "brk/b", "BRK-B", "BRK B"] {
+            let symbol = Symbol::parse(input).unwrap();
+
+            assert_eq!(symbol.to_rest(), "BRK.B");
+            assert_eq!(symbol.to_streamer(), "BRK/B");
+        }
+    }
+
+    #[test]
+    fn preferred_shares() {
+        for input in &["BAC-PL", "BAC.PR.L", "BAC/PR/L", "BAC PRL", "BAC^L"] {
+            assert_eq!(Symbol::parse(input).unwrap().to_rest(), "BAC-PL");
+        }
+    }
+
+    #[test]
+    fn passthrough() {
+        assert_eq!(Symbol::parse("$SPX.X").unwrap(), Symbol::Other("$SPX.X".to_string()));
+        assert_eq!(Symbol::parse("/ES").unwrap(), Symbol::Other("/ES".to_string()));
+        assert_eq!(Symbol::parse(" aapl ").unwrap().to_rest(), "AAPL");
+        assert_eq!(Symbol::parse(""), Err(ParseSymbolError::Empty));
+    }
+
+    #[test]
+    fn options() {
+        let tda = Symbol::parse("AAPL_061821C122.5").unwrap();
+        let occ = Symbol::parse("AAPL  210618C00122500").unwrap();
+
+        assert_eq!(tda, occ);
+        assert_eq!(tda.to_rest(), "AAPL_061821C122.5");
+
+        match occ {
+            Symbol::Option(option) => {
+                assert_eq!(option.to_occ(), "AAPL  210618C00122500");
+                assert_eq!(option.strike(), 122.5);
+                assert_eq!(option.put_call(), PutCall::Call);
+            }
+            _ => panic!("expected an option symbol"),
+        }
+
+        assert!(Symbol::parse("AAPL_BADC120").is_err());
+    }
+}
@@ -0,0 +1,351 @@
+//! Order templates kept in config files.
+//!
+//! An [`OrderTemplate`](struct.OrderTemplate.html) describes an order
+//! without committing to a symbol, quantity, or price: those are filled in
+//! when it is instantiated, with prices as offsets from a reference price
+//! such as the last trade. Child templates are placed once the order fills,
+//! which makes a bracket:
+//!
+//! ```
+//! use tda_sdk::{orders::OrderStrategyType, templates::{OrderTemplate, TemplateValues}};
+//!
+//! let template = OrderTemplate::from_json(r#"{
+//!     "instruction": "BUY",
+//!     "order_type": "LIMIT",
+//!     "limit_offset": {"amount": 0.05},
+//!     "one_cancels_other": true,
+//!     "children": [
+//!         {"instruction": "SELL", "order_type": "LIMIT", "limit_offset": {"percent": 5.0}},
+//!         {"instruction": "SELL", "order_type": "STOP", "stop_offset": {"percent": -2.0}}
+//!     ]
+//! }"#).unwrap();
+//!
+//! let order = template.instantiate(&TemplateValues {
+//!     symbol: Some("AAPL".to_string()),
+//!     quantity: Some(10.0),
+//!     reference_price: Some(100.0),
+//! }).unwrap();
+//!
+//! assert_eq!(order.price, Some(100.05));
+//! assert_eq!(order.order_strategy_type, Some(OrderStrategyType::Trigger));
+//! assert_eq!(order.child_order_strategies.unwrap()[0].order_strategy_type, Some(OrderStrategyType::Oco));
+//! ```
+//!
+//! Templates round-trip through JSON, and through TOML with the
+//! `toml-templates` feature.
+
+use crate::orders::{round_price, Instruction, Order, OrderDuration, OrderLeg, OrderStrategyType, OrderType, Session};
+use thiserror::Error;
+
+use std::{fs, io, path::Path};
+
+/// Order with placeholders for its symbol, quantity, and prices.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OrderTemplate {
+    pub instruction: Instruction,
+    pub order_type: OrderType,
+    /// Fixed symbol, or a placeholder filled by
+    /// [`TemplateValues::symbol`](struct.TemplateValues.html#structfield.symbol)
+    /// if `None`. Children default to the symbol of their parent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// Fixed quantity, or a placeholder filled by
+    /// [`TemplateValues::quantity`](struct.TemplateValues.html#structfield.quantity)
+    /// if `None`. Children default to the quantity of their parent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    /// Limit price, relative to the reference price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_offset: Option<PriceOffset>,
+    /// Stop price, relative to the reference price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_offset: Option<PriceOffset>,
+    #[serde(default = "default_duration")]
+    pub duration: OrderDuration,
+    #[serde(default = "default_session")]
+    pub session: Session,
+    /// Orders placed once this one fills, such as the exits of a bracket.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<OrderTemplate>,
+    /// Whether filling one child cancels the others.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub one_cancels_other: bool,
+}
+
+/// Price relative to a reference price.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceOffset {
+    /// Added to the reference price.
+    Amount(f64),
+    /// Percentage of the reference price added to it, so `-2.0` is 2% below.
+    Percent(f64),
+}
+
+impl PriceOffset {
+    /// Price `reference` is offset to, rounded to cents, or to hundredths of
+    /// a cent below a dollar.
+    pub fn apply(self, reference: f64) -> f64 {
+        round_price(match self {
+            PriceOffset::Amount(amount) => reference + amount,
+            PriceOffset::Percent(percent) => reference * (1.0 + percent / 100.0),
+        })
+    }
+}
+
+/// Values filling the placeholders of a template.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TemplateValues {
+    pub symbol: Option<String>,
+    pub quantity: Option<f64>,
+    /// Price the offsets of the template are relative to.
+    pub reference_price: Option<f64>,
+}
+
+/// Error loading, saving, or instantiating a template.
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("Could not read or write the template: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid JSON template: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[cfg(feature = "toml-templates")]
+    #[error("Invalid TOML template: {0}")]
+    TomlDeserialize(#[from] toml::de::Error),
+
+    #[cfg(feature = "toml-templates")]
+    #[error("Could not write the template as TOML: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+
+    /// The file extension isn't `.json`, or `.toml` with the
+    /// `toml-templates` feature.
+    #[error("Unsupported template format `{0}`")]
+    UnsupportedFormat(String),
+
+    /// A placeholder had no value to fill it.
+    #[error("No value for the template's {0}")]
+    MissingValue(&'static str),
+}
+
+impl OrderTemplate {
+    pub fn from_json(json: &str) -> Result<Self, TemplateError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, TemplateError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    #[cfg(feature = "toml-templates")]
+    pub fn from_toml(toml: &str) -> Result<Self, TemplateError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    #[cfg(feature = "toml-templates")]
+    pub fn to_toml(&self) -> Result<String, TemplateError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Load a template from a `.json` or `.toml` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TemplateError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+
+        match extension(path).as_str() {
+            "json" => Self::from_json(&text),
+            #[cfg(feature = "toml-templates")]
+            "toml" => Self::from_toml(&text),
+            other => Err(TemplateError::UnsupportedFormat(other.to_string())),
+        }
+    }
+
+    /// Save the template to a `.json` or `.toml` file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TemplateError> {
+        let path = path.as_ref();
+        let text = match extension(path).as_str() {
+            "json" => self.to_json()?,
+            #[cfg(feature = "toml-templates")]
+            "toml" => self.to_toml()?,
+            other => return Err(TemplateError::UnsupportedFormat(other.to_string())),
+        };
+
+        Ok(fs::write(path, text)?)
+    }
+
+    /// Order with the placeholders filled by `values`. Option instructions,
+    /// such as `BuyToOpen`, make option legs.
+    pub fn instantiate(&self, values: &TemplateValues) -> Result<Order, TemplateError> {
+        let symbol = self.symbol.as_deref().or(values.symbol.as_deref()).ok_or(TemplateError::MissingValue("symbol"))?;
+        let quantity = self.quantity.or(values.quantity).ok_or(TemplateError::MissingValue("quantity"))?;
+        let price = |offset: Option<PriceOffset>| -> Result<Option<f64>, TemplateError> {
+            match offset {
+                Some(offset) => Ok(Some(offset.apply(values.reference_price.ok_or(TemplateError::MissingValue("reference price"))?))),
+                None => Ok(None),
+            }
+        };
+
+        let leg = match self.instruction {
+            Instruction::BuyToOpen | Instruction::BuyToClose | Instruction::SellToOpen | Instruction::SellToClose => {
+                OrderLeg::option(self.instruction, symbol, quantity)
+            }
+            _ => OrderLeg::equity(self.instruction, symbol, quantity),
+        };
+
+        let child_values = TemplateValues {
+            symbol: Some(symbol.to_string()),
+            quantity: Some(quantity),
+            reference_price: values.reference_price,
+        };
+        let children = self.children.iter().map(|child| child.instantiate(&child_values)).collect::<Result<Vec<_>, _>>()?;
+
+        let child_order_strategies = if children.is_empty() {
+            None
+        } else if self.one_cancels_other && children.len() > 1 {
+            Some(vec![Order {
+                child_order_strategies: Some(children),
+                order_strategy_type: Some(OrderStrategyType::Oco),
+                ..Order::default()
+            }])
+        } else {
+            Some(children)
+        };
+
+        Ok(Order {
+            order_strategy_type: Some(match child_order_strategies {
+                Some(_) => OrderStrategyType::Trigger,
+                None => OrderStrategyType::Single,
+            }),
+            child_order_strategies,
+            duration: Some(self.duration),
+            order_leg_collection: vec![leg],
+            order_type: Some(self.order_type),
+            price: price(self.limit_offset)?,
+            session: Some(self.session),
+            stop_price: price(self.stop_offset)?,
+            ..Order::default()
+        })
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_lowercase()
+}
+
+fn default_duration() -> OrderDuration {
+    OrderDuration::Day
+}
+
+fn default_session() -> Session {
+    Session::Normal
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bracket() -> OrderTemplate {
+        OrderTemplate {
+            instruction: Instruction::Buy,
+            order_type: OrderType::Limit,
+            symbol: None,
+            quantity: None,
+            limit_offset: Some(PriceOffset::Amount(-0.1)),
+            stop_offset: None,
+            duration: OrderDuration::Day,
+            session: Session::Normal,
+            children: vec![
+                OrderTemplate {
+                    limit_offset: Some(PriceOffset::Percent(10.0)),
+                    ..bracket_exit()
+                },
+                OrderTemplate {
+                    order_type: OrderType::Stop,
+                    stop_offset: Some(PriceOffset::Percent(-5.0)),
+                    ..bracket_exit()
+                },
+            ],
+            one_cancels_other: true,
+        }
+    }
+
+    fn bracket_exit() -> OrderTemplate {
+        OrderTemplate {
+            instruction: Instruction::Sell,
+            order_type: OrderType::Limit,
+            symbol: None,
+            quantity: None,
+            limit_offset: None,
+            stop_offset: None,
+            duration: OrderDuration::GoodTillCancel,
+            session: Session::Normal,
+            children: Vec::new(),
+            one_cancels_other: false,
+        }
+    }
+
+    #[test]
+    fn instantiates_templates() {
+        let values = TemplateValues {
+            symbol: Some("AAPL".to_string()),
+            quantity: Some(10.0),
+            reference_price: Some(120.0),
+        };
+        let order = bracket().instantiate(&values).unwrap();
+        let oco = &order.child_order_strategies.as_ref().unwrap()[0];
+        let exits = oco.child_order_strategies.as_ref().unwrap();
+
+        assert_eq!(order.price, Some(119.9));
+        assert_eq!(order.order_strategy_type, Some(OrderStrategyType::Trigger));
+        assert_eq!(oco.order_strategy_type, Some(OrderStrategyType::Oco));
+        assert_eq!(exits[0].price, Some(132.0));
+        assert_eq!(exits[0].duration, Some(OrderDuration::GoodTillCancel));
+        assert_eq!(exits[1].stop_price, Some(114.0));
+        assert_eq!(exits[1].order_leg_collection[0].quantity, 10.0);
+        assert_eq!(exits[1].order_leg_collection[0].instrument.symbol(), "AAPL");
+
+        let missing = TemplateValues {
+            reference_price: None,
+            ..values
+        };
+
+        assert!(matches!(bracket().instantiate(&missing), Err(TemplateError::MissingValue("reference price"))));
+        assert!(matches!(bracket().instantiate(&TemplateValues::default()), Err(TemplateError::MissingValue("symbol"))));
+        assert_eq!(PriceOffset::Percent(-1.0).apply(0.5), 0.495);
+    }
+
+    #[test]
+    fn round_trips_json() {
+        let json = bracket().to_json().unwrap();
+
+        assert!(!json.contains("symbol"));
+        assert_eq!(OrderTemplate::from_json(&json).unwrap(), bracket());
+
+        let minimal = OrderTemplate::from_json(r#"{"instruction": "SELL", "order_type": "MARKET", "symbol": "MSFT"}"#).unwrap();
+
+        assert_eq!(minimal.duration, OrderDuration::Day);
+        assert_eq!(minimal.session, Session::Normal);
+    }
+
+    #[cfg(feature = "toml-templates")]
+    #[test]
+    fn round_trips_toml() {
+        let path = std::env::temp_dir().join(format!("tda-sdk-template-{}.toml", std::process::id()));
+
+        bracket().save(&path).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = OrderTemplate::load(&path);
+
+        fs::remove_file(&path).unwrap();
+
+        assert!(text.contains("[[children]]"));
+        assert_eq!(loaded.unwrap(), bracket());
+        assert!(matches!(bracket().save("template.yaml"), Err(TemplateError::UnsupportedFormat(format)) if format == "yaml"));
+    }
+}
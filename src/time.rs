@@ -0,0 +1,317 @@
+//! Timestamps of the API in the market's timezone.
+//!
+//! The API writes times as milliseconds since epoch, ISO-8601 strings with
+//! various offsets, local exchange times without an offset, or bare dates.
+//! [`TdaDateTime`](struct.TdaDateTime.html) parses all of them and exposes
+//! them in `America/New_York`, the timezone market hours and trading days
+//! follow. It serializes back to the representation it was parsed from, so
+//! responses round-trip unchanged.
+//!
+//! ```
+//! use chrono::Timelike;
+//! use tda_sdk::time::TdaDateTime;
+//!
+//! let entered: TdaDateTime = serde_json::from_str(r#""2020-05-04T14:31:02+0000""#).unwrap();
+//! let millis: TdaDateTime = serde_json::from_str("1588602662000").unwrap();
+//!
+//! assert_eq!(entered, millis);
+//! assert_eq!(entered.hour(), 10);
+//! assert_eq!(entered.to_string(), "2020-05-04T10:31:02-04:00");
+//! ```
+
+pub use chrono_tz::Tz;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    str::FromStr,
+};
+
+/// Timezone of the US equity and option markets.
+pub const MARKET_TIMEZONE: Tz = chrono_tz::America::New_York;
+
+/// Point in time from the API, in the market's timezone.
+///
+/// Compares, orders, and hashes by instant, whatever representation it was
+/// parsed from. Dereferences to a `DateTime<Tz>`.
+#[derive(Clone, Copy, Debug)]
+pub struct TdaDateTime {
+    date_time: DateTime<Tz>,
+    repr: Repr,
+}
+
+/// How a [`TdaDateTime`](struct.TdaDateTime.html) was written, to serialize
+/// it the same way.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Repr {
+    Millis,
+    MillisString,
+    /// ISO-8601 with an offset, written as `Z` or with or without a colon.
+    Offset {
+        offset: FixedOffset,
+        fraction: usize,
+        zulu: bool,
+        colon: bool,
+    },
+    /// Market time without an offset, with `T` or a space between the date
+    /// and the time.
+    Local {
+        fraction: usize,
+        separator: char,
+    },
+    Date,
+}
+
+impl TdaDateTime {
+    /// Time from milliseconds since epoch, or `None` if out of range.
+    pub fn from_millis(millis: i64) -> Option<Self> {
+        Some(Self::with_repr(Utc.timestamp_millis_opt(millis).single()?, Repr::Millis))
+    }
+
+    /// Time from a date and time in any timezone. Serializes as
+    /// milliseconds since epoch.
+    pub fn from_date_time<T: TimeZone>(date_time: DateTime<T>) -> Self {
+        Self::with_repr(date_time, Repr::Millis)
+    }
+
+    /// Midnight of a date in the market's timezone. Serializes as a bare
+    /// date.
+    pub fn from_date(date: NaiveDate) -> Option<Self> {
+        let midnight = MARKET_TIMEZONE.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest()?;
+
+        Some(Self::with_repr(midnight, Repr::Date))
+    }
+
+    fn with_repr<T: TimeZone>(date_time: DateTime<T>, repr: Repr) -> Self {
+        Self {
+            date_time: date_time.with_timezone(&MARKET_TIMEZONE),
+            repr,
+        }
+    }
+
+    /// Parse any of the representations used by the API.
+    pub fn parse(s: &str) -> Result<Self, ParseTimeError> {
+        let s = s.trim();
+        let invalid = || ParseTimeError(s.to_string());
+
+        if !s.is_empty() && s.trim_start_matches('-').chars().all(|c| c.is_ascii_digit()) {
+            let millis = s.parse().map_err(|_| invalid())?;
+
+            return Self::from_millis(millis).map(|time| Self { repr: Repr::MillisString, ..time }).ok_or_else(invalid);
+        }
+
+        if s.len() == 10 {
+            let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| invalid())?;
+
+            return Self::from_date(date).ok_or_else(invalid);
+        }
+
+        let separator = s.chars().nth(10).ok_or_else(invalid)?;
+        let fraction = s
+            .find('.')
+            .map_or(0, |dot| s[dot + 1..].chars().take_while(char::is_ascii_digit).count());
+
+        if let Ok(local) = NaiveDateTime::parse_from_str(s, &format!("%Y-%m-%d{}%H:%M:%S%.f", separator)) {
+            let date_time = MARKET_TIMEZONE.from_local_datetime(&local).earliest().ok_or_else(invalid)?;
+
+            return Ok(Self::with_repr(date_time, Repr::Local { fraction, separator }));
+        }
+
+        let zulu = s.ends_with('Z') || s.ends_with('z');
+        let date_time = match zulu {
+            true => DateTime::parse_from_rfc3339(s),
+            false => DateTime::parse_from_str(s, &format!("%Y-%m-%d{}%H:%M:%S%.f%z", separator)),
+        }
+        .map_err(|_| invalid())?;
+        let colon = s.len() >= 3 && s.as_bytes()[s.len() - 3] == b':';
+
+        Ok(Self::with_repr(date_time, Repr::Offset {
+            offset: *date_time.offset(),
+            fraction,
+            zulu,
+            colon,
+        }))
+    }
+
+    /// Date and time in the market's timezone.
+    pub fn date_time(&self) -> DateTime<Tz> {
+        self.date_time
+    }
+
+    /// Trading date, in the market's timezone.
+    pub fn date(&self) -> NaiveDate {
+        self.date_time.date_naive()
+    }
+
+    /// Write the time the way it was parsed.
+    fn to_api_string(self) -> String {
+        let fraction = |digits: usize| match digits {
+            0 => String::new(),
+            digits => format!("%.{}f", digits),
+        };
+
+        match self.repr {
+            Repr::Millis | Repr::MillisString => self.date_time.timestamp_millis().to_string(),
+            Repr::Offset {
+                offset,
+                fraction: digits,
+                zulu,
+                colon,
+            } => {
+                let zone = match (zulu, colon) {
+                    (true, _) => "Z",
+                    (false, true) => "%:z",
+                    (false, false) => "%z",
+                };
+
+                self.date_time.with_timezone(&offset).format(&format!("%Y-%m-%dT%H:%M:%S{}{}", fraction(digits), zone)).to_string()
+            }
+            Repr::Local { fraction: digits, separator } => {
+                self.date_time.format(&format!("%Y-%m-%d{}%H:%M:%S{}", separator, fraction(digits))).to_string()
+            }
+            Repr::Date => self.date().format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+impl Deref for TdaDateTime {
+    type Target = DateTime<Tz>;
+
+    fn deref(&self) -> &DateTime<Tz> {
+        &self.date_time
+    }
+}
+
+impl PartialEq for TdaDateTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.date_time == other.date_time
+    }
+}
+
+impl Eq for TdaDateTime {}
+
+impl PartialOrd for TdaDateTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TdaDateTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.date_time.cmp(&other.date_time)
+    }
+}
+
+impl Hash for TdaDateTime {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.date_time.timestamp().hash(state);
+        self.date_time.timestamp_subsec_nanos().hash(state);
+    }
+}
+
+/// Shows the time in RFC 3339 in the market's timezone.
+impl fmt::Display for TdaDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.date_time.to_rfc3339())
+    }
+}
+
+impl FromStr for TdaDateTime {
+    type Err = ParseTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl<T: TimeZone> From<DateTime<T>> for TdaDateTime {
+    fn from(date_time: DateTime<T>) -> Self {
+        Self::from_date_time(date_time)
+    }
+}
+
+impl<'de> Deserialize<'de> for TdaDateTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TimeVisitor;
+
+        impl de::Visitor<'_> for TimeVisitor {
+            type Value = TdaDateTime;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("milliseconds since epoch, or a date or time string")
+            }
+
+            fn visit_i64<E: de::Error>(self, millis: i64) -> Result<TdaDateTime, E> {
+                TdaDateTime::from_millis(millis).ok_or_else(|| E::custom(format!("timestamp out of range: {}", millis)))
+            }
+
+            fn visit_u64<E: de::Error>(self, millis: u64) -> Result<TdaDateTime, E> {
+                let millis = i64::try_from(millis).map_err(|_| E::custom(format!("timestamp out of range: {}", millis)))?;
+
+                self.visit_i64(millis)
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<TdaDateTime, E> {
+                TdaDateTime::parse(s).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TimeVisitor)
+    }
+}
+
+impl Serialize for TdaDateTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.repr {
+            Repr::Millis => serializer.serialize_i64(self.date_time.timestamp_millis()),
+            _ => serializer.serialize_str(&self.to_api_string()),
+        }
+    }
+}
+
+/// Error returned when a string isn't a time the API uses.
+#[derive(Debug, Error, Eq, PartialEq)]
+#[error("Invalid date or time: {0}")]
+pub struct ParseTimeError(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_round_trip() {
+        let cases = [
+            "1588602662000",
+            "\"1588602662000\"",
+            "\"2020-05-04T14:31:02+0000\"",
+            "\"2020-05-04T10:31:02-04:00\"",
+            "\"2020-05-04T14:31:02.000Z\"",
+            "\"2020-05-04 10:31:02\"",
+            "\"2020-05-04T10:31:02.000\"",
+        ];
+
+        for case in cases {
+            let time: TdaDateTime = serde_json::from_str(case).unwrap();
+
+            assert_eq!(time.timestamp_millis(), 1588602662000, "{}", case);
+            assert_eq!(serde_json::to_string(&time).unwrap(), case);
+        }
+
+        let date: TdaDateTime = serde_json::from_str("\"2020-03-08\"").unwrap();
+
+        assert_eq!(date.to_string(), "2020-03-08T00:00:00-05:00");
+        assert_eq!(date.date(), NaiveDate::from_ymd_opt(2020, 3, 8).unwrap());
+        assert_eq!(serde_json::to_string(&date).unwrap(), "\"2020-03-08\"");
+
+        // 02:30 doesn't exist on the day clocks spring forward.
+        assert!(TdaDateTime::parse("2020-03-08 02:30:00").is_err());
+        assert!(TdaDateTime::parse("yesterday").is_err());
+    }
+}
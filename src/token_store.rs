@@ -0,0 +1,436 @@
+//! Persistent storage for client credentials.
+//!
+//! Refresh tokens are long-lived, and renewing one invalidates the previous
+//! one, so apps must store them somewhere. A [`TokenStore`](trait.TokenStore.html)
+//! saves and loads the [`StoredTokens`](struct.StoredTokens.html) of a client.
+//!
+//! [`FileTokenStore`](struct.FileTokenStore.html) writes them as plain JSON.
+//! With the `encryption` feature, `EncryptedFileTokenStore` encrypts them with
+//! AES-256-GCM, using a key derived from a passphrase, so they aren't readable
+//! at rest. With the `keyring` feature, `KeyringTokenStore` keeps them in the
+//! keychain of the operating system instead of a file.
+//!
+//! ```no_run
+//! use tda_sdk::{token_store::FileTokenStore, Client};
+//!
+//! let store = FileTokenStore::new("tokens.json");
+//! let mut client = Client::from_store(&store).unwrap().unwrap();
+//!
+//! client.renew_refresh_token().unwrap();
+//! client.save_to(&store).unwrap();
+//! ```
+
+use crate::AccessToken;
+use thiserror::Error;
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Credentials of a client, as saved by a [`TokenStore`](trait.TokenStore.html).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredTokens {
+    pub client_id: String,
+    pub refresh_token: String,
+    /// Timestamp in milliseconds when the refresh token expires, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token_expires_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<AccessToken>,
+}
+
+/// Saves and loads client credentials.
+pub trait TokenStore {
+    /// Load the saved credentials, or `None` if nothing was saved yet.
+    fn load(&self) -> Result<Option<StoredTokens>, TokenStoreError>;
+
+    /// Save credentials, replacing the previous ones.
+    fn save(&self, tokens: &StoredTokens) -> Result<(), TokenStoreError>;
+}
+
+/// Stores credentials as plain JSON in a file.
+#[derive(Clone, Debug)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> Result<Option<StoredTokens>, TokenStoreError> {
+        match read_file(&self.path)? {
+            Some(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<(), TokenStoreError> {
+        write_file(&self.path, &serde_json::to_vec(tokens)?)
+    }
+}
+
+/// Stores credentials in a file encrypted with AES-256-GCM.
+///
+/// The key is derived from a passphrase with PBKDF2, using a new random salt
+/// every time the file is saved.
+#[cfg(feature = "encryption")]
+#[derive(Clone)]
+pub struct EncryptedFileTokenStore {
+    path: PathBuf,
+    passphrase: String,
+}
+
+#[cfg(feature = "encryption")]
+impl EncryptedFileTokenStore {
+    pub fn new(path: impl AsRef<Path>, passphrase: &str) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            passphrase: passphrase.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl std::fmt::Debug for EncryptedFileTokenStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedFileTokenStore").field("path", &self.path).finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl TokenStore for EncryptedFileTokenStore {
+    fn load(&self) -> Result<Option<StoredTokens>, TokenStoreError> {
+        match read_file(&self.path)? {
+            Some(sealed) => Ok(Some(serde_json::from_slice(&crypto::open(&self.passphrase, &sealed)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<(), TokenStoreError> {
+        write_file(&self.path, &crypto::seal(&self.passphrase, &serde_json::to_vec(tokens)?))
+    }
+}
+
+/// Stores credentials in the keychain of the operating system, such as the
+/// macOS Keychain, the Windows Credential Manager, or the Secret Service of
+/// Linux desktops (GNOME Keyring or KWallet), which keeps them across
+/// reboots.
+///
+/// ```no_run
+/// use tda_sdk::{token_store::KeyringTokenStore, Client};
+///
+/// let store = KeyringTokenStore::new("my-trading-app", "alice");
+/// let client = Client::from_store(&store).unwrap().expect("run the setup first");
+/// ```
+#[cfg(feature = "keyring")]
+#[derive(Clone, Debug)]
+pub struct KeyringTokenStore {
+    entry: KeyringEntry,
+}
+
+/// Where a [`KeyringTokenStore`](struct.KeyringTokenStore.html) keeps the
+/// credentials.
+#[cfg(feature = "keyring")]
+#[derive(Clone, Debug)]
+enum KeyringEntry {
+    Named { service: String, user: String },
+    Entry(std::sync::Arc<keyring::Entry>),
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringTokenStore {
+    /// Store credentials under a service name, such as the name of the app,
+    /// and a user.
+    pub fn new(service: &str, user: &str) -> Self {
+        Self {
+            entry: KeyringEntry::Named {
+                service: service.to_string(),
+                user: user.to_string(),
+            },
+        }
+    }
+
+    /// Store credentials in an entry of your own, such as one with a
+    /// specific target, or a mock credential in tests.
+    pub fn from_entry(entry: keyring::Entry) -> Self {
+        Self {
+            entry: KeyringEntry::Entry(std::sync::Arc::new(entry)),
+        }
+    }
+
+    /// Remove the stored credentials, if any.
+    pub fn delete(&self) -> Result<(), TokenStoreError> {
+        match self.entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn entry(&self) -> Result<std::sync::Arc<keyring::Entry>, TokenStoreError> {
+        match &self.entry {
+            KeyringEntry::Named { service, user } => Ok(std::sync::Arc::new(keyring::Entry::new(service, user)?)),
+            KeyringEntry::Entry(entry) => Ok(entry.clone()),
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl TokenStore for KeyringTokenStore {
+    fn load(&self) -> Result<Option<StoredTokens>, TokenStoreError> {
+        match self.entry()?.get_password() {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<(), TokenStoreError> {
+        Ok(self.entry()?.set_password(&serde_json::to_string(tokens)?)?)
+    }
+}
+
+/// Read a file, or `None` if it doesn't exist.
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>, TokenStoreError> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Write a file readable by its owner only, where supported.
+///
+/// The contents go to a temporary file in the same directory first, which
+/// then replaces the file, so a crash mid-write can't leave the credentials
+/// truncated. A temporary file left behind is removed first, and the new one
+/// is only ever created, so the credentials can't be written through a link
+/// planted at its path.
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), TokenStoreError> {
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("tokens");
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    match fs::remove_file(&temp_path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+        _ => {}
+    }
+
+    let result = write_temp_file(&temp_path, contents).and_then(|()| fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    Ok(result?)
+}
+
+fn write_temp_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+
+    io::Write::write_all(&mut file, contents)?;
+    file.sync_all()
+}
+
+/// Passphrase-based encryption of small payloads.
+#[cfg(feature = "encryption")]
+pub(crate) mod crypto {
+    use super::TokenStoreError;
+    use aes_gcm::{
+        aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+        Aes256Gcm,
+        Key,
+        Nonce,
+    };
+    use sha2::Sha256;
+
+    /// Prefix of sealed payloads, identifying the format version.
+    const MAGIC: &[u8] = b"TDA1";
+
+    /// PBKDF2 rounds used to derive keys from passphrases.
+    #[cfg(not(test))]
+    const ROUNDS: u32 = 600_000;
+
+    /// Fewer rounds keep the unoptimized tests fast.
+    #[cfg(test)]
+    const ROUNDS: u32 = 1_000;
+
+    const SALT_LEN: usize = 16;
+    const NONCE_LEN: usize = 12;
+
+    fn cipher(passphrase: &str, salt: &[u8]) -> Aes256Gcm {
+        let mut key = Key::<Aes256Gcm>::default();
+
+        pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, ROUNDS, &mut key);
+
+        Aes256Gcm::new(&key)
+    }
+
+    /// Encrypt `plaintext`, prefixed with the salt and nonce needed to decrypt
+    /// it.
+    pub(crate) fn seal(passphrase: &str, plaintext: &[u8]) -> Vec<u8> {
+        let mut salt = [0; SALT_LEN];
+
+        OsRng.fill_bytes(&mut salt);
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher(passphrase, &salt).encrypt(&nonce, plaintext).expect("AES-GCM encryption failed");
+
+        [MAGIC, &salt, &nonce, &ciphertext].concat()
+    }
+
+    /// Decrypt a payload produced by [`seal()`](fn.seal.html).
+    pub(crate) fn open(passphrase: &str, sealed: &[u8]) -> Result<Vec<u8>, TokenStoreError> {
+        let sealed = sealed.strip_prefix(MAGIC).ok_or(TokenStoreError::Decrypt)?;
+
+        if sealed.len() < SALT_LEN + NONCE_LEN {
+            return Err(TokenStoreError::Decrypt);
+        }
+
+        let (salt, sealed) = sealed.split_at(SALT_LEN);
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        cipher(passphrase, salt)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| TokenStoreError::Decrypt)
+    }
+}
+
+/// Represents all possible errors of a [`TokenStore`](trait.TokenStore.html).
+#[derive(Debug, Error)]
+pub enum TokenStoreError {
+    /// The store could not be read or written.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The stored credentials are not valid JSON.
+    #[error("Failed to parse stored tokens: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    /// The stored credentials could not be decrypted, because the passphrase
+    /// is wrong or the file was modified.
+    #[error("Failed to decrypt stored tokens")]
+    Decrypt,
+
+    /// The keychain of the operating system could not be accessed.
+    #[cfg(feature = "keyring")]
+    #[error("Keyring error: {0}")]
+    Keyring(#[from] keyring::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens() -> StoredTokens {
+        StoredTokens {
+            client_id: "CLIENT_ID".to_string(),
+            refresh_token: "REFRESH_TOKEN".to_string(),
+            refresh_token_expires_at: Some(1_600_000_000_000),
+            access_token: None,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tda-sdk-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn file_token_store() {
+        let path = temp_path("tokens.json");
+        let store = FileTokenStore::new(&path);
+
+        assert_eq!(store.load().unwrap(), None);
+
+        store.save(&tokens()).unwrap();
+        assert_eq!(store.load().unwrap(), Some(tokens()));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            // Saving again replaces the file, keeping it private.
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            store.save(&tokens()).unwrap();
+
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+            // A link planted at the temporary path isn't followed.
+            let planted = temp_path("planted");
+            let link = path.with_file_name(format!(".{}.{}.tmp", path.file_name().unwrap().to_str().unwrap(), std::process::id()));
+
+            fs::write(&planted, "").unwrap();
+            std::os::unix::fs::symlink(&planted, &link).unwrap();
+            store.save(&tokens()).unwrap();
+
+            assert_eq!(fs::read(&planted).unwrap(), b"");
+            assert_eq!(store.load().unwrap(), Some(tokens()));
+            fs::remove_file(&planted).unwrap();
+        }
+
+        let siblings = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.contains("tokens.json") && name.ends_with(".tmp"))
+            .count();
+
+        assert_eq!(siblings, 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn keyring_token_store() {
+        use keyring::{mock::MockCredential, Entry};
+
+        let store = KeyringTokenStore::from_entry(Entry::new_with_credential(Box::new(MockCredential::default())));
+
+        assert_eq!(store.load().unwrap(), None);
+
+        store.save(&tokens()).unwrap();
+        assert_eq!(store.load().unwrap(), Some(tokens()));
+
+        store.delete().unwrap();
+        store.delete().unwrap();
+        assert_eq!(store.load().unwrap(), None);
+
+        let entry = Entry::new_with_credential(Box::new(MockCredential::default()));
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+
+        mock.set_error(keyring::Error::NoStorageAccess("locked".into()));
+
+        assert!(matches!(KeyringTokenStore::from_entry(entry).load(), Err(TokenStoreError::Keyring(_))));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_file_token_store() {
+        let path = temp_path("tokens.enc");
+        let store = EncryptedFileTokenStore::new(&path, "correct horse");
+
+        store.save(&tokens()).unwrap();
+
+        let contents = fs::read(&path).unwrap();
+
+        assert!(!String::from_utf8_lossy(&contents).contains("REFRESH_TOKEN"));
+        assert_eq!(store.load().unwrap(), Some(tokens()));
+        assert!(matches!(EncryptedFileTokenStore::new(&path, "wrong").load(), Err(TokenStoreError::Decrypt)));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+//! Classification of account transactions.
+//!
+//! The API describes transactions with a type and a cryptic sub type code,
+//! such as `DIVIDEND_OR_INTEREST` and `QD` for a qualified dividend.
+//! [`classify()`](fn.classify.html) maps them to a
+//! [`TransactionKind`](enum.TransactionKind.html), keeping the raw codes
+//! around, and [`summarize()`](fn.summarize.html) adds up transactions by
+//! kind, such as for the income of a year:
+//!
+//! ```no_run
+//! use chrono::NaiveDate;
+//! use tda_sdk::{
+//!     params::GetTransactionsParams,
+//!     transactions::{self, TransactionKind},
+//!     Client,
+//! };
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let history = client
+//!     .get_transactions(
+//!         "123456789",
+//!         GetTransactionsParams {
+//!             start_date: NaiveDate::from_ymd_opt(2020, 1, 1),
+//!             end_date: NaiveDate::from_ymd_opt(2020, 12, 31),
+//!             ..GetTransactionsParams::default()
+//!         },
+//!     )
+//!     .unwrap();
+//!
+//! for (kind, summary) in transactions::summarize(&history) {
+//!     println!("{}: {} transactions, {:.2}", kind, summary.count, summary.net_amount);
+//! }
+//!
+//! for (symbol, amount) in transactions::totals_by_symbol(&history, TransactionKind::Dividend) {
+//!     println!("{} paid {:.2} in dividends", symbol, amount);
+//! }
+//! ```
+
+use crate::responses::Transaction;
+use serde::{Deserialize, Serialize};
+
+use std::{collections::BTreeMap, fmt};
+
+/// What a transaction is, regardless of the codes the API used for it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum TransactionKind {
+    /// Purchase or sale of an instrument.
+    Trade,
+    Dividend,
+    Interest,
+    /// Capital gain distribution of a fund.
+    CapitalGain,
+    /// Cash or securities deposited into the account.
+    TransferIn,
+    /// Cash or securities withdrawn from the account.
+    TransferOut,
+    /// Fee charged outside of a trade, such as for an ADR or a wire.
+    Fee,
+    /// Journal between accounts or sub accounts.
+    Journal,
+    /// Anything else, such as memos and money market sweeps.
+    Other,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Trade => "Trade",
+            Self::Dividend => "Dividend",
+            Self::Interest => "Interest",
+            Self::CapitalGain => "Capital gain",
+            Self::TransferIn => "Transfer in",
+            Self::TransferOut => "Transfer out",
+            Self::Fee => "Fee",
+            Self::Journal => "Journal",
+            Self::Other => "Other",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// Kind of a transaction along with the codes it was classified from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Classification {
+    pub kind: TransactionKind,
+    /// Type of the transaction, such as `DIVIDEND_OR_INTEREST`.
+    pub transaction_type: String,
+    /// Sub type code of the transaction, such as `QD`.
+    pub sub_type: Option<String>,
+}
+
+/// Dividend sub types: ordinary, qualified, special, non-taxable, and
+/// payments in lieu.
+const DIVIDEND_SUB_TYPES: [&str; 6] = ["DV", "OD", "QD", "SD", "ND", "PL"];
+/// Interest sub types: credit, bond, money market, and free balance
+/// interest.
+const INTEREST_SUB_TYPES: [&str; 5] = ["CI", "BI", "MI", "FI", "II"];
+/// Long and short term capital gain distribution sub types.
+const CAPITAL_GAIN_SUB_TYPES: [&str; 4] = ["LT", "ST", "CG", "CD"];
+/// Fee sub types, such as ADR and wire fees.
+const FEE_SUB_TYPES: [&str; 4] = ["AF", "FE", "WF", "MF"];
+
+/// Classify a transaction by its type, its sub type, and its description
+/// when the codes are ambiguous.
+pub fn classify(transaction: &Transaction) -> Classification {
+    Classification {
+        kind: kind(transaction),
+        transaction_type: transaction.transaction_type.clone(),
+        sub_type: transaction.transaction_sub_type.clone(),
+    }
+}
+
+fn kind(transaction: &Transaction) -> TransactionKind {
+    let sub_type = transaction.transaction_sub_type.as_deref().unwrap_or_default();
+    let description = transaction.description.as_deref().unwrap_or_default().to_uppercase();
+    let by_direction = if transaction.net_amount < 0.0 { TransactionKind::TransferOut } else { TransactionKind::TransferIn };
+
+    if FEE_SUB_TYPES.contains(&sub_type) {
+        return TransactionKind::Fee;
+    }
+
+    match transaction.transaction_type.as_str() {
+        "TRADE" => TransactionKind::Trade,
+        "DIVIDEND_OR_INTEREST" => {
+            if CAPITAL_GAIN_SUB_TYPES.contains(&sub_type) || description.contains("CAPITAL GAIN") {
+                TransactionKind::CapitalGain
+            } else if INTEREST_SUB_TYPES.contains(&sub_type) || (!DIVIDEND_SUB_TYPES.contains(&sub_type) && description.contains("INTEREST")) {
+                TransactionKind::Interest
+            } else {
+                TransactionKind::Dividend
+            }
+        }
+        "ACH_RECEIPT" | "CASH_RECEIPT" | "WIRE_IN" => TransactionKind::TransferIn,
+        "ACH_DISBURSEMENT" | "CASH_DISBURSEMENT" | "WIRE_OUT" => TransactionKind::TransferOut,
+        "ELECTRONIC_FUND" | "RECEIVE_AND_DELIVER" => by_direction,
+        "JOURNAL" if description.contains("FEE") && transaction.net_amount < 0.0 => TransactionKind::Fee,
+        "JOURNAL" => TransactionKind::Journal,
+        _ => TransactionKind::Other,
+    }
+}
+
+/// Transactions of a kind added up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KindSummary {
+    pub count: usize,
+    /// Net amounts added up, which include fees.
+    pub net_amount: f64,
+    /// Fees added up.
+    pub fees: f64,
+}
+
+/// Add up transactions by kind.
+pub fn summarize<'a>(transactions: impl IntoIterator<Item = &'a Transaction>) -> BTreeMap<TransactionKind, KindSummary> {
+    let mut summaries: BTreeMap<TransactionKind, KindSummary> = BTreeMap::new();
+
+    for transaction in transactions {
+        let summary = summaries.entry(transaction.kind()).or_default();
+
+        summary.count += 1;
+        summary.net_amount += transaction.net_amount;
+        summary.fees += transaction.total_fees();
+    }
+
+    summaries
+}
+
+/// Net amount of the transactions of a kind.
+pub fn total<'a>(transactions: impl IntoIterator<Item = &'a Transaction>, kind: TransactionKind) -> f64 {
+    transactions.into_iter().filter(|transaction| transaction.kind() == kind).map(|transaction| transaction.net_amount).sum()
+}
+
+/// Net amount of the transactions of a kind by symbol, such as the dividends
+/// paid by each holding. Transactions without a symbol are left out.
+pub fn totals_by_symbol<'a>(transactions: impl IntoIterator<Item = &'a Transaction>, kind: TransactionKind) -> BTreeMap<String, f64> {
+    let mut totals: BTreeMap<String, f64> = BTreeMap::new();
+
+    for transaction in transactions.into_iter().filter(|transaction| transaction.kind() == kind) {
+        if let Some(symbol) = transaction.symbol() {
+            *totals.entry(symbol.to_string()).or_default() += transaction.net_amount;
+        }
+    }
+
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transactions() -> Vec<Transaction> {
+        serde_json::from_str(include_str!("../tests/fixtures/transactions.json")).unwrap()
+    }
+
+    fn transaction(transaction_type: &str, sub_type: &str, description: &str, net_amount: f64) -> Transaction {
+        Transaction {
+            description: Some(description.to_string()),
+            net_amount,
+            transaction_sub_type: Some(sub_type.to_string()),
+            transaction_type: transaction_type.to_string(),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn classifies_transactions() {
+        let kinds: Vec<TransactionKind> = transactions().iter().map(Transaction::kind).collect();
+
+        assert_eq!(kinds, [TransactionKind::Trade, TransactionKind::Dividend, TransactionKind::TransferIn]);
+
+        let classification = classify(&transaction("DIVIDEND_OR_INTEREST", "LT", "LONG TERM GAIN DISTRIBUTION", 12.0));
+
+        assert_eq!(classification.kind, TransactionKind::CapitalGain);
+        assert_eq!(classification.transaction_type, "DIVIDEND_OR_INTEREST");
+        assert_eq!(classification.sub_type.as_deref(), Some("LT"));
+
+        assert_eq!(transaction("DIVIDEND_OR_INTEREST", "XX", "FREE BALANCE INTEREST ADJUSTMENT", 0.12).kind(), TransactionKind::Interest);
+        assert_eq!(transaction("ELECTRONIC_FUND", "TO", "CLIENT REQUESTED ELECTRONIC FUNDING DISBURSEMENT", -500.0).kind(), TransactionKind::TransferOut);
+        assert_eq!(transaction("JOURNAL", "JN", "ADR FEE", -0.5).kind(), TransactionKind::Fee);
+        assert_eq!(transaction("MEMORANDUM", "", "FREE BALANCE", 0.0).kind(), TransactionKind::Other);
+    }
+
+    #[test]
+    fn aggregates_by_kind() {
+        let mut transactions = transactions();
+
+        transactions.push(Transaction {
+            transaction_item: transactions[1].transaction_item.clone(),
+            ..transaction("DIVIDEND_OR_INTEREST", "QD", "QUALIFIED DIVIDEND", 4.5)
+        });
+
+        let summaries = summarize(&transactions);
+
+        assert_eq!(summaries[&TransactionKind::Dividend], KindSummary { count: 2, net_amount: 25.0, fees: 0.0 });
+        assert_eq!(summaries[&TransactionKind::Trade].net_amount, -1205.5);
+        assert_eq!(total(&transactions, TransactionKind::TransferIn), 5000.0);
+        assert_eq!(total(&transactions, TransactionKind::Fee), 0.0);
+        assert_eq!(totals_by_symbol(&transactions, TransactionKind::Dividend).into_iter().collect::<Vec<_>>(), [("AAPL".to_string(), 25.0)]);
+    }
+}
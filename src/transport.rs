@@ -0,0 +1,343 @@
+//! Pluggable HTTP transport of the client.
+//!
+//! By default the client sends requests with its own connection pool. Setting
+//! a [`Transport`](trait.Transport.html) with `Client::set_transport()` routes
+//! every request through it instead, which is how the
+//! [`MockTransport`](../mock/struct.MockTransport.html) serves canned
+//! responses in tests, and how the
+//! [`CassetteTransport`](../cassette/struct.CassetteTransport.html) records
+//! and replays real ones.
+//!
+//! A [`RequestHook`](trait.RequestHook.html) set with
+//! `Client::set_request_hook()` adds headers to every request, such as the
+//! API key of a gateway the API is reached through:
+//!
+//! ```
+//! use std::sync::Arc;
+//! use tda_sdk::{transport::StaticHeaders, Client};
+//!
+//! let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//!
+//! client.set_request_hook(Some(Arc::new(StaticHeaders::new(&[("X-Api-Key", "secret")]))));
+//! ```
+//!
+//! Hooks see the method, URL, and body of the request, so they can also sign
+//! it or add a trace ID of their own.
+
+use crate::network::TlsConfig;
+
+use std::{
+    fmt,
+    io::{self, Cursor, Read},
+    ops::Deref,
+    time::Duration,
+};
+
+/// Request sent through a [`Transport`](trait.Transport.html).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Full URL, including the query string.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// Form or JSON body, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl HttpRequest {
+    /// Value of a header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Path of the URL, without the scheme, host, or query string.
+    pub fn path(&self) -> &str {
+        let url = self.url.split('?').next().unwrap_or_default();
+        let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+
+        without_scheme.find('/').map_or("/", |start| &without_scheme[start..])
+    }
+
+    /// Decoded value of a query string parameter.
+    pub fn query(&self, name: &str) -> Option<String> {
+        let (_, query) = self.url.split_once('?')?;
+
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| percent_decode(value))
+    }
+}
+
+/// Response returned by a [`Transport`](trait.Transport.html).
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Body as sent by the server, possibly compressed as described by the
+    /// `Content-Encoding` header.
+    pub body: Box<dyn Read + Send>,
+}
+
+impl HttpResponse {
+    /// Create a response with a body and no headers.
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Box::new(Cursor::new(body.into())),
+        }
+    }
+
+    /// Add a header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// Value of a header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    pub(crate) fn from_ureq(response: ureq::Response) -> Self {
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.header(&name)?.to_string();
+
+                Some((name, value))
+            })
+            .collect();
+
+        Self {
+            status: response.status(),
+            headers,
+            body: Box::new(response.into_reader()),
+        }
+    }
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Parsed response along with the status, headers, and timing of the HTTP
+/// response, as returned by the `*_with_meta()` methods of the client.
+///
+/// Dereferences to the parsed value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithMeta<T> {
+    pub value: T,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Time from sending the request until the body was read.
+    pub elapsed: Duration,
+}
+
+impl<T> WithMeta<T> {
+    /// Value of a header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Validator of the response, to pass to the `*_if_none_match()`
+    /// methods of the client.
+    pub fn etag(&self) -> Option<&str> {
+        self.header("ETag")
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Transform the value, keeping the metadata.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> WithMeta<U> {
+        WithMeta {
+            value: f(self.value),
+            status: self.status,
+            headers: self.headers,
+            elapsed: self.elapsed,
+        }
+    }
+}
+
+impl<T> Deref for WithMeta<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Result of a conditional request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CacheStatus<T> {
+    /// The resource changed, or there was no validator to compare with.
+    Modified(WithMeta<T>),
+    /// The resource didn't change, so the cached copy is still current.
+    NotModified,
+}
+
+impl<T> CacheStatus<T> {
+    /// The new value, if the resource changed.
+    pub fn modified(self) -> Option<WithMeta<T>> {
+        match self {
+            CacheStatus::Modified(value) => Some(value),
+            CacheStatus::NotModified => None,
+        }
+    }
+}
+
+/// Sends requests on behalf of the client.
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Send a request and return the response, whatever its status. Errors
+    /// are for failures to get a response at all.
+    fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse>;
+}
+
+/// Adds headers to every request of a client, before it is sent.
+pub trait RequestHook: fmt::Debug + Send + Sync {
+    /// Headers to add to `request`, which already has the headers of the
+    /// client. A header of the same name replaces the one of the client.
+    fn headers(&self, request: &HttpRequest) -> Vec<(String, String)>;
+}
+
+/// Hook adding the same headers to every request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StaticHeaders(pub Vec<(String, String)>);
+
+impl StaticHeaders {
+    pub fn new(headers: &[(&str, &str)]) -> Self {
+        Self(headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect())
+    }
+}
+
+impl RequestHook for StaticHeaders {
+    fn headers(&self, _request: &HttpRequest) -> Vec<(String, String)> {
+        self.0.clone()
+    }
+}
+
+/// Transport sending requests over the network.
+///
+/// A new transport has its own connection pool and default settings. The
+/// one returned by `Client::http_transport()` shares the connection pool,
+/// proxy, and TLS settings of the client instead.
+#[derive(Debug)]
+pub struct HttpTransport {
+    agent: ureq::Agent,
+    tls_config: Option<TlsConfig>,
+}
+
+impl HttpTransport {
+    pub fn new() -> Self {
+        Self::with_agent(ureq::agent(), None)
+    }
+
+    pub(crate) fn with_agent(agent: ureq::Agent, tls_config: Option<TlsConfig>) -> Self {
+        Self { agent, tls_config }
+    }
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+        let mut http_request = self.agent.request(&request.method, &request.url);
+
+        for (name, value) in &request.headers {
+            http_request.set(name, value);
+        }
+
+        if let Some(tls_config) = &self.tls_config {
+            http_request.set_tls_config(tls_config.0.clone());
+        }
+
+        let response = match &request.body {
+            Some(body) => http_request.send_string(body),
+            None => http_request.call(),
+        };
+
+        Ok(HttpResponse::from_ureq(response))
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Encode a URL component.
+pub(crate) fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Decode a URL component, including `+` as a space.
+pub(crate) fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_path_and_query() {
+        let request = HttpRequest {
+            method: "GET".to_string(),
+            url: "https://api.tdameritrade.com/v1/marketdata/quotes?symbol=AAPL%2CMSFT&x=1".to_string(),
+            ..HttpRequest::default()
+        };
+
+        assert_eq!(request.path(), "/v1/marketdata/quotes");
+        assert_eq!(request.query("symbol").as_deref(), Some("AAPL,MSFT"));
+        assert_eq!(request.query("missing"), None);
+        assert_eq!(percent_decode(&percent_encode("a b+c/é")), "a b+c/é");
+    }
+}
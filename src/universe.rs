@@ -0,0 +1,266 @@
+//! Symbol universes, such as the constituents of an index.
+//!
+//! The API doesn't list the constituents of indexes, so the crate bundles
+//! snapshots of common ones, [`Universe::sp500()`](struct.Universe.html#method.sp500)
+//! and [`Universe::dow30()`](struct.Universe.html#method.dow30). A universe
+//! feeds the scanner and the bulk downloaders:
+//!
+//! ```no_run
+//! use tda_sdk::{scanner::{GapCriteria, Scanner}, storage::MemoryCandleStore, universe::Universe, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let universe = Universe::sp500();
+//!
+//! let report = Scanner::new().gaps(&client, &universe.as_strs(), &GapCriteria::default());
+//!
+//! let mut store = MemoryCandleStore::new();
+//!
+//! for symbol in &universe {
+//!     client.sync_price_history(symbol, &mut store).unwrap();
+//! }
+//! ```
+//!
+//! Snapshots go stale as indexes are rebalanced. Keep a universe current by
+//! refreshing it from a [`ConstituentSource`](trait.ConstituentSource.html),
+//! such as a directory of lists maintained elsewhere, and by pruning the
+//! symbols which no longer have quotes:
+//!
+//! ```no_run
+//! use tda_sdk::{universe::{DirectorySource, Universe}, Client};
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let mut universe = Universe::sp500();
+//!
+//! let change = universe.refresh(&DirectorySource::new("universes")).unwrap();
+//! println!("added {:?}, removed {:?}", change.added, change.removed);
+//!
+//! let delisted = universe.prune(&client).unwrap();
+//! ```
+//!
+//! Lists are text, with symbols separated by commas, spaces, or new lines,
+//! and `#` starting a comment.
+
+use crate::{scanner::QUOTE_BATCH_SIZE, Client, ClientError, Symbol};
+use chrono::{NaiveDate, Utc};
+
+use std::{
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+const SP500: &str = include_str!("../data/universes/sp500.txt");
+const DOW30: &str = include_str!("../data/universes/dow30.txt");
+
+/// Named list of symbols, sorted and without duplicates.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Universe {
+    name: String,
+    symbols: Vec<String>,
+    as_of: Option<NaiveDate>,
+}
+
+impl Universe {
+    /// Universe of `symbols`, normalized to how REST endpoints write them.
+    pub fn new<S: AsRef<str>>(name: &str, symbols: impl IntoIterator<Item = S>) -> Self {
+        let mut symbols: Vec<String> = symbols.into_iter().filter_map(|symbol| normalize(symbol.as_ref())).collect();
+
+        symbols.sort();
+        symbols.dedup();
+
+        Self {
+            name: name.to_string(),
+            symbols,
+            as_of: None,
+        }
+    }
+
+    /// Universe of the symbols listed in `text`.
+    pub fn parse(name: &str, text: &str) -> Self {
+        Self::new(name, parse_list(text))
+    }
+
+    /// Constituents of the S&P 500, as of the bundled snapshot.
+    pub fn sp500() -> Self {
+        Self::bundled("sp500", SP500)
+    }
+
+    /// Constituents of the Dow Jones Industrial Average, as of the bundled
+    /// snapshot.
+    pub fn dow30() -> Self {
+        Self::bundled("dow30", DOW30)
+    }
+
+    fn bundled(name: &str, text: &str) -> Self {
+        Self {
+            as_of: NaiveDate::from_ymd_opt(2021, 6, 30),
+            ..Self::parse(name, text)
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Date the symbols were current as of, if known.
+    pub fn as_of(&self) -> Option<NaiveDate> {
+        self.as_of
+    }
+
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    /// Symbols borrowed as `&str`, as the scanner and most client methods
+    /// take them.
+    pub fn as_strs(&self) -> Vec<&str> {
+        self.symbols.iter().map(String::as_str).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.symbols.iter().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Whether the universe has `symbol`, however it is spelled.
+    pub fn contains(&self, symbol: &str) -> bool {
+        normalize(symbol).is_some_and(|symbol| self.symbols.binary_search(&symbol).is_ok())
+    }
+
+    /// Replace the symbols with the ones `source` lists under the universe's
+    /// name, returning what changed.
+    pub fn refresh(&mut self, source: &dyn ConstituentSource) -> io::Result<UniverseChange> {
+        let refreshed = Self::new(&self.name, source.constituents(&self.name)?);
+        let change = UniverseChange {
+            added: refreshed.symbols.iter().filter(|symbol| !self.symbols.contains(symbol)).cloned().collect(),
+            removed: self.symbols.iter().filter(|symbol| !refreshed.symbols.contains(symbol)).cloned().collect(),
+        };
+
+        self.symbols = refreshed.symbols;
+        self.as_of = Some(Utc::now().date_naive());
+
+        Ok(change)
+    }
+
+    /// Remove the symbols the API has no quote for, such as delisted or
+    /// renamed ones, returning them.
+    pub fn prune(&mut self, client: &Client) -> Result<Vec<String>, ClientError> {
+        let mut removed = Vec::new();
+
+        for batch in self.as_strs().chunks(QUOTE_BATCH_SIZE) {
+            removed.extend(client.get_quotes(batch)?.not_found);
+        }
+
+        self.symbols.retain(|symbol| !removed.contains(symbol));
+
+        Ok(removed)
+    }
+}
+
+impl<'a> IntoIterator for &'a Universe {
+    type Item = &'a str;
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, String>, fn(&String) -> &str>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.symbols.iter().map(String::as_str)
+    }
+}
+
+/// Symbols added and removed by a refresh.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UniverseChange {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Source of up to date constituent lists, by universe name.
+pub trait ConstituentSource: fmt::Debug + Send + Sync {
+    fn constituents(&self, universe: &str) -> io::Result<Vec<String>>;
+}
+
+/// Directory holding a `<name>.txt` list for each universe.
+#[derive(Clone, Debug)]
+pub struct DirectorySource {
+    dir: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl ConstituentSource for DirectorySource {
+    fn constituents(&self, universe: &str) -> io::Result<Vec<String>> {
+        let text = fs::read_to_string(self.dir.join(format!("{}.txt", universe)))?;
+
+        Ok(parse_list(&text).map(str::to_string).collect())
+    }
+}
+
+fn parse_list(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|symbol| !symbol.is_empty())
+}
+
+fn normalize(symbol: &str) -> Option<String> {
+    let symbol = symbol.trim();
+
+    if symbol.is_empty() {
+        return None;
+    }
+
+    Some(Symbol::parse(symbol).map_or_else(|_| symbol.to_uppercase(), |symbol| symbol.to_rest()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_indexes() {
+        let sp500 = Universe::sp500();
+        let dow30 = Universe::dow30();
+
+        assert!(sp500.len() > 500);
+        assert_eq!(dow30.len(), 30);
+        assert!(dow30.iter().all(|symbol| sp500.contains(symbol)));
+        assert!(sp500.contains("brk/b"));
+        assert_eq!(sp500.as_of(), NaiveDate::from_ymd_opt(2021, 6, 30));
+    }
+
+    #[test]
+    fn refreshes_universes() {
+        let dir = std::env::temp_dir().join(format!("tda-sdk-universes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tech.txt"), "# Big tech\nmsft, AAPL\nNVDA # added\n").unwrap();
+
+        let mut universe = Universe::parse("tech", "AAPL MSFT INTC");
+        let change = universe.refresh(&DirectorySource::new(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            change.unwrap(),
+            UniverseChange {
+                added: vec!["NVDA".to_string()],
+                removed: vec!["INTC".to_string()],
+            }
+        );
+        assert_eq!(universe.symbols(), ["AAPL", "MSFT", "NVDA"]);
+        assert!(universe.as_of().is_some());
+        assert!(universe.refresh(&DirectorySource::new("missing")).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+//! Watchlists, and keeping them in sync with a local list of symbols.
+//!
+//! [`Client::sync_watchlist()`](../struct.Client.html#method.sync_watchlist)
+//! compares a watchlist on the server with the symbols it should contain, and
+//! sends the smallest update that makes them match, so the server watchlist
+//! can mirror a list kept by the app.
+//!
+//! ```no_run
+//! use tda_sdk::Client;
+//!
+//! let client = Client::new("CLIENT_ID", "REFRESH_TOKEN", None);
+//! let sync = client.sync_watchlist("123456789", "Tech", &["AAPL", "MSFT", "NVDA"]).unwrap();
+//!
+//! println!("added {:?}, removed {:?}", sync.added, sync.removed);
+//! ```
+
+use crate::{flexible::flexible_option_f64, ids::AccountId, instruments::Instrument, Symbol};
+
+use std::collections::HashSet;
+
+/// A watchlist, either to be saved or as returned by the API.
+///
+/// [API Documentation](https://developer.tdameritrade.com/watchlist/apis/get/accounts/%7BaccountId%7D/watchlists-0)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watchlist {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<AccountId>,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchlist_id: Option<String>,
+    #[serde(default)]
+    pub watchlist_items: Vec<WatchlistItem>,
+}
+
+impl Watchlist {
+    /// A new watchlist of symbols.
+    pub fn new(name: &str, symbols: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            watchlist_items: symbols.iter().map(|symbol| WatchlistItem::new(symbol)).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Symbols of the items, in order.
+    pub fn symbols(&self) -> Vec<&str> {
+        self.watchlist_items.iter().map(|item| item.instrument.symbol()).collect()
+    }
+}
+
+/// Item in a [`Watchlist`](struct.Watchlist.html)
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchlistItem {
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub average_price: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub commission: Option<f64>,
+    pub instrument: Instrument,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purchased_date: Option<String>,
+    #[serde(default, deserialize_with = "flexible_option_f64", skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    /// Position of the item in the watchlist. Only set by the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+impl WatchlistItem {
+    /// An item for a symbol, as an option, index, or equity depending on the
+    /// symbol.
+    pub fn new(symbol: &str) -> Self {
+        let instrument = match Symbol::parse(symbol) {
+            Ok(Symbol::Option(option)) => Instrument::option(&option.to_tda()),
+            Ok(Symbol::Other(other)) if other.starts_with('$') => Instrument::Index {
+                cusip: None,
+                description: None,
+                symbol: other,
+            },
+            Ok(symbol) => Instrument::equity(&symbol.to_rest()),
+            Err(_) => Instrument::equity(symbol),
+        };
+
+        Self {
+            average_price: None,
+            commission: None,
+            instrument,
+            purchased_date: None,
+            quantity: None,
+            sequence_id: None,
+            status: None,
+        }
+    }
+}
+
+/// Changes made by [`Client::sync_watchlist()`](../struct.Client.html#method.sync_watchlist).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WatchlistSync {
+    /// Whether the watchlist didn't exist and was created.
+    pub created: bool,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl WatchlistSync {
+    /// Whether the watchlist was already in sync.
+    pub fn is_empty(&self) -> bool {
+        !self.created && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Request needed to bring a watchlist in sync.
+#[derive(Debug, PartialEq)]
+pub(crate) enum WatchlistUpdate {
+    /// Nothing to do.
+    None,
+    /// Create a new watchlist.
+    Create(Watchlist),
+    /// Append items to the existing watchlist.
+    Append(Watchlist),
+    /// Replace the existing watchlist, keeping the items which stay.
+    Replace(Watchlist),
+}
+
+/// Plan the smallest update which makes `current` contain exactly the
+/// `desired` symbols.
+pub(crate) fn plan_sync(current: Option<&Watchlist>, name: &str, desired: &[&str]) -> (WatchlistUpdate, WatchlistSync) {
+    let mut seen = HashSet::new();
+    let desired: Vec<WatchlistItem> = desired
+        .iter()
+        .map(|symbol| WatchlistItem::new(symbol))
+        .filter(|item| seen.insert(item.instrument.symbol().to_string()))
+        .collect();
+
+    let current = match current {
+        Some(current) => current,
+        None => {
+            let watchlist = Watchlist {
+                name: name.to_string(),
+                watchlist_items: desired,
+                ..Watchlist::default()
+            };
+            let sync = WatchlistSync {
+                created: true,
+                added: watchlist.symbols().into_iter().map(str::to_string).collect(),
+                removed: Vec::new(),
+            };
+
+            return (WatchlistUpdate::Create(watchlist), sync);
+        }
+    };
+
+    let current_symbols: HashSet<&str> = current.symbols().into_iter().collect();
+    let added: Vec<WatchlistItem> = desired
+        .iter()
+        .filter(|item| !current_symbols.contains(item.instrument.symbol()))
+        .cloned()
+        .collect();
+    let (kept, removed): (Vec<&WatchlistItem>, Vec<&WatchlistItem>) = current
+        .watchlist_items
+        .iter()
+        .partition(|item| seen.contains(item.instrument.symbol()));
+
+    let sync = WatchlistSync {
+        created: false,
+        added: added.iter().map(|item| item.instrument.symbol().to_string()).collect(),
+        removed: removed.iter().map(|item| item.instrument.symbol().to_string()).collect(),
+    };
+
+    let update = if sync.is_empty() {
+        WatchlistUpdate::None
+    } else if removed.is_empty() {
+        WatchlistUpdate::Append(Watchlist {
+            name: current.name.clone(),
+            watchlist_id: current.watchlist_id.clone(),
+            watchlist_items: added,
+            ..Watchlist::default()
+        })
+    } else {
+        WatchlistUpdate::Replace(Watchlist {
+            name: current.name.clone(),
+            watchlist_id: current.watchlist_id.clone(),
+            watchlist_items: kept.into_iter().cloned().chain(added).collect(),
+            ..Watchlist::default()
+        })
+    };
+
+    (update, sync)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_watchlist() -> Watchlist {
+        serde_json::from_value(serde_json::json!({
+            "accountId": "123456789",
+            "name": "Tech",
+            "watchlistId": "42",
+            "watchlistItems": [
+                { "sequenceId": 1, "instrument": { "assetType": "EQUITY", "symbol": "AAPL" } },
+                { "sequenceId": 2, "instrument": { "assetType": "EQUITY", "symbol": "BRK.B" } },
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn plan_minimal_updates() {
+        let current = server_watchlist();
+
+        let (update, sync) = plan_sync(Some(&current), "Tech", &["aapl", "brk/b"]);
+
+        assert_eq!(update, WatchlistUpdate::None);
+        assert!(sync.is_empty());
+
+        let (update, sync) = plan_sync(Some(&current), "Tech", &["AAPL", "BRK.B", "MSFT", "MSFT"]);
+
+        assert!(matches!(update, WatchlistUpdate::Append(ref watchlist) if watchlist.symbols() == ["MSFT"]));
+        assert_eq!(sync.added, ["MSFT"]);
+
+        let (update, sync) = plan_sync(Some(&current), "Tech", &["$SPX.X", "AAPL"]);
+
+        match update {
+            WatchlistUpdate::Replace(watchlist) => {
+                assert_eq!(watchlist.symbols(), ["AAPL", "$SPX.X"]);
+                assert_eq!(watchlist.watchlist_items[0].sequence_id, Some(1));
+                assert_eq!(watchlist.watchlist_items[1].instrument, Instrument::Index {
+                    cusip: None,
+                    description: None,
+                    symbol: "$SPX.X".to_string(),
+                });
+            }
+            update => panic!("unexpected update {:?}", update),
+        }
+
+        assert_eq!(sync.removed, ["BRK.B"]);
+
+        let (update, sync) = plan_sync(None, "New", &["AAPL"]);
+
+        assert!(matches!(update, WatchlistUpdate::Create(ref watchlist) if watchlist.name == "New"));
+        assert!(sync.created);
+    }
+}
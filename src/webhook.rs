@@ -0,0 +1,233 @@
+//! Forwarding of account activity to a webhook.
+//!
+//! A [`WebhookForwarder`](struct.WebhookForwarder.html) posts every
+//! `ACCT_ACTIVITY` event of the streamer as JSON to a URL, retrying failed
+//! deliveries, so systems not written in Rust can be notified of fills. Each
+//! delivery is a JSON object with the `account`, `messageType`, and raw XML
+//! `messageData` of the event, and the parsed `fill` for `OrderFill`
+//! messages.
+//!
+//! Only available with the `webhook` feature enabled.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tda_sdk::{streamer::StreamerEvent, webhook::WebhookForwarder};
+//!
+//! let mut forwarder = WebhookForwarder::new("https://example.com/hooks/fills");
+//! forwarder.set_header("Authorization", "Bearer SECRET").set_retries(5, Duration::from_secs(1));
+//!
+//! # let events: Vec<StreamerEvent> = Vec::new();
+//! for event in &events {
+//!     if let StreamerEvent::AccountActivity(activity) = event {
+//!         forwarder.forward(activity).unwrap();
+//!     }
+//! }
+//! ```
+
+use crate::{
+    streamer::{AccountActivity, StreamerEvent},
+    transport::{HttpRequest, HttpTransport, Transport},
+};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use std::{
+    io::{self, Read},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// Retries of a failed delivery by default.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Delay before the first retry by default, doubled for every retry after.
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Posts account activity to a webhook URL.
+#[derive(Debug)]
+pub struct WebhookForwarder {
+    url: String,
+    headers: Vec<(String, String)>,
+    max_retries: u32,
+    backoff: Duration,
+    transport: Arc<dyn Transport>,
+}
+
+impl WebhookForwarder {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+            transport: Arc::new(HttpTransport::new()),
+        }
+    }
+
+    /// Add a header to every delivery, such as a shared secret.
+    pub fn set_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.headers.push((name.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// Set how many times a failed delivery is retried, and the delay before
+    /// the first retry, which doubles for every retry after it.
+    pub fn set_retries(&mut self, max_retries: u32, backoff: Duration) -> &mut Self {
+        self.max_retries = max_retries;
+        self.backoff = backoff;
+
+        self
+    }
+
+    /// Send deliveries through a transport instead of over the network, such
+    /// as a [`MockTransport`](../mock/struct.MockTransport.html) in tests.
+    pub fn set_transport(&mut self, transport: Arc<dyn Transport>) -> &mut Self {
+        self.transport = transport;
+
+        self
+    }
+
+    /// Body posted for an account activity event.
+    pub fn payload(activity: &AccountActivity) -> Value {
+        let fill = activity.fill().map(|fill| {
+            json!({
+                "accountId": fill.account_id.as_str(),
+                "orderId": fill.order_id,
+                "symbol": fill.symbol,
+                "instruction": fill.instruction,
+                "quantity": fill.quantity,
+                "price": fill.price,
+            })
+        });
+
+        json!({
+            "account": activity.account,
+            "messageType": activity.message_type,
+            "messageData": activity.message_data,
+            "fill": fill,
+        })
+    }
+
+    /// Post an account activity event, retrying connection failures, `429`
+    /// responses, and server errors. Subscription confirmations aren't
+    /// forwarded.
+    pub fn forward(&self, activity: &AccountActivity) -> Result<(), WebhookError> {
+        if activity.message_type == "SUBSCRIBED" {
+            return Ok(());
+        }
+
+        let request = HttpRequest {
+            method: "POST".to_string(),
+            url: self.url.clone(),
+            headers: self.headers.clone(),
+            body: Some(Self::payload(activity).to_string()),
+        };
+        let mut attempt = 0;
+
+        loop {
+            let error = match self.transport.send(&request) {
+                Ok(response) if (200..300).contains(&response.status) => return Ok(()),
+                Ok(mut response) => {
+                    let mut body = String::new();
+                    response.body.read_to_string(&mut body).unwrap_or_default();
+
+                    WebhookError::Status(response.status, body)
+                }
+                Err(error) => WebhookError::Io(error),
+            };
+
+            let retryable = match &error {
+                WebhookError::Status(status, _) => *status == 429 || *status >= 500,
+                WebhookError::Io(_) => true,
+            };
+
+            if !retryable || attempt >= self.max_retries {
+                return Err(error);
+            }
+
+            log::debug!("Retrying webhook delivery after: {}", error);
+            thread::sleep(self.backoff * 2u32.saturating_pow(attempt));
+            attempt += 1;
+        }
+    }
+
+    /// Forward the account activity among streamer events, logging
+    /// deliveries which fail.
+    pub fn forward_events(&self, events: &[StreamerEvent]) {
+        for event in events {
+            if let StreamerEvent::AccountActivity(activity) = event {
+                if let Err(error) = self.forward(activity) {
+                    log::warn!("Failed to forward {} to webhook: {}", activity.message_type, error);
+                }
+            }
+        }
+    }
+
+    /// Read events from a streamer connection and forward its account
+    /// activity until the connection fails.
+    #[cfg(feature = "streamer")]
+    pub fn run(&self, client: &mut crate::streamer::StreamerClient) -> Result<(), crate::streamer::StreamerError> {
+        loop {
+            self.forward_events(&client.read()?);
+        }
+    }
+}
+
+/// Errors encountered while delivering to a webhook, after retrying.
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    /// The webhook responded with a status other than 2xx.
+    #[error("Webhook responded with {0}: {1}")]
+    Status(u16, String),
+
+    /// The webhook couldn't be reached.
+    #[error("Failed to reach webhook: {0}")]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Fault, MockTransport};
+
+    fn forwarder(transport: &Arc<MockTransport>) -> WebhookForwarder {
+        let mut forwarder = WebhookForwarder::new("https://example.com/hooks/fills");
+
+        forwarder.set_retries(2, Duration::ZERO).set_transport(transport.clone());
+
+        forwarder
+    }
+
+    #[test]
+    fn retries_deliveries() {
+        let transport = Arc::new(MockTransport::new());
+        let activity = AccountActivity {
+            account: "123456789".to_string(),
+            message_type: "OrderFill".to_string(),
+            message_data: "<OrderFillMessage><AccountKey>123456789</AccountKey><OrderKey>4321</OrderKey><Symbol>AAPL</Symbol><OrderInstructions>Buy</OrderInstructions><Quantity>10</Quantity><ExecutionPrice>120.5</ExecutionPrice></OrderFillMessage>".to_string(),
+        };
+
+        transport
+            .respond("POST", "/hooks/fills", 204, "")
+            .push_fault(Fault::Status(503))
+            .push_fault(Fault::ConnectionReset);
+
+        forwarder(&transport).forward(&activity).unwrap();
+
+        let requests = transport.requests();
+        let body: Value = serde_json::from_str(requests[2].body.as_deref().unwrap()).unwrap();
+
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].header("Content-Type"), Some("application/json"));
+        assert_eq!(body["messageType"], "OrderFill");
+        assert_eq!(body["fill"]["orderId"], 4321);
+        assert_eq!(body["fill"]["price"], 120.5);
+
+        transport.push_fault(Fault::Status(400));
+
+        assert!(matches!(forwarder(&transport).forward(&activity), Err(WebhookError::Status(400, _))));
+        assert_eq!(transport.requests().len(), 4);
+    }
+}
@@ -0,0 +1,32 @@
+//! Replays recorded API responses, so the client is tested end to end without
+//! live credentials. Re-record a cassette against the API with `TDA_RECORD=1`
+//! and a working token.
+
+use std::sync::Arc;
+use tda_sdk::{cassette::CassetteTransport, AccessToken, Client};
+
+fn client(cassette: &str) -> Client {
+    let token = AccessToken {
+        expires_at: 0,
+        scope: Vec::new(),
+        token: std::env::var("TDA_ACCESS_TOKEN").unwrap_or_else(|_| "TOKEN".to_string()),
+    };
+    let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", Some(token));
+    let transport = CassetteTransport::from_env(format!("tests/cassettes/{}.json", cassette), Arc::new(client.http_transport())).unwrap();
+
+    client.set_transport(Some(Arc::new(transport)));
+
+    client
+}
+
+#[test]
+fn market_data() {
+    let client = client("market_data");
+
+    let quotes = client.get_quotes(&["AAPL", "$SPX.X"]).unwrap();
+    let history = client.get_price_history("AAPL", Default::default()).unwrap();
+
+    assert!(quotes.found.contains_key("AAPL"));
+    assert_eq!(history.symbol, "AAPL");
+    assert!(!history.candles.is_empty());
+}
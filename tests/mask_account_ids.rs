@@ -0,0 +1,49 @@
+//! Masking is a process-wide setting, so it's tested in its own binary to keep
+//! it from leaking into other tests.
+
+use std::sync::Arc;
+use tda_sdk::{
+    ids::set_mask_account_ids,
+    mock::MockTransport,
+    orders::{Instruction, Order},
+    AccessToken,
+    AccountId,
+    Client,
+};
+
+#[test]
+fn mask_account_ids() {
+    let id = AccountId::from("123456789");
+
+    set_mask_account_ids(true);
+
+    assert_eq!(id.to_string(), "*****6789");
+    assert_eq!(format!("{:?}", id), "AccountId(\"*****6789\")");
+    assert_eq!(serde_json::to_string(&id).unwrap(), "\"123456789\"");
+    assert_eq!(id.as_str(), "123456789");
+
+    // Requests carry the real ID, in their URLs and bodies.
+    let transport = Arc::new(MockTransport::new());
+    let token = AccessToken {
+        expires_at: 0,
+        scope: Vec::new(),
+        token: "TOKEN".to_string(),
+    };
+    let mut client = Client::new("CLIENT_ID", "REFRESH_TOKEN", Some(token));
+    let order = Order {
+        account_id: Some(id.clone()),
+        ..Order::equity_market(Instruction::Buy, "AAPL", 1.0)
+    };
+
+    client.set_transport(Some(transport.clone()));
+    assert!(client.place_order(&id, &order).is_err());
+
+    let request = &transport.requests()[0];
+
+    assert_eq!(request.path(), "/v1/accounts/123456789/orders");
+    assert!(request.body.as_deref().unwrap().contains(r#""accountId":"123456789""#));
+
+    set_mask_account_ids(false);
+
+    assert_eq!(id.to_string(), "123456789");
+}
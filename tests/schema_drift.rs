@@ -0,0 +1,147 @@
+//! Checks every fixture against its recorded schema in `tests/schemas`, so a
+//! re-recorded fixture with fields added or removed by TDA fails with a diff
+//! instead of parsing silently. A fixture without a schema fails too. Run with
+//! `TDA_UPDATE_SCHEMAS=1` to record schemas, or to accept new fields once the
+//! response types handle them.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeSet, env, fmt, fs, path::Path};
+use tda_sdk::{
+    orders::Order,
+    responses::{
+        AccessTokenResponse,
+        Account,
+        GetMarketHoursResponse,
+        GetPriceHistoryResponse,
+        GetQuotesResponse,
+        GetTransactionsResponse,
+        Mover,
+        OptionChain,
+        Preferences,
+        SearchInstrumentsResponse,
+        UserPrincipals,
+    },
+    schema::{field_paths, unknown_fields, SchemaDiff},
+};
+
+/// Name of the unknown field added by the variant property.
+const INJECTED_FIELD: &str = "injectedField";
+
+/// Compare a fixture with its recorded schema, and make sure `T` knows all of
+/// its fields.
+fn check<T>(name: &str, failures: &mut Vec<String>)
+where
+    T: DeserializeOwned + Serialize + PartialEq + fmt::Debug,
+{
+    let json = fs::read_to_string(format!("tests/fixtures/{}.json", name)).unwrap();
+    let schema_path = format!("tests/schemas/{}.txt", name);
+    let paths = field_paths(&serde_json::from_str(&json).unwrap());
+
+    if env::var_os("TDA_UPDATE_SCHEMAS").is_some() {
+        let lines: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+        fs::write(&schema_path, lines.join("\n") + "\n").unwrap();
+    } else if !Path::new(&schema_path).exists() {
+        failures.push(format!("{} has no recorded schema, run with TDA_UPDATE_SCHEMAS=1 to record {}", name, schema_path));
+
+        return;
+    }
+
+    let recorded: BTreeSet<String> = fs::read_to_string(&schema_path).unwrap().lines().map(str::to_string).collect();
+    let diff = SchemaDiff::new(&recorded, &paths);
+
+    if !diff.is_empty() {
+        failures.push(format!("{} differs from {}:\n{}", name, schema_path, diff));
+    }
+
+    match unknown_fields::<T>(&json) {
+        Ok(unknown) if unknown.is_empty() => {}
+        Ok(unknown) => failures.push(format!("{} has fields unknown to its type: {:?}", name, unknown)),
+        Err(error) => failures.push(format!("{} failed to parse: {}", name, error)),
+    }
+
+    check_variants::<T>(name, &json, failures);
+}
+
+/// Property: every variant of a response with a single field removed, or an
+/// unknown field added, either fails to parse or parses into a value that
+/// survives a round trip through JSON.
+fn check_variants<T>(name: &str, json: &str, failures: &mut Vec<String>)
+where
+    T: DeserializeOwned + Serialize + PartialEq + fmt::Debug,
+{
+    let value: Value = serde_json::from_str(json).unwrap();
+    let mut pointers = Vec::new();
+
+    object_pointers(&value, String::new(), &mut pointers);
+
+    for pointer in pointers {
+        let object = value.pointer(&pointer).and_then(Value::as_object).unwrap();
+        let mut variants = vec![(format!("{} added at {:?}", INJECTED_FIELD, pointer), Some(INJECTED_FIELD.to_string()), None)];
+
+        variants.extend(object.keys().map(|key| (format!("{:?} removed at {:?}", key, pointer), None, Some(key.clone()))));
+
+        for (description, added, removed) in variants {
+            let mut variant = value.clone();
+            let object = variant.pointer_mut(&pointer).and_then(Value::as_object_mut).unwrap();
+
+            if let Some(key) = added {
+                object.insert(key, Value::from(1));
+            }
+
+            if let Some(key) = removed {
+                object.remove(&key);
+            }
+
+            if let Ok(parsed) = serde_json::from_value::<T>(variant) {
+                let reparsed: T = serde_json::from_value(serde_json::to_value(&parsed).unwrap()).unwrap();
+
+                if parsed != reparsed {
+                    failures.push(format!("{} with {} changed in a round trip", name, description));
+                }
+            }
+        }
+    }
+}
+
+/// JSON pointers of every object in a value.
+fn object_pointers(value: &Value, pointer: String, pointers: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                object_pointers(value, format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1")), pointers);
+            }
+
+            pointers.push(pointer);
+        }
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                object_pointers(value, format!("{}/{}", pointer, index), pointers);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn fixtures_match_schemas() {
+    let mut failures = Vec::new();
+
+    check::<AccessTokenResponse>("access_token", &mut failures);
+    check::<AccessTokenResponse>("access_token_offline", &mut failures);
+    check::<Vec<Account>>("accounts", &mut failures);
+    check::<Vec<Account>>("accounts_by_type", &mut failures);
+    check::<SearchInstrumentsResponse>("instruments_fundamental", &mut failures);
+    check::<GetMarketHoursResponse>("market_hours", &mut failures);
+    check::<Vec<Mover>>("movers", &mut failures);
+    check::<OptionChain>("option_chain", &mut failures);
+    check::<Vec<Order>>("orders", &mut failures);
+    check::<Preferences>("preferences", &mut failures);
+    check::<GetPriceHistoryResponse>("price_history", &mut failures);
+    check::<GetQuotesResponse>("quotes", &mut failures);
+    check::<GetTransactionsResponse>("transactions", &mut failures);
+    check::<UserPrincipals>("user_principals", &mut failures);
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}